@@ -0,0 +1,79 @@
+//! Compares reading of many small frames with a new codec per frame (what
+//! `parse_raw_frame` does) and with a single codec per connection (`FrameCodec`),
+//! which reuses its buffer. Time and numbers of heap allocations are reported.
+//!
+//! Run with `cargo bench --bench frame_codec`.
+extern crate cdrs;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use cdrs::compression::Compression;
+use cdrs::frame::Frame;
+use cdrs::frame::parser::{parse_raw_frame, FrameCodec};
+
+const FRAMES: usize = 100_000;
+
+// Allocator which counts allocations made by the benchmark.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Builds a stream of 13-byte frames, each of them is a Void result.
+fn frames() -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(FRAMES * 13);
+    for i in 0..FRAMES {
+        let stream = (i % 0x7FFF) as u16;
+        bytes.extend_from_slice(&[0x84, 0, (stream >> 8) as u8, stream as u8, 0x08]);
+        bytes.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 1]);
+    }
+    bytes
+}
+
+fn run<F: FnMut(&mut Cursor<&[u8]>) -> Frame>(bytes: &[u8], mut read: F) -> (usize, Duration) {
+    let mut cursor = Cursor::new(bytes);
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        assert_eq!(read(&mut cursor).body.len(), 4);
+    }
+
+    (ALLOCATIONS.load(Ordering::SeqCst) - allocations, start.elapsed())
+}
+
+fn report(name: &str, (allocations, elapsed): (usize, Duration)) {
+    let micros = elapsed.as_secs() * 1_000_000 + (elapsed.subsec_nanos() / 1_000) as u64;
+    println!("{}: {} frames, {:.2} allocations per frame, {} us in total",
+             name,
+             FRAMES,
+             allocations as f64 / FRAMES as f64,
+             micros);
+}
+
+fn main() {
+    let bytes = frames();
+    report("codec per frame",
+           run(&bytes,
+               |cursor| parse_raw_frame(cursor, &Compression::None).unwrap()));
+
+    let mut codec = FrameCodec::new();
+    report("codec per connection",
+           run(&bytes,
+               |cursor| codec.read_raw_frame(cursor, &Compression::None).unwrap()));
+}
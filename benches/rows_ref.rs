@@ -0,0 +1,107 @@
+//! Compares decoding of a text column of a large Rows result into owned rows
+//! (`Row`, which copies every cell) and into borrowed rows (`RowsRef`, which lends
+//! slices of the frame body). Time and numbers of heap allocations are reported.
+//!
+//! Run with `cargo bench --bench rows_ref`.
+extern crate cdrs;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use cdrs::IntoBytes;
+use cdrs::frame::{Frame, Opcode, Version};
+use cdrs::types::{to_int, to_short, CBytes, CString};
+use cdrs::types::rows_ref::RowsRef;
+
+const ROWS: usize = 10_000;
+const ITERATIONS: usize = 20;
+
+// Allocator which counts allocations made by the benchmark.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Builds a body of Rows result with a single column name varchar.
+fn rows_body() -> Vec<u8> {
+    let mut body = to_int(0x0002);
+    body.extend_from_slice(to_int(0x0001).as_slice());
+    body.extend_from_slice(to_int(1).as_slice());
+    for name in &["ks", "users", "name"] {
+        body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+    }
+    body.extend_from_slice(to_short(0x000D).as_slice());
+    body.extend_from_slice(to_int(ROWS as i32).as_slice());
+    for i in 0..ROWS {
+        let name = format!("user name number {}", i);
+        body.extend_from_slice(CBytes::new(name.into_bytes()).into_cbytes().as_slice());
+    }
+    body
+}
+
+fn result_frame(body: Vec<u8>) -> Frame {
+    Frame {
+        version: Version::Response,
+        flags: vec![],
+        opcode: Opcode::Result,
+        stream: 0,
+        body: body,
+        tracing_id: None,
+        warnings: vec![],
+        custom_payload: None,
+    }
+}
+
+fn owned(frame: &Frame) -> usize {
+    let rows = frame.get_body().into_rows().unwrap();
+    rows.iter()
+        .map(|row| {
+            let name: String = row.get("name").unwrap();
+            name.len()
+        })
+        .sum()
+}
+
+fn borrowed(frame: &Frame) -> usize {
+    let rows = RowsRef::from_frame(frame).unwrap();
+    rows.iter().map(|row| row.get_str("name").unwrap().unwrap().len()).sum()
+}
+
+fn run<F: Fn(&Frame) -> usize>(frame: &Frame, decode: F) -> (usize, Duration) {
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        assert!(decode(frame) > 0);
+    }
+
+    (ALLOCATIONS.load(Ordering::SeqCst) - allocations, start.elapsed())
+}
+
+fn report(name: &str, (allocations, elapsed): (usize, Duration)) {
+    let micros = elapsed.as_secs() * 1_000_000 + (elapsed.subsec_nanos() / 1_000) as u64;
+    println!("{}: {} rows, {} allocations per result, {} us per result",
+             name,
+             ROWS,
+             allocations / ITERATIONS,
+             micros / ITERATIONS as u64);
+}
+
+fn main() {
+    let frame = result_frame(rows_body());
+    report("owned rows", run(&frame, owned));
+    report("borrowed rows", run(&frame, borrowed));
+}
@@ -0,0 +1,93 @@
+//! Compares numbers of writes to a transport (i.e. system calls of a real socket)
+//! made by a multiplexed connection with and without write coalescing while
+//! requests are sent concurrently.
+//!
+//! Run with `cargo bench --bench write_coalescing`.
+extern crate cdrs;
+
+use std::io::{self, Read, Write};
+use std::net;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cdrs::compression::Compression;
+use cdrs::frame::Frame;
+use cdrs::multiplexer::{MultiplexedConnection, WriteCoalescing};
+use cdrs::transport::CDRSTransport;
+
+const THREADS: usize = 8;
+const REQUESTS_PER_THREAD: usize = 2000;
+
+// Transport which counts writes and simulates the cost of a system call.
+struct CountingTransport {
+    writes: Arc<AtomicUsize>,
+}
+
+impl Read for CountingTransport {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for CountingTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::new(0, 2_000));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CDRSTransport for CountingTransport {
+    fn try_clone(&self) -> io::Result<CountingTransport> {
+        Ok(CountingTransport { writes: self.writes.clone() })
+    }
+
+    fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run(write_coalescing: Option<WriteCoalescing>) -> (usize, Duration) {
+    let writes = Arc::new(AtomicUsize::new(0));
+    let transport = CountingTransport { writes: writes.clone() };
+    let mut connection = MultiplexedConnection::new(transport, Compression::None);
+    if let Some(write_coalescing) = write_coalescing {
+        connection = connection.write_coalescing(write_coalescing);
+    }
+    let connection = Arc::new(connection);
+
+    let start = Instant::now();
+    let senders: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let connection = connection.clone();
+            thread::spawn(move || for _ in 0..REQUESTS_PER_THREAD {
+                connection.send(Frame::new_req_options()).unwrap();
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.join().unwrap();
+    }
+
+    (writes.load(Ordering::SeqCst), start.elapsed())
+}
+
+fn report(name: &str, (writes, elapsed): (usize, Duration)) {
+    let millis = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64;
+    println!("{}: {} frames, {} writes, {} ms",
+             name,
+             THREADS * REQUESTS_PER_THREAD,
+             writes,
+             millis);
+}
+
+fn main() {
+    report("without coalescing", run(None));
+    report("with coalescing", run(Some(WriteCoalescing::default())));
+}
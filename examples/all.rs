@@ -437,7 +437,7 @@ fn create_table_udt(session: &mut Session<NoneAuthenticator, TransportTcp>) -> b
 
 fn insert_table_udt(session: &mut Session<NoneAuthenticator, TransportTcp>) -> bool {
     let udt = Udt { number: 12 };
-    let values: Vec<Value> = vec![(1 as i32).into(), udt.into()];
+    let values: Vec<Value> = vec![(1 as i32).into(), Value::new_normal(udt)];
 
     let query = QueryBuilder::new(INSERT_UDT).values(values).finalize();
     let inserted = session.query(query, false, false);
@@ -0,0 +1,300 @@
+//! The module contains an asynchronous session which is available with `tokio` feature.
+//!
+//! A session works on top of any `AsyncRead + AsyncWrite` stream, e.g. `TcpStream`
+//! of `tokio-core`. Requests return futures of response frames. Many requests share
+//! a single connection concurrently, responses are matched to requests by stream id
+//! of a frame the same way `MultiplexedConnection` does it. Frames are read with
+//! the same `FrameCodec` which is used by synchronous sessions.
+//!
+//! `AsyncSession::new` returns a session along with a connection future which performs
+//! the actual IO, so it should be spawned onto an event loop:
+//!
+//! ```ignore
+//! let (session, connection) = AsyncSession::new(stream, Compression::None);
+//! handle.spawn(connection.map_err(|err| error!("Connection failed: {:?}", err)));
+//! let rows = core.run(session.startup().and_then(|_| session.query(query)));
+//! ```
+use std::collections::HashMap;
+
+use bytes::BytesMut;
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, Stream};
+use futures::sync::{mpsc, oneshot};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_io::codec::{Decoder, Encoder, Framed};
+
+use compression::{Compression, DEFAULT_COMPRESSION_THRESHOLD};
+use error;
+use frame::{Frame, Opcode};
+use frame::events::EVENT_STREAM_ID;
+use frame::parser::{FrameCodec, convert_frame_into_result};
+use multiplexer::{StreamIdAllocator, MAX_STREAMS};
+use query::{Query, QueryParams};
+use types::CBytesShort;
+
+/// Future of a response to a request sent by `AsyncSession`.
+pub type FrameFuture = Box<Future<Item = Frame, Error = error::Error> + Send>;
+
+/// Codec of frames for non-blocking streams. It decodes frames with `FrameCodec`
+/// and compresses request bodies with provided compressor.
+#[derive(Debug)]
+pub struct AsyncFrameCodec {
+    codec: FrameCodec,
+    compressor: Compression,
+}
+
+impl AsyncFrameCodec {
+    pub fn new(codec: FrameCodec, compressor: Compression) -> AsyncFrameCodec {
+        AsyncFrameCodec {
+            codec: codec,
+            compressor: compressor,
+        }
+    }
+}
+
+impl Decoder for AsyncFrameCodec {
+    type Item = Frame;
+    type Error = error::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> error::Result<Option<Frame>> {
+        match try!(self.codec.decode_raw_frame(&src[..], &self.compressor)) {
+            Some((frame, len)) => {
+                src.split_to(len);
+                Ok(Some(frame))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder for AsyncFrameCodec {
+    type Item = Frame;
+    type Error = error::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> error::Result<()> {
+        // a compression algorithm is not negotiated until a startup request is handled
+        let compressor = if frame.opcode == Opcode::Startup {
+            Compression::None
+        } else {
+            self.compressor
+        };
+        let bytes = try!(frame.encode_with_threshold(compressor, DEFAULT_COMPRESSION_THRESHOLD));
+        dst.extend_from_slice(bytes.as_slice());
+        Ok(())
+    }
+}
+
+struct Request {
+    frame: Frame,
+    responder: oneshot::Sender<error::Result<Frame>>,
+}
+
+/// Asynchronous session. It's cheap to clone, clones share the same connection.
+#[derive(Clone)]
+pub struct AsyncSession {
+    compressor: Compression,
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+impl AsyncSession {
+    /// Creates new session on top of provided stream. The returned connection future
+    /// sends requests and dispatches responses, nothing is sent until it's spawned.
+    /// It resolves once all clones of the session are dropped and all responses
+    /// are received.
+    pub fn new<S>(stream: S, compressor: Compression) -> (AsyncSession, AsyncConnection<S>)
+        where S: AsyncRead + AsyncWrite
+    {
+        let (sender, receiver) = mpsc::unbounded();
+        let session = AsyncSession {
+            compressor: compressor,
+            requests: sender,
+        };
+        let codec = AsyncFrameCodec::new(FrameCodec::new(), compressor);
+        let connection = AsyncConnection {
+            framed: stream.framed(codec),
+            requests: receiver,
+            requests_closed: false,
+            next_request: None,
+            streams: StreamIdAllocator::new(MAX_STREAMS),
+            pending: HashMap::new(),
+        };
+
+        (session, connection)
+    }
+
+    /// Sends provided request frame. Frames of type `Error` are converted into
+    /// `error::Error::Server`.
+    pub fn send(&self, frame: Frame) -> FrameFuture {
+        let (responder, response) = oneshot::channel();
+        let request = Request {
+            frame: frame,
+            responder: responder,
+        };
+        if self.requests.unbounded_send(request).is_err() {
+            return Box::new(future::err(closed_err()));
+        }
+
+        Box::new(response.then(|result| match result {
+            Ok(response) => response,
+            Err(_) => Err(closed_err()),
+        }))
+    }
+
+    /// Starts the session with the compression algorithm it was created with.
+    /// Servers which require authentication are not supported yet.
+    pub fn startup(&self) -> FrameFuture {
+        Box::new(self.send(Frame::new_req_startup(self.compressor.as_str()))
+            .and_then(|frame| match frame.opcode {
+                Opcode::Ready => Ok(frame),
+                opcode => {
+                    let msg = format!("Ready response is expected on startup, got {:?}", opcode);
+                    Err(error::Error::General(msg))
+                }
+            }))
+    }
+
+    pub fn query(&self, query: Query) -> FrameFuture {
+        self.send(Frame::query(query))
+    }
+
+    pub fn prepare(&self, query: String) -> FrameFuture {
+        self.send(Frame::new_req_prepare(query, vec![]))
+    }
+
+    pub fn execute(&self, id: &CBytesShort, query_parameters: QueryParams) -> FrameFuture {
+        self.send(Frame::new_req_execute(id, query_parameters, vec![]))
+    }
+}
+
+fn closed_err() -> error::Error {
+    error::Error::General("Connection is closed".to_string())
+}
+
+/// Future which drives IO of a connection shared by `AsyncSession` clones.
+pub struct AsyncConnection<S> {
+    framed: Framed<S, AsyncFrameCodec>,
+    requests: mpsc::UnboundedReceiver<Request>,
+    requests_closed: bool,
+    // a request which waits for a free stream id or for the stream to accept it
+    next_request: Option<Request>,
+    streams: StreamIdAllocator,
+    pending: HashMap<u64, oneshot::Sender<error::Result<Frame>>>,
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncConnection<S> {
+    // Sends queued requests while stream ids are available. Returns `true` if any
+    // request was sent.
+    fn send_requests(&mut self) -> error::Result<bool> {
+        let mut sent = false;
+        loop {
+            if self.next_request.is_none() {
+                match self.requests.poll() {
+                    Ok(Async::Ready(Some(request))) => self.next_request = Some(request),
+                    Ok(Async::Ready(None)) => {
+                        self.requests_closed = true;
+                        break;
+                    }
+                    Ok(Async::NotReady) | Err(_) => break,
+                }
+            }
+
+            // requests wait for a response to release a stream id if all of them are in use
+            let stream = match self.streams.try_allocate() {
+                Some(stream) => stream,
+                None => break,
+            };
+            let Request { mut frame, responder } = self.next_request.take().unwrap();
+            if responder.is_canceled() {
+                self.streams.release(stream);
+                continue;
+            }
+            frame.stream = stream;
+            match try!(self.framed.start_send(frame)) {
+                AsyncSink::Ready => {
+                    self.pending.insert(stream, responder);
+                    sent = true;
+                }
+                AsyncSink::NotReady(frame) => {
+                    self.streams.release(stream);
+                    self.next_request = Some(Request {
+                        frame: frame,
+                        responder: responder,
+                    });
+                    break;
+                }
+            }
+        }
+
+        try!(self.framed.poll_complete());
+        Ok(sent)
+    }
+
+    // Dispatches received responses to requests. Returns `true` if any response
+    // was received.
+    fn receive_responses(&mut self) -> error::Result<bool> {
+        let mut received = false;
+        loop {
+            let frame = match try!(self.framed.poll()) {
+                Async::Ready(Some(frame)) => frame,
+                Async::Ready(None) => {
+                    if self.pending.is_empty() {
+                        return Ok(received);
+                    }
+                    return Err(closed_err());
+                }
+                Async::NotReady => return Ok(received),
+            };
+            received = true;
+
+            if frame.stream == EVENT_STREAM_ID {
+                warn!("Event is received by a connection which is not registered for events");
+                continue;
+            }
+            match self.pending.remove(&frame.stream) {
+                Some(responder) => {
+                    self.streams.release(frame.stream);
+                    // the request may be dropped by now, the response is not needed then
+                    let _ = responder.send(convert_frame_into_result(frame));
+                }
+                None => warn!("Response to unknown stream {} is dropped", frame.stream),
+            }
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.requests_closed && self.next_request.is_none() && self.pending.is_empty()
+    }
+
+    fn poll_io(&mut self) -> Poll<(), error::Error> {
+        loop {
+            let sent = try!(self.send_requests());
+            let received = try!(self.receive_responses());
+            if self.is_done() {
+                return Ok(Async::Ready(()));
+            }
+            if !sent && !received {
+                return Ok(Async::NotReady);
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> Future for AsyncConnection<S> {
+    type Item = ();
+    type Error = error::Error;
+
+    /// The connection fails on IO errors. Pending requests fail along with it.
+    fn poll(&mut self) -> Poll<(), error::Error> {
+        let result = self.poll_io();
+        if let Err(ref err) = result {
+            let msg = format!("Connection failed: {}", err);
+            for (_, responder) in self.pending.drain() {
+                let _ = responder.send(Err(error::Error::General(msg.clone())));
+            }
+            if let Some(request) = self.next_request.take() {
+                let _ = request.responder.send(Err(error::Error::General(msg.clone())));
+            }
+        }
+
+        result
+    }
+}
@@ -1,8 +1,36 @@
 use types::CBytes;
 
+/// Authenticator drives SASL handshake which is started by a server with AUTHENTICATE
+/// response. A driver sends `initial_response` in AUTH_RESPONSE request, then the server
+/// either accepts it with AUTH_SUCCESS or sends AUTH_CHALLENGE which is answered with
+/// `evaluate_challenge` result until the server is satisfied. Mechanisms which require
+/// several round trips (e.g. Kerberos) keep their state between challenges.
 pub trait Authenticator: Clone {
     fn get_auth_token(&self) -> CBytes;
     fn get_cassandra_name(&self) -> Option<&str>;
+
+    /// Returns a token which is sent in the first AUTH_RESPONSE request.
+    fn initial_response(&self) -> CBytes {
+        self.get_auth_token()
+    }
+
+    /// Returns a token which is sent in response to AUTH_CHALLENGE with provided
+    /// `challenge` token. `None` means that the authenticator has completed its part
+    /// of the exchange, so one more challenge from a server is treated as an authentication
+    /// error. Authenticators which don't expect challenges at all return `None`.
+    fn evaluate_challenge(&mut self, _challenge: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+// Builds SASL PLAIN token `\0username\0password`.
+fn plain_token(username: &str, password: &str) -> CBytes {
+    let mut token = vec![0];
+    token.extend_from_slice(username.as_bytes());
+    token.push(0);
+    token.extend_from_slice(password.as_bytes());
+
+    CBytes::new(token)
 }
 
 #[derive(Debug, Clone)]
@@ -22,12 +50,35 @@ impl<'a> PasswordAuthenticator<'a> {
 
 impl<'a> Authenticator for PasswordAuthenticator<'a> {
     fn get_auth_token(&self) -> CBytes {
-        let mut token = vec![0];
-        token.extend_from_slice(self.username.as_bytes());
-        token.push(0);
-        token.extend_from_slice(self.password.as_bytes());
+        return plain_token(self.username, self.password);
+    }
+
+    fn get_cassandra_name(&self) -> Option<&str> {
+        return Some("org.apache.cassandra.auth.PasswordAuthenticator");
+    }
+}
 
-        return CBytes::new(token);
+/// Authenticator which uses SASL PLAIN mechanism to authenticate with username
+/// and password, i.e. it's intended to be used with `PasswordAuthenticator`
+/// of Cassandra. In opposite to `PasswordAuthenticator` it owns credentials.
+#[derive(Debug, Clone)]
+pub struct PlainTextAuthenticator {
+    username: String,
+    password: String,
+}
+
+impl PlainTextAuthenticator {
+    pub fn new<S: Into<String>>(username: S, password: S) -> PlainTextAuthenticator {
+        return PlainTextAuthenticator {
+            username: username.into(),
+            password: password.into(),
+        };
+    }
+}
+
+impl Authenticator for PlainTextAuthenticator {
+    fn get_auth_token(&self) -> CBytes {
+        return plain_token(self.username.as_str(), self.password.as_str());
     }
 
     fn get_cassandra_name(&self) -> Option<&str> {
@@ -83,6 +134,17 @@ mod tests {
         assert_eq!(auth.get_auth_token().into_plain(), expected_token);
     }
 
+    #[test]
+    fn test_plain_text_authenticator() {
+        let auth = PlainTextAuthenticator::new("user", "password");
+        assert_eq!(auth.get_cassandra_name(),
+                   Some("org.apache.cassandra.auth.PasswordAuthenticator"));
+        assert_eq!(auth.initial_response().into_plain(),
+                   b"\0user\0password".to_vec());
+        let mut auth = auth;
+        assert_eq!(auth.evaluate_challenge(&[1]), None);
+    }
+
     #[test]
     fn test_authenticator_none_get_cassandra_name() {
         let auth = NoneAuthenticator;
@@ -1,30 +1,80 @@
 //! The modules which contains CDRS Cassandra client.
+use std::cmp;
 use std::net;
 use std::io;
 use std::collections::HashMap;
-use query::{Query, QueryParams, QueryBatch};
-use frame::{Frame, Opcode, Flag};
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use query::{Query, QueryBuilder, QueryParams, QueryBatch, PreparedStatement};
+use frame::{Frame, Opcode, Flag, Version, ProtocolVersion};
+use frame::frame_builder::FrameBuilder;
+use frame::frame_query::BodyReqQuery;
 use frame::frame_response::ResponseBody;
-use IntoBytes;
-use frame::parser::parse_frame;
+use frame::frame_result::{ResultKind, ResResultBody};
+use frame::frame_supported::{BodyResSupported, Supported};
+use frame::frame_error::ErrorCode;
+use {IntoBytes, TryFromCursor};
+use frame::parser::FrameCodec;
 use types::*;
+use types::value::Value;
+use types::rows::Row;
 use frame::events::SimpleServerEvent;
 
-use compression::Compression;
+use compression::{Compression, DEFAULT_COMPRESSION_THRESHOLD};
 use authenticators::Authenticator;
 use error;
 use transport::CDRSTransport;
+use retry_policy::{RetryPolicy, RetryDecision, DefaultRetryPolicy};
+use timestamp_generator::{TimestampGenerator, MonotonicTimestampGenerator};
+use metrics::{Metrics, NoopMetrics};
+use load_balancing::{LoadBalancingPolicy, Node, TokenRing, LOCAL_TOKENS_QUERY,
+                     PEERS_TOKENS_QUERY};
+use cluster::{Cluster, Host, LOCAL_HOSTS_QUERY, PEERS_HOSTS_QUERY};
+use reconnection_policy::{NodeStates, ReconnectionPolicy};
+use speculative_execution::{SpeculativeExecutionPolicy, execute_speculatively};
 
 use events::{Listener, EventStream, new_listener};
 
 /// CDRS driver structure that provides a basic functionality to work with DB including
 /// establishing new connection, getting supported options, preparing and executing CQL
 /// queries, using compression and other.
-#[derive(Eq,PartialEq,Ord,PartialOrd)]
 pub struct CDRS<T: Authenticator, X: CDRSTransport> {
     compressor: Compression,
     authenticator: T,
     transport: X,
+    protocol_version: ProtocolVersion,
+    codec: FrameCodec,
+}
+
+// Drivers are compared by their settings and transports. A codec is only a read buffer
+// of a connection, so it doesn't take part in comparisons.
+impl<T: Authenticator + PartialEq, X: CDRSTransport + PartialEq> PartialEq for CDRS<T, X> {
+    fn eq(&self, other: &CDRS<T, X>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T: Authenticator + Eq, X: CDRSTransport + Eq> Eq for CDRS<T, X> {}
+
+impl<T: Authenticator + PartialOrd, X: CDRSTransport + PartialOrd> PartialOrd for CDRS<T, X> {
+    fn partial_cmp(&self, other: &CDRS<T, X>) -> Option<cmp::Ordering> {
+        self.key().partial_cmp(&other.key())
+    }
+}
+
+impl<T: Authenticator + Ord, X: CDRSTransport + Ord> Ord for CDRS<T, X> {
+    fn cmp(&self, other: &CDRS<T, X>) -> cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl<T: Authenticator, X: CDRSTransport> CDRS<T, X> {
+    fn key(&self) -> (&Compression, &T, &X, ProtocolVersion) {
+        (&self.compressor, &self.authenticator, &self.transport, self.protocol_version)
+    }
 }
 
 /// Map of options supported by Cassandra server.
@@ -41,21 +91,42 @@ impl<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> CDRS<T, X> {
             compressor: Compression::None,
             authenticator: authenticator,
             transport: transport,
+            protocol_version: ProtocolVersion::default(),
+            codec: FrameCodec::new(),
         };
     }
 
     /// The method makes an Option request to DB Server. As a response the server returns
     /// a map of supported options.
     pub fn get_options(&mut self) -> error::Result<CassandraOptions> {
-        let options_frame = Frame::new_req_options().into_cbytes();
+        let options_frame = try!(self.encode(Frame::new_req_options()));
 
         try!(self.transport.write(options_frame.as_slice()));
 
-        return parse_frame(&mut self.transport, &self.compressor)
-            .map(|frame| match frame.get_body() {
-                ResponseBody::Supported(ref supported_body) => supported_body.data.clone(),
-                _ => unreachable!(),
-            });
+        self.read_supported().map(|supported_body| supported_body.data)
+    }
+
+    /// The method makes an Option request to DB Server the same way `get_options` does
+    /// and returns well-known options, i.e. supported CQL versions, compression
+    /// algorithms and protocol versions.
+    pub fn get_supported(&mut self) -> error::Result<Supported> {
+        let options_frame = try!(self.encode(Frame::new_req_options()));
+
+        try!(self.transport.write(options_frame.as_slice()));
+
+        self.read_supported().map(|supported_body| supported_body.supported())
+    }
+
+    // Reads a response to Options request. Malformed body of Supported response is
+    // returned as an error rather than a panic.
+    fn read_supported(&mut self) -> error::Result<BodyResSupported> {
+        let frame = try!(self.codec.read_frame(&mut self.transport, &self.compressor));
+        match frame.opcode {
+            Opcode::Supported => {
+                BodyResSupported::try_from_cursor(&mut io::Cursor::new(frame.body.as_slice()))
+            }
+            _ => Err(error::Error::General("Options response is not a Supported one".to_string())),
+        }
     }
 
     /// The method establishes connection to the server which address was provided on previous
@@ -67,10 +138,7 @@ impl<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> CDRS<T, X> {
     /// will be decompressed automatically.
     pub fn start(mut self, compressor: Compression) -> error::Result<Session<T, X>> {
         self.compressor = compressor;
-        let startup_frame = Frame::new_req_startup(compressor.as_str()).into_cbytes();
-
-        try!(self.transport.write(startup_frame.as_slice()));
-        let start_response = try!(parse_frame(&mut self.transport, &compressor));
+        let start_response = try!(self.startup(compressor));
 
         if start_response.opcode == Opcode::Ready {
             return Ok(Session::start(self));
@@ -78,48 +146,132 @@ impl<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> CDRS<T, X> {
 
         if start_response.opcode == Opcode::Authenticate {
             let body = start_response.get_body();
-            let authenticator = body.get_authenticator()
-                .expect("Cassandra Server did communicate that it needed password
-                authentication but the  auth schema was missing in the body response");
-
-            // This creates a new scope; avoiding a clone
-            // and we check whether
-            // 1. any authenticators has been passed in by client and if not send error back
-            // 2. authenticator is provided by the client and `auth_scheme` presented by
-            //      the server and client are same if not send error back
-            // 3. if it falls through it means the preliminary conditions are true
-
-            let auth_check = self.authenticator
-                .get_cassandra_name()
-                .ok_or(error::Error::General("No authenticator was provided ".to_string()))
-                .map(|auth| {
-                    if authenticator != auth {
-                        let io_err =
-                            io::Error::new(io::ErrorKind::NotFound,
-                                           format!("Unsupported type of authenticator. {:?} got,
-                             but {} is supported.",
-                                                   authenticator,
-                                                   authenticator));
-                        return Err(error::Error::Io(io_err));
-                    }
-                    Ok(())
-                });
+            let authenticator = try!(body.get_authenticator().ok_or(error::Error::General(
+                "Authenticate response does not contain authenticator class name".to_string())));
 
-            if let Err(err) = auth_check {
-                return Err(err);
+            // check whether
+            // 1. any authenticator has been passed in by client and if not send error back
+            // 2. `auth_scheme` presented by the server and the client are same
+            //      if not send error back
+            let auth = match self.authenticator.get_cassandra_name() {
+                Some(auth) => auth,
+                None => return Err(error::Error::AuthenticationRequired(authenticator.to_string())),
+            };
+            if authenticator != auth {
+                let io_err = io::Error::new(io::ErrorKind::NotFound,
+                                            format!("Unsupported type of authenticator. {} got, \
+                                                     but {} is supported.",
+                                                    authenticator,
+                                                    auth));
+                return Err(error::Error::Io(io_err));
             }
 
-            let auth_token_bytes = self.authenticator.get_auth_token().into_cbytes();
-            try!(self.transport
-                .write(Frame::new_req_auth_response(auth_token_bytes).into_cbytes().as_slice()));
-            try!(parse_frame(&mut self.transport, &compressor));
+            try!(self.authenticate(&compressor));
 
             return Ok(Session::start(self));
+        }
 
+        // e.g. AUTH_CHALLENGE which may only follow AUTH_RESPONSE request
+        Err(error::Error::General(format!("Unexpected {:?} response to startup request",
+                                          start_response.opcode)))
+    }
+
+    /// The method establishes connection the same way `start` does but it chooses
+    /// compression method during negotiation with the server. It requests options
+    /// supported by the server and takes the first compression from `preferred` list
+    /// which is supported by the server. If none of them is supported `Compression::None`
+    /// is used.
+    pub fn start_negotiated(mut self, preferred: &[Compression]) -> error::Result<Session<T, X>> {
+        let supported = try!(self.get_supported());
+        let compressor = Compression::negotiate(preferred, supported.compression.as_slice());
+
+        self.start(compressor)
+    }
 
+    /// Returns version of native protocol which is used by the connection.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// The method overrides maximal length (in bytes) of a response frame body, including
+    /// responses to Startup and Options requests. See `Session::max_frame_size`.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> CDRS<T, X> {
+        self.codec.set_max_frame_size(max_frame_size);
+        self
+    }
+
+    // Sends Startup request. If a server responds with protocol error because it does not
+    // support current protocol version the request is retried with the highest lower
+    // version supported by both the driver and the server.
+    fn startup(&mut self, compressor: Compression) -> error::Result<Frame> {
+        loop {
+            let startup_frame = try!(self.encode(Frame::new_req_startup(compressor.as_str())));
+            try!(self.transport.write(startup_frame.as_slice()));
+
+            let downgraded = match self.codec.read_frame(&mut self.transport, &compressor) {
+                Err(error::Error::Server(ref err)) if err.kind() == ErrorCode::Protocol => {
+                    self.protocol_version.downgrade(err.message.as_str())
+                }
+                result => return result,
+            };
+
+            match downgraded {
+                Some(version) => {
+                    warn!("Protocol version {:?} is not supported by a server, {:?} is used",
+                          self.protocol_version,
+                          version);
+                    self.protocol_version = version;
+                    self.codec.set_protocol_version(version);
+                }
+                None => {
+                    let msg = format!("None of protocol versions {:?} is supported by a server",
+                                      ProtocolVersion::all());
+                    return Err(error::Error::General(msg));
+                }
+            }
+        }
+    }
+
+    // Sends initial authentication token and answers challenges of a server until
+    // it accepts the credentials with AuthSuccess response. Rejected credentials
+    // result in `error::Error::Authentication`.
+    fn authenticate(&mut self, compressor: &Compression) -> error::Result<()> {
+        let mut token = self.authenticator.initial_response();
+
+        loop {
+            let auth_response = try!(self.encode(Frame::new_req_auth_response(token.into_plain())));
+            try!(self.transport.write(auth_response.as_slice()));
+
+            let response = match self.codec.read_frame(&mut self.transport, compressor) {
+                Err(error::Error::Server(ref err)) if err.kind() == ErrorCode::Authentication => {
+                    return Err(error::Error::Authentication(err.message.as_str().to_string()))
+                }
+                result => try!(result),
+            };
+            match response.get_body() {
+                ResponseBody::AuthSuccess(_) => return Ok(()),
+                ResponseBody::AuthChallenge(challenge) => {
+                    match self.authenticator.evaluate_challenge(challenge.data.as_slice()) {
+                        Some(response) => token = CBytes::new(response),
+                        None => {
+                            return Err(error::Error::General("Authentication is not \
+                                                              completed by a server"
+                                .to_string()))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(error::Error::General("Unexpected response to authentication \
+                                                      request"
+                        .to_string()))
+                }
+            }
         }
+    }
 
-        unimplemented!();
+    // Encodes handshake request with protocol version of the connection.
+    fn encode(&self, frame: Frame) -> error::Result<Vec<u8>> {
+        frame.encode_with_protocol(Compression::None, 0, self.protocol_version)
     }
 
     fn drop_connection(&mut self) -> error::Result<()> {
@@ -129,44 +281,312 @@ impl<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> CDRS<T, X> {
     }
 }
 
+/// Default time (in seconds) a server is given to answer a heartbeat.
+pub const DEFAULT_HEARTBEAT_TIMEOUT_SECS: u64 = 5;
+
 /// The object that provides functionality for communication with Cassandra server.
 pub struct Session<T: Authenticator, X: CDRSTransport> {
     started: bool,
     cdrs: CDRS<T, X>,
     compressor: Compression,
+    compression_threshold: usize,
+    retry_policy: Box<RetryPolicy>,
+    prepared_cache: HashMap<String, PreparedStatement>,
+    timestamp_generator: Option<Box<TimestampGenerator>>,
+    metrics: Box<Metrics>,
+    protocol_version: ProtocolVersion,
+    last_activity: Instant,
+    broken: bool,
+    heartbeat_timeout: Duration,
 }
 
 impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
     /// Creates new session basing on CDRS instance.
     pub fn start(cdrs: CDRS<T, X>) -> Session<T, X> {
         let compressor = cdrs.compressor.clone();
+        let protocol_version = cdrs.protocol_version;
         return Session {
             cdrs: cdrs,
             started: true,
             compressor: compressor,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            retry_policy: Box::new(DefaultRetryPolicy),
+            prepared_cache: HashMap::new(),
+            timestamp_generator: Some(Box::new(MonotonicTimestampGenerator::new())),
+            metrics: Box::new(NoopMetrics),
+            protocol_version: protocol_version,
+            last_activity: Instant::now(),
+            broken: false,
+            heartbeat_timeout: Duration::from_secs(DEFAULT_HEARTBEAT_TIMEOUT_SECS),
         };
     }
 
+    /// Returns version of native protocol negotiated with a server.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Creates new session connected to the first available node of a query plan
+    /// produced by provided load balancing policy. `connect` establishes a session
+    /// to a single node; if it fails the next node of the plan is tried. The error
+    /// of the last node is returned if none of them is available.
+    ///
+    /// ```no_run
+    /// use cdrs::client::{CDRS, Session};
+    /// use cdrs::authenticators::NoneAuthenticator;
+    /// use cdrs::compression::Compression;
+    /// use cdrs::load_balancing::{Node, RoundRobin};
+    /// use cdrs::transport::TransportTcp;
+    ///
+    /// let nodes = vec![Node::new("127.0.0.1".parse().unwrap(), "dc1".to_string()),
+    ///                  Node::new("127.0.0.2".parse().unwrap(), "dc1".to_string())];
+    /// let session = Session::connect(&RoundRobin::new(nodes), |node| {
+    ///     let addr = format!("{}:9042", node.address);
+    ///     let transport = try!(TransportTcp::new(addr.as_str()));
+    ///     CDRS::new(transport, NoneAuthenticator).start(Compression::None)
+    /// });
+    /// ```
+    pub fn connect<P, F>(policy: &P, mut connect: F) -> error::Result<Session<T, X>>
+        where P: LoadBalancingPolicy + ?Sized,
+              F: FnMut(&Node) -> error::Result<Session<T, X>>
+    {
+        let mut last_err = error::Error::General("Query plan is empty".to_string());
+        for node in policy.query_plan(None) {
+            match connect(node) {
+                Ok(session) => return Ok(session),
+                Err(err) => {
+                    warn!("Failed to connect to {}: {:?}", node.address, err);
+                    last_err = err;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Creates new session the same way `connect` does but skips nodes which are marked
+    /// down by `states`. A node which cannot be connected is marked down and reconnected
+    /// in background in accordance to the reconnection policy of `states`, so next sessions
+    /// are not established to it until a reconnection succeeds. Background reconnection
+    /// is stopped by `NodeStates::stop`.
+    pub fn connect_with_states<P, R, F>(policy: &P,
+                                        states: &Arc<NodeStates<R>>,
+                                        connect: F)
+                                        -> error::Result<Session<T, X>>
+        where P: LoadBalancingPolicy + ?Sized,
+              R: ReconnectionPolicy + 'static,
+              F: Fn(&Node) -> error::Result<Session<T, X>> + Send + Sync + 'static,
+              T: 'static,
+              X: 'static
+    {
+        let connect = Arc::new(connect);
+        let mut last_err = error::Error::General("Query plan is empty".to_string());
+        for node in policy.query_plan(None) {
+            if !states.is_up(node) {
+                last_err = error::Error::General(format!("Node {} is down", node.address));
+                continue;
+            }
+
+            match connect(node) {
+                Ok(session) => return Ok(session),
+                Err(err) => {
+                    warn!("Failed to connect to {}: {:?}", node.address, err);
+                    let connect = connect.clone();
+                    // the established session only proves the node is up again
+                    let reconnect = move |node: &Node| connect(node).map(|mut s| s.end());
+                    NodeStates::reconnect(states, node.clone(), reconnect);
+                    last_err = err;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Executes provided query on nodes of a query plan produced by `policy` in accordance
+    /// to speculative execution policy (see `execute_speculatively`). Each execution uses
+    /// a session to its node returned by `session`, e.g. a session borrowed from a pool
+    /// of the node. Only idempotent queries (see `QueryBuilder::idempotent`) are executed
+    /// speculatively. A cancelled execution doesn't send the query if it's not sent yet,
+    /// otherwise it waits for a response no longer than a read timeout of the transport.
+    pub fn query_speculatively<P, S, F, C>(policy: &P,
+                                           speculative_policy: &S,
+                                           query: Query,
+                                           session: F)
+                                           -> error::Result<Frame>
+        where P: LoadBalancingPolicy + ?Sized,
+              S: SpeculativeExecutionPolicy + ?Sized,
+              F: Fn(&Node) -> error::Result<C> + Send + Sync + 'static,
+              C: DerefMut<Target = Session<T, X>>
+    {
+        let nodes = policy.query_plan(None).cloned().collect();
+        let idempotent = query.idempotent;
+        execute_speculatively(speculative_policy, idempotent, nodes, move |node, cancellation| {
+            let mut session = try!(session(node));
+            try!(cancellation.check());
+            session.query(query.clone(), false, false)
+        })
+    }
+
     /// The method overrides a compression method of current session
     pub fn compressor(&mut self, compressor: Compression) -> &mut Self {
         self.compressor = compressor;
         return self;
     }
 
+    /// The method overrides a minimal size of request body (in bytes) which gets compressed.
+    /// Smaller bodies are sent uncompressed. It's `DEFAULT_COMPRESSION_THRESHOLD` by default.
+    pub fn compression_threshold(&mut self, threshold: usize) -> &mut Self {
+        self.compression_threshold = threshold;
+        return self;
+    }
+
+    /// The method overrides maximal length (in bytes) of a response frame body. Frames
+    /// with longer bodies are rejected with an IO error. It's `DEFAULT_MAX_FRAME_SIZE`
+    /// (256 MB) by default.
+    pub fn max_frame_size(&mut self, max_frame_size: usize) -> &mut Self {
+        self.cdrs.codec.set_max_frame_size(max_frame_size);
+        return self;
+    }
+
+    /// The method overrides a retry policy of current session. `DefaultRetryPolicy`
+    /// is used if it's not set. The policy is consulted when a server responds to
+    /// query, execute or batch request with `ReadTimeout`, `WriteTimeout` or `Unavailable`
+    /// error.
+    pub fn retry_policy<P: RetryPolicy + 'static>(&mut self, retry_policy: P) -> &mut Self {
+        self.retry_policy = Box::new(retry_policy);
+        return self;
+    }
+
+    /// The method sets a generator of client-side timestamps. Query, execute and batch
+    /// requests which don't have explicitly provided timestamp are sent with a timestamp
+    /// produced by the generator. It's `MonotonicTimestampGenerator` by default.
+    pub fn timestamp_generator<G: TimestampGenerator + 'static>(&mut self,
+                                                                generator: G)
+                                                                -> &mut Self {
+        self.timestamp_generator = Some(Box::new(generator));
+        return self;
+    }
+
+    /// The method disables client-side timestamps, so a server assigns timestamps
+    /// to requests which don't have explicitly provided ones.
+    pub fn without_timestamp_generator(&mut self) -> &mut Self {
+        self.timestamp_generator = None;
+        return self;
+    }
+
+    /// The method sets metrics which are notified about requests, responses and errors
+    /// of the session. `NoopMetrics` are used if it's not set. Since the connection
+    /// is already established, `on_connection_opened` is called at this point.
+    pub fn metrics<M: Metrics + 'static>(&mut self, metrics: M) -> &mut Self {
+        self.metrics = Box::new(metrics);
+        if self.started {
+            self.metrics.on_connection_opened();
+        }
+        return self;
+    }
+
+    /// Returns `true` if the connection of the session is considered dead, i.e. a heartbeat
+    /// was not answered or a request failed with IO error. Such session should be replaced.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// Returns time elapsed since the last request sent via the session.
+    pub fn idle_time(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// The method overrides time a server is given to answer a heartbeat. It's
+    /// `DEFAULT_HEARTBEAT_TIMEOUT_SECS` by default.
+    pub fn heartbeat_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.heartbeat_timeout = timeout;
+        return self;
+    }
+
+    /// Sends Options request and expects Supported response to make sure the connection
+    /// is alive. If the server does not answer properly or within the heartbeat timeout
+    /// (see `heartbeat_timeout`) the session is marked as broken. The timeout is applied
+    /// only if the transport supports read timeouts.
+    pub fn heartbeat(&mut self) -> error::Result<()> {
+        let options_frame = try!(self.cdrs.encode(Frame::new_req_options()));
+        let previous_timeout = self.set_heartbeat_timeout();
+        let mut result = self.request(options_frame.as_slice()).and_then(|frame| {
+            match frame.opcode {
+                Opcode::Supported => Ok(()),
+                opcode => {
+                    let msg = format!("Unexpected response {:?} to heartbeat", opcode);
+                    Err(error::Error::General(msg))
+                }
+            }
+        });
+        if let Some(previous_timeout) = previous_timeout {
+            let restored = self.cdrs.transport.set_read_timeout(previous_timeout);
+            result = result.and_then(|_| restored.map_err(error::Error::Io));
+        }
+
+        if result.is_err() {
+            self.broken = true;
+        }
+
+        result
+    }
+
+    // Sets read timeout of the transport to the heartbeat timeout unless a shorter one
+    // is already set. It returns the timeout which should be restored afterwards.
+    fn set_heartbeat_timeout(&mut self) -> Option<Option<Duration>> {
+        let timeout = self.heartbeat_timeout;
+        let previous = match self.cdrs.transport.read_timeout() {
+            Ok(previous) => previous,
+            Err(_) => return None,
+        };
+        if previous.map(|previous| previous <= timeout).unwrap_or(false) {
+            return None;
+        }
+
+        match self.cdrs.transport.set_read_timeout(Some(timeout)) {
+            Ok(_) => Some(previous),
+            Err(err) => {
+                debug!("Heartbeat is sent without timeout: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Sends a heartbeat (see `heartbeat`) if the session has been idle for at least
+    /// `interval`.
+    pub fn heartbeat_if_idle(&mut self, interval: Duration) -> error::Result<()> {
+        if self.idle_time() < interval {
+            return Ok(());
+        }
+
+        self.heartbeat()
+    }
+
     /// Manually ends current session.
     /// Apart of that session will be ended automatically when the instance is dropped.
     pub fn end(&mut self) {
-        if self.started {
-            self.started = false;
-            match self.cdrs.drop_connection() {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("Error occured during dropping CDRS {:?}", err);
-                }
+        match self.close() {
+            Ok(_) => (),
+            Err(err) => {
+                println!("Error occured during dropping CDRS {:?}", err);
             }
         }
     }
 
+    /// Closes the session the same way `end` does but returns an error if the connection
+    /// cannot be shut down. Requests made via the session are rejected from now on.
+    /// Closing of already closed session has no effect.
+    pub fn close(&mut self) -> error::Result<()> {
+        if !self.started {
+            return Ok(());
+        }
+
+        self.started = false;
+        self.metrics.on_connection_closed();
+        self.cdrs.drop_connection()
+    }
+
     /// The method makes a request to DB Server to prepare provided query.
     pub fn prepare(&mut self,
                    query: String,
@@ -181,11 +601,30 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
             flags.push(Flag::Warning);
         }
 
-        let options_frame = Frame::new_req_prepare(query, flags).into_cbytes();
+        let options_frame = try!(Frame::new_req_prepare(query, flags)
+            .encode_with_protocol(self.compressor,
+                                  self.compression_threshold,
+                                  self.protocol_version));
 
-        try!(self.cdrs.transport.write(options_frame.as_slice()));
+        self.request(options_frame.as_slice())
+    }
+
+    /// The method prepares provided query the same way `prepare` does but caches
+    /// prepared statement by query string. Subsequent calls with the same query
+    /// return cached statement without making a request to DB Server.
+    pub fn prepare_cached(&mut self, query: &str) -> error::Result<PreparedStatement> {
+        if let Some(prepared) = self.prepared_cache.get(query) {
+            return Ok(prepared.clone());
+        }
 
-        parse_frame(&mut self.cdrs.transport, &self.compressor)
+        let prepared = try!(try!(self.prepare(query.to_string(), false, false))
+            .get_body()
+            .into_prepared()
+            .ok_or(error::Error::General("Prepare response does not contain prepared result"
+                .to_string())));
+        self.prepared_cache.insert(query.to_string(), prepared.clone());
+
+        Ok(prepared)
     }
 
     /// The method makes a request to DB Server to execute a query with provided id
@@ -193,7 +632,7 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
     /// returns back to a driver as a response to `prepare` request.
     pub fn execute(&mut self,
                    id: &CBytesShort,
-                   query_parameters: QueryParams,
+                   mut query_parameters: QueryParams,
                    with_tracing: bool,
                    with_warnings: bool)
                    -> error::Result<Frame> {
@@ -205,10 +644,47 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
         if with_warnings {
             flags.push(Flag::Warning);
         }
-        let options_frame = Frame::new_req_execute(id, query_parameters, flags).into_cbytes();
+        if query_parameters.timestamp.is_none() {
+            if let Some(timestamp) = self.next_timestamp() {
+                query_parameters.set_timestamp(timestamp);
+            }
+        }
+        let idempotent = query_parameters.idempotent;
+        let options_frame = try!(Frame::new_req_execute(id, query_parameters, flags)
+            .encode_with_protocol(self.compressor,
+                                  self.compression_threshold,
+                                  self.protocol_version));
+
+        return self.send_with_retries(options_frame, idempotent);
+    }
+
+    /// The method executes a prepared query the same way `execute` does. If the server
+    /// does not know provided `id` (i.e. responds with `Unprepared` error, which may happen
+    /// when a node was restarted) then `query` is re-prepared and execution is retried
+    /// once with the id returned by the server. Cached prepared statement of `query`
+    /// (see `prepare_cached`) is replaced with the re-prepared one.
+    pub fn execute_or_reprepare(&mut self,
+                                query: &str,
+                                id: &CBytesShort,
+                                query_parameters: QueryParams,
+                                with_tracing: bool,
+                                with_warnings: bool)
+                                -> error::Result<Frame> {
+        let result = self.execute(id, query_parameters.clone(), with_tracing, with_warnings);
+
+        let is_unprepared = match result {
+            Err(error::Error::Server(ref err)) => err.unprepared().is_some(),
+            _ => false,
+        };
+
+        if !is_unprepared {
+            return result;
+        }
+
+        self.prepared_cache.remove(query);
+        let prepared = try!(self.prepare_cached(query));
 
-        (self.cdrs.transport.write(options_frame.as_slice()))?;
-        return parse_frame(&mut self.cdrs.transport, &self.compressor);
+        self.execute(&prepared.id, query_parameters, with_tracing, with_warnings)
     }
 
     /// The method makes a request to DB Server to execute a query provided in `query` argument.
@@ -219,13 +695,13 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
     /// session.query_with_builder(qb);
     /// ```
     pub fn query(&mut self,
-                 query: Query,
+                 mut query: Query,
                  with_tracing: bool,
                  with_warnings: bool)
                  -> error::Result<Frame> {
         let mut flags = vec![];
 
-        if with_tracing {
+        if with_tracing || query.with_tracing.unwrap_or(false) {
             flags.push(Flag::Tracing);
         }
 
@@ -233,23 +709,86 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
             flags.push(Flag::Warning);
         }
 
-        let query_frame = Frame::new_req_query(query.query,
-                                               query.consistency,
-                                               query.values,
-                                               query.with_names,
-                                               query.page_size,
-                                               query.paging_state,
-                                               query.serial_consistency,
-                                               query.timestamp,
-                                               flags)
-            .into_cbytes();
+        query.timestamp = query.timestamp.or_else(|| self.next_timestamp());
+        let idempotent = query.idempotent;
+        let custom_payload = query.custom_payload.take();
 
-        try!(self.cdrs.transport.write(query_frame.as_slice()));
-        return parse_frame(&mut self.cdrs.transport, &self.compressor);
+        let body = BodyReqQuery::from_query(query);
+        try!(body.query_params.check_protocol(self.protocol_version));
+        let mut query_frame = FrameBuilder::new(Opcode::Query)
+            .flags(flags)
+            .body(body.into_cbytes_with_protocol(self.protocol_version))
+            .build();
+        if let Some(custom_payload) = custom_payload {
+            query_frame = query_frame.with_custom_payload(custom_payload);
+        }
+        let query_frame = try!(query_frame.encode_with_protocol(self.compressor,
+                                                                self.compression_threshold,
+                                                                self.protocol_version));
+
+        return self.send_with_retries(query_frame, idempotent);
+    }
+
+    /// The method returns an iterator over pages of rows returned by provided `query`.
+    /// The query is re-issued with a paging state returned by the server until the last
+    /// page is fetched. Size of a page is defined by `page_size` of the query.
+    pub fn query_pages<'a>(&'a mut self, query: Query) -> RowsPager<'a, T, X> {
+        RowsPager {
+            session: self,
+            query: query,
+            has_more_pages: true,
+        }
+    }
+
+    /// The method queries events of a trace session with provided id, i.e. `tracing_id`
+    /// of a response to a query which was executed with tracing enabled.
+    pub fn trace_events(&mut self, tracing_id: &Uuid) -> error::Result<Frame> {
+        let query = QueryBuilder::new("SELECT * FROM system_traces.events WHERE session_id = ?")
+            .values(vec![Value::from(tracing_id.clone())])
+            .finalize();
+
+        self.query(query, false, false)
+    }
+
+    /// The method fetches token ring of a cluster from `system.local` and `system.peers`
+    /// tables. The ring can be used by `TokenAwareLoadBalancing` policy.
+    pub fn token_ring(&mut self) -> error::Result<TokenRing> {
+        let rows = try!(self.system_rows(&[LOCAL_TOKENS_QUERY, PEERS_TOKENS_QUERY]));
+        TokenRing::from_rows(rows)
+    }
+
+    /// The method fetches all hosts of a cluster along with their data centers, racks
+    /// and tokens from `system.local` and `system.peers` tables. Discovered hosts
+    /// are usually registered in a `Cluster`.
+    pub fn discover_hosts(&mut self) -> error::Result<Vec<Host>> {
+        let rows = try!(self.system_rows(&[LOCAL_HOSTS_QUERY, PEERS_HOSTS_QUERY]));
+        let mut hosts = Vec::with_capacity(rows.len());
+        for row in rows {
+            hosts.push(try!(Host::from_row(&row)));
+        }
+
+        Ok(hosts)
+    }
+
+    fn system_rows(&mut self, queries: &[&str]) -> error::Result<Vec<Row>> {
+        let mut rows = vec![];
+        for cql in queries {
+            let query = QueryBuilder::new(*cql).finalize();
+            let body = try!(self.query(query, false, false)).get_body();
+            match body.into_rows() {
+                Some(query_rows) => rows.extend(query_rows),
+                None => {
+                    let msg = format!("Query {} did not return rows", cql);
+                    return Err(error::Error::General(msg));
+                }
+            }
+        }
+
+        Ok(rows)
     }
 
     pub fn batch(&mut self,
-                 batch_query: QueryBatch,
+                 mut batch_query: QueryBatch,
                  with_tracing: bool,
                  with_warnings: bool)
                  -> error::Result<Frame> {
@@ -263,19 +802,1219 @@ impl<T: Authenticator, X: CDRSTransport> Session<T, X> {
             flags.push(Flag::Warning);
         }
 
-        let query_frame = Frame::new_req_batch(batch_query, flags).into_cbytes();
+        if batch_query.timestamp.is_none() {
+            if let Some(timestamp) = self.next_timestamp() {
+                batch_query.set_timestamp(timestamp);
+            }
+        }
+
+        try!(batch_query.check_protocol(self.protocol_version));
+        let idempotent = batch_query.idempotent;
+        let custom_payload = batch_query.custom_payload.take();
+        let mut query_frame = FrameBuilder::new(Opcode::Batch)
+            .flags(flags)
+            .body(batch_query.into_cbytes_with_protocol(self.protocol_version))
+            .build();
+        if let Some(custom_payload) = custom_payload {
+            query_frame = query_frame.with_custom_payload(custom_payload);
+        }
+        let query_frame = try!(query_frame.encode_with_protocol(self.compressor,
+                                                                self.compression_threshold,
+                                                                self.protocol_version));
 
-        try!(self.cdrs.transport.write(query_frame.as_slice()));
-        return parse_frame(&mut self.cdrs.transport, &self.compressor);
+        return self.send_with_retries(query_frame, idempotent);
+    }
+
+    // Returns next timestamp of timestamp generator if it's set.
+    fn next_timestamp(&self) -> Option<i64> {
+        self.timestamp_generator.as_ref().map(|generator| generator.next_timestamp())
+    }
+
+    // Sends provided frame and reads a response to it. The session is marked as broken
+    // if it fails with IO error.
+    fn request(&mut self, frame_bytes: &[u8]) -> error::Result<Frame> {
+        if !self.started {
+            return Err(error::Error::General("Session is closed".to_string()));
+        }
+
+        self.last_activity = Instant::now();
+        self.metrics.on_request_started();
+        let result = self.cdrs.transport.write(frame_bytes).map_err(error::Error::Io);
+        if result.is_ok() && frame_bytes.len() > 4 {
+            self.metrics.on_request_sent(&Opcode::from(frame_bytes[4]), frame_bytes.len());
+        }
+        let mut bytes_read = 0;
+        let result = result.and_then(|_| {
+            let mut reader = CountingReader {
+                inner: &mut self.cdrs.transport,
+                bytes: 0,
+            };
+            let result = self.cdrs.codec.read_frame(&mut reader, &self.compressor);
+            bytes_read = reader.bytes;
+            result
+        });
+        if bytes_read > 0 {
+            self.metrics.on_bytes_read(bytes_read);
+        }
+
+        match result {
+            Ok(ref frame) => self.metrics.on_response(self.last_activity.elapsed(), &frame.opcode),
+            Err(error::Error::Server(ref err)) => {
+                self.metrics.on_response(self.last_activity.elapsed(), &Opcode::Error);
+                self.metrics.on_error(err);
+            }
+            Err(error::Error::Io(ref err)) => {
+                self.broken = true;
+                self.metrics.on_io_error(err);
+            }
+            Err(_) => (),
+        }
+        self.metrics.on_request_finished();
+
+        result
     }
 
-    /// It consumes CDRS
+    // Sends provided frame and consults retry policy if a server responds with an error.
+    // Timeouts are retried only if the request is `idempotent`. The session has no other
+    // host, so `RetryNextHost` retries the request on the same connection.
+    fn send_with_retries(&mut self,
+                         frame_bytes: Vec<u8>,
+                         idempotent: bool)
+                         -> error::Result<Frame> {
+        let mut retries = 0;
+
+        loop {
+            let result = self.request(frame_bytes.as_slice());
+
+            let decision = match result {
+                Err(error::Error::Server(ref err)) => {
+                    self.retry_policy.on_request_error(err, retries, idempotent)
+                }
+                _ => return result,
+            };
+
+            match decision {
+                RetryDecision::Retry |
+                RetryDecision::RetryNextHost => retries += 1,
+                RetryDecision::Ignore => return Ok(new_res_void()),
+                RetryDecision::Rethrow => return result,
+            }
+        }
+    }
+
+    /// It consumes CDRS. The session registers its connection for provided events
+    /// (`REGISTER` request) and turns it into a `Listener` of events pushed by a server,
+    /// so the connection is not used for other requests anymore.
     pub fn listen_for<'a>(mut self,
                           events: Vec<SimpleServerEvent>)
                           -> error::Result<(Listener<X>, EventStream)> {
-        let query_frame = Frame::new_req_register(events).into_cbytes();
+        let query_frame = try!(self.cdrs.encode(Frame::new_req_register(events)));
         try!(self.cdrs.transport.write(query_frame.as_slice()));
-        try!(parse_frame(&mut self.cdrs.transport, &self.compressor));
+        let response = try!(self.cdrs.codec.read_frame(&mut self.cdrs.transport, &self.compressor));
+        if response.opcode != Opcode::Ready {
+            let msg = format!("Unexpected response {:?} to Register request", response.opcode);
+            return Err(error::Error::General(msg));
+        }
+
         Ok(new_listener(self.cdrs.transport))
     }
+
+    /// It consumes the session. Hosts of a cluster the session is connected to are
+    /// discovered (see `discover_hosts`) and registered in a new `Cluster`. Then the session
+    /// is registered for topology and status change events (see `listen_for`) and a thread
+    /// is spawned which updates the cluster with received events (see `Cluster::on_event`).
+    /// Hosts are re-discovered via sessions established by `connect`. The returned listener
+    /// should be started in a separate thread, the cluster isn't updated once it stops.
+    pub fn discover_cluster<F>(mut self,
+                               mut connect: F)
+                               -> error::Result<(Arc<Cluster>, Listener<X>)>
+        where F: FnMut() -> error::Result<Session<T, X>> + Send + 'static,
+              T: 'static,
+              X: 'static
+    {
+        let cluster = Arc::new(Cluster::new(try!(self.discover_hosts())));
+        let (listener, stream) = try!(self.listen_for(vec![SimpleServerEvent::TopologyChange,
+                                                           SimpleServerEvent::StatusChange]));
+
+        let updated = cluster.clone();
+        thread::spawn(move || for event in stream {
+            let discover = || connect().and_then(|mut session| session.discover_hosts());
+            match updated.on_event(&event, discover) {
+                Ok(ref added) if !added.is_empty() => debug!("Hosts {:?} are discovered", added),
+                Ok(_) => (),
+                Err(err) => warn!("Failed to update cluster with {:?}: {:?}", event, err),
+            }
+        });
+
+        Ok((cluster, listener))
+    }
+}
+
+/// Iterator over pages of rows returned by a query. Each item is either rows of
+/// a single page or an error which happened while fetching it. It's created by
+/// `Session::query_pages`.
+pub struct RowsPager<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> {
+    session: &'a mut Session<T, X>,
+    query: Query,
+    has_more_pages: bool,
+}
+
+// Reader which counts bytes read from an underlying reader.
+struct CountingReader<'a, R: io::Read + 'a> {
+    inner: &'a mut R,
+    bytes: usize,
+}
+
+impl<'a, R: io::Read + 'a> io::Read for CountingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(self.inner.read(buf));
+        self.bytes += read;
+        Ok(read)
+    }
+}
+
+impl<'a, T: Authenticator + 'a, X: CDRSTransport + 'a> Iterator for RowsPager<'a, T, X> {
+    type Item = error::Result<Vec<Row>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.has_more_pages {
+            return None;
+        }
+
+        // the pager stops after the first error
+        self.has_more_pages = false;
+        let body = match self.session.query(self.query.clone(), false, false) {
+            Ok(frame) => frame.get_body(),
+            Err(err) => return Some(Err(err)),
+        };
+
+        match body {
+            ResponseBody::Result(ResResultBody::Rows(rows)) => {
+                self.has_more_pages = rows.metadata.paging_state.is_some();
+                self.query.paging_state = rows.metadata.paging_state.clone();
+                Some(Ok(Row::from_frame_body(rows)))
+            }
+            _ => Some(Err(error::Error::General("Paged query did not return rows".to_string()))),
+        }
+    }
+}
+
+// Creates a void result frame which is returned when an error is ignored by retry policy.
+fn new_res_void() -> Frame {
+    Frame {
+        version: Version::Response,
+        flags: vec![],
+        opcode: Opcode::Result,
+        stream: 0,
+        body: ResultKind::Void.into_cbytes(),
+        tracing_id: None,
+        warnings: vec![],
+        custom_payload: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use authenticators::NoneAuthenticator;
+    use transport::TransportMock;
+    use frame::events::{ServerEvent, StatusChangeType};
+    use consistency::Consistency;
+    use query::{BatchQueryBuilder, QueryParamsBuilder};
+    use types::{to_int, to_short, CBytes, IntoRustByName};
+    use std::sync::{Arc, Mutex};
+    use frame::frame_error::{CDRSError, ReadTimeoutError, UnavailableError, WriteTimeoutError};
+    use super::*;
+
+    // Builds a Rows result frame with a single int column `id`.
+    fn rows_frame(ids: &[i32], paging_state: Option<Vec<u8>>) -> Vec<u8> {
+        let mut body = to_int(0x0002);
+        let flags = if paging_state.is_some() { 0x0003 } else { 0x0001 };
+        body.extend_from_slice(to_int(flags).as_slice());
+        body.extend_from_slice(to_int(1).as_slice());
+        if let Some(state) = paging_state {
+            body.extend_from_slice(CBytes::new(state).into_cbytes().as_slice());
+        }
+        for name in &["ks", "table", "id"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(to_short(0x0009).as_slice());
+        body.extend_from_slice(to_int(ids.len() as i32).as_slice());
+        for id in ids {
+            body.extend_from_slice(CBytes::new(to_int(*id)).into_cbytes().as_slice());
+        }
+
+        let mut frame = vec![0x84, 0, 0, 0, 0x08];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    // Builds a Supported frame which contains provided compression algorithms.
+    fn supported_frame(compression: &[&str]) -> Vec<u8> {
+        let mut body = to_short(1);
+        body.extend_from_slice(CString::new("COMPRESSION".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(to_short(compression.len() as i16).as_slice());
+        for name in compression {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+
+        let mut frame = vec![0x84, 0, 0, 0, 0x06];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    #[test]
+    fn malformed_supported_is_error() {
+        let mut supported = supported_frame(&["lz4"]);
+        let truncated_len = supported.len() - 2;
+        supported.truncate(truncated_len);
+        // the body length is fixed up so the frame itself is complete
+        let body_len = to_int((truncated_len - 9) as i32);
+        supported[5..9].copy_from_slice(body_len.as_slice());
+        let mut cdrs = CDRS::new(TransportMock::new(supported.clone()), NoneAuthenticator);
+        assert!(cdrs.get_supported().is_err());
+        let mut cdrs = CDRS::new(TransportMock::new(supported), NoneAuthenticator);
+        assert!(cdrs.get_options().is_err());
+
+        let mut cdrs = CDRS::new(TransportMock::new(supported_frame(&["lz4"])), NoneAuthenticator);
+        assert_eq!(cdrs.get_options().unwrap().get("COMPRESSION"),
+                   Some(&vec!["lz4".to_string()]));
+    }
+
+    #[test]
+    fn start_negotiated() {
+        let mut input = supported_frame(&["snappy", "lz4"]);
+        input.extend_from_slice(&[0x84, 0, 0, 0, 0x02, 0, 0, 0, 0]);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+
+        let session = cdrs.start_negotiated(&[Compression::Lz4, Compression::Snappy]).unwrap();
+        assert_eq!(session.compressor, Compression::Lz4);
+        let startup = &session.cdrs.transport.written[1];
+        assert_eq!(startup[4], 0x01);
+        assert!(startup.windows(3).any(|bytes| bytes == b"lz4"));
+    }
+
+    #[test]
+    fn start_downgrades_protocol_version() {
+        let message = "Invalid or unsupported protocol version (4); supported versions are \
+                       (1/v1, 2/v2, 3/v3)";
+        let mut body = to_int(0x000A);
+        body.extend_from_slice(CString::new(message.to_string()).into_cbytes().as_slice());
+        let mut input = vec![0x83, 0, 0, 0, 0x00];
+        input.extend_from_slice(to_int(body.len() as i32).as_slice());
+        input.extend_from_slice(body.as_slice());
+        input.extend_from_slice(&[0x83, 0, 0, 0, 0x02, 0, 0, 0, 0]);
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+
+        let mut session = cdrs.start(Compression::None).unwrap();
+        assert_eq!(session.protocol_version(), ProtocolVersion::V3);
+        session.query(QueryBuilder::new("SELECT * FROM ks.t").finalize(), false, false).unwrap();
+
+        let versions: Vec<u8> =
+            session.cdrs.transport.written.iter().map(|frame| frame[0]).collect();
+        assert_eq!(versions, vec![0x04, 0x03, 0x03]);
+    }
+
+    #[test]
+    fn start_fails_without_common_protocol_version() {
+        let message = "Invalid or unsupported protocol version (4); supported versions are \
+                       (1/v1, 2/v2)";
+        let mut body = to_int(0x000A);
+        body.extend_from_slice(CString::new(message.to_string()).into_cbytes().as_slice());
+        let mut input = vec![0x83, 0, 0, 0, 0x00];
+        input.extend_from_slice(to_int(body.len() as i32).as_slice());
+        input.extend_from_slice(body.as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+
+        assert!(cdrs.start(Compression::None).is_err());
+    }
+
+    // Builds a response frame with provided opcode and body.
+    fn response_frame(opcode: u8, body: Vec<u8>) -> Vec<u8> {
+        let mut frame = vec![0x84, 0, 0, 0, opcode];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    fn authenticate_frame() -> Vec<u8> {
+        let class = "org.apache.cassandra.auth.PasswordAuthenticator".to_string();
+        response_frame(0x03, CString::new(class).into_cbytes())
+    }
+
+    #[test]
+    fn start_with_plain_text_authentication() {
+        use authenticators::PlainTextAuthenticator;
+
+        let mut input = authenticate_frame();
+        input.extend_from_slice(response_frame(0x10, to_int(-1)).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input),
+                             PlainTextAuthenticator::new("user", "password"));
+
+        let session = cdrs.start(Compression::None).unwrap();
+        let auth_response = &session.cdrs.transport.written[1];
+        assert_eq!(auth_response[4], 0x0F);
+        assert_eq!(&auth_response[9..], CBytes::new(b"\0user\0password".to_vec())
+            .into_cbytes()
+            .as_slice());
+    }
+
+    #[test]
+    fn start_with_rejected_credentials() {
+        use authenticators::PlainTextAuthenticator;
+
+        let message = "Provided username user and/or password are incorrect";
+        let mut body = to_int(0x0100);
+        body.extend_from_slice(CString::new(message.to_string()).into_cbytes().as_slice());
+        let mut input = authenticate_frame();
+        input.extend_from_slice(response_frame(0x00, body).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input),
+                             PlainTextAuthenticator::new("user", "wrong"));
+
+        match cdrs.start(Compression::None) {
+            Err(error::Error::Authentication(ref err)) => assert_eq!(err, message),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("session should not be started"),
+        }
+    }
+
+    #[test]
+    fn start_without_required_authenticator() {
+        let cdrs = CDRS::new(TransportMock::new(authenticate_frame()), NoneAuthenticator);
+        match cdrs.start(Compression::None) {
+            Err(error::Error::AuthenticationRequired(ref class)) => {
+                assert_eq!(class, "org.apache.cassandra.auth.PasswordAuthenticator")
+            }
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("session should not be started"),
+        }
+
+        // challenge is not expected before any AUTH_RESPONSE
+        let challenge = response_frame(0x0E, CBytes::new(vec![1]).into_cbytes());
+        let cdrs = CDRS::new(TransportMock::new(challenge), NoneAuthenticator);
+        assert!(cdrs.start(Compression::None).is_err());
+    }
+
+    // Authenticator which requires two challenge rounds: it answers the first challenge
+    // with the challenge reversed and the second one with the number of rounds.
+    #[derive(Clone)]
+    struct TwoRoundAuthenticator {
+        rounds: u8,
+    }
+
+    impl Authenticator for TwoRoundAuthenticator {
+        fn get_auth_token(&self) -> CBytes {
+            CBytes::new(b"hello".to_vec())
+        }
+
+        fn get_cassandra_name(&self) -> Option<&str> {
+            Some("org.apache.cassandra.auth.PasswordAuthenticator")
+        }
+
+        fn evaluate_challenge(&mut self, challenge: &[u8]) -> Option<Vec<u8>> {
+            self.rounds += 1;
+            match self.rounds {
+                1 => Some(challenge.iter().rev().cloned().collect()),
+                2 => Some(vec![self.rounds]),
+                _ => None,
+            }
+        }
+    }
+
+    fn challenge_frame(challenge: &[u8]) -> Vec<u8> {
+        response_frame(0x0E, CBytes::new(challenge.to_vec()).into_cbytes())
+    }
+
+    #[test]
+    fn start_with_authentication_challenges() {
+        let mut input = authenticate_frame();
+        input.extend_from_slice(challenge_frame(b"abc").as_slice());
+        input.extend_from_slice(challenge_frame(b"next").as_slice());
+        input.extend_from_slice(response_frame(0x10, to_int(-1)).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), TwoRoundAuthenticator { rounds: 0 });
+
+        let session = cdrs.start(Compression::None).unwrap();
+        let tokens: Vec<&[u8]> =
+            session.cdrs.transport.written[1..].iter().map(|frame| &frame[13..]).collect();
+        assert_eq!(tokens, vec![&b"hello"[..], &b"cba"[..], &[2][..]]);
+    }
+
+    #[test]
+    fn start_fails_on_extra_authentication_challenge() {
+        let mut input = authenticate_frame();
+        for challenge in &[b"abc", b"def", b"ghi"] {
+            input.extend_from_slice(challenge_frame(*challenge).as_slice());
+        }
+        let cdrs = CDRS::new(TransportMock::new(input), TwoRoundAuthenticator { rounds: 0 });
+
+        assert!(cdrs.start(Compression::None).is_err());
+    }
+
+    #[test]
+    fn listen_for_events() {
+        let mut body = vec![];
+        for value in &["STATUS_CHANGE", "UP"] {
+            body.extend_from_slice(CString::new(value.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(&[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52]);
+        let mut input = response_frame(0x02, vec![]);
+        let mut event = vec![0x84, 0, 0xFF, 0xFF, 0x0C];
+        event.extend_from_slice(to_int(body.len() as i32).as_slice());
+        event.extend_from_slice(body.as_slice());
+        input.extend_from_slice(event.as_slice());
+        let session = Session::start(CDRS::new(TransportMock::new(input), NoneAuthenticator));
+
+        let (mut listener, stream) = session.listen_for(vec![SimpleServerEvent::StatusChange])
+            .unwrap();
+        // the listener stops once the transport is exhausted
+        assert!(listener.start(&Compression::None).is_err());
+        drop(listener);
+
+        let events: Vec<_> = stream.collect();
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            ServerEvent::StatusChange(ref change) => {
+                assert_eq!(change.change_type, StatusChangeType::Up);
+                assert_eq!(change.addr.addr, "127.0.0.1:9042".parse().unwrap());
+            }
+            _ => panic!("should be status change"),
+        }
+    }
+
+    #[test]
+    fn listener_fails_on_malformed_event() {
+        let mut body = vec![];
+        for value in &["STATUS_CHANGE", "SIDEWAYS"] {
+            body.extend_from_slice(CString::new(value.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(&[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52]);
+        let mut input = response_frame(0x02, vec![]);
+        let mut event = vec![0x84, 0, 0xFF, 0xFF, 0x0C];
+        event.extend_from_slice(to_int(body.len() as i32).as_slice());
+        event.extend_from_slice(body.as_slice());
+        input.extend_from_slice(event.as_slice());
+        let session = Session::start(CDRS::new(TransportMock::new(input), NoneAuthenticator));
+
+        let (mut listener, stream) = session.listen_for(vec![SimpleServerEvent::StatusChange])
+            .unwrap();
+        match listener.start(&Compression::None) {
+            Err(error::Error::General(ref msg)) => assert!(msg.contains("SIDEWAYS"), "{}", msg),
+            other => panic!("unexpected status change type error expected, got {:?}", other),
+        }
+        drop(listener);
+        assert_eq!(stream.count(), 0);
+    }
+
+    // Builds a Rows result frame as returned by `LOCAL_HOSTS_QUERY` or `PEERS_HOSTS_QUERY`
+    // with hosts 10.0.0.x of dc1 where x is one of `last_octets`.
+    fn hosts_frame(last_octets: &[u8]) -> Vec<u8> {
+        let mut body = to_int(0x0002);
+        body.extend_from_slice(to_int(0x0001).as_slice());
+        body.extend_from_slice(to_int(4).as_slice());
+        for name in &["system", "peers"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns: [(&str, &[i16]); 4] = [("rpc_address", &[0x0010]),
+                                            ("data_center", &[0x000D]),
+                                            ("rack", &[0x000D]),
+                                            ("tokens", &[0x0022, 0x000D])];
+        for &(name, types) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            for col_type in types {
+                body.extend_from_slice(to_short(*col_type).as_slice());
+            }
+        }
+
+        body.extend_from_slice(to_int(last_octets.len() as i32).as_slice());
+        for last_octet in last_octets {
+            let mut tokens = to_int(1);
+            tokens.extend_from_slice(CBytes::new(last_octet.to_string().into_bytes())
+                .into_cbytes()
+                .as_slice());
+            let values = vec![vec![10, 0, 0, *last_octet], b"dc1".to_vec(), b"r1".to_vec(), tokens];
+            for value in values {
+                body.extend_from_slice(CBytes::new(value).into_cbytes().as_slice());
+            }
+        }
+
+        response_frame(0x08, body)
+    }
+
+    #[test]
+    fn discover_cluster() {
+        use std::thread;
+
+        let mut body = vec![];
+        for value in &["TOPOLOGY_CHANGE", "NEW_NODE"] {
+            body.extend_from_slice(CString::new(value.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(&[4, 10, 0, 0, 3, 0, 0, 0x23, 0x52]);
+        let mut event = vec![0x84, 0, 0xFF, 0xFF, 0x0C];
+        event.extend_from_slice(to_int(body.len() as i32).as_slice());
+        event.extend_from_slice(body.as_slice());
+
+        // local and peers hosts, a response to Register request and an event
+        let mut input = hosts_frame(&[1]);
+        input.extend_from_slice(hosts_frame(&[2]).as_slice());
+        input.extend_from_slice(response_frame(0x02, vec![]).as_slice());
+        input.extend_from_slice(event.as_slice());
+        let session = Session::start(CDRS::new(TransportMock::new(input), NoneAuthenticator));
+
+        let (cluster, mut listener) = session.discover_cluster(|| {
+                let mut input = hosts_frame(&[1]);
+                input.extend_from_slice(hosts_frame(&[2, 3]).as_slice());
+                Ok(Session::start(CDRS::new(TransportMock::new(input), NoneAuthenticator)))
+            })
+            .unwrap();
+        let addresses = |cluster: &Cluster| -> Vec<String> {
+            cluster.nodes().iter().map(|node| node.address.to_string()).collect()
+        };
+        assert_eq!(addresses(&cluster), vec!["10.0.0.1", "10.0.0.2"]);
+
+        // the listener stops once the transport is exhausted
+        assert!(listener.start(&Compression::None).is_err());
+        let started = Instant::now();
+        while !cluster.contains(&"10.0.0.3".parse().unwrap()) {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(addresses(&cluster), vec!["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+    }
+
+    #[test]
+    fn listen_for_fails_on_unexpected_response() {
+        let session = Session::start(CDRS::new(TransportMock::new(VOID_FRAME.to_vec()),
+                                               NoneAuthenticator));
+        assert!(session.listen_for(vec![SimpleServerEvent::SchemaChange]).is_err());
+    }
+
+    #[test]
+    fn connect_moves_to_next_node() {
+        use load_balancing::RoundRobin;
+
+        let nodes: Vec<Node> = (1..4)
+            .map(|i| Node::new(format!("10.0.0.{}", i).parse().unwrap(), "dc1".to_string()))
+            .collect();
+        let policy = RoundRobin::new(nodes.clone());
+
+        let mut tried = vec![];
+        let session = Session::connect(&policy, |node| {
+            tried.push(node.clone());
+            if node.address == nodes[1].address {
+                let cdrs = CDRS::new(TransportMock::new(vec![]), NoneAuthenticator);
+                return Ok(Session::start(cdrs));
+            }
+            Err(error::Error::General("connection refused".to_string()))
+        });
+        assert!(session.is_ok());
+        assert_eq!(tried, vec![nodes[0].clone(), nodes[1].clone()]);
+
+        let session: error::Result<Session<NoneAuthenticator, TransportMock>> =
+            Session::connect(&policy, |_| Err(error::Error::General("refused".to_string())));
+        assert!(session.is_err());
+    }
+
+    #[test]
+    fn connect_with_states_skips_down_nodes() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use load_balancing::RoundRobin;
+        use reconnection_policy::ConstantReconnectionPolicy;
+
+        let nodes: Vec<Node> = (1..3)
+            .map(|i| Node::new(format!("10.0.1.{}", i).parse().unwrap(), "dc1".to_string()))
+            .collect();
+        let policy = RoundRobin::new(nodes.clone());
+        let states = Arc::new(NodeStates::new(ConstantReconnectionPolicy::new(
+            Duration::from_secs(60))));
+        let down = nodes[0].address;
+        let attempts = Arc::new(AtomicUsize::new(0));
+
+        let recorded = attempts.clone();
+        let connect = move |node: &Node| {
+            if node.address == down {
+                recorded.fetch_add(1, Ordering::SeqCst);
+                return Err(error::Error::General("connection refused".to_string()));
+            }
+            Ok(Session::start(CDRS::new(TransportMock::new(vec![]), NoneAuthenticator)))
+        };
+        let connect = Arc::new(connect);
+        for _ in 0..4 {
+            let connect = connect.clone();
+            assert!(Session::connect_with_states(&policy, &states, move |node| connect(node))
+                .is_ok());
+        }
+        assert!(!states.is_up(&nodes[0]));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        states.stop();
+    }
+
+    #[test]
+    fn query_speculatively() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+        use load_balancing::RoundRobin;
+        use speculative_execution::ConstantSpeculativeExecutionPolicy;
+
+        let nodes: Vec<Node> = (1..4)
+            .map(|i| Node::new(format!("10.0.2.{}", i).parse().unwrap(), "dc1".to_string()))
+            .collect();
+        let policy = RoundRobin::new(nodes);
+        let speculative_policy = ConstantSpeculativeExecutionPolicy::new(Duration::from_millis(20),
+                                                                         2);
+        // the first execution gets its session slowly
+        let new_session = || {
+            let executions = AtomicUsize::new(0);
+            move |_: &Node| {
+                if executions.fetch_add(1, Ordering::SeqCst) == 0 {
+                    thread::sleep(Duration::from_millis(500));
+                }
+                let cdrs = CDRS::new(TransportMock::new(VOID_FRAME.to_vec()), NoneAuthenticator);
+                Ok(Box::new(Session::start(cdrs)))
+            }
+        };
+
+        let started = Instant::now();
+        let query = QueryBuilder::new("SELECT 1").idempotent(true).finalize();
+        let response = Session::query_speculatively(&policy,
+                                                    &speculative_policy,
+                                                    query,
+                                                    new_session());
+        assert_eq!(response.unwrap().opcode, Opcode::Result);
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        let query = QueryBuilder::new("SELECT 1").finalize();
+        let response = Session::query_speculatively(&policy,
+                                                    &speculative_policy,
+                                                    query,
+                                                    new_session());
+        assert_eq!(response.unwrap().opcode, Opcode::Result);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn query_pages() {
+        let mut input = rows_frame(&[1, 2], Some(vec![0xAB, 0xCD]));
+        input.extend_from_slice(rows_frame(&[3], None).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.without_timestamp_generator();
+
+        let query = QueryBuilder::new("SELECT id FROM ks.table").page_size(2).finalize();
+        let pages: Vec<Vec<i32>> = session.query_pages(query)
+            .map(|page| {
+                page.unwrap()
+                    .iter()
+                    .map(|row| row.get_by_name("id").unwrap().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(pages, vec![vec![1, 2], vec![3]]);
+
+        let written = &session.cdrs.transport.written;
+        assert_eq!(written.len(), 2);
+        assert!(!written[0].windows(2).any(|w| w == &[0xAB, 0xCD][..]));
+        assert!(written[1].ends_with(&[0, 0, 0, 2, 0xAB, 0xCD]));
+    }
+
+    #[test]
+    fn query_resumed_with_paging_state() {
+        let mut input = rows_frame(&[1, 2], Some(vec![0xAB, 0xCD]));
+        input.extend_from_slice(rows_frame(&[3], None).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.without_timestamp_generator();
+
+        let query = QueryBuilder::new("SELECT id FROM ks.table").page_size(2).finalize();
+        let body = session.query(query, false, false).unwrap().get_body();
+        let paging_state = body.paging_state().unwrap();
+        assert_eq!(paging_state, vec![0xAB, 0xCD]);
+
+        let resumed = QueryBuilder::new("SELECT id FROM ks.table")
+            .page_size(2)
+            .with_paging_state(paging_state)
+            .finalize();
+        let body = session.query(resumed, false, false).unwrap().get_body();
+        assert!(body.paging_state().is_none());
+        let ids: Vec<i32> = body.into_rows()
+            .unwrap()
+            .iter()
+            .map(|row| row.get_by_name("id").unwrap().unwrap())
+            .collect();
+        assert_eq!(ids, vec![3]);
+
+        let resumed_request = &session.cdrs.transport.written[1];
+        assert!(resumed_request.ends_with(&[0, 0, 0, 2, 0xAB, 0xCD]));
+    }
+
+    // Builds a Prepared result frame of a statement without bound and result columns.
+    fn prepared_frame(id: Vec<u8>) -> Vec<u8> {
+        let mut body = to_int(0x0004);
+        body.extend_from_slice(CBytesShort::new(id).into_cbytes().as_slice());
+        // prepared metadata: flags, columns_count, pk_count
+        body.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // result metadata: no_metadata flag, columns_count
+        body.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0]);
+
+        let mut frame = vec![0x84, 0, 0, 0, 0x08];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    fn unprepared_frame(id: Vec<u8>) -> Vec<u8> {
+        let mut body = to_int(0x2500);
+        body.extend_from_slice(CString::new("unprepared".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(CBytesShort::new(id).into_cbytes().as_slice());
+
+        let mut frame = vec![0x84, 0, 0, 0, 0x00];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    #[test]
+    fn prepare_cached() {
+        let input = prepared_frame(vec![1, 2]);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let first = session.prepare_cached("SELECT id FROM ks.table").unwrap();
+        let second = session.prepare_cached("SELECT id FROM ks.table").unwrap();
+        assert_eq!(first.id.into_plain(), vec![1, 2]);
+        assert_eq!(second.id.into_plain(), vec![1, 2]);
+        assert_eq!(session.cdrs.transport.written.len(), 1);
+    }
+
+    #[test]
+    fn prepare_cached_invalidated_when_unprepared() {
+        let mut input = prepared_frame(vec![1, 2]);
+        input.extend_from_slice(unprepared_frame(vec![1, 2]).as_slice());
+        input.extend_from_slice(prepared_frame(vec![3, 4]).as_slice());
+        input.extend_from_slice(&[0x84, 0, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let query = "SELECT id FROM ks.table";
+        let prepared = session.prepare_cached(query).unwrap();
+        let params = QueryParamsBuilder::new(Consistency::One).finalize();
+        session.execute_or_reprepare(query, &prepared.id, params, false, false).unwrap();
+        assert_eq!(session.cdrs.transport.written.len(), 4);
+
+        let reprepared = session.prepare_cached(query).unwrap();
+        assert_eq!(reprepared.id.into_plain(), vec![3, 4]);
+        assert_eq!(session.cdrs.transport.written.len(), 4);
+    }
+
+    struct FixedTimestamp;
+
+    impl TimestampGenerator for FixedTimestamp {
+        fn next_timestamp(&self) -> i64 {
+            1234567
+        }
+    }
+
+    const VOID_FRAME: [u8; 13] = [0x84, 0, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1];
+
+    #[test]
+    fn query_with_explicit_timestamp() {
+        let cdrs = CDRS::new(TransportMock::new(VOID_FRAME.to_vec()), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.timestamp_generator(FixedTimestamp);
+
+        let query = QueryBuilder::new("INSERT INTO ks.table (id) VALUES (1)")
+            .with_timestamp(42)
+            .finalize();
+        session.query(query, false, false).unwrap();
+
+        let request = &session.cdrs.transport.written[0];
+        assert!(request.ends_with(&[0, 1, 0x20, 0, 0, 0, 0, 0, 0, 0, 42]));
+    }
+
+    #[test]
+    fn query_with_generated_timestamp() {
+        let mut input = VOID_FRAME.to_vec();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let query = QueryBuilder::new("INSERT INTO ks.table (id) VALUES (1)").finalize();
+        session.without_timestamp_generator();
+        session.query(query.clone(), false, false).unwrap();
+        session.timestamp_generator(FixedTimestamp);
+        session.query(query, false, false).unwrap();
+
+        let written = &session.cdrs.transport.written;
+        assert!(written[0].ends_with(&[0, 1, 0]));
+        assert!(written[1].ends_with(&[0, 1, 0x20, 0, 0, 0, 0, 0, 0x12, 0xD6, 0x87]));
+    }
+
+    #[test]
+    fn query_with_default_timestamp_generator() {
+        let mut input = VOID_FRAME.to_vec();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let query = QueryBuilder::new("INSERT INTO ks.table (id) VALUES (1)").finalize();
+        session.query(query.clone(), false, false).unwrap();
+        session.query(query, false, false).unwrap();
+
+        let timestamps: Vec<i64> = session.cdrs.transport
+            .written
+            .iter()
+            .map(|request| {
+                let (flags, timestamp) = request.split_at(request.len() - 8);
+                assert_eq!(flags[flags.len() - 1], 0x20);
+                from_bytes(timestamp) as i64
+            })
+            .collect();
+        assert!(timestamps[0] > 0);
+        assert!(timestamps[1] > timestamps[0]);
+    }
+
+    #[test]
+    fn heartbeat() {
+        let cdrs = CDRS::new(TransportMock::new(supported_frame(&["lz4"])), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        assert!(session.heartbeat().is_ok());
+        assert!(!session.is_broken());
+        assert_eq!(session.cdrs.transport.written[0][4], 0x05);
+        // the read timeout is applied to the heartbeat only
+        assert_eq!(session.cdrs.transport.read_timeouts,
+                   vec![Some(Duration::from_secs(DEFAULT_HEARTBEAT_TIMEOUT_SECS)), None]);
+    }
+
+    #[test]
+    fn heartbeat_times_out() {
+        use std::net::TcpListener;
+        use std::thread;
+        use transport::TransportTcp;
+
+        // the server accepts a connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || listener.accept().map(|(socket, _)| socket));
+        let transport = TransportTcp::new(addr.as_str()).unwrap();
+        let mut session = Session::start(CDRS::new(transport, NoneAuthenticator));
+        session.heartbeat_timeout(Duration::from_millis(50));
+
+        let started = Instant::now();
+        assert!(session.heartbeat().is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(session.is_broken());
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn heartbeat_marks_dead_connection() {
+        // the server does not respond anymore
+        let cdrs = CDRS::new(TransportMock::new(vec![]), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        assert!(session.heartbeat_if_idle(Duration::from_secs(60)).is_ok());
+        assert!(session.cdrs.transport.written.is_empty());
+
+        assert!(session.heartbeat_if_idle(Duration::from_secs(0)).is_err());
+        assert!(session.is_broken());
+    }
+
+    // Metrics which record names of called callbacks.
+    struct RecordingMetrics {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RecordingMetrics {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    impl Metrics for RecordingMetrics {
+        fn on_connection_opened(&self) {
+            self.record("opened".to_string());
+        }
+
+        fn on_connection_closed(&self) {
+            self.record("closed".to_string());
+        }
+
+        fn on_request_started(&self) {
+            self.record("started".to_string());
+        }
+
+        fn on_request_finished(&self) {
+            self.record("finished".to_string());
+        }
+
+        fn on_request_sent(&self, opcode: &Opcode, bytes: usize) {
+            self.record(format!("sent {:?} {}", opcode, bytes));
+        }
+
+        fn on_bytes_read(&self, bytes: usize) {
+            self.record(format!("read {}", bytes));
+        }
+
+        fn on_response(&self, _latency: Duration, opcode: &Opcode) {
+            self.record(format!("response {:?}", opcode));
+        }
+
+        fn on_error(&self, error: &CDRSError) {
+            self.record(format!("error {}", error.error_code));
+        }
+
+        fn on_io_error(&self, error: &io::Error) {
+            self.record(format!("io error {:?}", error.kind()));
+        }
+    }
+
+    #[test]
+    fn metrics_callbacks() {
+        let mut input = VOID_FRAME.to_vec();
+        input.extend_from_slice(unprepared_frame(vec![1]).as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        let calls = Arc::new(Mutex::new(vec![]));
+        session.metrics(RecordingMetrics { calls: calls.clone() });
+
+        let query = QueryBuilder::new("SELECT 1").finalize();
+        session.query(query.clone(), false, false).unwrap();
+        assert!(session.query(query, false, false).is_err());
+        session.end();
+
+        let request_len = session.cdrs.transport.written[0].len();
+        let expected = vec!["opened".to_string(),
+                            "started".to_string(),
+                            format!("sent Query {}", request_len),
+                            format!("read {}", VOID_FRAME.len()),
+                            "response Result".to_string(),
+                            "finished".to_string(),
+                            "started".to_string(),
+                            format!("sent Query {}", request_len),
+                            format!("read {}", unprepared_frame(vec![1]).len()),
+                            "response Error".to_string(),
+                            "error 9472".to_string(),
+                            "finished".to_string(),
+                            "closed".to_string()];
+        assert_eq!(*calls.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn metrics_of_io_errors() {
+        // the response is cut off in the middle of its body
+        let cdrs = CDRS::new(TransportMock::new(VOID_FRAME[..11].to_vec()), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        let calls = Arc::new(Mutex::new(vec![]));
+        session.metrics(RecordingMetrics { calls: calls.clone() });
+
+        let query = QueryBuilder::new("SELECT 1").finalize();
+        assert!(session.query(query, false, false).is_err());
+        assert!(session.is_broken());
+
+        let request_len = session.cdrs.transport.written[0].len();
+        let expected = vec!["opened".to_string(),
+                            "started".to_string(),
+                            format!("sent Query {}", request_len),
+                            "read 11".to_string(),
+                            "io error UnexpectedEof".to_string(),
+                            "finished".to_string()];
+        assert_eq!(*calls.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn close_session() {
+        let cdrs = CDRS::new(TransportMock::new(VOID_FRAME.to_vec()), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.close().unwrap();
+        assert!(session.cdrs.transport.closed);
+
+        let query = QueryBuilder::new("SELECT 1").finalize();
+        assert!(session.query(query, false, false).is_err());
+        assert!(session.heartbeat().is_err());
+        assert!(session.cdrs.transport.written.is_empty());
+        session.close().unwrap();
+    }
+
+    fn write_timeout_frame() -> Vec<u8> {
+        let mut body = to_int(0x1100);
+        body.extend_from_slice(CString::new("timeout".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        body.extend_from_slice(CString::new("SIMPLE".to_string()).into_cbytes().as_slice());
+        response_frame(0x00, body)
+    }
+
+    fn unavailable_frame() -> Vec<u8> {
+        let mut body = to_int(0x1000);
+        body.extend_from_slice(CString::new("unavailable".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(&[0, 4, 0, 0, 0, 2, 0, 0, 0, 1]);
+        response_frame(0x00, body)
+    }
+
+    #[test]
+    fn unavailable_retried_by_default_policy() {
+        let mut input = unavailable_frame();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        let query = QueryBuilder::new("SELECT 1").finalize();
+        assert_eq!(session.query(query, false, false).unwrap().opcode, Opcode::Result);
+        assert_eq!(session.cdrs.transport.written.len(), 2);
+
+        // the second unavailable error is returned to a caller
+        let mut input = unavailable_frame();
+        input.extend_from_slice(unavailable_frame().as_slice());
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        match session.query(QueryBuilder::new("SELECT 1").finalize(), false, false) {
+            Err(error::Error::Server(ref err)) => assert_eq!(err.error_code, 0x1000),
+            other => panic!("unavailable error is expected, got {:?}", other),
+        }
+        assert_eq!(session.cdrs.transport.written.len(), 2);
+    }
+
+    // Retry policy which retries each error once.
+    struct RetryOnce;
+
+    impl RetryPolicy for RetryOnce {
+        fn on_read_timeout(&self, _: &ReadTimeoutError, retries: u32) -> RetryDecision {
+            if retries == 0 { RetryDecision::Retry } else { RetryDecision::Rethrow }
+        }
+
+        fn on_write_timeout(&self, _: &WriteTimeoutError, retries: u32) -> RetryDecision {
+            if retries == 0 { RetryDecision::Retry } else { RetryDecision::Rethrow }
+        }
+
+        fn on_unavailable(&self, _: &UnavailableError, retries: u32) -> RetryDecision {
+            if retries == 0 { RetryDecision::Retry } else { RetryDecision::Rethrow }
+        }
+    }
+
+    #[test]
+    fn write_timeout_retried_only_if_idempotent() {
+        let update = "UPDATE ks.t SET c = c + 1 WHERE id = 1";
+
+        let mut input = write_timeout_frame();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.retry_policy(RetryOnce);
+        match session.query(QueryBuilder::new(update).finalize(), false, false) {
+            Err(error::Error::Server(ref err)) => assert_eq!(err.error_code, 0x1100),
+            other => panic!("write timeout is expected, got {:?}", other),
+        }
+        assert_eq!(session.cdrs.transport.written.len(), 1);
+
+        let mut input = write_timeout_frame();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        session.retry_policy(RetryOnce);
+        let query = QueryBuilder::new(update).idempotent(true).finalize();
+        assert_eq!(session.query(query, false, false).unwrap().opcode, Opcode::Result);
+        assert_eq!(session.cdrs.transport.written.len(), 2);
+    }
+
+    #[test]
+    fn custom_payload_round_trip() {
+        let mut payload = HashMap::new();
+        payload.insert("key".to_string(), vec![1, 2, 3]);
+        let payload_bytes = CBytesMap::new(payload.clone()).into_cbytes();
+
+        // the server echoes the payload in a void result
+        let mut body = payload_bytes.clone();
+        body.extend_from_slice(to_int(0x0001).as_slice());
+        let mut input = vec![0x84, 0x04, 0, 0, 0x08];
+        input.extend_from_slice(to_int(body.len() as i32).as_slice());
+        input.extend_from_slice(body.as_slice());
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let query = QueryBuilder::new("SELECT 1").with_custom_payload(payload.clone()).finalize();
+        let response = session.query(query, false, false).unwrap();
+        assert_eq!(response.custom_payload(), Some(&payload));
+        assert_eq!(response.body, to_int(0x0001));
+
+        let request = &session.cdrs.transport.written[0];
+        assert_eq!(request[1], 0x04);
+        assert!(request[9..].starts_with(payload_bytes.as_slice()));
+        assert!(request[9 + payload_bytes.len()..].starts_with(b"\x00\x00\x00\x08SELECT 1"));
+    }
+
+    #[test]
+    fn custom_payload_of_execute_and_batch() {
+        let mut payload = HashMap::new();
+        payload.insert("key".to_string(), vec![1, 2, 3]);
+        let payload_bytes = CBytesMap::new(payload.clone()).into_cbytes();
+        let mut input = VOID_FRAME.to_vec();
+        input.extend_from_slice(&VOID_FRAME);
+        let cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+
+        let params = QueryParamsBuilder::new(Consistency::One)
+            .with_custom_payload(payload.clone())
+            .finalize();
+        session.execute(&CBytesShort::new(vec![1, 2]), params, false, false).unwrap();
+        let batch = BatchQueryBuilder::new()
+            .add_query("INSERT INTO t (id) VALUES (1)", vec![])
+            .with_custom_payload(payload)
+            .finalize()
+            .unwrap();
+        session.batch(batch, false, false).unwrap();
+
+        for request in &session.cdrs.transport.written {
+            assert_eq!(request[1], 0x04);
+            assert!(request[9..].starts_with(payload_bytes.as_slice()));
+        }
+    }
+
+    #[test]
+    fn custom_payload_is_rejected_by_v3() {
+        let mut cdrs = CDRS::new(TransportMock::new(VOID_FRAME.to_vec()), NoneAuthenticator);
+        cdrs.protocol_version = ProtocolVersion::V3;
+        let mut session = Session::start(cdrs);
+
+        let query = QueryBuilder::new("SELECT 1").with_custom_payload(HashMap::new()).finalize();
+        assert!(session.query(query, false, false).is_err());
+        assert!(session.cdrs.transport.written.is_empty());
+    }
+
+    #[test]
+    fn query_with_keyspace() {
+        let mut input = VOID_FRAME.to_vec();
+        input.extend_from_slice(&VOID_FRAME);
+        // v5 is never negotiated, it's forced to check encoding of v5 options
+        let mut cdrs = CDRS::new(TransportMock::new(input), NoneAuthenticator);
+        cdrs.protocol_version = ProtocolVersion::V5;
+        let mut session = Session::start(cdrs);
+        session.without_timestamp_generator();
+
+        let query = QueryBuilder::new("SELECT * FROM t").with_keyspace("ks").finalize();
+        session.query(query.clone(), false, false).unwrap();
+        let batch = BatchQueryBuilder::new()
+            .add_query("INSERT INTO t (id) VALUES (?)",
+                       vec![(None, Value::from(1 as i32))])
+            .with_keyspace("ks")
+            .finalize()
+            .unwrap();
+        session.batch(batch.clone(), false, false).unwrap();
+
+        // consistency, [int] flags with WITH_KEYSPACE set and [string] keyspace
+        let written = &session.cdrs.transport.written;
+        assert_eq!(written[0][0], 0x05);
+        assert_eq!(&written[0][9..],
+                   &b"\x00\x00\x00\x0FSELECT * FROM t\x00\x01\x00\x00\x00\x80\x00\x02ks"[..]);
+        assert_eq!(written[1][0], 0x05);
+        assert!(written[1].ends_with(b"\x00\x01\x00\x00\x00\x80\x00\x02ks"));
+
+        // keyspace is not supported by protocol v4
+        let cdrs = CDRS::new(TransportMock::new(vec![]), NoneAuthenticator);
+        let mut session = Session::start(cdrs);
+        assert!(session.query(query, false, false).is_err());
+        assert!(session.batch(batch, false, false).is_err());
+        assert!(session.cdrs.transport.written.is_empty());
+    }
 }
@@ -0,0 +1,280 @@
+//! The module contains discovery of Cassandra cluster hosts.
+//!
+//! A session connected to any node of a cluster can fetch the whole list of hosts
+//! from `system.local` and `system.peers` tables with `Session::discover_hosts`.
+//! Discovered hosts are registered in a `Cluster` which provides nodes for
+//! load balancing policies, e.g. `RoundRobin::new(cluster.nodes())`, so that
+//! `Session::connect` can open connections to any of them. The cluster is kept
+//! up to date with topology and status change events received from a server,
+//! see `Session::discover_cluster`.
+use std::net;
+use std::sync::RwLock;
+
+use error;
+use frame::events::{ServerEvent, StatusChangeType, TopologyChangeType};
+use load_balancing::{Node, TokenRing};
+use types::IntoRustByName;
+use types::rows::Row;
+
+/// Query of information about a node a session is connected to.
+pub const LOCAL_HOSTS_QUERY: &'static str = "SELECT rpc_address, data_center, rack, tokens \
+                                             FROM system.local";
+
+/// Query of information about other nodes of a cluster.
+pub const PEERS_HOSTS_QUERY: &'static str = "SELECT rpc_address, data_center, rack, tokens \
+                                             FROM system.peers";
+
+/// Host of Cassandra cluster discovered from system tables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host {
+    /// Node a host is accessible by.
+    pub node: Node,
+    /// Rack the host belongs to.
+    pub rack: String,
+    /// Tokens owned by the host.
+    pub tokens: Vec<i64>,
+}
+
+impl Host {
+    /// Creates new host from a row returned by `LOCAL_HOSTS_QUERY` or `PEERS_HOSTS_QUERY`.
+    pub fn from_row(row: &Row) -> error::Result<Host> {
+        let (node, tokens) = try!(TokenRing::parse_row(row));
+        let rack: String = match row.get_by_name("rack") {
+            Some(rack) => try!(rack),
+            None => return Err(error::Error::General("Column rack is not found".to_string())),
+        };
+
+        Ok(Host {
+            node: node,
+            rack: rack,
+            tokens: tokens,
+        })
+    }
+}
+
+/// Registry of known hosts of a cluster.
+#[derive(Debug)]
+pub struct Cluster {
+    hosts: RwLock<Vec<Host>>,
+}
+
+impl Cluster {
+    /// Creates new cluster which consists of provided hosts.
+    pub fn new(hosts: Vec<Host>) -> Cluster {
+        let cluster = Cluster { hosts: RwLock::new(vec![]) };
+        cluster.register(hosts);
+        cluster
+    }
+
+    /// Returns all known hosts.
+    pub fn hosts(&self) -> Vec<Host> {
+        self.hosts.read().unwrap().clone()
+    }
+
+    /// Returns nodes of all known hosts in order they were registered.
+    pub fn nodes(&self) -> Vec<Node> {
+        self.hosts.read().unwrap().iter().map(|host| host.node.clone()).collect()
+    }
+
+    /// Returns a token ring built of all known hosts.
+    pub fn token_ring(&self) -> TokenRing {
+        let hosts = self.hosts.read().unwrap();
+        TokenRing::new(hosts.iter().map(|host| (host.node.clone(), host.tokens.clone())).collect())
+    }
+
+    /// Returns `true` if a host with provided address is known.
+    pub fn contains(&self, address: &net::IpAddr) -> bool {
+        self.hosts.read().unwrap().iter().any(|host| host.node.address == *address)
+    }
+
+    /// Registers provided hosts. Information about already known hosts gets updated.
+    /// It returns addresses of hosts which were not known before.
+    pub fn register(&self, hosts: Vec<Host>) -> Vec<net::IpAddr> {
+        let mut known = self.hosts.write().unwrap();
+        let mut added = vec![];
+        for host in hosts {
+            match known.iter().position(|h| h.node.address == host.node.address) {
+                Some(i) => known[i] = host,
+                None => {
+                    added.push(host.node.address);
+                    known.push(host);
+                }
+            }
+        }
+
+        added
+    }
+
+    /// Removes a host with provided address. It returns `false` if the host is unknown.
+    pub fn remove(&self, address: &net::IpAddr) -> bool {
+        let mut known = self.hosts.write().unwrap();
+        let len = known.len();
+        known.retain(|host| host.node.address != *address);
+        known.len() != len
+    }
+
+    /// Updates the cluster according to a topology or status change event. Data center,
+    /// rack and tokens of a new node are not a part of the event, so `discover` is called
+    /// to fetch up to date hosts, e.g. with `Session::discover_hosts`. A node which comes
+    /// up while it's unknown is discovered the same way. It returns addresses of hosts
+    /// which were added.
+    pub fn on_event<F>(&self, event: &ServerEvent, discover: F) -> error::Result<Vec<net::IpAddr>>
+        where F: FnOnce() -> error::Result<Vec<Host>>
+    {
+        let (address, is_new) = match event {
+            &ServerEvent::TopologyChange(ref change) => {
+                let address = change.addr.addr.ip();
+                if change.change_type == TopologyChangeType::RemovedNode {
+                    self.remove(&address);
+                    return Ok(vec![]);
+                }
+                (address, true)
+            }
+            &ServerEvent::StatusChange(ref change) => {
+                (change.addr.addr.ip(), change.change_type == StatusChangeType::Up)
+            }
+            _ => return Ok(vec![]),
+        };
+
+        if !is_new || self.contains(&address) {
+            return Ok(vec![]);
+        }
+        let hosts = try!(discover());
+        Ok(self.register(hosts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+    use frame::events::{StatusChange, TopologyChange};
+    use frame::frame_result::BodyResResultRows;
+    use types::{to_int, to_short, CBytes, CInet, CString};
+    use {FromCursor, IntoBytes};
+    use super::*;
+
+    // Builds rows as returned by `LOCAL_HOSTS_QUERY` or `PEERS_HOSTS_QUERY`.
+    fn hosts_rows(hosts: &[(u8, &str, &str, &[&str])]) -> Vec<Row> {
+        let mut body = to_int(0x0001);
+        body.extend_from_slice(to_int(4).as_slice());
+        for name in &["system", "peers"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns: [(&str, &[i16]); 4] = [("rpc_address", &[0x0010]),
+                                            ("data_center", &[0x000D]),
+                                            ("rack", &[0x000D]),
+                                            ("tokens", &[0x0022, 0x000D])];
+        for &(name, types) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            for col_type in types {
+                body.extend_from_slice(to_short(*col_type).as_slice());
+            }
+        }
+
+        body.extend_from_slice(to_int(hosts.len() as i32).as_slice());
+        for &(last_octet, dc, rack, tokens) in hosts {
+            let mut set = to_int(tokens.len() as i32);
+            for token in tokens {
+                set.extend_from_slice(CBytes::new(token.as_bytes().to_vec())
+                    .into_cbytes()
+                    .as_slice());
+            }
+            let values = vec![vec![10, 0, 0, last_octet],
+                              dc.as_bytes().to_vec(),
+                              rack.as_bytes().to_vec(),
+                              set];
+            for value in values {
+                body.extend_from_slice(CBytes::new(value).into_cbytes().as_slice());
+            }
+        }
+
+        let mut cursor = Cursor::new(body.as_slice());
+        Row::from_frame_body(BodyResResultRows::from_cursor(&mut cursor))
+    }
+
+    fn hosts(rows: Vec<Row>) -> Vec<Host> {
+        rows.iter().map(|row| Host::from_row(row).unwrap()).collect()
+    }
+
+    fn address(last_octet: u8) -> net::IpAddr {
+        net::IpAddr::V4(net::Ipv4Addr::new(10, 0, 0, last_octet))
+    }
+
+    fn topology_change(change_type: TopologyChangeType, last_octet: u8) -> ServerEvent {
+        let addr = SocketAddr::new(address(last_octet), 9042);
+        ServerEvent::TopologyChange(TopologyChange {
+            change_type: change_type,
+            addr: CInet { addr: addr },
+        })
+    }
+
+    #[test]
+    fn discovered_peers_registered() {
+        let mut rows = hosts_rows(&[(1, "dc1", "rack1", &["-100"])]);
+        rows.extend(hosts_rows(&[(2, "dc1", "rack2", &["0", "200"]),
+                                 (3, "dc2", "rack1", &["100"])]));
+        let cluster = Cluster::new(hosts(rows));
+
+        let discovered = cluster.hosts();
+        assert_eq!(discovered.len(), 3);
+        assert_eq!(discovered[1].node, Node::new(address(2), "dc1".to_string()));
+        assert_eq!(discovered[1].rack, "rack2");
+        assert_eq!(discovered[1].tokens, vec![0, 200]);
+        assert_eq!(discovered[2].node, Node::new(address(3), "dc2".to_string()));
+        assert_eq!(cluster.nodes().iter().map(|n| n.address).collect::<Vec<_>>(),
+                   vec![address(1), address(2), address(3)]);
+        assert_eq!(cluster.token_ring().replica(50).map(|n| n.address),
+                   Some(address(3)));
+    }
+
+    #[test]
+    fn topology_changes() {
+        let cluster = Cluster::new(hosts(hosts_rows(&[(1, "dc1", "rack1", &["0"])])));
+
+        let new_node = topology_change(TopologyChangeType::NewNode, 2);
+        let added = cluster.on_event(&new_node, || {
+                Ok(hosts(hosts_rows(&[(1, "dc1", "rack1", &["0"]),
+                                      (2, "dc1", "rack1", &["100"])])))
+            })
+            .unwrap();
+        assert_eq!(added, vec![address(2)]);
+        assert!(cluster.contains(&address(2)));
+
+        // known nodes are not discovered again
+        let added = cluster.on_event(&new_node, || panic!("unexpected discovery")).unwrap();
+        assert!(added.is_empty());
+
+        let removed_node = topology_change(TopologyChangeType::RemovedNode, 1);
+        cluster.on_event(&removed_node, || panic!("unexpected discovery")).unwrap();
+        assert_eq!(cluster.nodes(), vec![Node::new(address(2), "dc1".to_string())]);
+
+        let discovery_failed = cluster.on_event(&topology_change(TopologyChangeType::NewNode, 3),
+                                                || Err(error::Error::General("down".to_string())));
+        assert!(discovery_failed.is_err());
+    }
+
+    #[test]
+    fn status_changes() {
+        let cluster = Cluster::new(hosts(hosts_rows(&[(1, "dc1", "rack1", &["0"])])));
+        let status_change = |change_type, last_octet| {
+            ServerEvent::StatusChange(StatusChange {
+                change_type: change_type,
+                addr: CInet { addr: SocketAddr::new(address(last_octet), 9042) },
+            })
+        };
+
+        cluster.on_event(&status_change(StatusChangeType::Down, 1), || panic!("unexpected"))
+            .unwrap();
+        cluster.on_event(&status_change(StatusChangeType::Up, 1), || panic!("unexpected"))
+            .unwrap();
+        assert_eq!(cluster.nodes().len(), 1);
+
+        // a node which is unknown yet is discovered once it's up
+        let added = cluster.on_event(&status_change(StatusChangeType::Up, 2), || {
+                Ok(hosts(hosts_rows(&[(2, "dc1", "rack1", &["100"])])))
+            })
+            .unwrap();
+        assert_eq!(added, vec![address(2)]);
+    }
+}
@@ -4,12 +4,17 @@ use std::result;
 use std::fmt;
 use snap;
 use lz4_compress as lz4;
+use types::{from_bytes, to_int, INT_LEN};
 
 type Result<T> = result::Result<T, CompressionError>;
 
 pub const LZ4: &'static str = "lz4";
 pub const SNAPPY: &'static str = "snappy";
 
+/// Default size of a frame body (in bytes) starting from which the body gets compressed.
+/// Compression of smaller bodies usually makes them bigger.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 64;
+
 
 /// It's an error which may occure during encoding or deconding
 /// frame body. As there are only two types of compressors it
@@ -17,7 +22,7 @@ pub const SNAPPY: &'static str = "snappy";
 #[derive(Debug)]
 pub enum CompressionError {
     /// Snappy error.
-    Snappy(Box<Error>),
+    Snappy(Box<Error + Send + Sync>),
     /// Lz4 error.
     Lz4(String),
 }
@@ -97,10 +102,7 @@ impl Compression {
     ///     let lz4_compression = Compression::Lz4;
     ///     let bytes = String::from("Hello World").into_bytes().to_vec();
     ///     let encoded = lz4_compression.encode(bytes.clone()).unwrap();
-    ///     let len = encoded.len() as u8;
-    ///     let mut input = vec![0, 0, 0, len];
-    ///     input.extend_from_slice(encoded.as_slice());
-    ///     assert_eq!(lz4_compression.decode(input).unwrap(), bytes);
+    ///     assert_eq!(lz4_compression.decode(encoded).unwrap(), bytes);
     /// ```
     pub fn decode(&self, bytes: Vec<u8>) -> Result<Vec<u8>> {
         match self {
@@ -110,6 +112,28 @@ impl Compression {
         }
     }
 
+    /// It decodes `bytes` the same way `decode` does but it takes a slice, so a buffer
+    /// holding compressed bytes could be reused. Uncompressed bytes are copied.
+    pub fn decode_slice(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            &Compression::Lz4 => Compression::decode_lz4_slice(bytes),
+            &Compression::Snappy => Compression::decode_snappy_slice(bytes),
+            &Compression::None => Ok(bytes.to_vec()),
+        }
+    }
+
+    /// It returns a length of `bytes` once they are decoded. The length is read from
+    /// a header of compressed bytes, so it is known before anything is decompressed.
+    pub fn decoded_len(&self, bytes: &[u8]) -> Result<usize> {
+        match self {
+            &Compression::Lz4 => Compression::decoded_lz4_len(bytes),
+            &Compression::Snappy => {
+                snap::decompress_len(bytes).map_err(|err| CompressionError::Snappy(Box::new(err)))
+            }
+            &Compression::None => Ok(bytes.len()),
+        }
+    }
+
     /// It transforms compression method into a `&str`.
     pub fn as_str(&self) -> Option<&'static str> {
         match self {
@@ -119,6 +143,21 @@ impl Compression {
         }
     }
 
+    /// It chooses the first compression from `preferred` list which is in a list of
+    /// compressions `supported` by a server (i.e. `COMPRESSION` option returned
+    /// in a response to `Options` request). If there is no such compression
+    /// `Compression::None` is returned.
+    pub fn negotiate(preferred: &[Compression], supported: &[String]) -> Compression {
+        preferred.iter()
+            .find(|compression| {
+                compression.as_str()
+                    .map(|name| supported.iter().any(|s| s.as_str() == name))
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .unwrap_or(Compression::None)
+    }
+
     fn encode_snappy(bytes: Vec<u8>) -> Result<Vec<u8>> {
         let mut encoder = snap::Encoder::new();
         encoder.compress_vec(bytes.as_slice())
@@ -126,21 +165,49 @@ impl Compression {
     }
 
     fn decode_snappy(bytes: Vec<u8>) -> Result<Vec<u8>> {
+        Compression::decode_snappy_slice(bytes.as_slice())
+    }
+
+    fn decode_snappy_slice(bytes: &[u8]) -> Result<Vec<u8>> {
         let mut decoder = snap::Decoder::new();
-        decoder.decompress_vec(bytes.as_slice())
+        decoder.decompress_vec(bytes)
             .map_err(|err| CompressionError::Snappy(Box::new(err)))
     }
 
     fn encode_lz4(bytes: Vec<u8>) -> Result<Vec<u8>> {
-        return Ok(lz4::compress(bytes.as_slice()));
+        // prepend 4 bytes of uncompressed length in accordance to
+        // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L805
+        let mut encoded = to_int(bytes.len() as i32);
+        encoded.extend_from_slice(lz4::compress(bytes.as_slice()).as_slice());
+        return Ok(encoded);
     }
 
     fn decode_lz4(bytes: Vec<u8>) -> Result<Vec<u8>> {
+        Compression::decode_lz4_slice(bytes.as_slice())
+    }
+
+    fn decode_lz4_slice(bytes: &[u8]) -> Result<Vec<u8>> {
+        try!(Compression::decoded_lz4_len(bytes));
+
         // skip first 4 bytes in accordance to
         // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L805
-        lz4::decompress(&bytes[4..])
+        lz4::decompress(&bytes[INT_LEN..])
             .map_err(|err| CompressionError::Lz4(err.description().to_string()))
     }
+
+    fn decoded_lz4_len(bytes: &[u8]) -> Result<usize> {
+        if bytes.len() < INT_LEN {
+            return Err(CompressionError::Lz4(format!("lz4 body should contain at least {} \
+                                                      bytes of uncompressed length",
+                                                     INT_LEN)));
+        }
+
+        let len = from_bytes(&bytes[..INT_LEN]) as i32;
+        if len < 0 {
+            return Err(CompressionError::Lz4(format!("negative uncompressed length {}", len)));
+        }
+        Ok(len as usize)
+    }
 }
 
 impl From<String> for Compression {
@@ -188,6 +255,19 @@ mod tests {
         assert_eq!(Compression::from(none), Compression::None);
     }
 
+    #[test]
+    fn test_compression_negotiate() {
+        let preferred = vec![Compression::Lz4, Compression::Snappy];
+        let only_snappy = vec!["snappy".to_string()];
+        assert_eq!(Compression::negotiate(&preferred, &only_snappy),
+                   Compression::Snappy);
+        let both = vec!["snappy".to_string(), "lz4".to_string()];
+        assert_eq!(Compression::negotiate(&preferred, &both), Compression::Lz4);
+        assert_eq!(Compression::negotiate(&preferred, &[]), Compression::None);
+        assert_eq!(Compression::negotiate(&[Compression::Lz4], &only_snappy),
+                   Compression::None);
+    }
+
     #[test]
     fn test_compression_encode_snappy() {
         let snappy_compression = Compression::Snappy;
@@ -215,10 +295,8 @@ mod tests {
         let lz4_compression = Compression::Lz4;
         let bytes = String::from("Hello World").into_bytes().to_vec();
         let encoded = lz4_compression.encode(bytes.clone()).unwrap();
-        let len = encoded.len() as u8;
-        let mut input = vec![0, 0, 0, len];
-        input.extend_from_slice(encoded.as_slice());
-        assert_eq!(lz4_compression.decode(input).unwrap(), bytes);
+        assert_eq!(&encoded[..4], &[0, 0, 0, 11]);
+        assert_eq!(lz4_compression.decode(encoded).unwrap(), bytes);
     }
 
     #[test]
@@ -243,8 +321,8 @@ mod tests {
         let lz4_compression = Compression::Lz4;
         let bytes: Vec<u8> = vec![0x7f, 0x7f, 0x7f, 0x7f, 0x7f];
         let encoded = lz4_compression.encode(bytes.clone()).unwrap();
-        let decode = lz4_compression.decode(encoded);
-        assert_eq!(decode.is_err(), true);
+        assert_eq!(lz4_compression.decode(encoded).unwrap(), bytes);
+        assert!(lz4_compression.decode(vec![0, 0, 0]).is_err());
     }
 
 
@@ -256,4 +334,14 @@ mod tests {
         assert_eq!(snappy_compression.decode(encoded).unwrap(), v);
     }
 
+    #[test]
+    fn test_compression_decoded_len() {
+        let bytes = String::from("Hello World").into_bytes().to_vec();
+        for compression in &[Compression::Lz4, Compression::Snappy, Compression::None] {
+            let encoded = compression.encode(bytes.clone()).unwrap();
+            assert_eq!(compression.decoded_len(encoded.as_slice()).unwrap(), 11);
+        }
+        assert!(Compression::Lz4.decoded_len(&[0, 0, 0]).is_err());
+        assert!(Compression::Lz4.decoded_len(&[0xFF, 0, 0, 0]).is_err());
+    }
 }
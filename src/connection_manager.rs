@@ -1,9 +1,21 @@
 //! This modules contains an implementation of [r2d2](https://github.com/sfackler/r2d2)
 //! functionality of connection pools. To get more details about creating r2d2 pools
 //! please refer to original documentation.
-use query::QueryBuilder;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use query::{Query, QueryBuilder, QueryParams, QueryBatch, BatchQueryBuilder, PreparedStatement};
 use client::{CDRS, Session};
+use consistency::Consistency;
+use error;
 use error::Error as CError;
+use frame::Frame;
+use frame::parser::DEFAULT_MAX_FRAME_SIZE;
+use frame::frame_batch::BatchType;
+use types::CBytesShort;
+use types::value::Value;
 use authenticators::Authenticator;
 use compression::Compression;
 use r2d2;
@@ -14,6 +26,8 @@ pub struct ConnectionManager<T, X> {
     transport: X,
     authenticator: T,
     compression: Compression,
+    heartbeat_interval: Option<Duration>,
+    max_frame_size: usize,
 }
 
 impl<T: Authenticator + Send + Sync + 'static, X: CDRSTransport + Send + Sync + 'static>
@@ -28,8 +42,25 @@ impl<T: Authenticator + Send + Sync + 'static, X: CDRSTransport + Send + Sync +
             transport: transport,
             authenticator: authenticator,
             compression: compression,
+            heartbeat_interval: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
         }
     }
+
+    /// Sets maximal length (in bytes) of a response frame body of established connections.
+    /// It's `DEFAULT_MAX_FRAME_SIZE` (256 MB) by default.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> ConnectionManager<T, X> {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Sets an interval of heartbeats. If it's set then a connection which has been idle
+    /// for the interval is checked with Options request rather than with a query when it's
+    /// borrowed, and connections which don't answer are replaced with new ones.
+    pub fn heartbeat_interval(mut self, interval: Option<Duration>) -> ConnectionManager<T, X> {
+        self.heartbeat_interval = interval;
+        self
+    }
 }
 
 impl<T: Authenticator + Send + Sync + 'static, X: CDRSTransport + Send + Sync + 'static>
@@ -40,18 +71,625 @@ r2d2::ManageConnection for ConnectionManager<T, X> {
     fn connect(&self) -> Result<Self::Connection, Self::Error> {
         let transport = try!(self.transport.try_clone());
         let compression = self.compression.clone();
-        let cdrs = CDRS::new(transport, self.authenticator.clone());
+        let cdrs = CDRS::new(transport, self.authenticator.clone())
+            .max_frame_size(self.max_frame_size);
 
         cdrs.start(compression)
     }
 
     fn is_valid(&self, connection: &mut Self::Connection) -> Result<(), Self::Error> {
+        if let Some(interval) = self.heartbeat_interval {
+            return connection.heartbeat_if_idle(interval);
+        }
+
         let query = QueryBuilder::new("SELECT * FROM system.peers;").finalize();
 
         connection.query(query, false, false).map(|_| (()))
     }
 
-    fn has_broken(&self, _connection: &mut Self::Connection) -> bool {
-        false
+    fn has_broken(&self, connection: &mut Self::Connection) -> bool {
+        connection.is_broken()
+    }
+}
+
+/// Configuration of `Pool`.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Minimal number of idle connections which the pool tries to maintain.
+    /// If it's `None` then it equals to `max_size`.
+    pub min_size: Option<u32>,
+    /// Maximal number of connections managed by the pool.
+    pub max_size: u32,
+    /// Idle connections are closed after this duration. If it's `None` then
+    /// idle connections are never closed.
+    pub idle_timeout: Option<Duration>,
+    /// Connections which have been idle for this duration are checked with a heartbeat
+    /// before they are borrowed. Idle connections of the pool are checked in background
+    /// with the same interval as well. Dead connections are replaced with new ones.
+    /// If it's `None` then heartbeats are not sent.
+    pub heartbeat_interval: Option<Duration>,
+    /// Maximal length (in bytes) of a response frame body. Longer frames, compressed
+    /// or not, are rejected and their connections are replaced. If it's `None` then
+    /// the limit of the connection manager is used (256 MB by default).
+    pub max_frame_size: Option<usize>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> PoolConfig {
+        PoolConfig {
+            min_size: None,
+            max_size: 10,
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            heartbeat_interval: None,
+            max_frame_size: None,
+        }
+    }
+}
+
+/// Pool of sessions. Each request borrows a session from the pool and returns it back
+/// once a response is received, so that concurrent requests are spread across different
+/// connections. Connections are validated on checkout and broken ones are replaced
+/// with new ones.
+pub struct Pool<T, X>
+    where T: Authenticator + Send + Sync + 'static,
+          X: CDRSTransport + Send + Sync + 'static
+{
+    inner: r2d2::Pool<ConnectionManager<T, X>>,
+    // shared by clones of the pool, heartbeats are stopped once the last clone is dropped
+    heartbeats: Option<Arc<Heartbeats>>,
+}
+
+// Handle of a thread which sends heartbeats to idle connections of a pool.
+struct Heartbeats {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Heartbeats {
+    // Spawns a thread which borrows all idle connections of `pool` each `interval`,
+    // so they are checked with heartbeats by `ConnectionManager::is_valid`.
+    fn start<M: r2d2::ManageConnection>(pool: r2d2::Pool<M>, interval: Duration) -> Heartbeats {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let signal = stopped.clone();
+        thread::spawn(move || {
+            let (ref stopped, ref condvar) = *signal;
+            loop {
+                let is_stopped = stopped.lock().unwrap();
+                if *is_stopped || *condvar.wait_timeout(is_stopped, interval).unwrap().0 {
+                    return;
+                }
+
+                let idle = pool.state().idle_connections;
+                let sessions: Vec<_> = (0..idle).filter_map(|_| pool.get().ok()).collect();
+                drop(sessions);
+            }
+        });
+
+        Heartbeats { stopped: stopped }
+    }
+}
+
+impl Drop for Heartbeats {
+    fn drop(&mut self) {
+        let (ref stopped, ref condvar) = *self.stopped;
+        *stopped.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+}
+
+impl<T, X> Pool<T, X>
+    where T: Authenticator + Send + Sync + 'static,
+          X: CDRSTransport + Send + Sync + 'static
+{
+    /// Creates new pool of sessions established via provided connection manager.
+    pub fn new(manager: ConnectionManager<T, X>, config: PoolConfig) -> error::Result<Pool<T, X>> {
+        let manager = match config.heartbeat_interval {
+            Some(interval) => manager.heartbeat_interval(Some(interval)),
+            None => manager,
+        };
+        let manager = match config.max_frame_size {
+            Some(max_frame_size) => manager.max_frame_size(max_frame_size),
+            None => manager,
+        };
+        let r2d2_config = r2d2::Config::builder()
+            .pool_size(config.max_size)
+            .min_idle(config.min_size)
+            .idle_timeout(config.idle_timeout)
+            .build();
+        let inner = try!(r2d2::Pool::new(r2d2_config, manager)
+            .map_err(|err| CError::General(err.to_string())));
+        let heartbeats = config.heartbeat_interval
+            .map(|interval| Arc::new(Heartbeats::start(inner.clone(), interval)));
+
+        Ok(Pool {
+            inner: inner,
+            heartbeats: heartbeats,
+        })
+    }
+
+    /// Borrows a session from the pool. The session is returned back to the pool
+    /// when it's dropped.
+    pub fn get(&self) -> error::Result<r2d2::PooledConnection<ConnectionManager<T, X>>> {
+        self.inner.get().map_err(|err| CError::General(err.to_string()))
+    }
+
+    /// Prepares a query using a session borrowed from the pool. See `Session::prepare`.
+    pub fn prepare(&self,
+                   query: String,
+                   with_tracing: bool,
+                   with_warnings: bool)
+                   -> error::Result<Frame> {
+        try!(self.get()).prepare(query, with_tracing, with_warnings)
+    }
+
+    /// Executes a prepared query using a session borrowed from the pool.
+    /// See `Session::execute`.
+    pub fn execute(&self,
+                   id: &CBytesShort,
+                   query_parameters: QueryParams,
+                   with_tracing: bool,
+                   with_warnings: bool)
+                   -> error::Result<Frame> {
+        try!(self.get()).execute(id, query_parameters, with_tracing, with_warnings)
+    }
+
+    /// Executes a query using a session borrowed from the pool. See `Session::query`.
+    pub fn query(&self,
+                 query: Query,
+                 with_tracing: bool,
+                 with_warnings: bool)
+                 -> error::Result<Frame> {
+        try!(self.get()).query(query, with_tracing, with_warnings)
+    }
+
+    /// Executes a batch using a session borrowed from the pool. See `Session::batch`.
+    pub fn batch(&self,
+                 batch_query: QueryBatch,
+                 with_tracing: bool,
+                 with_warnings: bool)
+                 -> error::Result<Frame> {
+        try!(self.get()).batch(batch_query, with_tracing, with_warnings)
+    }
+}
+
+/// Configuration of bulk execution of a prepared statement (see `Pool::bulk_execute`).
+#[derive(Debug, Clone)]
+pub struct BulkConfig {
+    /// Number of rows sent within a single unlogged batch.
+    pub batch_size: usize,
+    /// Maximal number of batches which are executed concurrently.
+    pub concurrency: usize,
+    /// Consistency of batches.
+    pub consistency: Consistency,
+    /// If it's `true` then no more batches are sent after the first failed one.
+    /// Otherwise errors are collected and the whole input is loaded.
+    pub stop_on_error: bool,
+}
+
+impl Default for BulkConfig {
+    fn default() -> BulkConfig {
+        BulkConfig {
+            batch_size: 100,
+            concurrency: 4,
+            consistency: Consistency::One,
+            stop_on_error: false,
+        }
+    }
+}
+
+/// Error of a single batch of bulk execution.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Index of the failed batch starting from 0. Rows of the batch are rows
+    /// `index * batch_size .. (index + 1) * batch_size` of the input.
+    pub index: usize,
+    pub error: CError,
+}
+
+/// Result of bulk execution.
+#[derive(Debug)]
+pub struct BulkResult {
+    /// Number of batches which were executed successfully. Batches which were skipped
+    /// after a failure (see `BulkConfig::stop_on_error`) are not counted.
+    pub batches: usize,
+    /// Errors of failed batches ordered by index of a batch.
+    pub errors: Vec<BatchError>,
+}
+
+impl BulkResult {
+    /// Returns `true` if all batches were executed successfully.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl<T, X> Pool<T, X>
+    where T: Authenticator + Send + Sync + 'static,
+          X: CDRSTransport + Send + Sync + 'static
+{
+    /// Executes provided prepared statement with each item of `rows` as its values.
+    /// Rows are grouped into unlogged batches of `config.batch_size` rows which are
+    /// executed by up to `config.concurrency` sessions borrowed from the pool.
+    /// Reading of `rows` is paused while all sessions are busy, so the input is
+    /// not buffered in memory.
+    pub fn bulk_execute<I>(&self,
+                           prepared: &PreparedStatement,
+                           rows: I,
+                           config: &BulkConfig)
+                           -> BulkResult
+        where I: IntoIterator<Item = Vec<Value>>
+    {
+        let concurrency = config.concurrency.max(1);
+        let (sender, receiver) = mpsc::sync_channel::<(usize, QueryBatch)>(concurrency);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let errors = Arc::new(Mutex::new(vec![]));
+        let failed = Arc::new(AtomicBool::new(false));
+        let stop_on_error = config.stop_on_error;
+
+        let executed = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..concurrency)
+            .map(|_| {
+                let pool = self.clone();
+                let receiver = receiver.clone();
+                let errors = errors.clone();
+                let failed = failed.clone();
+                let executed = executed.clone();
+                thread::spawn(move || loop {
+                    let next = receiver.lock().unwrap().recv();
+                    let (index, batch) = match next {
+                        Ok(next) => next,
+                        Err(_) => return,
+                    };
+                    if stop_on_error && failed.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    match pool.batch(batch, false, false) {
+                        Ok(_) => {
+                            executed.fetch_add(1, Ordering::SeqCst);
+                        }
+                        Err(error) => {
+                            failed.store(true, Ordering::SeqCst);
+                            errors.lock().unwrap().push(BatchError {
+                                index: index,
+                                error: error,
+                            });
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut batches = 0;
+        let mut rows = rows.into_iter().peekable();
+        while rows.peek().is_some() {
+            if stop_on_error && failed.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let batch = rows.by_ref()
+                .take(config.batch_size.max(1))
+                .fold(BatchQueryBuilder::new()
+                          .batch_type(BatchType::Unlogged)
+                          .consistency(config.consistency.clone()),
+                      |builder, values| {
+                    let values = values.into_iter().map(|value| (None, value)).collect();
+                    builder.add_query_prepared(prepared.id.clone(), values)
+                })
+                .finalize();
+            let index = batches;
+            batches += 1;
+
+            match batch {
+                Ok(batch) => {
+                    if sender.send((index, batch)).is_err() {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    failed.store(true, Ordering::SeqCst);
+                    errors.lock().unwrap().push(BatchError {
+                        index: index,
+                        error: error,
+                    });
+                }
+            }
+        }
+
+        drop(sender);
+        for worker in workers {
+            if worker.join().is_err() {
+                warn!("Bulk execution worker panicked");
+            }
+        }
+
+        let mut errors = Arc::try_unwrap(errors)
+            .map(|errors| errors.into_inner().unwrap())
+            .unwrap_or_else(|errors| errors.lock().unwrap().drain(..).collect());
+        errors.sort_by_key(|error| error.index);
+
+        BulkResult {
+            batches: executed.load(Ordering::SeqCst),
+            errors: errors,
+        }
+    }
+}
+
+impl<T, X> Clone for Pool<T, X>
+    where T: Authenticator + Send + Sync + 'static,
+          X: CDRSTransport + Send + Sync + 'static
+{
+    fn clone(&self) -> Pool<T, X> {
+        Pool {
+            inner: self.inner.clone(),
+            heartbeats: self.heartbeats.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+    use std::io::{self, Read, Write};
+    use std::net;
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::Instant;
+    use authenticators::NoneAuthenticator;
+    use frame::frame_result::{PreparedMetadata, RowsMetadata};
+    use super::*;
+
+    // Transport which responds to Startup with Ready and to other requests with Void
+    // result. It records ids of connections which received Query requests and counts
+    // Batch requests.
+    struct ResponderMock {
+        id: usize,
+        connections: Arc<Mutex<usize>>,
+        queried: Arc<Mutex<Vec<usize>>>,
+        batches: Arc<Mutex<usize>>,
+        // batches starting from this number (counting from 1) fail
+        failing_batch: usize,
+        pending: VecDeque<u8>,
+    }
+
+    impl ResponderMock {
+        fn new(queried: Arc<Mutex<Vec<usize>>>) -> ResponderMock {
+            ResponderMock {
+                id: 0,
+                connections: Arc::new(Mutex::new(0)),
+                queried: queried,
+                batches: Arc::new(Mutex::new(0)),
+                failing_batch: usize::max_value(),
+                pending: VecDeque::new(),
+            }
+        }
+
+        fn with_failing_batch(mut self, failing_batch: usize) -> ResponderMock {
+            self.failing_batch = failing_batch;
+            self
+        }
+
+        fn with_batches(mut self, batches: Arc<Mutex<usize>>) -> ResponderMock {
+            self.batches = batches;
+            self
+        }
+
+        fn with_connections(mut self, connections: Arc<Mutex<usize>>) -> ResponderMock {
+            self.connections = connections;
+            self
+        }
+    }
+
+    impl Read for ResponderMock {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.pending.len());
+            for (i, byte) in self.pending.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for ResponderMock {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let response: &[u8] = match buf[4] {
+                // startup
+                0x01 => &[0x84, 0, 0, 0, 0x02, 0, 0, 0, 0],
+                // query
+                0x07 => {
+                    self.queried.lock().unwrap().push(self.id);
+                    &[0x84, 0, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]
+                }
+                // batch
+                0x0D => {
+                    let mut batches = self.batches.lock().unwrap();
+                    *batches += 1;
+                    if *batches >= self.failing_batch {
+                        // Invalid error with empty message
+                        &[0x84, 0, 0, 0, 0x00, 0, 0, 0, 6, 0, 0, 0x22, 0, 0, 0]
+                    } else {
+                        &[0x84, 0, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]
+                    }
+                }
+                _ => &[0x84, 0, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1],
+            };
+            self.pending.extend(response.iter());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl CDRSTransport for ResponderMock {
+        fn try_clone(&self) -> io::Result<ResponderMock> {
+            let mut connections = self.connections.lock().unwrap();
+            *connections += 1;
+            Ok(ResponderMock {
+                id: *connections,
+                connections: self.connections.clone(),
+                queried: self.queried.clone(),
+                batches: self.batches.clone(),
+                failing_batch: self.failing_batch,
+                pending: VecDeque::new(),
+            })
+        }
+
+        fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn concurrent_queries_use_different_connections() {
+        let queried = Arc::new(Mutex::new(vec![]));
+        let manager = ConnectionManager::new(ResponderMock::new(queried.clone()),
+                                             NoneAuthenticator,
+                                             Compression::None);
+        let config = PoolConfig {
+            min_size: Some(4),
+            max_size: 4,
+            idle_timeout: None,
+            heartbeat_interval: None,
+            max_frame_size: None,
+        };
+        let pool = Pool::new(manager, config).unwrap();
+
+        let barrier = Arc::new(Barrier::new(4));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    let mut session = pool.get().unwrap();
+                    barrier.wait();
+                    let query = QueryBuilder::new("SELECT * FROM ks.table").finalize();
+                    session.query(query, false, false).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let query = QueryBuilder::new("SELECT * FROM ks.table").finalize();
+        assert!(pool.query(query, false, false).is_ok());
+
+        let used: HashSet<usize> = queried.lock().unwrap().iter().cloned().collect();
+        assert_eq!(used.len(), 4);
+    }
+
+    #[test]
+    fn heartbeat_replaces_dead_connection() {
+        // the mock answers Options request with a result instead of Supported
+        let manager = ConnectionManager::new(ResponderMock::new(Arc::new(Mutex::new(vec![]))),
+                                             NoneAuthenticator,
+                                             Compression::None)
+            .heartbeat_interval(Some(Duration::from_secs(0)));
+        let mut session = r2d2::ManageConnection::connect(&manager).unwrap();
+
+        assert!(r2d2::ManageConnection::is_valid(&manager, &mut session).is_err());
+        assert!(r2d2::ManageConnection::has_broken(&manager, &mut session));
+    }
+
+    #[test]
+    fn heartbeats_of_idle_connections() {
+        // dead connections are replaced without being borrowed by a user
+        let connections = Arc::new(Mutex::new(0));
+        let transport = ResponderMock::new(Arc::new(Mutex::new(vec![])))
+            .with_connections(connections.clone());
+        let manager = ConnectionManager::new(transport, NoneAuthenticator, Compression::None);
+        let config = PoolConfig {
+            min_size: Some(1),
+            max_size: 1,
+            idle_timeout: None,
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            max_frame_size: None,
+        };
+        let pool = Pool::new(manager, config).unwrap();
+
+        let started = Instant::now();
+        while *connections.lock().unwrap() < 3 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        drop(pool);
+    }
+
+    #[test]
+    fn max_frame_size_of_connections() {
+        // results of the mock have 4-byte bodies
+        let manager = ConnectionManager::new(ResponderMock::new(Arc::new(Mutex::new(vec![]))),
+                                             NoneAuthenticator,
+                                             Compression::None)
+            .max_frame_size(3);
+        let mut session = r2d2::ManageConnection::connect(&manager).unwrap();
+
+        let query = QueryBuilder::new("SELECT * FROM ks.table").finalize();
+        assert!(session.query(query, false, false).is_err());
+        assert!(r2d2::ManageConnection::has_broken(&manager, &mut session));
+    }
+
+    fn bulk_prepared() -> PreparedStatement {
+        PreparedStatement {
+            id: CBytesShort::new(vec![1, 2, 3]),
+            metadata: PreparedMetadata {
+                flags: 0,
+                columns_count: 0,
+                pk_count: 0,
+                pk_indexes: vec![],
+                global_table_spec: None,
+                col_specs: vec![],
+            },
+            result_metadata: RowsMetadata {
+                flags: 0,
+                columns_count: 0,
+                paging_state: None,
+                global_table_space: None,
+                col_specs: vec![],
+            },
+        }
+    }
+
+    fn bulk_rows() -> Box<Iterator<Item = Vec<Value>>> {
+        Box::new((0..1000).map(|i: i32| vec![Value::from(i), Value::from(format!("row {}", i))]))
+    }
+
+    #[test]
+    fn bulk_execute() {
+        let batches = Arc::new(Mutex::new(0));
+        let transport = ResponderMock::new(Arc::new(Mutex::new(vec![])))
+            .with_batches(batches.clone());
+        let manager = ConnectionManager::new(transport, NoneAuthenticator, Compression::None);
+        let pool = Pool::new(manager, PoolConfig::default()).unwrap();
+        let config = BulkConfig { batch_size: 30, ..BulkConfig::default() };
+
+        let result = pool.bulk_execute(&bulk_prepared(), bulk_rows(), &config);
+        assert!(result.is_ok());
+        assert_eq!(result.batches, 34);
+        assert_eq!(*batches.lock().unwrap(), 34);
+    }
+
+    #[test]
+    fn bulk_execute_stops_on_error() {
+        let batches = Arc::new(Mutex::new(0));
+        let transport = ResponderMock::new(Arc::new(Mutex::new(vec![])))
+            .with_batches(batches.clone())
+            .with_failing_batch(4);
+        let manager = ConnectionManager::new(transport, NoneAuthenticator, Compression::None);
+        let pool = Pool::new(manager, PoolConfig::default()).unwrap();
+        let config = BulkConfig {
+            batch_size: 30,
+            concurrency: 1,
+            stop_on_error: true,
+            ..BulkConfig::default()
+        };
+
+        let result = pool.bulk_execute(&bulk_prepared(), bulk_rows(), &config);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].index, 3);
+        // batches which were skipped after the failure are not counted
+        assert_eq!(result.batches, 3);
+        assert_eq!(*batches.lock().unwrap(), 4);
     }
 }
@@ -1,9 +1,13 @@
 #![warn(missing_docs)]
 //! The module contains Rust representation of Cassandra consistency levels.
 use std::io;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
 use std::convert::From;
 use std::default::Default;
-use super::{IntoBytes, FromCursor};
+use super::{IntoBytes, FromCursor, TryFromCursor};
+use super::error::Result as CDRSResult;
 use super::types::*;
 use super::FromBytes;
 
@@ -77,6 +81,76 @@ impl Default for Consistency {
     }
 }
 
+impl Consistency {
+    /// Returns `true` if consistency level is either `Serial` or `LocalSerial`, i.e. it
+    /// can be used as a serial consistency of conditional statements.
+    pub fn is_serial(&self) -> bool {
+        match self {
+            &Consistency::Serial | &Consistency::LocalSerial => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Consistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            &Consistency::Any => "ANY",
+            &Consistency::One => "ONE",
+            &Consistency::Two => "TWO",
+            &Consistency::Three => "THREE",
+            &Consistency::Quorum => "QUORUM",
+            &Consistency::All => "ALL",
+            &Consistency::LocalQuorum => "LOCAL_QUORUM",
+            &Consistency::EachQuorum => "EACH_QUORUM",
+            &Consistency::Serial => "SERIAL",
+            &Consistency::LocalSerial => "LOCAL_SERIAL",
+            &Consistency::LocalOne => "LOCAL_ONE",
+            &Consistency::Unknown => "UNKNOWN",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Error which is returned when a string cannot be parsed into `Consistency`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseConsistencyError(String);
+
+impl fmt::Display for ParseConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown consistency level: {}", self.0)
+    }
+}
+
+impl error::Error for ParseConsistencyError {
+    fn description(&self) -> &str {
+        "unknown consistency level"
+    }
+}
+
+impl FromStr for Consistency {
+    type Err = ParseConsistencyError;
+
+    /// Parses canonical name of consistency level (e.g. `LOCAL_QUORUM`) ignoring case.
+    fn from_str(s: &str) -> Result<Consistency, ParseConsistencyError> {
+        return match s.to_uppercase().as_str() {
+            "ANY" => Ok(Consistency::Any),
+            "ONE" => Ok(Consistency::One),
+            "TWO" => Ok(Consistency::Two),
+            "THREE" => Ok(Consistency::Three),
+            "QUORUM" => Ok(Consistency::Quorum),
+            "ALL" => Ok(Consistency::All),
+            "LOCAL_QUORUM" => Ok(Consistency::LocalQuorum),
+            "EACH_QUORUM" => Ok(Consistency::EachQuorum),
+            "SERIAL" => Ok(Consistency::Serial),
+            "LOCAL_SERIAL" => Ok(Consistency::LocalSerial),
+            "LOCAL_ONE" => Ok(Consistency::LocalOne),
+            _ => Err(ParseConsistencyError(s.to_string())),
+        };
+    }
+}
+
 impl IntoBytes for Consistency {
     fn into_cbytes(&self) -> Vec<u8> {
         return match self {
@@ -142,6 +216,13 @@ impl FromCursor for Consistency {
     }
 }
 
+impl TryFromCursor for Consistency {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> CDRSResult<Consistency> {
+        let consistency_num = try!(CIntShort::try_from_cursor(&mut cursor)) as i32;
+        Ok(Consistency::from(consistency_num))
+    }
+}
+
 
 
 #[cfg(test)]
@@ -166,6 +247,14 @@ mod tests {
         assert_eq!(Consistency::Unknown.into_cbytes(), &[0, 99]);
     }
 
+    #[test]
+    fn test_consistency_is_serial() {
+        assert!(Consistency::Serial.is_serial());
+        assert!(Consistency::LocalSerial.is_serial());
+        assert!(!Consistency::Quorum.is_serial());
+        assert!(!Consistency::One.is_serial());
+    }
+
     #[test]
     fn test_consistency_from() {
         assert_eq!(Consistency::from(0), Consistency::Any);
@@ -224,4 +313,30 @@ mod tests {
                    Consistency::LocalOne);
     }
 
+    #[test]
+    fn test_consistency_from_str() {
+        let levels = vec![Consistency::Any,
+                          Consistency::One,
+                          Consistency::Two,
+                          Consistency::Three,
+                          Consistency::Quorum,
+                          Consistency::All,
+                          Consistency::LocalQuorum,
+                          Consistency::EachQuorum,
+                          Consistency::Serial,
+                          Consistency::LocalSerial,
+                          Consistency::LocalOne];
+
+        for level in levels {
+            let name = level.to_string();
+            assert_eq!(name.parse::<Consistency>(), Ok(level.clone()));
+            assert_eq!(name.to_lowercase().parse::<Consistency>(), Ok(level));
+        }
+
+        assert_eq!("local_quorum".parse::<Consistency>(), Ok(Consistency::LocalQuorum));
+        assert_eq!("UNKNOWN".parse::<Consistency>(),
+                   Err(ParseConsistencyError("UNKNOWN".to_string())));
+        assert!("QUORUMS".parse::<Consistency>().is_err());
+    }
+
 }
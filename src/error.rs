@@ -28,12 +28,34 @@ pub enum Error {
     Compression(CompressionError),
     /// Server error.
     Server(CDRSError),
+    /// Error which is returned when a value of some Cassandra type is requested
+    /// as an incompatible Rust type.
+    TypeMismatch(String),
+    /// Error which is returned when a server requires authentication but no authenticator
+    /// was provided. Contains the class name of the authenticator used by the server,
+    /// e.g. `org.apache.cassandra.auth.PasswordAuthenticator`.
+    AuthenticationRequired(String),
+    /// Authentication error which is returned when a server rejects credentials provided
+    /// by an authenticator. Contains the message of the server, e.g. `Username and/or
+    /// password are incorrect`.
+    Authentication(String),
+    /// Error which is returned when values of rows received without metadata
+    /// (`No_metadata` flag, e.g. executes of prepared statements) are requested.
+    /// Result metadata of the prepared statement should be provided to decode them.
+    NoMetadata,
 }
 
 pub fn column_is_empty_err() -> Error {
     Error::General("Column or UDT property is empty".to_string())
 }
 
+pub fn type_mismatch_err<T: fmt::Debug>(expected: &str, got: T) -> Error {
+    Error::TypeMismatch(format!("Unsupported type of converter. {:?} got, but ({}) \
+                                 is only supported.",
+                                got,
+                                expected))
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -43,6 +65,16 @@ impl fmt::Display for Error {
             Error::FromUtf8(ref err) => write!(f, "FromUtf8Error error: {:?}", err),
             Error::UUIDParse(ref err) => write!(f, "UUIDParse error: {:?}", err),
             Error::General(ref err) => write!(f, "GeneralParsing error: {:?}", err),
+            Error::TypeMismatch(ref err) => write!(f, "Type mismatch error: {}", err),
+            Error::AuthenticationRequired(ref authenticator) => {
+                write!(f,
+                       "Server requires authentication with {} but no authenticator was provided",
+                       authenticator)
+            }
+            Error::Authentication(ref err) => write!(f, "Authentication error: {}", err),
+            Error::NoMetadata => {
+                write!(f, "Rows are received without metadata, result metadata is required")
+            }
         }
     }
 }
@@ -57,6 +89,10 @@ impl error::Error for Error {
             // FIXME: err.description not found in current scope, std::error::Error not satisfied
             Error::UUIDParse(_) => "UUID Parse Error",
             Error::General(ref err) => err.as_str(),
+            Error::TypeMismatch(ref err) => err.as_str(),
+            Error::AuthenticationRequired(_) => "Authentication is required by server",
+            Error::Authentication(ref err) => err.as_str(),
+            Error::NoMetadata => "Rows are received without metadata",
         }
     }
 }
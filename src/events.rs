@@ -1,11 +1,15 @@
+use std::io::Cursor;
 use std::sync::mpsc::{Sender, Receiver, channel};
 use std::iter::Iterator;
 
 use std::error::Error;
+use TryFromCursor;
 use error;
+use frame::Opcode;
+use frame::frame_event::BodyResEvent;
 use frame::events::{ServerEvent as FrameServerEvent, SimpleServerEvent as FrameSimpleServerEvent,
                     SchemaChange as FrameSchemaChange};
-use frame::parser::parse_frame;
+use frame::parser::FrameCodec;
 use compression::Compression;
 use transport::CDRSTransport;
 
@@ -31,6 +35,7 @@ pub fn new_listener<X>(transport: X) -> (Listener<X>, EventStream) {
     let (tx, rx) = channel();
     let listener = Listener {
         transport: transport,
+        codec: FrameCodec::new(),
         tx: tx,
     };
     let stream = EventStream { rx: rx };
@@ -43,22 +48,22 @@ pub fn new_listener<X>(transport: X) -> (Listener<X>, EventStream) {
 
 pub struct Listener<X> {
     transport: X,
+    codec: FrameCodec,
     tx: Sender<ServerEvent>,
 }
 
 impl<X: CDRSTransport> Listener<X> {
     /// It starts a process of listening to new events. Locks a frame.
+    /// Malformed events (e.g. of unknown type) stop the listener with an error.
     pub fn start(&mut self, compressor: &Compression) -> error::Result<()> {
         loop {
-            let event_opt = try!(parse_frame(&mut self.transport, compressor))
-                .get_body()
-                .into_server_event();
-
-            let event = if event_opt.is_some() {
-                event_opt.unwrap().event as ServerEvent
-            } else {
+            let frame = try!(self.codec.read_frame(&mut self.transport, compressor));
+            if frame.opcode != Opcode::Event {
                 continue;
-            };
+            }
+
+            let mut cursor: Cursor<&[u8]> = Cursor::new(frame.body.as_slice());
+            let event = try!(BodyResEvent::try_from_cursor(&mut cursor)).event;
             match self.tx.send(event) {
                 Err(err) => return Err(error::Error::General(err.description().to_string())),
                 _ => continue,
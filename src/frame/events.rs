@@ -1,9 +1,14 @@
 use std::io::Cursor;
 use std::cmp::PartialEq;
 
-use FromCursor;
+use {FromCursor, TryFromCursor};
+use error;
 use types::{CString, CStringList, CInet};
 
+/// Stream id of EVENT frames which a server pushes to a connection registered
+/// for events, i.e. `-1` encoded as two bytes.
+pub const EVENT_STREAM_ID: u64 = 0xFFFF;
+
 // Event types
 const TOPOLOGY_CHANGE: &'static str = "TOPOLOGY_CHANGE";
 const STATUS_CHANGE: &'static str = "STATUS_CHANGE";
@@ -95,15 +100,29 @@ impl PartialEq<SimpleServerEvent> for ServerEvent {
 
 impl FromCursor for ServerEvent {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> ServerEvent {
-        let event_type = CString::from_cursor(&mut cursor);
-        match event_type.as_str() {
+        ServerEvent::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for ServerEvent {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ServerEvent> {
+        let event_type = try!(CString::try_from_cursor(&mut cursor));
+        let event = match event_type.as_str() {
             TOPOLOGY_CHANGE => {
-                ServerEvent::TopologyChange(TopologyChange::from_cursor(&mut cursor))
+                ServerEvent::TopologyChange(try!(TopologyChange::try_from_cursor(&mut cursor)))
             }
-            STATUS_CHANGE => ServerEvent::StatusChange(StatusChange::from_cursor(&mut cursor)),
-            SCHEMA_CHANGE => ServerEvent::SchemaChange(SchemaChange::from_cursor(&mut cursor)),
-            _ => unreachable!(),
-        }
+            STATUS_CHANGE => {
+                ServerEvent::StatusChange(try!(StatusChange::try_from_cursor(&mut cursor)))
+            }
+            SCHEMA_CHANGE => {
+                ServerEvent::SchemaChange(try!(SchemaChange::try_from_cursor(&mut cursor)))
+            }
+            other => {
+                return Err(error::Error::General(format!("Unexpected event type {}", other)))
+            }
+        };
+
+        Ok(event)
     }
 }
 
@@ -116,13 +135,19 @@ pub struct TopologyChange {
 
 impl FromCursor for TopologyChange {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> TopologyChange {
-        let change_type = TopologyChangeType::from_cursor(&mut cursor);
-        let addr = CInet::from_cursor(&mut cursor);
+        TopologyChange::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for TopologyChange {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<TopologyChange> {
+        let change_type = try!(TopologyChangeType::try_from_cursor(&mut cursor));
+        let addr = try!(CInet::try_from_cursor(&mut cursor));
 
-        TopologyChange {
+        Ok(TopologyChange {
             change_type: change_type,
             addr: addr,
-        }
+        })
     }
 }
 
@@ -134,10 +159,18 @@ pub enum TopologyChangeType {
 
 impl FromCursor for TopologyChangeType {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> TopologyChangeType {
-        match CString::from_cursor(&mut cursor).as_str() {
-            NEW_NODE => TopologyChangeType::NewNode,
-            REMOVED_NODE => TopologyChangeType::RemovedNode,
-            _ => unreachable!(),
+        TopologyChangeType::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for TopologyChangeType {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<TopologyChangeType> {
+        match try!(CString::try_from_cursor(&mut cursor)).as_str() {
+            NEW_NODE => Ok(TopologyChangeType::NewNode),
+            REMOVED_NODE => Ok(TopologyChangeType::RemovedNode),
+            other => {
+                Err(error::Error::General(format!("Unexpected topology change type {}", other)))
+            }
         }
     }
 }
@@ -151,13 +184,19 @@ pub struct StatusChange {
 
 impl FromCursor for StatusChange {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> StatusChange {
-        let change_type = StatusChangeType::from_cursor(&mut cursor);
-        let addr = CInet::from_cursor(&mut cursor);
+        StatusChange::try_from_cursor(&mut cursor).unwrap()
+    }
+}
 
-        StatusChange {
+impl TryFromCursor for StatusChange {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<StatusChange> {
+        let change_type = try!(StatusChangeType::try_from_cursor(&mut cursor));
+        let addr = try!(CInet::try_from_cursor(&mut cursor));
+
+        Ok(StatusChange {
             change_type: change_type,
             addr: addr,
-        }
+        })
     }
 }
 
@@ -169,10 +208,18 @@ pub enum StatusChangeType {
 
 impl FromCursor for StatusChangeType {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> StatusChangeType {
-        match CString::from_cursor(&mut cursor).as_str() {
-            UP => StatusChangeType::Up,
-            DOWN => StatusChangeType::Down,
-            _ => unreachable!(),
+        StatusChangeType::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for StatusChangeType {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<StatusChangeType> {
+        match try!(CString::try_from_cursor(&mut cursor)).as_str() {
+            UP => Ok(StatusChangeType::Up),
+            DOWN => Ok(StatusChangeType::Down),
+            other => {
+                Err(error::Error::General(format!("Unexpected status change type {}", other)))
+            }
         }
     }
 }
@@ -185,17 +232,53 @@ pub struct SchemaChange {
     pub options: ChangeSchemeOptions,
 }
 
+impl SchemaChange {
+    /// Returns a name of a keyspace which was changed or contains a changed object.
+    pub fn keyspace(&self) -> &str {
+        match self.options {
+            ChangeSchemeOptions::Keyspace(ref keyspace) |
+            ChangeSchemeOptions::TableType((ref keyspace, _)) |
+            ChangeSchemeOptions::FunctionAggregate((ref keyspace, _, _)) => keyspace.as_str(),
+        }
+    }
+
+    /// Returns a name of a changed table, type, function or aggregate.
+    /// It's `None` if a keyspace itself was changed.
+    pub fn name(&self) -> Option<&str> {
+        match self.options {
+            ChangeSchemeOptions::Keyspace(_) => None,
+            ChangeSchemeOptions::TableType((_, ref name)) |
+            ChangeSchemeOptions::FunctionAggregate((_, ref name, _)) => Some(name.as_str()),
+        }
+    }
+
+    /// Returns CQL types of arguments of a changed function or aggregate.
+    /// It's empty for other targets.
+    pub fn arg_types(&self) -> &[String] {
+        match self.options {
+            ChangeSchemeOptions::FunctionAggregate((_, _, ref arg_types)) => arg_types.as_slice(),
+            _ => &[],
+        }
+    }
+}
+
 impl FromCursor for SchemaChange {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> SchemaChange {
-        let change_type = ChangeType::from_cursor(&mut cursor);
-        let target = Target::from_cursor(&mut cursor);
-        let options = ChangeSchemeOptions::from_cursor_and_target(&mut cursor, &target);
+        SchemaChange::try_from_cursor(&mut cursor).unwrap()
+    }
+}
 
-        SchemaChange {
+impl TryFromCursor for SchemaChange {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<SchemaChange> {
+        let change_type = try!(ChangeType::try_from_cursor(&mut cursor));
+        let target = try!(Target::try_from_cursor(&mut cursor));
+        let options = try!(ChangeSchemeOptions::from_cursor_and_target(&mut cursor, &target));
+
+        Ok(SchemaChange {
             change_type: change_type,
             target: target,
             options: options,
-        }
+        })
     }
 }
 
@@ -209,11 +292,19 @@ pub enum ChangeType {
 
 impl FromCursor for ChangeType {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> ChangeType {
-        match CString::from_cursor(&mut cursor).as_str() {
-            CREATED => ChangeType::Created,
-            UPDATED => ChangeType::Updated,
-            DROPPED => ChangeType::Dropped,
-            _ => unreachable!(),
+        ChangeType::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for ChangeType {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ChangeType> {
+        match try!(CString::try_from_cursor(&mut cursor)).as_str() {
+            CREATED => Ok(ChangeType::Created),
+            UPDATED => Ok(ChangeType::Updated),
+            DROPPED => Ok(ChangeType::Dropped),
+            other => {
+                Err(error::Error::General(format!("Unexpected schema change type {}", other)))
+            }
         }
     }
 }
@@ -230,13 +321,21 @@ pub enum Target {
 
 impl FromCursor for Target {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> Target {
-        match CString::from_cursor(&mut cursor).as_str() {
-            KEYSPACE => Target::Keyspace,
-            TABLE => Target::Table,
-            TYPE => Target::Type,
-            FUNCTION => Target::Function,
-            AGGREGATE => Target::Aggregate,
-            _ => unreachable!(),
+        Target::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for Target {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<Target> {
+        match try!(CString::try_from_cursor(&mut cursor)).as_str() {
+            KEYSPACE => Ok(Target::Keyspace),
+            TABLE => Ok(Target::Table),
+            TYPE => Ok(Target::Type),
+            FUNCTION => Ok(Target::Function),
+            AGGREGATE => Ok(Target::Aggregate),
+            other => {
+                Err(error::Error::General(format!("Unexpected schema change target {}", other)))
+            }
         }
     }
 }
@@ -258,7 +357,7 @@ pub enum ChangeSchemeOptions {
 impl ChangeSchemeOptions {
     fn from_cursor_and_target(mut cursor: &mut Cursor<&[u8]>,
                               target: &Target)
-                              -> ChangeSchemeOptions {
+                              -> error::Result<ChangeSchemeOptions> {
         match target {
             &Target::Keyspace => ChangeSchemeOptions::from_cursor_keyspace(&mut cursor),
             &Target::Table | &Target::Type => {
@@ -269,21 +368,25 @@ impl ChangeSchemeOptions {
         }
     }
 
-    fn from_cursor_keyspace(mut cursor: &mut Cursor<&[u8]>) -> ChangeSchemeOptions {
-        ChangeSchemeOptions::Keyspace(CString::from_cursor(&mut cursor).into_plain())
+    fn from_cursor_keyspace(mut cursor: &mut Cursor<&[u8]>)
+                            -> error::Result<ChangeSchemeOptions> {
+        let keyspace = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+        Ok(ChangeSchemeOptions::Keyspace(keyspace))
     }
 
-    fn from_cursor_table_type(mut cursor: &mut Cursor<&[u8]>) -> ChangeSchemeOptions {
-        let keyspace = CString::from_cursor(&mut cursor).into_plain();
-        let name = CString::from_cursor(&mut cursor).into_plain();
-        ChangeSchemeOptions::TableType((keyspace, name))
+    fn from_cursor_table_type(mut cursor: &mut Cursor<&[u8]>)
+                              -> error::Result<ChangeSchemeOptions> {
+        let keyspace = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+        let name = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+        Ok(ChangeSchemeOptions::TableType((keyspace, name)))
     }
 
-    fn from_cursor_function_aggregate(mut cursor: &mut Cursor<&[u8]>) -> ChangeSchemeOptions {
-        let keyspace = CString::from_cursor(&mut cursor).into_plain();
-        let name = CString::from_cursor(&mut cursor).into_plain();
-        let types = CStringList::from_cursor(&mut cursor).into_plain();
-        ChangeSchemeOptions::FunctionAggregate((keyspace, name, types))
+    fn from_cursor_function_aggregate(mut cursor: &mut Cursor<&[u8]>)
+                                      -> error::Result<ChangeSchemeOptions> {
+        let keyspace = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+        let name = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+        let types = try!(CStringList::try_from_cursor(&mut cursor)).into_plain();
+        Ok(ChangeSchemeOptions::FunctionAggregate((keyspace, name, types)))
     }
 }
 
@@ -429,7 +532,7 @@ mod schema_change_target_test {
 mod server_event {
     use super::*;
     use std::io::Cursor;
-    use FromCursor;
+    use {FromCursor, TryFromCursor};
 
     #[test]
     fn topology_change_new_node() {
@@ -463,7 +566,6 @@ mod server_event {
                       68,
                       69,
                       // 127.0.0.1:1
-                      0,
                       4,
                       127,
                       0,
@@ -520,7 +622,6 @@ mod server_event {
                       68,
                       69,
                       // 127.0.0.1:1
-                      0,
                       4,
                       127,
                       0,
@@ -565,7 +666,6 @@ mod server_event {
                       85,
                       80,
                       // 127.0.0.1:1
-                      0,
                       4,
                       127,
                       0,
@@ -612,7 +712,6 @@ mod server_event {
                       87,
                       78,
                       // 127.0.0.1:1
-                      0,
                       4,
                       127,
                       0,
@@ -1661,4 +1760,41 @@ mod server_event {
             _ => panic!("should be schema change"),
         }
     }
+
+    fn strings(values: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for value in values {
+            bytes.extend_from_slice(&[0, value.len() as u8]);
+            bytes.extend_from_slice(value.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn try_from_cursor_errors() {
+        let try_event = |bytes: &[u8]| ServerEvent::try_from_cursor(&mut Cursor::new(bytes));
+
+        let mut status = strings(&["STATUS_CHANGE", "UP"]);
+        status.extend_from_slice(&[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52]);
+        assert!(try_event(status.as_slice()).is_ok());
+        // truncated inet
+        assert!(try_event(&status[..status.len() - 1]).is_err());
+        assert!(try_event(&status[..status.len() - 5]).is_err());
+
+        let mut unknown = strings(&["STATUS_CHANGE", "SIDEWAYS"]);
+        unknown.extend_from_slice(&[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52]);
+        assert!(try_event(unknown.as_slice()).is_err());
+        let mut unknown = strings(&["TOPOLOGY_CHANGE", "MOVED_NODE"]);
+        unknown.extend_from_slice(&[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52]);
+        assert!(try_event(unknown.as_slice()).is_err());
+        assert!(try_event(strings(&["WEATHER_CHANGE"]).as_slice()).is_err());
+        assert!(try_event(strings(&["SCHEMA_CHANGE", "RENAMED", "KEYSPACE", "ks"]).as_slice())
+            .is_err());
+        assert!(try_event(strings(&["SCHEMA_CHANGE", "CREATED", "VIEW", "ks"]).as_slice())
+            .is_err());
+        assert!(try_event(strings(&["SCHEMA_CHANGE", "CREATED", "TABLE", "ks"]).as_slice())
+            .is_err());
+        assert!(try_event(strings(&["SCHEMA_CHANGE", "CREATED", "TABLE", "ks", "t"]).as_slice())
+            .is_ok());
+    }
 }
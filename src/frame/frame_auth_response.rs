@@ -40,6 +40,7 @@ impl Frame {
             // for request frames it's always None
             tracing_id: None,
             warnings: vec![],
+            custom_payload: None,
         }
     }
 }
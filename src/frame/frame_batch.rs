@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use {AsByte, FromSingleByte, IntoBytes};
 use frame::*;
-use frame::frame_query::QueryFlags;
+use error;
+use frame::frame_query::{QueryFlags, check_protocol_options, query_flags_into_cbytes};
 use types::*;
 use types::value::Value;
 use consistency::Consistency;
@@ -16,10 +18,33 @@ pub struct BodyReqBatch {
     pub query_flags: Vec<QueryFlags>,
     pub serial_consistency: Option<Consistency>,
     pub timestamp: Option<i64>,
+    /// Keyspace the batch is executed in. It's supported since 5-th version of the protocol.
+    pub keyspace: Option<CString>,
+    /// Shows if the batch is idempotent. It's a client-side option which isn't sent
+    /// to a server, only idempotent batches are retried on timeouts.
+    pub idempotent: bool,
+    /// Custom payload of the request. It's not a part of the body but it's sent
+    /// within a frame (see `Frame::with_custom_payload`).
+    pub custom_payload: Option<HashMap<String, Vec<u8>>>,
 }
 
-impl IntoBytes for BodyReqBatch {
-    fn into_cbytes(&self) -> Vec<u8> {
+impl BodyReqBatch {
+    /// Sets default timestamp (in microseconds) of the batch.
+    pub fn set_timestamp(&mut self, timestamp: i64) {
+        if self.timestamp.is_none() {
+            self.query_flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+        self.timestamp = Some(timestamp);
+    }
+
+    /// Returns an error if the batch contains options which are not supported
+    /// by provided version of native protocol.
+    pub fn check_protocol(&self, protocol: ProtocolVersion) -> error::Result<()> {
+        check_protocol_options(self.keyspace.as_ref(), None, protocol)
+    }
+
+    /// Encodes the batch in accordance with provided version of native protocol.
+    pub fn into_cbytes_with_protocol(&self, protocol: ProtocolVersion) -> Vec<u8> {
         let mut bytes = vec![];
 
         bytes.push(self.batch_type.as_byte());
@@ -35,10 +60,10 @@ impl IntoBytes for BodyReqBatch {
 
         bytes.extend_from_slice(self.consistency.into_cbytes().as_slice());
 
-        let flag_byte = self.query_flags
+        let flags = self.query_flags
             .iter()
-            .fold(0, |mut _bytes, f| _bytes | f.as_byte());
-        bytes.push(flag_byte);
+            .fold(0, |mut _bytes, f| _bytes | f.as_int());
+        bytes.extend_from_slice(query_flags_into_cbytes(flags, protocol).as_slice());
 
         if let Some(ref serial_consistency) = self.serial_consistency {
             bytes.extend_from_slice(serial_consistency.into_cbytes().as_slice());
@@ -48,10 +73,20 @@ impl IntoBytes for BodyReqBatch {
             bytes.extend_from_slice(to_bigint(timestamp.clone()).as_slice());
         }
 
+        if let Some(ref keyspace) = self.keyspace {
+            bytes.extend_from_slice(keyspace.into_cbytes().as_slice());
+        }
+
         bytes
     }
 }
 
+impl IntoBytes for BodyReqBatch {
+    fn into_cbytes(&self) -> Vec<u8> {
+        self.into_cbytes_with_protocol(ProtocolVersion::default())
+    }
+}
+
 /// Batch type
 #[derive(Debug, Clone)]
 pub enum BatchType {
@@ -165,6 +200,7 @@ impl Frame {
             // for request frames it's always None
             tracing_id: None,
             warnings: vec![],
+            custom_payload: None,
         }
     }
 }
@@ -0,0 +1,184 @@
+//! The module contains a builder of request frames and convenience constructors
+//! of the most common requests built on top of it.
+use frame::*;
+use frame::frame_query::{BodyReqQuery, ParamsReqQuery};
+use IntoBytes;
+use query::Query;
+use types::CBytesShort;
+
+/// Builder of request frames, e.g.
+///
+/// ```ignore
+/// let frame = FrameBuilder::new(Opcode::Query)
+///     .stream_id(1)
+///     .flags(vec![Flag::Tracing])
+///     .body(body)
+///     .build();
+/// ```
+///
+/// A frame is built with stream id 0 and without flags unless other ones are set.
+#[derive(Debug)]
+pub struct FrameBuilder {
+    opcode: Opcode,
+    stream: u64,
+    flags: Vec<Flag>,
+    body: Vec<u8>,
+}
+
+impl FrameBuilder {
+    /// Creates new builder of a request frame of provided type with empty body.
+    pub fn new(opcode: Opcode) -> FrameBuilder {
+        FrameBuilder {
+            opcode: opcode,
+            stream: 0,
+            flags: vec![],
+            body: vec![],
+        }
+    }
+
+    /// Sets type of the frame.
+    pub fn opcode(mut self, opcode: Opcode) -> Self {
+        self.opcode = opcode;
+        self
+    }
+
+    /// Sets stream id of the frame.
+    pub fn stream_id(mut self, stream: u64) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Sets flags of the frame.
+    pub fn flags(mut self, flags: Vec<Flag>) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets encoded body of the frame.
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn build(self) -> Frame {
+        Frame {
+            version: Version::Request,
+            flags: self.flags,
+            opcode: self.opcode,
+            stream: self.stream,
+            body: self.body,
+            // for request frames it's always None
+            tracing_id: None,
+            warnings: vec![],
+            custom_payload: None,
+        }
+    }
+}
+
+impl Frame {
+    /// Creates new frame of type `options`.
+    pub fn options() -> Frame {
+        Frame::new_req_options()
+    }
+
+    /// Creates new frame of type `startup` which enables provided compression
+    /// (e.g. `Some("lz4")`).
+    pub fn startup(compression: Option<&str>) -> Frame {
+        Frame::new_req_startup(compression)
+    }
+
+    /// Creates new frame of type `prepare`.
+    pub fn prepare<S: Into<String>>(query: S) -> Frame {
+        Frame::new_req_prepare(query.into(), vec![])
+    }
+
+    /// Creates new frame of type `query`. `Tracing` flag is set if the query
+    /// has tracing enabled, custom payload of the query is attached to the frame.
+    pub fn query(mut query: Query) -> Frame {
+        let flags = if query.with_tracing.unwrap_or(false) {
+            vec![Flag::Tracing]
+        } else {
+            vec![]
+        };
+
+        let custom_payload = query.custom_payload.take();
+        let frame = FrameBuilder::new(Opcode::Query)
+            .flags(flags)
+            .body(BodyReqQuery::from_query(query).into_cbytes())
+            .build();
+        match custom_payload {
+            Some(custom_payload) => frame.with_custom_payload(custom_payload),
+            None => frame,
+        }
+    }
+
+    /// Creates new frame of type `execute` of a prepared statement with provided id.
+    pub fn execute(id: &CBytesShort, query_parameters: ParamsReqQuery) -> Frame {
+        Frame::new_req_execute(id, query_parameters, vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use consistency::Consistency;
+    use query::{QueryBuilder, QueryParamsBuilder};
+    use IntoBytes;
+    use super::*;
+
+    // Asserts the 9-byte header and returns the body of the encoded frame.
+    fn assert_header(frame: Frame, flags: u8, stream: u16, opcode: u8) -> Vec<u8> {
+        let bytes = frame.into_cbytes();
+        let body_len = bytes.len() - HEADER_LEN;
+        assert_eq!(bytes[..HEADER_LEN].to_vec(),
+                   vec![0x04,
+                        flags,
+                        (stream >> 8) as u8,
+                        stream as u8,
+                        opcode,
+                        (body_len >> 24) as u8,
+                        (body_len >> 16) as u8,
+                        (body_len >> 8) as u8,
+                        body_len as u8]);
+        bytes[HEADER_LEN..].to_vec()
+    }
+
+    #[test]
+    fn frame_builder() {
+        let frame = FrameBuilder::new(Opcode::Options)
+            .opcode(Opcode::Query)
+            .stream_id(0x0102)
+            .flags(vec![Flag::Tracing, Flag::Warning])
+            .body(vec![1, 2, 3])
+            .build();
+        assert_eq!(assert_header(frame, 0x0A, 0x0102, 0x07), vec![1, 2, 3]);
+
+        let frame = FrameBuilder::new(Opcode::Options).build();
+        assert_eq!(frame.version, Version::Request);
+        assert_eq!(assert_header(frame, 0x00, 0, 0x05), vec![]);
+    }
+
+    #[test]
+    fn convenience_constructors() {
+        assert!(assert_header(Frame::options(), 0x00, 0, 0x05).is_empty());
+
+        // {"CQL_VERSION": "3.0.0"} [string map]
+        let startup = assert_header(Frame::startup(None), 0x00, 0, 0x01);
+        assert_eq!(&startup[..4], &[0, 1, 0, 11]);
+
+        let prepare = assert_header(Frame::prepare("SELECT 1"), 0x00, 0, 0x09);
+        assert_eq!(prepare, b"\x00\x00\x00\x08SELECT 1".to_vec());
+
+        let query = QueryBuilder::new("SELECT 1").with_tracing(true).finalize();
+        let query = assert_header(Frame::query(query), 0x02, 0, 0x07);
+        assert_eq!(&query[..12], b"\x00\x00\x00\x08SELECT 1");
+        // consistency ONE and no flags
+        assert_eq!(&query[12..], &[0, 1, 0]);
+
+        let params = QueryParamsBuilder::new(Consistency::Quorum).finalize();
+        let execute = assert_header(Frame::execute(&CBytesShort::new(vec![7]), params),
+                                    0x00,
+                                    0,
+                                    0x0A);
+        assert_eq!(execute, vec![0, 1, 7, 0, 4, 0]);
+    }
+}
@@ -3,11 +3,14 @@
 //! which server could respond to client.
 
 use std::io;
+use std::net;
 use std::result;
 use consistency::Consistency;
 use types::*;
-use FromCursor;
-use frame::Frame;
+use types::data_serialization_types::decode_inet;
+use {FromCursor, TryFromCursor};
+use frame::{Frame, ProtocolVersion};
+use error;
 
 /// CDRS specific `Result` which contains a [`Frame`] in case of `Ok` and `CDRSError` if `Err`.
 ///
@@ -28,15 +31,103 @@ pub struct CDRSError {
     pub additional_info: AdditionalErrorInfo,
 }
 
+impl CDRSError {
+    /// Returns a typed kind of the error basing on its raw `error_code`.
+    pub fn kind(&self) -> ErrorCode {
+        ErrorCode::from(self.error_code)
+    }
+
+    /// Returns `UnpreparedError` if a server could not find a prepared statement
+    /// with provided id. It can be used to re-prepare a query and retry an execution.
+    pub fn unprepared(&self) -> Option<&UnpreparedError> {
+        match self.additional_info {
+            AdditionalErrorInfo::Unprepared(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl FromCursor for CDRSError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> CDRSError {
-        let error_code = CInt::from_cursor(&mut cursor);
-        let message = CString::from_cursor(&mut cursor);
-        let additional_info = AdditionalErrorInfo::from_cursor_with_code(&mut cursor, error_code);
-        CDRSError {
+        CDRSError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for CDRSError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<CDRSError> {
+        CDRSError::try_from_cursor_with_protocol(&mut cursor, ProtocolVersion::default())
+    }
+}
+
+impl CDRSError {
+    /// Decodes an error the same way `try_from_cursor` does but in accordance
+    /// with provided version of native protocol.
+    pub fn try_from_cursor_with_protocol(mut cursor: &mut io::Cursor<&[u8]>,
+                                         protocol: ProtocolVersion)
+                                         -> error::Result<CDRSError> {
+        let error_code = try!(CInt::try_from_cursor(&mut cursor));
+        let message = try!(CString::try_from_cursor(&mut cursor));
+        let additional_info =
+            try!(AdditionalErrorInfo::try_from_cursor_with_protocol(&mut cursor,
+                                                                    error_code,
+                                                                    protocol));
+        Ok(CDRSError {
             error_code: error_code,
             message: message,
             additional_info: additional_info,
+        })
+    }
+}
+
+/// Typed representation of error codes defined by
+/// [Cassandra protocol v4]
+/// (https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L1011).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorCode {
+    Server,
+    Protocol,
+    Authentication,
+    Unavailable,
+    Overloaded,
+    IsBootstrapping,
+    Truncate,
+    WriteTimeout,
+    ReadTimeout,
+    ReadFailure,
+    FunctionFailure,
+    WriteFailure,
+    Syntax,
+    Unauthorized,
+    Invalid,
+    Config,
+    AlreadyExists,
+    Unprepared,
+    /// Error code which is not known by the driver. Contains the raw error code.
+    Unknown(CInt),
+}
+
+impl From<CInt> for ErrorCode {
+    fn from(code: CInt) -> ErrorCode {
+        match code {
+            0x0000 => ErrorCode::Server,
+            0x000A => ErrorCode::Protocol,
+            0x0100 => ErrorCode::Authentication,
+            0x1000 => ErrorCode::Unavailable,
+            0x1001 => ErrorCode::Overloaded,
+            0x1002 => ErrorCode::IsBootstrapping,
+            0x1003 => ErrorCode::Truncate,
+            0x1100 => ErrorCode::WriteTimeout,
+            0x1200 => ErrorCode::ReadTimeout,
+            0x1300 => ErrorCode::ReadFailure,
+            0x1400 => ErrorCode::FunctionFailure,
+            0x1500 => ErrorCode::WriteFailure,
+            0x2000 => ErrorCode::Syntax,
+            0x2100 => ErrorCode::Unauthorized,
+            0x2200 => ErrorCode::Invalid,
+            0x2300 => ErrorCode::Config,
+            0x2400 => ErrorCode::AlreadyExists,
+            0x2500 => ErrorCode::Unprepared,
+            _ => ErrorCode::Unknown(code),
         }
     }
 }
@@ -64,41 +155,78 @@ pub enum AdditionalErrorInfo {
     Config(SimpleError),
     AlreadyExists(AlreadyExistsError),
     Unprepared(UnpreparedError),
+    /// Error code which is not known by the driver (e.g. introduced by a newer
+    /// protocol version or a vendor-specific one). Contains the raw error code.
+    Unknown(CInt),
 }
 
 impl AdditionalErrorInfo {
     pub fn from_cursor_with_code(mut cursor: &mut io::Cursor<&[u8]>,
                                  error_code: CInt)
                                  -> AdditionalErrorInfo {
-        match error_code {
-            0x0000 => AdditionalErrorInfo::Server(SimpleError::from_cursor(&mut cursor)),
-            0x000A => AdditionalErrorInfo::Protocol(SimpleError::from_cursor(&mut cursor)),
-            0x0100 => AdditionalErrorInfo::Authentication(SimpleError::from_cursor(&mut cursor)),
-            0x1000 => AdditionalErrorInfo::Unavailable(UnavailableError::from_cursor(&mut cursor)),
-            0x1001 => AdditionalErrorInfo::Overloaded(SimpleError::from_cursor(&mut cursor)),
-            0x1002 => AdditionalErrorInfo::IsBootstrapping(SimpleError::from_cursor(&mut cursor)),
-            0x1003 => AdditionalErrorInfo::Truncate(SimpleError::from_cursor(&mut cursor)),
+        AdditionalErrorInfo::try_from_cursor_with_code(&mut cursor, error_code).unwrap()
+    }
+
+    /// Decodes additional info of an error with provided code. It returns an error
+    /// if the cursor does not contain enough bytes.
+    pub fn try_from_cursor_with_code(cursor: &mut io::Cursor<&[u8]>,
+                                     error_code: CInt)
+                                     -> error::Result<AdditionalErrorInfo> {
+        AdditionalErrorInfo::try_from_cursor_with_protocol(cursor,
+                                                           error_code,
+                                                           ProtocolVersion::default())
+    }
+
+    /// Decodes additional info of an error with provided code in accordance with
+    /// provided version of native protocol, e.g. failures of 5-th version contain
+    /// a reason map instead of a number of failures.
+    pub fn try_from_cursor_with_protocol(cursor: &mut io::Cursor<&[u8]>,
+                                         error_code: CInt,
+                                         protocol: ProtocolVersion)
+                                         -> error::Result<AdditionalErrorInfo> {
+        let info = match error_code {
+            0x0000 => AdditionalErrorInfo::Server(SimpleError {}),
+            0x000A => AdditionalErrorInfo::Protocol(SimpleError {}),
+            0x0100 => AdditionalErrorInfo::Authentication(SimpleError {}),
+            0x1000 => {
+                AdditionalErrorInfo::Unavailable(try!(UnavailableError::try_from_cursor(cursor)))
+            }
+            0x1001 => AdditionalErrorInfo::Overloaded(SimpleError {}),
+            0x1002 => AdditionalErrorInfo::IsBootstrapping(SimpleError {}),
+            0x1003 => AdditionalErrorInfo::Truncate(SimpleError {}),
             0x1100 => {
-                AdditionalErrorInfo::WriteTimeout(WriteTimeoutError::from_cursor(&mut cursor))
+                AdditionalErrorInfo::WriteTimeout(try!(WriteTimeoutError::try_from_cursor(cursor)))
+            }
+            0x1200 => {
+                AdditionalErrorInfo::ReadTimeout(try!(ReadTimeoutError::try_from_cursor(cursor)))
+            }
+            0x1300 => {
+                let err = try!(ReadFailureError::try_from_cursor_with_protocol(cursor, protocol));
+                AdditionalErrorInfo::ReadFailure(err)
             }
-            0x1200 => AdditionalErrorInfo::ReadTimeout(ReadTimeoutError::from_cursor(&mut cursor)),
-            0x1300 => AdditionalErrorInfo::ReadFailure(ReadFailureError::from_cursor(&mut cursor)),
             0x1400 => {
-                AdditionalErrorInfo::FunctionFailure(FunctionFailureError::from_cursor(&mut cursor))
+                let err = try!(FunctionFailureError::try_from_cursor(cursor));
+                AdditionalErrorInfo::FunctionFailure(err)
             }
             0x1500 => {
-                AdditionalErrorInfo::WriteFailure(WriteFailureError::from_cursor(&mut cursor))
+                let err = try!(WriteFailureError::try_from_cursor_with_protocol(cursor, protocol));
+                AdditionalErrorInfo::WriteFailure(err)
             }
-            0x2000 => AdditionalErrorInfo::Syntax(SimpleError::from_cursor(&mut cursor)),
-            0x2100 => AdditionalErrorInfo::Unauthorized(SimpleError::from_cursor(&mut cursor)),
-            0x2200 => AdditionalErrorInfo::Invalid(SimpleError::from_cursor(&mut cursor)),
-            0x2300 => AdditionalErrorInfo::Config(SimpleError::from_cursor(&mut cursor)),
+            0x2000 => AdditionalErrorInfo::Syntax(SimpleError {}),
+            0x2100 => AdditionalErrorInfo::Unauthorized(SimpleError {}),
+            0x2200 => AdditionalErrorInfo::Invalid(SimpleError {}),
+            0x2300 => AdditionalErrorInfo::Config(SimpleError {}),
             0x2400 => {
-                AdditionalErrorInfo::AlreadyExists(AlreadyExistsError::from_cursor(&mut cursor))
+                let err = try!(AlreadyExistsError::try_from_cursor(cursor));
+                AdditionalErrorInfo::AlreadyExists(err)
             }
-            0x2500 => AdditionalErrorInfo::Unprepared(UnpreparedError::from_cursor(&mut cursor)),
-            _ => unreachable!(),
-        }
+            0x2500 => {
+                AdditionalErrorInfo::Unprepared(try!(UnpreparedError::try_from_cursor(cursor)))
+            }
+            _ => AdditionalErrorInfo::Unknown(error_code),
+        };
+
+        Ok(info)
     }
 }
 
@@ -112,6 +240,12 @@ impl FromCursor for SimpleError {
     }
 }
 
+impl TryFromCursor for SimpleError {
+    fn try_from_cursor(mut _cursor: &mut io::Cursor<&[u8]>) -> error::Result<SimpleError> {
+        Ok(SimpleError {})
+    }
+}
+
 /// Additional info about
 /// [unavailable exception]
 /// (https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L1025)
@@ -127,15 +261,21 @@ pub struct UnavailableError {
 
 impl FromCursor for UnavailableError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> UnavailableError {
-        let cl = Consistency::from_cursor(&mut cursor);
-        let required = CInt::from_cursor(&mut cursor);
-        let alive = CInt::from_cursor(&mut cursor);
+        UnavailableError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for UnavailableError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<UnavailableError> {
+        let cl = try!(Consistency::try_from_cursor(&mut cursor));
+        let required = try!(CInt::try_from_cursor(&mut cursor));
+        let alive = try!(CInt::try_from_cursor(&mut cursor));
 
-        UnavailableError {
+        Ok(UnavailableError {
             cl: cl,
             required: required,
             alive: alive,
-        }
+        })
     }
 }
 
@@ -154,17 +294,23 @@ pub struct WriteTimeoutError {
 
 impl FromCursor for WriteTimeoutError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> WriteTimeoutError {
-        let cl = Consistency::from_cursor(&mut cursor);
-        let received = CInt::from_cursor(&mut cursor);
-        let blockfor = CInt::from_cursor(&mut cursor);
-        let write_type = WriteType::from_cursor(&mut cursor);
+        WriteTimeoutError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for WriteTimeoutError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<WriteTimeoutError> {
+        let cl = try!(Consistency::try_from_cursor(&mut cursor));
+        let received = try!(CInt::try_from_cursor(&mut cursor));
+        let blockfor = try!(CInt::try_from_cursor(&mut cursor));
+        let write_type = try!(WriteType::try_from_cursor(&mut cursor));
 
-        return WriteTimeoutError {
+        Ok(WriteTimeoutError {
             cl: cl,
             received: received,
             blockfor: blockfor,
             write_type: write_type,
-        };
+        })
     }
 }
 
@@ -189,16 +335,22 @@ impl ReadTimeoutError {
 
 impl FromCursor for ReadTimeoutError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> ReadTimeoutError {
-        let cl = Consistency::from_cursor(&mut cursor);
-        let received = CInt::from_cursor(&mut cursor);
-        let blockfor = CInt::from_cursor(&mut cursor);
-        let data_present = from_bytes(cursor_next_value(&mut cursor, 1).as_slice()) as u8;
-        ReadTimeoutError {
+        ReadTimeoutError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for ReadTimeoutError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<ReadTimeoutError> {
+        let cl = try!(Consistency::try_from_cursor(&mut cursor));
+        let received = try!(CInt::try_from_cursor(&mut cursor));
+        let blockfor = try!(CInt::try_from_cursor(&mut cursor));
+        let data_present = try!(try_cursor_next_value(&mut cursor, 1))[0];
+        Ok(ReadTimeoutError {
             cl: cl,
             received: received,
             blockfor: blockfor,
             data_present: data_present,
-        }
+        })
     }
 }
 
@@ -213,6 +365,9 @@ pub struct ReadFailureError {
     pub blockfor: CInt,
     /// Represents the number of nodes that experience a failure while executing the request.
     pub num_failures: CInt,
+    /// Addresses of nodes that experience a failure along with failure codes. It's sent
+    /// only by 5-th version of the protocol, so it's empty for earlier versions.
+    pub reason_map: Vec<(net::IpAddr, u16)>,
     data_present: u8,
 }
 
@@ -221,25 +376,62 @@ impl ReadFailureError {
     pub fn replica_has_responded(&self) -> bool {
         self.data_present != 0
     }
-}
 
-impl FromCursor for ReadFailureError {
-    fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> ReadFailureError {
-        let cl = Consistency::from_cursor(&mut cursor);
-        let received = CInt::from_cursor(&mut cursor);
-        let blockfor = CInt::from_cursor(&mut cursor);
-        let num_failures = CInt::from_cursor(&mut cursor);
-        let data_present = from_bytes(cursor_next_value(&mut cursor, 1).as_slice()) as u8;
-        ReadFailureError {
+    /// Decodes the error in accordance with provided version of native protocol.
+    pub fn try_from_cursor_with_protocol(mut cursor: &mut io::Cursor<&[u8]>,
+                                         protocol: ProtocolVersion)
+                                         -> error::Result<ReadFailureError> {
+        let cl = try!(Consistency::try_from_cursor(&mut cursor));
+        let received = try!(CInt::try_from_cursor(&mut cursor));
+        let blockfor = try!(CInt::try_from_cursor(&mut cursor));
+        let (num_failures, reason_map) = try!(try_failures_from_cursor(&mut cursor, protocol));
+        let data_present = try!(try_cursor_next_value(&mut cursor, 1))[0];
+        Ok(ReadFailureError {
             cl: cl,
             received: received,
             blockfor: blockfor,
             num_failures: num_failures,
+            reason_map: reason_map,
             data_present: data_present,
-        }
+        })
     }
 }
 
+impl FromCursor for ReadFailureError {
+    fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> ReadFailureError {
+        ReadFailureError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for ReadFailureError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<ReadFailureError> {
+        ReadFailureError::try_from_cursor_with_protocol(&mut cursor, ProtocolVersion::default())
+    }
+}
+
+// Reads a number of failures of protocol versions prior to 5-th or a reason map
+// of 5-th one. The number of failures is a size of the reason map in the latter case.
+fn try_failures_from_cursor(mut cursor: &mut io::Cursor<&[u8]>,
+                            protocol: ProtocolVersion)
+                            -> error::Result<(CInt, Vec<(net::IpAddr, u16)>)> {
+    let num_failures = try!(CInt::try_from_cursor(&mut cursor));
+    if protocol < ProtocolVersion::V5 {
+        return Ok((num_failures, vec![]));
+    }
+
+    let mut reason_map = vec![];
+    for _ in 0..num_failures {
+        // [inetaddr] starts with a single byte which is a length of an address
+        let n = try!(try_cursor_next_value(&mut cursor, 1))[0];
+        let address = try!(decode_inet(try!(try_cursor_next_value(&mut cursor, n as u64))
+            .as_slice()));
+        let code = try!(CIntShort::try_from_cursor(&mut cursor));
+        reason_map.push((address, code as u16));
+    }
+
+    Ok((num_failures, reason_map))
+}
+
 /// A (user defined) function failed during execution.
 #[derive(Debug)]
 pub struct FunctionFailureError {
@@ -253,14 +445,20 @@ pub struct FunctionFailureError {
 
 impl FromCursor for FunctionFailureError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> FunctionFailureError {
-        let keyspace = CString::from_cursor(&mut cursor);
-        let function = CString::from_cursor(&mut cursor);
-        let arg_types = CStringList::from_cursor(&mut cursor);
-        FunctionFailureError {
+        FunctionFailureError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for FunctionFailureError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<FunctionFailureError> {
+        let keyspace = try!(CString::try_from_cursor(&mut cursor));
+        let function = try!(CString::try_from_cursor(&mut cursor));
+        let arg_types = try!(CStringList::try_from_cursor(&mut cursor));
+        Ok(FunctionFailureError {
             keyspace: keyspace,
             function: function,
             arg_types: arg_types,
-        }
+        })
     }
 }
 
@@ -276,30 +474,49 @@ pub struct WriteFailureError {
     pub blockfor: CInt,
     /// Represents the number of nodes that experience a failure while executing the request.
     pub num_failures: CInt,
+    /// Addresses of nodes that experience a failure along with failure codes. It's sent
+    /// only by 5-th version of the protocol, so it's empty for earlier versions.
+    pub reason_map: Vec<(net::IpAddr, u16)>,
     /// describes the type of the write that failed.
     pub write_type: WriteType,
 }
 
-impl FromCursor for WriteFailureError {
-    fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> WriteFailureError {
-        let cl = Consistency::from_cursor(&mut cursor);
-        let received = CInt::from_cursor(&mut cursor);
-        let blockfor = CInt::from_cursor(&mut cursor);
-        let num_failures = CInt::from_cursor(&mut cursor);
-        let write_type = WriteType::from_cursor(&mut cursor);
-        WriteFailureError {
+impl WriteFailureError {
+    /// Decodes the error in accordance with provided version of native protocol.
+    pub fn try_from_cursor_with_protocol(mut cursor: &mut io::Cursor<&[u8]>,
+                                         protocol: ProtocolVersion)
+                                         -> error::Result<WriteFailureError> {
+        let cl = try!(Consistency::try_from_cursor(&mut cursor));
+        let received = try!(CInt::try_from_cursor(&mut cursor));
+        let blockfor = try!(CInt::try_from_cursor(&mut cursor));
+        let (num_failures, reason_map) = try!(try_failures_from_cursor(&mut cursor, protocol));
+        let write_type = try!(WriteType::try_from_cursor(&mut cursor));
+        Ok(WriteFailureError {
             cl: cl,
             received: received,
             blockfor: blockfor,
             num_failures: num_failures,
+            reason_map: reason_map,
             write_type: write_type,
-        }
+        })
+    }
+}
+
+impl FromCursor for WriteFailureError {
+    fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> WriteFailureError {
+        WriteFailureError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for WriteFailureError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<WriteFailureError> {
+        WriteFailureError::try_from_cursor_with_protocol(&mut cursor, ProtocolVersion::default())
     }
 }
 
 /// Describes the type of the write that failed.
 /// [Read more...](https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L1118)
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum WriteType {
     /// The write was a non-batched non-counter write
     Simple,
@@ -314,18 +531,37 @@ pub enum WriteType {
     /// The failure occured during the write to the batch log when a (logged) batch
     /// write was requested.
     BatchLog,
+    /// The write was a compare-and-set (lightweight transaction) write.
+    Cas,
+    /// The failure occured during the write to a materialized view.
+    View,
+    /// The failure occured during the write to the CDC commit log.
+    Cdc,
+    /// The write type is not known by the driver. Contains the raw value sent by a server.
+    Unknown(String),
 }
 
 impl FromCursor for WriteType {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> WriteType {
-        match CString::from_cursor(&mut cursor).as_str() {
+        WriteType::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for WriteType {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<WriteType> {
+        let write_type = match try!(CString::try_from_cursor(&mut cursor)).as_str() {
             "SIMPLE" => WriteType::Simple,
             "BATCH" => WriteType::Batch,
             "UNLOGGED_BATCH" => WriteType::UnloggedBatch,
             "COUNTER" => WriteType::Counter,
             "BATCH_LOG" => WriteType::BatchLog,
-            _ => unreachable!(),
-        }
+            "CAS" => WriteType::Cas,
+            "VIEW" => WriteType::View,
+            "CDC" => WriteType::Cdc,
+            unknown => WriteType::Unknown(unknown.to_string()),
+        };
+
+        Ok(write_type)
     }
 }
 
@@ -342,13 +578,19 @@ pub struct AlreadyExistsError {
 
 impl FromCursor for AlreadyExistsError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> AlreadyExistsError {
-        let ks = CString::from_cursor(&mut cursor);
-        let table = CString::from_cursor(&mut cursor);
+        AlreadyExistsError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
 
-        AlreadyExistsError {
+impl TryFromCursor for AlreadyExistsError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<AlreadyExistsError> {
+        let ks = try!(CString::try_from_cursor(&mut cursor));
+        let table = try!(CString::try_from_cursor(&mut cursor));
+
+        Ok(AlreadyExistsError {
             ks: ks,
             table: table,
-        }
+        })
     }
 }
 
@@ -362,10 +604,269 @@ pub struct UnpreparedError {
     pub id: CBytes,
 }
 
+impl UnpreparedError {
+    /// Returns the unknown prepared statement id as a slice of bytes.
+    pub fn id_as_slice(&self) -> &[u8] {
+        self.id.as_slice()
+    }
+}
+
 impl FromCursor for UnpreparedError {
     fn from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> UnpreparedError {
-        let id = CBytes::from_cursor(&mut cursor);
+        UnpreparedError::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for UnpreparedError {
+    fn try_from_cursor(mut cursor: &mut io::Cursor<&[u8]>) -> error::Result<UnpreparedError> {
+        // the id is [short bytes]
+        let id = try!(CBytesShort::try_from_cursor(&mut cursor));
+        let id = CBytes::new(id.into_plain());
 
-        UnpreparedError { id: id }
+        Ok(UnpreparedError { id: id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use {FromCursor, IntoBytes, TryFromCursor};
+    use types::CString;
+    use super::*;
+
+    fn write_type_from_str(write_type: &str) -> WriteType {
+        let bytes = CString::new(write_type.to_string()).into_cbytes();
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        WriteType::from_cursor(&mut cursor)
+    }
+
+    #[test]
+    fn write_type_from_cursor() {
+        assert_eq!(write_type_from_str("SIMPLE"), WriteType::Simple);
+        assert_eq!(write_type_from_str("BATCH"), WriteType::Batch);
+        assert_eq!(write_type_from_str("UNLOGGED_BATCH"), WriteType::UnloggedBatch);
+        assert_eq!(write_type_from_str("COUNTER"), WriteType::Counter);
+        assert_eq!(write_type_from_str("BATCH_LOG"), WriteType::BatchLog);
+        assert_eq!(write_type_from_str("CAS"), WriteType::Cas);
+        assert_eq!(write_type_from_str("VIEW"), WriteType::View);
+        assert_eq!(write_type_from_str("CDC"), WriteType::Cdc);
+    }
+
+    #[test]
+    fn error_code_from() {
+        assert_eq!(ErrorCode::from(0x0000), ErrorCode::Server);
+        assert_eq!(ErrorCode::from(0x000A), ErrorCode::Protocol);
+        assert_eq!(ErrorCode::from(0x0100), ErrorCode::Authentication);
+        assert_eq!(ErrorCode::from(0x1000), ErrorCode::Unavailable);
+        assert_eq!(ErrorCode::from(0x1001), ErrorCode::Overloaded);
+        assert_eq!(ErrorCode::from(0x1002), ErrorCode::IsBootstrapping);
+        assert_eq!(ErrorCode::from(0x1003), ErrorCode::Truncate);
+        assert_eq!(ErrorCode::from(0x1100), ErrorCode::WriteTimeout);
+        assert_eq!(ErrorCode::from(0x1200), ErrorCode::ReadTimeout);
+        assert_eq!(ErrorCode::from(0x1300), ErrorCode::ReadFailure);
+        assert_eq!(ErrorCode::from(0x1400), ErrorCode::FunctionFailure);
+        assert_eq!(ErrorCode::from(0x1500), ErrorCode::WriteFailure);
+        assert_eq!(ErrorCode::from(0x2000), ErrorCode::Syntax);
+        assert_eq!(ErrorCode::from(0x2100), ErrorCode::Unauthorized);
+        assert_eq!(ErrorCode::from(0x2200), ErrorCode::Invalid);
+        assert_eq!(ErrorCode::from(0x2300), ErrorCode::Config);
+        assert_eq!(ErrorCode::from(0x2400), ErrorCode::AlreadyExists);
+        assert_eq!(ErrorCode::from(0x2500), ErrorCode::Unprepared);
+        assert_eq!(ErrorCode::from(0x9999), ErrorCode::Unknown(0x9999));
+    }
+
+    #[test]
+    fn cdrs_error_unknown_code() {
+        let mut bytes = vec![0, 0, 0x99, 0x99];
+        bytes.extend_from_slice(CString::new("oops".to_string()).into_cbytes().as_slice());
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        let err = CDRSError::from_cursor(&mut cursor);
+
+        assert_eq!(err.error_code, 0x9999);
+        assert_eq!(err.message.as_str(), "oops");
+        assert_eq!(err.kind(), ErrorCode::Unknown(0x9999));
+        match err.additional_info {
+            AdditionalErrorInfo::Unknown(code) => assert_eq!(code, 0x9999),
+            _ => panic!("should be unknown error"),
+        }
+    }
+
+    #[test]
+    fn cdrs_error_unprepared() {
+        let mut bytes = vec![0, 0, 0x25, 0];
+        bytes.extend_from_slice(CString::new("unprepared".to_string()).into_cbytes().as_slice());
+        bytes.extend_from_slice(&[0, 3, 1, 2, 3]);
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        let err = CDRSError::from_cursor(&mut cursor);
+
+        assert_eq!(err.kind(), ErrorCode::Unprepared);
+        assert_eq!(err.unprepared().map(|e| e.id_as_slice()), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn write_type_from_cursor_unknown() {
+        assert_eq!(write_type_from_str("SOMETHING_NEW"),
+                   WriteType::Unknown("SOMETHING_NEW".to_string()));
+    }
+
+    // Asserts that decoding of `bytes` succeeds and decoding of `bytes` without
+    // the last byte results in an error.
+    fn assert_truncated_err<T: TryFromCursor>(bytes: &[u8]) {
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes);
+        assert!(T::try_from_cursor(&mut cursor).is_ok());
+        assert_eq!(cursor.position() as usize, bytes.len());
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(&bytes[..bytes.len() - 1]);
+        assert!(T::try_from_cursor(&mut cursor).is_err());
+    }
+
+    fn cstring(s: &str) -> Vec<u8> {
+        CString::new(s.to_string()).into_cbytes()
+    }
+
+    #[test]
+    fn truncated_error_infos() {
+        // consistency, required, alive
+        let unavailable = vec![0, 1, 0, 0, 0, 2, 0, 0, 0, 1];
+        assert_truncated_err::<UnavailableError>(unavailable.as_slice());
+
+        // consistency, received, blockfor, write type
+        let mut write_timeout = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        write_timeout.extend_from_slice(cstring("SIMPLE").as_slice());
+        assert_truncated_err::<WriteTimeoutError>(write_timeout.as_slice());
+
+        // consistency, received, blockfor, data present
+        let read_timeout = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+        assert_truncated_err::<ReadTimeoutError>(read_timeout.as_slice());
+
+        // consistency, received, blockfor, num failures, data present
+        let read_failure = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1];
+        assert_truncated_err::<ReadFailureError>(read_failure.as_slice());
+
+        // keyspace, function, arg types
+        let mut function_failure = cstring("ks");
+        function_failure.extend_from_slice(cstring("f").as_slice());
+        function_failure.extend_from_slice(&[0, 1]);
+        function_failure.extend_from_slice(cstring("int").as_slice());
+        assert_truncated_err::<FunctionFailureError>(function_failure.as_slice());
+
+        // consistency, received, blockfor, num failures, write type
+        let mut write_failure = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1];
+        write_failure.extend_from_slice(cstring("BATCH").as_slice());
+        assert_truncated_err::<WriteFailureError>(write_failure.as_slice());
+
+        assert_truncated_err::<WriteType>(cstring("CAS").as_slice());
+
+        let mut already_exists = cstring("ks");
+        already_exists.extend_from_slice(cstring("table").as_slice());
+        assert_truncated_err::<AlreadyExistsError>(already_exists.as_slice());
+
+        assert_truncated_err::<UnpreparedError>(&[0, 2, 1, 2]);
+
+        let mut error = vec![0, 0, 0x10, 0];
+        error.extend_from_slice(cstring("unavailable").as_slice());
+        error.extend_from_slice(unavailable.as_slice());
+        assert_truncated_err::<CDRSError>(error.as_slice());
+    }
+
+    #[test]
+    fn truncated_error_frame() {
+        use frame::{Frame, Opcode, Version};
+        use frame::parser::convert_frame_into_result;
+        use error::Error;
+
+        let mut body = vec![0, 0, 0x11, 0];
+        body.extend_from_slice(cstring("timeout").as_slice());
+        body.extend_from_slice(&[0, 1, 0, 0]);
+        let frame = Frame {
+            version: Version::Response,
+            flags: vec![],
+            opcode: Opcode::Error,
+            stream: 0,
+            body: body,
+            tracing_id: None,
+            warnings: vec![],
+            custom_payload: None,
+        };
+        match convert_frame_into_result(frame) {
+            Err(Error::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    // Reason map of 5-th version of the protocol with an IPv4 and an IPv6 address.
+    fn reason_map() -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0, 2, 4, 10, 0, 0, 1, 0, 0];
+        bytes.push(16);
+        bytes.extend_from_slice(&[0; 15]);
+        bytes.extend_from_slice(&[1, 0, 5]);
+        bytes
+    }
+
+    fn reasons() -> Vec<(net::IpAddr, u16)> {
+        vec![("10.0.0.1".parse().unwrap(), 0), ("::1".parse().unwrap(), 5)]
+    }
+
+    #[test]
+    fn read_failure_reason_map() {
+        // consistency, received, blockfor, num failures, data present
+        let v4 = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 1];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(v4.as_slice());
+        let err = ReadFailureError::try_from_cursor_with_protocol(&mut cursor,
+                                                                  ProtocolVersion::V4)
+            .unwrap();
+        assert_eq!(err.num_failures, 2);
+        assert!(err.reason_map.is_empty());
+        assert!(err.replica_has_responded());
+
+        // consistency, received, blockfor, reason map, data present
+        let mut v5 = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        v5.extend_from_slice(reason_map().as_slice());
+        v5.push(1);
+        let mut cursor: Cursor<&[u8]> = Cursor::new(v5.as_slice());
+        let err = ReadFailureError::try_from_cursor_with_protocol(&mut cursor,
+                                                                  ProtocolVersion::V5)
+            .unwrap();
+        assert_eq!(cursor.position() as usize, v5.len());
+        assert_eq!(err.num_failures, 2);
+        assert_eq!(err.reason_map, reasons());
+        assert!(err.replica_has_responded());
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(&v5[..v5.len() - 4]);
+        assert!(ReadFailureError::try_from_cursor_with_protocol(&mut cursor, ProtocolVersion::V5)
+            .is_err());
+    }
+
+    #[test]
+    fn write_failure_reason_map() {
+        // consistency, received, blockfor, num failures, write type
+        let mut v4 = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2];
+        v4.extend_from_slice(cstring("BATCH").as_slice());
+        let mut cursor: Cursor<&[u8]> = Cursor::new(v4.as_slice());
+        let err = WriteFailureError::try_from_cursor_with_protocol(&mut cursor,
+                                                                   ProtocolVersion::V4)
+            .unwrap();
+        assert_eq!(err.num_failures, 2);
+        assert!(err.reason_map.is_empty());
+        assert_eq!(err.write_type, WriteType::Batch);
+
+        // error code and message precede consistency, received, blockfor, reason map
+        // and write type
+        let mut v5 = vec![0, 0, 0x15, 0];
+        v5.extend_from_slice(cstring("write failure").as_slice());
+        v5.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        v5.extend_from_slice(reason_map().as_slice());
+        v5.extend_from_slice(cstring("BATCH").as_slice());
+        let mut cursor: Cursor<&[u8]> = Cursor::new(v5.as_slice());
+        let err = CDRSError::try_from_cursor_with_protocol(&mut cursor, ProtocolVersion::V5)
+            .unwrap();
+        match err.additional_info {
+            AdditionalErrorInfo::WriteFailure(ref failure) => {
+                assert_eq!(failure.num_failures, 2);
+                assert_eq!(failure.reason_map, reasons());
+                assert_eq!(failure.write_type, WriteType::Batch);
+            }
+            ref other => panic!("unexpected additional info {:?}", other),
+        }
     }
 }
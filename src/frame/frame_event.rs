@@ -1,5 +1,6 @@
-use FromCursor;
+use {FromCursor, TryFromCursor};
 use std::io::Cursor;
+use error;
 use frame::events::ServerEvent;
 
 #[derive(Debug)]
@@ -9,8 +10,14 @@ pub struct BodyResEvent {
 
 impl FromCursor for BodyResEvent {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> BodyResEvent {
-        let event = ServerEvent::from_cursor(&mut cursor);
-        BodyResEvent { event: event }
+        BodyResEvent::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for BodyResEvent {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResEvent> {
+        let event = try!(ServerEvent::try_from_cursor(&mut cursor));
+        Ok(BodyResEvent { event: event })
     }
 }
 
@@ -53,7 +60,6 @@ mod tests {
                      68,
                      69,
                      // inet - 127.0.0.1:1
-                     0,
                      4,
                      127,
                      0,
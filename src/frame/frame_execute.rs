@@ -34,7 +34,7 @@ impl<'a> IntoBytes for BodyReqExecute<'a> {
 impl Frame {
     /// **Note:** This function should be used internally for building query request frames.
     pub fn new_req_execute(id: &CBytesShort,
-                           query_parameters: ParamsReqQuery,
+                           mut query_parameters: ParamsReqQuery,
                            flags: Vec<Flag>)
                            -> Frame {
         let version = Version::Request;
@@ -44,9 +44,10 @@ impl Frame {
         debug!("prepared statement id{:?} getting executed  with parameters  {:?}",
                id,
                query_parameters);
+        let custom_payload = query_parameters.custom_payload.take();
         let body = BodyReqExecute::new(id, query_parameters);
 
-        Frame {
+        let frame = Frame {
             version: version,
             flags: flags,
             stream: stream,
@@ -55,6 +56,11 @@ impl Frame {
             // for request frames it's always None
             tracing_id: None,
             warnings: vec![],
+            custom_payload: None,
+        };
+        match custom_payload {
+            Some(custom_payload) => frame.with_custom_payload(custom_payload),
+            None => frame,
         }
     }
 }
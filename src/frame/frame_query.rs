@@ -1,11 +1,13 @@
 #![warn(missing_docs)]
 //! Contains Query Frame related functionality.
 //! use self::frame::*;
+use std::collections::HashMap;
 use super::*;
 use consistency::Consistency;
 use {AsByte, IntoBytes};
 use types::*;
 use types::value::*;
+use query::{Query, QueryParamsBuilder};
 
 /// Structure which represents body of Query request
 #[derive(Debug)]
@@ -20,7 +22,7 @@ impl BodyReqQuery {
     // Fabric function that produces Query request body.
     fn new(query: String,
            consistency: Consistency,
-           values: Option<Vec<Value>>,
+           values: Option<QueryValues>,
            with_names: Option<bool>,
            page_size: Option<i32>,
            paging_state: Option<CBytes>,
@@ -28,57 +30,75 @@ impl BodyReqQuery {
            timestamp: Option<i64>)
            -> BodyReqQuery {
 
-        // query flags
-        let mut flags: Vec<QueryFlags> = vec![];
-        if values.is_some() {
-            flags.push(QueryFlags::Value);
+        let mut builder = QueryParamsBuilder::new(consistency)
+            .with_names(with_names.unwrap_or(false));
+        if let Some(values) = values {
+            builder = builder.values(values);
         }
-        if with_names.unwrap_or(false) {
-            flags.push(QueryFlags::WithNamesForValues);
+        if let Some(page_size) = page_size {
+            builder = builder.page_size(page_size);
         }
-        if page_size.is_some() {
-            flags.push(QueryFlags::PageSize);
+        if let Some(paging_state) = paging_state {
+            builder = builder.paging_state(paging_state);
         }
-        if serial_consistency.is_some() {
-            flags.push(QueryFlags::WithSerialConsistency);
+        if let Some(serial_consistency) = serial_consistency {
+            builder = builder.serial_consistency(serial_consistency);
         }
-        if timestamp.is_some() {
-            flags.push(QueryFlags::WithDefaultTimestamp);
+        if let Some(timestamp) = timestamp {
+            builder = builder.timestamp(timestamp);
         }
 
         BodyReqQuery {
             query: CStringLong::new(query),
-            query_params: ParamsReqQuery {
-                consistency: consistency,
-                flags: flags,
-                values: values,
-                page_size: page_size,
-                paging_state: paging_state,
-                serial_consistency: serial_consistency,
-                timestamp: timestamp,
-            },
+            query_params: builder.finalize(),
         }
     }
-}
 
-impl IntoBytes for BodyReqQuery {
-    fn into_cbytes(&self) -> Vec<u8> {
+    /// Creates Query request body of provided query. Custom payload of the query is not
+    /// a part of the body, so it's ignored.
+    pub fn from_query(query: Query) -> BodyReqQuery {
+        let mut body = BodyReqQuery::new(query.query,
+                                         query.consistency,
+                                         query.values,
+                                         query.with_names,
+                                         query.page_size,
+                                         query.paging_state,
+                                         query.serial_consistency,
+                                         query.timestamp);
+        if let Some(keyspace) = query.keyspace {
+            body.query_params.set_keyspace(keyspace);
+        }
+        if let Some(now_in_seconds) = query.now_in_seconds {
+            body.query_params.set_now_in_seconds(now_in_seconds);
+        }
+
+        body
+    }
+
+    /// Encodes the body in accordance with provided version of native protocol.
+    pub fn into_cbytes_with_protocol(&self, protocol: ProtocolVersion) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
         v.extend_from_slice(self.query.clone().into_cbytes().as_slice());
-        v.extend_from_slice(self.query_params.into_cbytes().as_slice());
+        v.extend_from_slice(self.query_params.into_cbytes_with_protocol(protocol).as_slice());
         v
     }
 }
 
+impl IntoBytes for BodyReqQuery {
+    fn into_cbytes(&self) -> Vec<u8> {
+        self.into_cbytes_with_protocol(ProtocolVersion::default())
+    }
+}
+
 /// Parameters of Query request.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParamsReqQuery {
     /// Cassandra consistency level.
     pub consistency: Consistency,
     /// Array of query flags.
     pub flags: Vec<QueryFlags>,
-    /// Array of values.
-    pub values: Option<Vec<Value>>,
+    /// Values bound by position or by names.
+    pub values: Option<QueryValues>,
     /// Page size.
     pub page_size: Option<i32>,
     /// Array of bytes which represents paging state.
@@ -87,19 +107,70 @@ pub struct ParamsReqQuery {
     pub serial_consistency: Option<Consistency>,
     /// Timestamp.
     pub timestamp: Option<i64>,
+    /// Keyspace the query is executed in. It's supported since 5-th version of the protocol.
+    pub keyspace: Option<CString>,
+    /// Current time (in seconds) which a server uses for the query. It's supported
+    /// since 5-th version of the protocol.
+    pub now_in_seconds: Option<i32>,
+    /// Shows if the query is idempotent. It's a client-side option which isn't sent
+    /// to a server, only idempotent queries are retried on timeouts.
+    pub idempotent: bool,
+    /// Custom payload of the request. It's not a part of the params but it's sent
+    /// within a frame (see `Frame::with_custom_payload`).
+    pub custom_payload: Option<HashMap<String, Vec<u8>>>,
 }
 
 impl ParamsReqQuery {
     /// Sets values of Query request params.
-    pub fn set_values(&mut self, values: Vec<Value>) {
+    pub fn set_values<V: Into<QueryValues>>(&mut self, values: V) {
+        let values = values.into();
         self.flags.push(QueryFlags::Value);
+        if values.with_names() {
+            self.flags.push(QueryFlags::WithNamesForValues);
+        }
         self.values = Some(values);
     }
 
+    /// Sets default timestamp (in microseconds) of Query request params.
+    pub fn set_timestamp(&mut self, timestamp: i64) {
+        if self.timestamp.is_none() {
+            self.flags.push(QueryFlags::WithDefaultTimestamp);
+        }
+        self.timestamp = Some(timestamp);
+    }
+
+    /// Sets keyspace of Query request params. It's supported since 5-th version
+    /// of the protocol.
+    pub fn set_keyspace<S: Into<String>>(&mut self, keyspace: S) {
+        if self.keyspace.is_none() {
+            self.flags.push(QueryFlags::WithKeyspace);
+        }
+        self.keyspace = Some(CString::new(keyspace.into()));
+    }
+
+    /// Sets current time (in seconds) which a server uses for TTLs and tombstones
+    /// of the query. It's supported since 5-th version of the protocol.
+    pub fn set_now_in_seconds(&mut self, now_in_seconds: i32) {
+        if self.now_in_seconds.is_none() {
+            self.flags.push(QueryFlags::WithNowInSeconds);
+        }
+        self.now_in_seconds = Some(now_in_seconds);
+    }
+
+    /// Returns an error if the params contain options which are not supported
+    /// by provided version of native protocol.
+    pub fn check_protocol(&self, protocol: ProtocolVersion) -> error::Result<()> {
+        check_protocol_options(self.keyspace.as_ref(), self.now_in_seconds, protocol)
+    }
+
     fn flags_as_byte(&self) -> u8 {
         return self.flags.iter().fold(0, |acc, flag| acc | flag.as_byte());
     }
 
+    fn flags_as_int(&self) -> i32 {
+        self.flags.iter().fold(0, |acc, flag| acc | flag.as_int())
+    }
+
     #[allow(dead_code)]
     fn parse_query_flags(byte: u8) -> Vec<QueryFlags> {
         let mut flags: Vec<QueryFlags> = vec![];
@@ -125,24 +196,25 @@ impl ParamsReqQuery {
         if QueryFlags::has_with_names_for_values(byte) {
             flags.push(QueryFlags::WithNamesForValues);
         }
+        if QueryFlags::has_with_keyspace(byte) {
+            flags.push(QueryFlags::WithKeyspace);
+        }
 
         flags
     }
-}
 
-impl IntoBytes for ParamsReqQuery {
-    fn into_cbytes(&self) -> Vec<u8> {
+    /// Encodes the params in accordance with provided version of native protocol.
+    /// Flags are encoded as `[int]` since 5-th version and as a single byte before.
+    pub fn into_cbytes_with_protocol(&self, protocol: ProtocolVersion) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
 
         v.extend_from_slice(self.consistency.into_cbytes().as_slice());
-        v.push(self.flags_as_byte());
+        v.extend_from_slice(query_flags_into_cbytes(self.flags_as_int(), protocol).as_slice());
         if QueryFlags::has_value(self.flags_as_byte()) {
-            // XXX clone
-            let values = self.values.clone().unwrap();
-            v.extend_from_slice(to_short(values.len() as i16).as_slice());
-            for val in values.iter() {
-                v.extend_from_slice(val.into_cbytes().as_slice());
-            }
+            v.extend_from_slice(self.values.as_ref().unwrap().into_cbytes().as_slice());
+        }
+        if QueryFlags::has_page_size(self.flags_as_byte()) {
+            v.extend_from_slice(to_int(self.page_size.unwrap()).as_slice());
         }
         if QueryFlags::has_with_paging_state(self.flags_as_byte()) {
             // XXX clone
@@ -155,11 +227,56 @@ impl IntoBytes for ParamsReqQuery {
         if QueryFlags::has_with_default_timestamp(self.flags_as_byte()) {
             v.extend_from_slice(to_bigint(self.timestamp.unwrap()).as_slice());
         }
+        if QueryFlags::has_with_keyspace(self.flags_as_byte()) {
+            v.extend_from_slice(self.keyspace.as_ref().unwrap().into_cbytes().as_slice());
+        }
+        if QueryFlags::has_with_now_in_seconds(self.flags_as_int()) {
+            v.extend_from_slice(to_int(self.now_in_seconds.unwrap()).as_slice());
+        }
 
         v
     }
 }
 
+impl IntoBytes for ParamsReqQuery {
+    fn into_cbytes(&self) -> Vec<u8> {
+        self.into_cbytes_with_protocol(ProtocolVersion::default())
+    }
+}
+
+/// Encodes query flags in accordance with provided version of native protocol,
+/// i.e. as `[int]` since 5-th version and as a single byte before.
+pub fn query_flags_into_cbytes(flags: i32, protocol: ProtocolVersion) -> Vec<u8> {
+    if protocol >= ProtocolVersion::V5 {
+        to_int(flags)
+    } else {
+        vec![flags as u8]
+    }
+}
+
+/// Returns an error if `keyspace` or `now_in_seconds` is set but provided version
+/// of native protocol does not support them.
+pub fn check_protocol_options(keyspace: Option<&CString>,
+                              now_in_seconds: Option<i32>,
+                              protocol: ProtocolVersion)
+                              -> error::Result<()> {
+    if protocol >= ProtocolVersion::V5 {
+        return Ok(());
+    }
+
+    let option = if keyspace.is_some() {
+        "Keyspace"
+    } else if now_in_seconds.is_some() {
+        "Now in seconds"
+    } else {
+        return Ok(());
+    };
+    let msg = format!("{} of a request is supported since protocol version V5, {:?} is used",
+                      option,
+                      protocol);
+    Err(error::Error::General(msg))
+}
+
 const FLAGS_VALUE: u8 = 0x01;
 const FLAGS_SKIP_METADATA: u8 = 0x02;
 const WITH_PAGE_SIZE: u8 = 0x04;
@@ -167,6 +284,9 @@ const WITH_PAGING_STATE: u8 = 0x08;
 const WITH_SERIAL_CONSISTENCY: u8 = 0x10;
 const WITH_DEFAULT_TIMESTAMP: u8 = 0x20;
 const WITH_NAME_FOR_VALUES: u8 = 0x40;
+const WITH_KEYSPACE: u8 = 0x80;
+// flags of 5-th version of the protocol are `[int]`, so the flag doesn't fit a byte
+const WITH_NOW_IN_SECONDS: i32 = 0x100;
 
 /// Cassandra Query Flags.
 #[derive(Clone, Debug)]
@@ -185,6 +305,10 @@ pub enum QueryFlags {
     WithDefaultTimestamp,
     /// If set indicates that Query Params values are named ones.
     WithNamesForValues,
+    /// If set indicates that Query Params contains keyspace (protocol v5).
+    WithKeyspace,
+    /// If set indicates that Query Params contains current time in seconds (protocol v5).
+    WithNowInSeconds,
 }
 
 impl QueryFlags {
@@ -257,6 +381,34 @@ impl QueryFlags {
     pub fn set_with_names_for_values(byte: u8) -> u8 {
         byte | WITH_NAME_FOR_VALUES
     }
+
+    #[doc(hidden)]
+    pub fn has_with_keyspace(byte: u8) -> bool {
+        (byte & WITH_KEYSPACE) != 0
+    }
+
+    #[doc(hidden)]
+    pub fn set_with_keyspace(byte: u8) -> u8 {
+        byte | WITH_KEYSPACE
+    }
+
+    #[doc(hidden)]
+    pub fn has_with_now_in_seconds(flags: i32) -> bool {
+        (flags & WITH_NOW_IN_SECONDS) != 0
+    }
+
+    #[doc(hidden)]
+    pub fn set_with_now_in_seconds(flags: i32) -> i32 {
+        flags | WITH_NOW_IN_SECONDS
+    }
+
+    /// Returns the flag as a part of `[int]` flags of 5-th version of the protocol.
+    pub fn as_int(&self) -> i32 {
+        match *self {
+            QueryFlags::WithNowInSeconds => WITH_NOW_IN_SECONDS,
+            _ => self.as_byte() as i32,
+        }
+    }
 }
 
 impl AsByte for QueryFlags {
@@ -269,6 +421,9 @@ impl AsByte for QueryFlags {
             QueryFlags::WithSerialConsistency => WITH_SERIAL_CONSISTENCY,
             QueryFlags::WithDefaultTimestamp => WITH_DEFAULT_TIMESTAMP,
             QueryFlags::WithNamesForValues => WITH_NAME_FOR_VALUES,
+            QueryFlags::WithKeyspace => WITH_KEYSPACE,
+            // it's a part of `[int]` flags only, see `as_int`
+            QueryFlags::WithNowInSeconds => 0,
         }
     }
 }
@@ -279,7 +434,7 @@ impl Frame {
     /// **Note:** This function should be used internally for building query request frames.
     pub fn new_req_query<'a>(query: String,
                              consistency: Consistency,
-                             values: Option<Vec<Value>>,
+                             values: Option<QueryValues>,
                              with_names: Option<bool>,
                              page_size: Option<i32>,
                              paging_state: Option<CBytes>,
@@ -309,6 +464,7 @@ impl Frame {
             // for request frames it's always None
             tracing_id: None,
             warnings: vec![],
+            custom_payload: None,
         }
     }
 }
@@ -5,12 +5,14 @@ use frame::Opcode;
 use frame::frame_result::{BodyResResultVoid, BodyResResultPrepared, BodyResResultRows,
                           BodyResResultSetKeyspace, ResResultBody};
 use frame::frame_event::BodyResEvent;
+use frame::events::SchemaChange;
 use frame::frame_error::CDRSError;
 use frame::frame_supported::*;
 use frame::frame_auth_challenge::*;
 use frame::frame_authenticate::BodyResAuthenticate;
 use frame::frame_auth_success::BodyReqAuthSuccess;
-use types::rows::Row;
+use types::rows::{Row, CasResult};
+use error;
 
 #[derive(Debug)]
 pub enum ResponseBody {
@@ -73,6 +75,15 @@ impl ResponseBody {
         }
     }
 
+    /// It converts body into `CasResult`, i.e. a result of conditional statement.
+    /// If frame body is not of type `Result` with rows this method returns `None`.
+    pub fn into_cas(self) -> Option<error::Result<CasResult>> {
+        match self {
+            ResponseBody::Result(res) => res.into_cas(),
+            _ => None,
+        }
+    }
+
     pub fn as_cols(&self) -> Option<&BodyResResultRows> {
         match self {
             &ResponseBody::Result(ref res) => {
@@ -103,6 +114,15 @@ impl ResponseBody {
         }
     }
 
+    /// It unwraps body and returns `SchemaChange` which describes a result of DDL query.
+    /// If frame body is not of type `Result` this method returns `None`.
+    pub fn into_schema_change(self) -> Option<SchemaChange> {
+        match self {
+            ResponseBody::Result(res) => res.into_schema_change(),
+            _ => None,
+        }
+    }
+
     /// It unwraps body and returns BodyResEvent.
     /// If frame body is not of type `Result` this method returns `None`.
     pub fn into_server_event(self) -> Option<BodyResEvent> {
@@ -112,6 +132,15 @@ impl ResponseBody {
         }
     }
 
+    /// It returns a paging state of rows result if there are more pages to fetch.
+    /// If frame body is not of type `Result` this method returns `None`.
+    pub fn paging_state(&self) -> Option<Vec<u8>> {
+        match self {
+            &ResponseBody::Result(ref res) => res.paging_state(),
+            _ => None,
+        }
+    }
+
     pub fn get_authenticator<'a>(&'a self) -> Option<&'a str> {
         match self {
             &ResponseBody::Authenticate(ref auth) => Some(auth.data.as_str()),
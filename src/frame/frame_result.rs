@@ -1,7 +1,10 @@
+use std::fmt;
 use std::io::Cursor;
-use {IntoBytes, FromBytes, FromCursor};
+use {IntoBytes, FromBytes, FromCursor, TryFromCursor};
 use types::*;
-use types::rows::Row;
+use types::rows::{Row, CasResult};
+use types::value::{QueryValues, Value};
+use error;
 use frame::events::SchemaChange;
 
 
@@ -98,6 +101,21 @@ impl ResResultBody {
         }
     }
 
+    /// It converts body into `CasResult` if body's type is `Row`, i.e. it's a result of
+    /// conditional statement, and returns `None` otherwise.
+    pub fn into_cas(self) -> Option<error::Result<CasResult>> {
+        match self.into_rows() {
+            Some(mut rows) => {
+                if rows.is_empty() {
+                    return Some(Err(error::Error::General("Result does not contain rows"
+                        .to_string())));
+                }
+                Some(CasResult::from_row(rows.remove(0)))
+            }
+            None => None,
+        }
+    }
+
     /// It unwraps body and returns BodyResResultPrepared which contains an exact result of
     /// PREPARE query.
     pub fn into_prepared(self) -> Option<BodyResResultPrepared> {
@@ -115,6 +133,26 @@ impl ResResultBody {
             _ => None,
         }
     }
+
+    /// It unwraps body and returns `SchemaChange` which describes a result of DDL query,
+    /// e.g. `CREATE TABLE`.
+    pub fn into_schema_change(self) -> Option<SchemaChange> {
+        match self {
+            ResResultBody::SchemaChange(schema_change) => Some(schema_change),
+            _ => None,
+        }
+    }
+
+    /// It returns a paging state of rows result if there are more pages to fetch.
+    /// The paging state can be passed to a subsequent query to resume fetching.
+    pub fn paging_state(&self) -> Option<Vec<u8>> {
+        match self {
+            &ResResultBody::Rows(ref rows) => {
+                rows.metadata.paging_state.as_ref().map(|state| state.as_plain())
+            }
+            _ => None,
+        }
+    }
 }
 
 impl FromCursor for ResResultBody {
@@ -241,6 +279,18 @@ impl FromCursor for RowsMetadata {
             paging_state = Some(CBytes::from_cursor(&mut cursor))
         }
 
+        // neither global table spec nor column specs are sent with No_metadata flag,
+        // e.g. for executes of prepared statements which already have result metadata
+        if RowsMetadataFlag::has_no_metadata(flags) {
+            return RowsMetadata {
+                flags: flags,
+                columns_count: columns_count,
+                paging_state: paging_state,
+                global_table_space: None,
+                col_specs: vec![],
+            };
+        }
+
         let mut global_table_space: Option<Vec<CString>> = None;
         let has_global_table_space = RowsMetadataFlag::has_global_table_space(flags);
         if has_global_table_space {
@@ -261,6 +311,48 @@ impl FromCursor for RowsMetadata {
     }
 }
 
+impl TryFromCursor for RowsMetadata {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<RowsMetadata> {
+        let flags = try!(CInt::try_from_cursor(&mut cursor));
+        let columns_count = try!(CInt::try_from_cursor(&mut cursor));
+
+        let mut paging_state: Option<CBytes> = None;
+        if RowsMetadataFlag::has_has_more_pages(flags) {
+            paging_state = Some(try!(CBytes::try_from_cursor(&mut cursor)))
+        }
+
+        if RowsMetadataFlag::has_no_metadata(flags) {
+            return Ok(RowsMetadata {
+                flags: flags,
+                columns_count: columns_count,
+                paging_state: paging_state,
+                global_table_space: None,
+                col_specs: vec![],
+            });
+        }
+
+        let mut global_table_space: Option<Vec<CString>> = None;
+        let has_global_table_space = RowsMetadataFlag::has_global_table_space(flags);
+        if has_global_table_space {
+            let keyspace = try!(CString::try_from_cursor(&mut cursor));
+            let tablename = try!(CString::try_from_cursor(&mut cursor));
+            global_table_space = Some(vec![keyspace, tablename])
+        }
+
+        let col_specs = try!(ColSpec::try_parse_colspecs(&mut cursor,
+                                                         columns_count,
+                                                         has_global_table_space));
+
+        Ok(RowsMetadata {
+            flags: flags,
+            columns_count: columns_count,
+            paging_state: paging_state,
+            global_table_space: global_table_space,
+            col_specs: col_specs,
+        })
+    }
+}
+
 const GLOBAL_TABLE_SPACE: i32 = 0x0001;
 const HAS_MORE_PAGES: i32 = 0x0002;
 const NO_METADATA: i32 = 0x0004;
@@ -371,6 +463,38 @@ impl ColSpec {
             })
             .collect()
     }
+
+    /// Parses column specifications the same way `parse_colspecs` does but returns
+    /// an error rather than panics if the cursor doesn't contain all of them.
+    pub fn try_parse_colspecs(mut cursor: &mut Cursor<&[u8]>,
+                              column_count: i32,
+                              with_globale_table_spec: bool)
+                              -> error::Result<Vec<ColSpec>> {
+        (0..column_count)
+            .map(|_| {
+                let mut ksname: Option<CString> = None;
+                let mut tablename: Option<CString> = None;
+                if !with_globale_table_spec {
+                    ksname = Some(try!(CString::try_from_cursor(&mut cursor)));
+                    tablename = Some(try!(CString::try_from_cursor(&mut cursor)));
+                }
+                let name = try!(CString::try_from_cursor(&mut cursor));
+                let col_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+
+                Ok(ColSpec {
+                    ksname: ksname,
+                    tablename: tablename,
+                    name: name,
+                    col_type: col_type,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns typed description of the column type.
+    pub fn column_type(&self) -> ColumnType {
+        self.col_type.column_type()
+    }
 }
 
 /// Cassandra data types which clould be returned by a server.
@@ -396,6 +520,7 @@ pub enum ColType {
     Time,
     Smallint,
     Tinyint,
+    Duration,
     List,
     Map,
     Set,
@@ -427,6 +552,7 @@ impl FromBytes for ColType {
             0x0012 => ColType::Time,
             0x0013 => ColType::Smallint,
             0x0014 => ColType::Tinyint,
+            0x0015 => ColType::Duration,
             0x0020 => ColType::List,
             0x0021 => ColType::Map,
             0x0022 => ColType::Set,
@@ -445,6 +571,40 @@ impl FromCursor for ColType {
     }
 }
 
+impl TryFromCursor for ColType {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ColType> {
+        match try!(CIntShort::try_from_cursor(&mut cursor)) {
+            0x0000 => Ok(ColType::Custom),
+            0x0001 => Ok(ColType::Ascii),
+            0x0002 => Ok(ColType::Bigint),
+            0x0003 => Ok(ColType::Blob),
+            0x0004 => Ok(ColType::Boolean),
+            0x0005 => Ok(ColType::Counter),
+            0x0006 => Ok(ColType::Decimal),
+            0x0007 => Ok(ColType::Double),
+            0x0008 => Ok(ColType::Float),
+            0x0009 => Ok(ColType::Int),
+            0x000B => Ok(ColType::Timestamp),
+            0x000C => Ok(ColType::Uuid),
+            0x000D => Ok(ColType::Varchar),
+            0x000E => Ok(ColType::Varint),
+            0x000F => Ok(ColType::Timeuuid),
+            0x0010 => Ok(ColType::Inet),
+            0x0011 => Ok(ColType::Date),
+            0x0012 => Ok(ColType::Time),
+            0x0013 => Ok(ColType::Smallint),
+            0x0014 => Ok(ColType::Tinyint),
+            0x0015 => Ok(ColType::Duration),
+            0x0020 => Ok(ColType::List),
+            0x0021 => Ok(ColType::Map),
+            0x0022 => Ok(ColType::Set),
+            0x0030 => Ok(ColType::Udt),
+            0x0031 => Ok(ColType::Tuple),
+            id => Err(error::Error::General(format!("Unexpected column type {:#06x}", id))),
+        }
+    }
+}
+
 /// Cassandra option that represent column type.
 #[derive(Debug, Clone)]
 pub struct ColTypeOption {
@@ -475,6 +635,13 @@ impl FromCursor for ColTypeOption {
                 let value_type = ColTypeOption::from_cursor(&mut cursor);
                 Some(ColTypeOptionValue::CMap((Box::new(name_type), Box::new(value_type))))
             }
+            ColType::Tuple => {
+                let n = from_bytes(cursor_next_value(&mut cursor, SHORT_LEN as u64).as_slice());
+                let element_types = (0..n)
+                    .map(|_| ColTypeOption::from_cursor(&mut cursor))
+                    .collect();
+                Some(ColTypeOptionValue::TupleType(element_types))
+            }
             _ => None,
         };
 
@@ -485,6 +652,46 @@ impl FromCursor for ColTypeOption {
     }
 }
 
+impl TryFromCursor for ColTypeOption {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<ColTypeOption> {
+        let id = try!(ColType::try_from_cursor(&mut cursor));
+        let value = match id {
+            ColType::Custom => {
+                Some(ColTypeOptionValue::CString(try!(CString::try_from_cursor(&mut cursor))))
+            }
+            ColType::Set => {
+                let col_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+                Some(ColTypeOptionValue::CSet(Box::new(col_type)))
+            }
+            ColType::List => {
+                let col_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+                Some(ColTypeOptionValue::CList(Box::new(col_type)))
+            }
+            ColType::Udt => {
+                Some(ColTypeOptionValue::UdtType(try!(CUdt::try_from_cursor(&mut cursor))))
+            }
+            ColType::Map => {
+                let name_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+                let value_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+                Some(ColTypeOptionValue::CMap((Box::new(name_type), Box::new(value_type))))
+            }
+            ColType::Tuple => {
+                let n = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+                let element_types = try!((0..n)
+                    .map(|_| ColTypeOption::try_from_cursor(&mut cursor))
+                    .collect());
+                Some(ColTypeOptionValue::TupleType(element_types))
+            }
+            _ => None,
+        };
+
+        Ok(ColTypeOption {
+            id: id,
+            value: value,
+        })
+    }
+}
+
 /// Enum that represents all possible types of `value` of `ColTypeOption`.
 #[derive(Debug, Clone)]
 pub enum ColTypeOptionValue {
@@ -494,6 +701,8 @@ pub enum ColTypeOptionValue {
     CList(Box<ColTypeOption>),
     UdtType(CUdt),
     CMap((Box<ColTypeOption>, Box<ColTypeOption>)),
+    /// Types of tuple elements in the order of tuple definition.
+    TupleType(Vec<ColTypeOption>),
 }
 
 /// User defined type.
@@ -529,8 +738,208 @@ impl FromCursor for CUdt {
     }
 }
 
+impl TryFromCursor for CUdt {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<CUdt> {
+        let ks = try!(CString::try_from_cursor(&mut cursor));
+        let udt_name = try!(CString::try_from_cursor(&mut cursor));
+        let n = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let descriptions: Vec<(CString, ColTypeOption)> = try!((0..n)
+            .map(|_| -> error::Result<(CString, ColTypeOption)> {
+                let name = try!(CString::try_from_cursor(&mut cursor));
+                let col_type = try!(ColTypeOption::try_from_cursor(&mut cursor));
+                Ok((name, col_type))
+            })
+            .collect());
+
+        Ok(CUdt {
+            ks: ks,
+            udt_name: udt_name,
+            descriptions: descriptions,
+        })
+    }
+}
+
+/// Typed description of a column type, including types of elements of collections,
+/// fields of user defined types and elements of tuples. It is displayed in CQL form,
+/// e.g. `map<text, list<int>>`. Note that `frozen` is not transmitted by a server,
+/// so frozen and non-frozen types are described in the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Custom type with its Java class name.
+    Custom(String),
+    Ascii,
+    Bigint,
+    Blob,
+    Boolean,
+    Counter,
+    Decimal,
+    Double,
+    Float,
+    Int,
+    Timestamp,
+    Uuid,
+    Varchar,
+    Varint,
+    Timeuuid,
+    Inet,
+    Date,
+    Time,
+    Smallint,
+    Tinyint,
+    Duration,
+    List(Box<ColumnType>),
+    Map(Box<ColumnType>, Box<ColumnType>),
+    Set(Box<ColumnType>),
+    Udt {
+        keyspace: String,
+        name: String,
+        /// Pairs `(name, type)` of fields in the order of type definition.
+        fields: Vec<(String, ColumnType)>,
+    },
+    Tuple(Vec<ColumnType>),
+    Null,
+}
+
+// Returns a type of elements of a collection option. Options which are decoded
+// by `ColTypeOption::from_cursor` always have it.
+fn element_type(option: &Option<ColTypeOptionValue>) -> Box<ColumnType> {
+    match option {
+        &Some(ColTypeOptionValue::CList(ref t)) |
+        &Some(ColTypeOptionValue::CSet(ref t)) => Box::new(ColumnType::from(t.as_ref())),
+        _ => Box::new(ColumnType::Null),
+    }
+}
+
+impl<'a> From<&'a ColTypeOption> for ColumnType {
+    fn from(option: &'a ColTypeOption) -> ColumnType {
+        match option.id {
+            ColType::Custom => {
+                match option.value {
+                    Some(ColTypeOptionValue::CString(ref class_name)) => {
+                        ColumnType::Custom(class_name.as_str().to_string())
+                    }
+                    _ => ColumnType::Custom(String::new()),
+                }
+            }
+            ColType::Ascii => ColumnType::Ascii,
+            ColType::Bigint => ColumnType::Bigint,
+            ColType::Blob => ColumnType::Blob,
+            ColType::Boolean => ColumnType::Boolean,
+            ColType::Counter => ColumnType::Counter,
+            ColType::Decimal => ColumnType::Decimal,
+            ColType::Double => ColumnType::Double,
+            ColType::Float => ColumnType::Float,
+            ColType::Int => ColumnType::Int,
+            ColType::Timestamp => ColumnType::Timestamp,
+            ColType::Uuid => ColumnType::Uuid,
+            ColType::Varchar => ColumnType::Varchar,
+            ColType::Varint => ColumnType::Varint,
+            ColType::Timeuuid => ColumnType::Timeuuid,
+            ColType::Inet => ColumnType::Inet,
+            ColType::Date => ColumnType::Date,
+            ColType::Time => ColumnType::Time,
+            ColType::Smallint => ColumnType::Smallint,
+            ColType::Tinyint => ColumnType::Tinyint,
+            ColType::Duration => ColumnType::Duration,
+            ColType::List => ColumnType::List(element_type(&option.value)),
+            ColType::Set => ColumnType::Set(element_type(&option.value)),
+            ColType::Map => {
+                match option.value {
+                    Some(ColTypeOptionValue::CMap((ref key, ref value))) => {
+                        ColumnType::Map(Box::new(ColumnType::from(key.as_ref())),
+                                        Box::new(ColumnType::from(value.as_ref())))
+                    }
+                    _ => ColumnType::Map(Box::new(ColumnType::Null), Box::new(ColumnType::Null)),
+                }
+            }
+            ColType::Udt => {
+                match option.value {
+                    Some(ColTypeOptionValue::UdtType(ref udt)) => {
+                        ColumnType::Udt {
+                            keyspace: udt.ks.as_str().to_string(),
+                            name: udt.udt_name.as_str().to_string(),
+                            fields: udt.descriptions
+                                .iter()
+                                .map(|&(ref name, ref t)| {
+                                    (name.as_str().to_string(), ColumnType::from(t))
+                                })
+                                .collect(),
+                        }
+                    }
+                    _ => {
+                        ColumnType::Udt {
+                            keyspace: String::new(),
+                            name: String::new(),
+                            fields: vec![],
+                        }
+                    }
+                }
+            }
+            ColType::Tuple => {
+                match option.value {
+                    Some(ColTypeOptionValue::TupleType(ref types)) => {
+                        ColumnType::Tuple(types.iter().map(ColumnType::from).collect())
+                    }
+                    _ => ColumnType::Tuple(vec![]),
+                }
+            }
+            ColType::Null => ColumnType::Null,
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ColumnType::Custom(ref class_name) => write!(f, "'{}'", class_name),
+            &ColumnType::Ascii => write!(f, "ascii"),
+            &ColumnType::Bigint => write!(f, "bigint"),
+            &ColumnType::Blob => write!(f, "blob"),
+            &ColumnType::Boolean => write!(f, "boolean"),
+            &ColumnType::Counter => write!(f, "counter"),
+            &ColumnType::Decimal => write!(f, "decimal"),
+            &ColumnType::Double => write!(f, "double"),
+            &ColumnType::Float => write!(f, "float"),
+            &ColumnType::Int => write!(f, "int"),
+            &ColumnType::Timestamp => write!(f, "timestamp"),
+            &ColumnType::Uuid => write!(f, "uuid"),
+            &ColumnType::Varchar => write!(f, "text"),
+            &ColumnType::Varint => write!(f, "varint"),
+            &ColumnType::Timeuuid => write!(f, "timeuuid"),
+            &ColumnType::Inet => write!(f, "inet"),
+            &ColumnType::Date => write!(f, "date"),
+            &ColumnType::Time => write!(f, "time"),
+            &ColumnType::Smallint => write!(f, "smallint"),
+            &ColumnType::Tinyint => write!(f, "tinyint"),
+            &ColumnType::Duration => write!(f, "duration"),
+            &ColumnType::List(ref t) => write!(f, "list<{}>", t),
+            &ColumnType::Map(ref k, ref v) => write!(f, "map<{}, {}>", k, v),
+            &ColumnType::Set(ref t) => write!(f, "set<{}>", t),
+            &ColumnType::Udt { ref keyspace, ref name, .. } => write!(f, "{}.{}", keyspace, name),
+            &ColumnType::Tuple(ref types) => {
+                try!(write!(f, "tuple<"));
+                for (i, t) in types.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", t));
+                }
+                write!(f, ">")
+            }
+            &ColumnType::Null => write!(f, "null"),
+        }
+    }
+}
+
+impl ColTypeOption {
+    /// Returns typed description of the column type.
+    pub fn column_type(&self) -> ColumnType {
+        ColumnType::from(self)
+    }
+}
+
 /// The structure represents a body of a response frame of type `prepared`
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BodyResResultPrepared {
     /// id of prepared request
     pub id: CBytesShort,
@@ -541,6 +950,94 @@ pub struct BodyResResultPrepared {
     pub result_metadata: RowsMetadata,
 }
 
+impl BodyResResultPrepared {
+    /// It checks that provided values can be bound to the prepared statement and
+    /// returns them. Values bound by names should contain a value for each bind marker
+    /// of the statement, values bound by position should match the number of markers.
+    pub fn bind<V: Into<QueryValues>>(&self, values: V) -> error::Result<QueryValues> {
+        let values = values.into();
+        match values {
+            QueryValues::Simple(ref simple) => {
+                let expected = self.metadata.columns_count as usize;
+                if simple.len() != expected {
+                    let msg = format!("Expected {} values but {} provided", expected, simple.len());
+                    return Err(error::Error::General(msg));
+                }
+            }
+            QueryValues::Named(ref named) => {
+                let missing = self.metadata
+                    .col_specs
+                    .iter()
+                    .find(|spec| !named.contains_key(spec.name.as_str()));
+                if let Some(spec) = missing {
+                    let msg = format!("Value of bind marker {} is not provided",
+                                      spec.name.as_str());
+                    return Err(error::Error::General(msg));
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Returns specifications of bind markers which correspond to columns of partition
+    /// key in the order of partition key definition. It's empty if the statement
+    /// doesn't bind the whole partition key.
+    pub fn pk_columns(&self) -> Vec<&ColSpec> {
+        self.metadata
+            .pk_indexes
+            .iter()
+            .filter_map(|&index| self.metadata.col_specs.get(index as usize))
+            .collect()
+    }
+
+    /// Returns a routing key of the statement with provided values bound, i.e. serialized
+    /// partition key `TokenAwareLoadBalancing` policy routes requests by. Values of
+    /// a composite partition key are concatenated in the order of partition key definition,
+    /// each one as 2-byte length followed by the value and `0x00`. It's `None` if the whole
+    /// partition key is not bound or any of its values is null or not set.
+    pub fn routing_key(&self, values: &QueryValues) -> Option<Vec<u8>> {
+        let mut pk_values = Vec::with_capacity(self.metadata.pk_indexes.len());
+        for &index in &self.metadata.pk_indexes {
+            let value = match *values {
+                QueryValues::Simple(ref simple) => simple.get(index as usize),
+                QueryValues::Named(ref named) => {
+                    self.metadata
+                        .col_specs
+                        .get(index as usize)
+                        .and_then(|spec| named.get(spec.name.as_str()))
+                }
+            };
+            match value {
+                Some(&Value::Null) | Some(&Value::NotSet) | None => return None,
+                Some(value) => pk_values.push(value.body()),
+            }
+        }
+
+        match pk_values.len() {
+            0 => None,
+            1 => pk_values.pop(),
+            _ => {
+                let mut key = vec![];
+                for value in pk_values {
+                    key.extend_from_slice(to_short(value.len() as i16).as_slice());
+                    key.extend_from_slice(value.as_slice());
+                    key.push(0x00);
+                }
+                Some(key)
+            }
+        }
+    }
+
+    /// Shows if the statement returns rows which are described by `result_metadata`.
+    /// It's `false` for statements like `INSERT` or `UPDATE` which result metadata
+    /// is sent with `No_metadata` flag and without columns.
+    pub fn has_result_metadata(&self) -> bool {
+        !RowsMetadataFlag::has_no_metadata(self.result_metadata.flags) &&
+        !self.result_metadata.col_specs.is_empty()
+    }
+}
+
 impl FromCursor for BodyResResultPrepared {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> BodyResResultPrepared {
         let id = CBytesShort::from_cursor(&mut cursor);
@@ -556,13 +1053,20 @@ impl FromCursor for BodyResResultPrepared {
 }
 
 /// The structure that represents metadata of prepared response.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PreparedMetadata {
+    /// Flags, only `Global_tables_spec` is used.
     pub flags: i32,
+    /// Number of bind markers.
     pub columns_count: i32,
+    /// Number of columns of partition key which are bound.
     pub pk_count: i32,
+    /// Indexes of bind markers which correspond to columns of partition key
+    /// in the order of partition key definition.
     pub pk_indexes: Vec<i16>,
+    /// Keyspace and table names if `Global_tables_spec` flag is set.
     pub global_table_spec: Option<(CString, CString)>,
+    /// Specifications of bind markers.
     pub col_specs: Vec<ColSpec>,
 }
 
@@ -596,3 +1100,359 @@ impl FromCursor for PreparedMetadata {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use frame::frame_query::{ParamsReqQuery, QueryFlags};
+    use types::value::Value;
+    use consistency::Consistency;
+    use types::{IntoRustByName, to_int, to_short};
+    use frame::events::{ChangeType, Target};
+    use AsByte;
+    use super::*;
+
+    // Prepared result of `INSERT INTO ks.table (id, name) VALUES (:id, :name)`.
+    fn prepared_insert() -> BodyResResultPrepared {
+        let mut bytes = vec![0, 2, 1, 2];
+        // flags: global table spec, columns_count: 2, pk_count: 1, pk index: 0
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0]);
+        bytes.extend_from_slice(&[0, 2, 107, 115, 0, 5, 116, 97, 98, 108, 101]);
+        // id int, name varchar
+        bytes.extend_from_slice(&[0, 2, 105, 100, 0, 9]);
+        bytes.extend_from_slice(&[0, 4, 110, 97, 109, 101, 0, 13]);
+        // result metadata: no_metadata flag, columns_count
+        bytes.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0]);
+        BodyResResultPrepared::from_cursor(&mut Cursor::new(bytes.as_slice()))
+    }
+
+    // Rows result of `INSERT INTO ks.table (id, name) VALUES (1, 'john') IF NOT EXISTS`
+    // with `columns` ([applied], id, name) and a single row of `values`.
+    fn cas_rows(columns: usize, values: Vec<Vec<u8>>) -> ResResultBody {
+        let mut bytes = vec![0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, columns as u8];
+        bytes.extend_from_slice(&[0, 2, 107, 115, 0, 5, 116, 97, 98, 108, 101]);
+        let specs: Vec<&[u8]> = vec![&[0, 9, 91, 97, 112, 112, 108, 105, 101, 100, 93, 0, 4],
+                                     &[0, 2, 105, 100, 0, 9],
+                                     &[0, 4, 110, 97, 109, 101, 0, 13]];
+        for spec in specs.iter().take(columns) {
+            bytes.extend_from_slice(spec);
+        }
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+        for value in values {
+            bytes.extend_from_slice(CBytes::new(value).into_cbytes().as_slice());
+        }
+        ResResultBody::from_cursor(&mut Cursor::new(bytes.as_slice()))
+    }
+
+    fn schema_change(values: &[&str], arg_types: Option<&[&str]>) -> ResResultBody {
+        let mut bytes = to_int(0x0005);
+        for value in values {
+            bytes.extend_from_slice(CString::new(value.to_string()).into_cbytes().as_slice());
+        }
+        if let Some(arg_types) = arg_types {
+            bytes.extend_from_slice(to_short(arg_types.len() as i16).as_slice());
+            for arg_type in arg_types {
+                bytes.extend_from_slice(CString::new(arg_type.to_string())
+                    .into_cbytes()
+                    .as_slice());
+            }
+        }
+        ResResultBody::from_cursor(&mut Cursor::new(bytes.as_slice()))
+    }
+
+    #[test]
+    fn schema_change_create_table() {
+        let change = schema_change(&["CREATED", "TABLE", "ks", "users"], None)
+            .into_schema_change()
+            .unwrap();
+        assert_eq!(change.change_type, ChangeType::Created);
+        assert_eq!(change.target, Target::Table);
+        assert_eq!(change.keyspace(), "ks");
+        assert_eq!(change.name(), Some("users"));
+        assert!(change.arg_types().is_empty());
+    }
+
+    #[test]
+    fn schema_change_drop_function() {
+        let change = schema_change(&["DROPPED", "FUNCTION", "ks", "avg_state"],
+                                   Some(&["tuple<int, bigint>", "int"]))
+            .into_schema_change()
+            .unwrap();
+        assert_eq!(change.change_type, ChangeType::Dropped);
+        assert_eq!(change.target, Target::Function);
+        assert_eq!(change.keyspace(), "ks");
+        assert_eq!(change.name(), Some("avg_state"));
+        assert_eq!(change.arg_types(),
+                   &["tuple<int, bigint>".to_string(), "int".to_string()]);
+    }
+
+    #[test]
+    fn schema_change_keyspace() {
+        let change = schema_change(&["UPDATED", "KEYSPACE", "ks"], None)
+            .into_schema_change()
+            .unwrap();
+        assert_eq!(change.keyspace(), "ks");
+        assert_eq!(change.name(), None);
+    }
+
+    #[test]
+    fn into_cas_applied() {
+        let cas = cas_rows(1, vec![vec![1]]).into_cas().unwrap().unwrap();
+        assert!(cas.applied);
+        assert!(cas.existing.is_none());
+    }
+
+    #[test]
+    fn into_cas_not_applied() {
+        let values = vec![vec![0], vec![0, 0, 0, 1], b"jack".to_vec()];
+        let cas = cas_rows(3, values).into_cas().unwrap().unwrap();
+        assert!(!cas.applied);
+
+        let existing = cas.existing.unwrap();
+        let id: i32 = existing.get_by_name("id").unwrap().unwrap();
+        let name: String = existing.get_by_name("name").unwrap().unwrap();
+        let applied: Option<error::Result<bool>> = existing.get_by_name("[applied]");
+        assert_eq!(id, 1);
+        assert_eq!(name, "jack");
+        assert!(applied.is_none());
+    }
+
+    #[test]
+    fn decode_prepared_insert() {
+        let prepared = prepared_insert();
+        assert_eq!(prepared.id.clone().into_plain(), vec![1, 2]);
+        assert_eq!(prepared.metadata.columns_count, 2);
+        assert_eq!(prepared.metadata.pk_indexes, vec![0]);
+        let (ref ks, ref table) = *prepared.metadata.global_table_spec.as_ref().unwrap();
+        assert_eq!((ks.as_str(), table.as_str()), ("ks", "table"));
+        let names: Vec<&str> =
+            prepared.metadata.col_specs.iter().map(|spec| spec.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "name"]);
+        assert_eq!(prepared.metadata.col_specs[1].column_type(), ColumnType::Varchar);
+
+        let pk_columns = prepared.pk_columns();
+        assert_eq!(pk_columns.len(), 1);
+        assert_eq!(pk_columns[0].name.as_str(), "id");
+        assert_eq!(pk_columns[0].column_type(), ColumnType::Int);
+
+        assert!(!prepared.has_result_metadata());
+        assert!(prepared.result_metadata.col_specs.is_empty());
+    }
+
+    #[test]
+    fn decode_prepared_select() {
+        // SELECT name FROM ks.table WHERE id = ?
+        let mut bytes = vec![0, 1, 3, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0];
+        bytes.extend_from_slice(&[0, 2, 107, 115, 0, 5, 116, 97, 98, 108, 101]);
+        bytes.extend_from_slice(&[0, 2, 105, 100, 0, 9]);
+        // result metadata: global table spec, columns_count: 1
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+        bytes.extend_from_slice(&[0, 2, 107, 115, 0, 5, 116, 97, 98, 108, 101]);
+        bytes.extend_from_slice(&[0, 4, 110, 97, 109, 101, 0, 13]);
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let prepared = BodyResResultPrepared::from_cursor(&mut cursor);
+        assert_eq!(cursor.position(), bytes.len() as u64);
+
+        assert_eq!(prepared.pk_columns()[0].name.as_str(), "id");
+        assert!(prepared.has_result_metadata());
+        assert_eq!(prepared.result_metadata.col_specs[0].name.as_str(), "name");
+    }
+
+    #[test]
+    fn bind_named_values() {
+        let prepared = prepared_insert();
+        let mut values = HashMap::new();
+        values.insert("id".to_string(), Value::from(1 as i32));
+        values.insert("name".to_string(), Value::from("john"));
+        let values = prepared.bind(values).unwrap();
+
+        let mut params = ParamsReqQuery {
+            consistency: Consistency::One,
+            flags: vec![],
+            values: None,
+            page_size: None,
+            paging_state: None,
+            serial_consistency: None,
+            timestamp: None,
+            keyspace: None,
+            now_in_seconds: None,
+            idempotent: false,
+            custom_payload: None,
+        };
+        params.set_values(values);
+        let bytes = params.into_cbytes();
+        let flags = QueryFlags::Value.as_byte() | QueryFlags::WithNamesForValues.as_byte();
+        assert_eq!(&bytes[..5], &[0, 1, flags, 0, 2]);
+        assert_eq!(bytes.len(), 5 + 12 + 14);
+        assert!(bytes.windows(12).any(|w| w == &[0, 2, 105, 100, 0, 0, 0, 4, 0, 0, 0, 1][..]));
+        assert!(bytes.windows(14)
+            .any(|w| w == &[0, 4, 110, 97, 109, 101, 0, 0, 0, 4, 106, 111, 104, 110][..]));
+    }
+
+    #[test]
+    fn bind_missing_named_value() {
+        let prepared = prepared_insert();
+        let mut values = HashMap::new();
+        values.insert("id".to_string(), Value::from(1 as i32));
+        assert!(prepared.bind(values).is_err());
+    }
+
+    #[test]
+    fn routing_key_of_single_column_partition_key() {
+        let prepared = prepared_insert();
+        let simple = QueryValues::from(vec![Value::from(1 as i32), Value::from("john")]);
+        assert_eq!(prepared.routing_key(&simple), Some(vec![0, 0, 0, 1]));
+
+        let mut named = HashMap::new();
+        named.insert("name".to_string(), Value::from("john"));
+        named.insert("id".to_string(), Value::from(1 as i32));
+        assert_eq!(prepared.routing_key(&QueryValues::from(named)), Some(vec![0, 0, 0, 1]));
+
+        let null = QueryValues::from(vec![Value::new_null(), Value::from("john")]);
+        assert_eq!(prepared.routing_key(&null), None);
+        assert_eq!(prepared.routing_key(&QueryValues::from(vec![])), None);
+    }
+
+    #[test]
+    fn routing_key_of_composite_partition_key() {
+        // INSERT INTO ks.table (id, name) VALUES (?, ?) where PRIMARY KEY ((name, id))
+        let mut bytes = vec![0, 2, 1, 2];
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 2, 0, 1, 0, 0]);
+        bytes.extend_from_slice(&[0, 2, 107, 115, 0, 5, 116, 97, 98, 108, 101]);
+        bytes.extend_from_slice(&[0, 2, 105, 100, 0, 9]);
+        bytes.extend_from_slice(&[0, 4, 110, 97, 109, 101, 0, 13]);
+        bytes.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0]);
+        let prepared = BodyResResultPrepared::from_cursor(&mut Cursor::new(bytes.as_slice()));
+        assert_eq!(prepared.metadata.pk_indexes, vec![1, 0]);
+
+        let values = QueryValues::from(vec![Value::from(1 as i32), Value::from("john")]);
+        assert_eq!(prepared.routing_key(&values),
+                   Some(vec![0, 4, 106, 111, 104, 110, 0, 0, 4, 0, 0, 0, 1, 0]));
+
+        let not_set = QueryValues::from(vec![Value::new_not_set(), Value::from("john")]);
+        assert_eq!(prepared.routing_key(&not_set), None);
+    }
+
+    #[test]
+    fn bind_simple_values() {
+        let prepared = prepared_insert();
+        assert!(prepared.bind(vec![Value::from(1 as i32), Value::from("john")]).is_ok());
+        assert!(prepared.bind(vec![Value::from(1 as i32)]).is_err());
+    }
+
+    #[test]
+    fn tuple_col_type_option() {
+        // tuple<int, text> followed by a next column type
+        let bytes = [0x00, 0x31, 0, 2, 0, 0x09, 0, 0x0D, 0, 0x09];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(&bytes);
+        let col_type = ColTypeOption::from_cursor(&mut cursor);
+        match col_type.value {
+            Some(ColTypeOptionValue::TupleType(ref element_types)) => {
+                let ids: Vec<u8> = element_types.iter().map(|t| t.id.clone() as u8).collect();
+                assert_eq!(ids, vec![ColType::Int as u8, ColType::Varchar as u8]);
+            }
+            ref value => panic!("tuple type is expected, got {:?}", value),
+        }
+        assert_eq!(cursor.position(), 8);
+    }
+
+    #[test]
+    fn duration_col_type_option() {
+        let bytes = [0x00, 0x15];
+        let col_type = ColTypeOption::try_from_cursor(&mut Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(col_type.id.clone() as u8, ColType::Duration as u8);
+        assert_eq!(ColumnType::from(&col_type), ColumnType::Duration);
+        assert_eq!(ColumnType::Duration.to_string(), "duration");
+        assert_eq!(ColTypeOption::from_cursor(&mut Cursor::new(&bytes[..])).id as u8,
+                   ColType::Duration as u8);
+    }
+
+    #[test]
+    fn rows_metadata_column_types() {
+        // table ks.t (id int, m map<text, frozen<list<int>>>, p tuple<int, frozen<address>>)
+        // without global table spec
+        let mut bytes = vec![0, 0, 0, 0, 0, 0, 0, 3];
+        let ks_table: &[u8] = &[0, 2, 107, 115, 0, 1, 116];
+        bytes.extend_from_slice(ks_table);
+        bytes.extend_from_slice(&[0, 2, 105, 100, 0, 0x09]);
+        bytes.extend_from_slice(ks_table);
+        bytes.extend_from_slice(&[0, 1, 109, 0, 0x21, 0, 0x0D, 0, 0x20, 0, 0x09]);
+        bytes.extend_from_slice(ks_table);
+        bytes.extend_from_slice(&[0, 1, 112, 0, 0x31, 0, 2, 0, 0x09]);
+        // ks.address (street text)
+        bytes.extend_from_slice(&[0, 0x30, 0, 2, 107, 115, 0, 7, 97, 100, 100, 114, 101, 115, 115]);
+        bytes.extend_from_slice(&[0, 1, 0, 6, 115, 116, 114, 101, 101, 116, 0, 0x0D]);
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let metadata = RowsMetadata::from_cursor(&mut cursor);
+        assert_eq!(cursor.position(), bytes.len() as u64);
+        assert!(metadata.global_table_space.is_none());
+        assert_eq!(metadata.col_specs.len(), 3);
+
+        let spec = &metadata.col_specs[1];
+        assert_eq!(spec.ksname.as_ref().map(|ks| ks.as_str()), Some("ks"));
+        assert_eq!(spec.tablename.as_ref().map(|t| t.as_str()), Some("t"));
+        assert_eq!(spec.name.as_str(), "m");
+        let map_type = ColumnType::Map(Box::new(ColumnType::Varchar),
+                                       Box::new(ColumnType::List(Box::new(ColumnType::Int))));
+        assert_eq!(spec.column_type(), map_type);
+        assert_eq!(map_type.to_string(), "map<text, list<int>>");
+
+        assert_eq!(metadata.col_specs[0].column_type(), ColumnType::Int);
+        let address = ColumnType::Udt {
+            keyspace: "ks".to_string(),
+            name: "address".to_string(),
+            fields: vec![("street".to_string(), ColumnType::Varchar)],
+        };
+        let tuple_type = metadata.col_specs[2].column_type();
+        assert_eq!(tuple_type, ColumnType::Tuple(vec![ColumnType::Int, address]));
+        assert_eq!(tuple_type.to_string(), "tuple<int, ks.address>");
+    }
+
+    // Rows result of `SELECT id, name FROM ks.t` with a single row (1, 'a') and
+    // metadata of provided `flags` followed by `metadata` bytes.
+    fn id_name_rows(flags: u8, metadata: &[u8]) -> BodyResResultRows {
+        let mut bytes = vec![0, 0, 0, flags, 0, 0, 0, 2];
+        bytes.extend_from_slice(metadata);
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 97]);
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let body = BodyResResultRows::from_cursor(&mut cursor);
+        assert_eq!(cursor.position(), bytes.len() as u64);
+        assert_eq!(body.rows_count, 1);
+        assert_eq!(body.rows_content[0][0].as_plain(), vec![0, 0, 0, 1]);
+        assert_eq!(body.rows_content[0][1].as_plain(), vec![97]);
+        body
+    }
+
+    #[test]
+    fn rows_metadata_shapes() {
+        // global table spec
+        let mut metadata = vec![0, 2, 107, 115, 0, 1, 116];
+        metadata.extend_from_slice(&[0, 2, 105, 100, 0, 9, 0, 4, 110, 97, 109, 101, 0, 13]);
+        let body = id_name_rows(1, &metadata);
+        let global_table_space = body.metadata.global_table_space.unwrap();
+        assert_eq!(global_table_space[0].as_str(), "ks");
+        assert_eq!(global_table_space[1].as_str(), "t");
+        assert_eq!(body.metadata.col_specs.len(), 2);
+        assert!(body.metadata.col_specs[0].ksname.is_none());
+        assert_eq!(body.metadata.col_specs[1].name.as_str(), "name");
+        assert_eq!(body.metadata.col_specs[1].column_type(), ColumnType::Varchar);
+
+        // keyspace and table name per column
+        let mut metadata = vec![0, 2, 107, 115, 0, 1, 116, 0, 2, 105, 100, 0, 9];
+        metadata.extend_from_slice(&[0, 2, 107, 115, 0, 1, 116, 0, 4, 110, 97, 109, 101, 0, 13]);
+        let body = id_name_rows(0, &metadata);
+        assert!(body.metadata.global_table_space.is_none());
+        assert_eq!(body.metadata.col_specs.len(), 2);
+        for spec in body.metadata.col_specs.iter() {
+            assert_eq!(spec.ksname.as_ref().unwrap().as_str(), "ks");
+            assert_eq!(spec.tablename.as_ref().unwrap().as_str(), "t");
+        }
+        assert_eq!(body.metadata.col_specs[0].column_type(), ColumnType::Int);
+
+        // no metadata
+        let body = id_name_rows(4, &[]);
+        assert_eq!(body.metadata.columns_count, 2);
+        assert!(body.metadata.global_table_space.is_none());
+        assert!(body.metadata.col_specs.is_empty());
+    }
+}
@@ -1,32 +1,62 @@
 use std::collections::HashMap;
 use std::io::Cursor;
-use FromCursor;
-use types::{SHORT_LEN, cursor_next_value, from_bytes, CString, CStringList};
+use {FromCursor, TryFromCursor};
+use error;
+use types::CStringMultimap;
+
+/// Name of an option which contains supported CQL versions.
+pub const CQL_VERSION: &'static str = "CQL_VERSION";
+/// Name of an option which contains supported compression algorithms.
+pub const COMPRESSION: &'static str = "COMPRESSION";
+/// Name of an option which contains supported versions of native protocol.
+pub const PROTOCOL_VERSIONS: &'static str = "PROTOCOL_VERSIONS";
 
 #[derive(Debug)]
 pub struct BodyResSupported {
     pub data: HashMap<String, Vec<String>>,
 }
 
+impl BodyResSupported {
+    /// Returns well-known options supported by a server.
+    pub fn supported(&self) -> Supported {
+        let option = |name: &str| self.data.get(name).cloned().unwrap_or(vec![]);
+        Supported {
+            cql_versions: option(CQL_VERSION),
+            compression: option(COMPRESSION),
+            protocol_versions: option(PROTOCOL_VERSIONS),
+        }
+    }
+}
+
+/// Options supported by a server which it returns in response to OPTIONS request.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Supported {
+    /// Supported CQL versions, e.g. `3.4.4`.
+    pub cql_versions: Vec<String>,
+    /// Supported compression algorithms, e.g. `snappy` and `lz4`.
+    pub compression: Vec<String>,
+    /// Supported versions of native protocol, e.g. `4/v4`.
+    pub protocol_versions: Vec<String>,
+}
+
 impl FromCursor for BodyResSupported {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> BodyResSupported {
-        let l = from_bytes(cursor_next_value(&mut cursor, SHORT_LEN as u64).as_slice()) as i16;
-        let acc: HashMap<String, Vec<String>> = HashMap::new();
-        let map = (0..l).fold(acc, |mut m, _| {
-            let name = CString::from_cursor(&mut cursor).into_plain();
-            let val = CStringList::from_cursor(&mut cursor).into_plain();
-            m.insert(name, val);
-            return m;
-        });
-
-        BodyResSupported { data: map }
+        BodyResSupported::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for BodyResSupported {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> error::Result<BodyResSupported> {
+        let data = try!(CStringMultimap::try_from_cursor(&mut cursor)).into_plain();
+        Ok(BodyResSupported { data: data })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
-    use FromCursor;
+    use {FromCursor, IntoBytes};
+    use types::{to_short, CString};
     use super::*;
 
     #[test]
@@ -53,4 +83,31 @@ mod tests {
         assert_eq!(option_ab[0], "a".to_string());
         assert_eq!(option_ab[1], "b".to_string());
     }
+
+    #[test]
+    fn supported() {
+        let mut bytes = vec![0, 3];
+        let options: [(&str, &[&str]); 3] = [("PROTOCOL_VERSIONS", &["3/v3", "4/v4", "5/v5-beta"]),
+                                             ("COMPRESSION", &["snappy", "lz4"]),
+                                             ("CQL_VERSION", &["3.4.4"])];
+        for &(name, values) in options.iter() {
+            bytes.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            bytes.extend_from_slice(to_short(values.len() as i16).as_slice());
+            for value in values {
+                bytes.extend_from_slice(CString::new(value.to_string()).into_cbytes().as_slice());
+            }
+        }
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        let supported = BodyResSupported::from_cursor(&mut cursor).supported();
+        assert_eq!(supported.cql_versions, vec!["3.4.4".to_string()]);
+        assert_eq!(supported.compression,
+                   vec!["snappy".to_string(), "lz4".to_string()]);
+        assert_eq!(supported.protocol_versions,
+                   vec!["3/v3".to_string(), "4/v4".to_string(), "5/v5-beta".to_string()]);
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(&[0, 0]);
+        assert_eq!(BodyResSupported::from_cursor(&mut cursor).supported(),
+                   Supported::default());
+    }
 }
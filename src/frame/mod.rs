@@ -1,5 +1,7 @@
 //! `frame` module contains general Frame functionality.
-use types::to_n_bytes;
+use std::collections::HashMap;
+use std::ops::BitOr;
+use types::{to_n_bytes, CBytesMap};
 use {AsByte, IntoBytes};
 use frame::frame_response::ResponseBody;
 use compression::Compression;
@@ -15,6 +17,8 @@ pub const OPCODE_LEN: usize = 1;
 pub const STREAM_LEN: usize = 2;
 /// Number of body length bytes in accordance to protocol.
 pub const LENGTH_LEN: usize = 4;
+/// Number of bytes of a frame header.
+pub const HEADER_LEN: usize = VERSION_LEN + FLAG_LEN + STREAM_LEN + OPCODE_LEN + LENGTH_LEN;
 
 pub mod events;
 pub mod frame_auth_challenge;
@@ -22,6 +26,7 @@ pub mod frame_auth_response;
 pub mod frame_auth_success;
 pub mod frame_authenticate;
 pub mod frame_batch;
+pub mod frame_builder;
 pub mod frame_error;
 pub mod frame_event;
 pub mod frame_execute;
@@ -47,6 +52,8 @@ pub struct Frame {
     pub body: Vec<u8>,
     pub tracing_id: Option<Uuid>,
     pub warnings: Vec<String>,
+    /// Custom payload of the frame, it's sent only if `CustomPayload` flag is set.
+    pub custom_payload: Option<HashMap<String, Vec<u8>>>,
 }
 
 impl Frame {
@@ -54,21 +61,101 @@ impl Frame {
         ResponseBody::from(self.body.as_slice(), &self.opcode)
     }
 
-    pub fn tracing_id(&self) -> &Option<Uuid> {
-        &self.tracing_id
+    /// Returns tracing id of the response if tracing was requested. It can be used
+    /// to look up trace events via `Session::trace_events`.
+    pub fn tracing_id(&self) -> Option<Uuid> {
+        self.tracing_id
     }
 
-    pub fn warnings(&self) -> &Vec<String> {
-        &self.warnings
+    /// Returns warnings attached by a server to the response if `Warning` flag is set.
+    pub fn warnings(&self) -> Option<Vec<String>> {
+        if self.flags.contains(&Flag::Warning) {
+            Some(self.warnings.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns custom payload attached by a server to the response if `CustomPayload`
+    /// flag is set.
+    pub fn custom_payload(&self) -> Option<&HashMap<String, Vec<u8>>> {
+        if self.flags.contains(&Flag::CustomPayload) {
+            self.custom_payload.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Attaches provided custom payload to the request and sets `CustomPayload` flag.
+    /// The payload is passed to a custom query handler of a server.
+    pub fn with_custom_payload(mut self, custom_payload: HashMap<String, Vec<u8>>) -> Self {
+        if !self.flags.contains(&Flag::CustomPayload) {
+            self.flags.push(Flag::CustomPayload);
+        }
+        self.custom_payload = Some(custom_payload);
+        self
+    }
+
+    /// Returns flags which are set on the frame.
+    pub fn frame_flags(&self) -> FrameFlags {
+        FrameFlags::from_bits(Flag::many_to_cbytes(&self.flags))
+    }
+
+    /// Sets provided flags on the frame, e.g. `frame.with_flag(FrameFlags::TRACING)`.
+    pub fn with_flag(mut self, flags: FrameFlags) -> Self {
+        let all = self.frame_flags() | flags;
+        self.flags = Flag::get_collection(all.bits());
+        self
     }
 
     pub fn encode_with(self, compressor: Compression) -> error::Result<Vec<u8>> {
+        self.encode_with_threshold(compressor, 0)
+    }
+
+    /// Encodes the frame compressing its body with `compressor` if the body length is not
+    /// less than `threshold`. `Compression` flag is set only if the body was compressed.
+    pub fn encode_with_threshold(self,
+                                 compressor: Compression,
+                                 threshold: usize)
+                                 -> error::Result<Vec<u8>> {
+        self.encode_with_protocol(compressor, threshold, ProtocolVersion::default())
+    }
+
+    /// Encodes the frame the same way `encode_with_threshold` does but with provided
+    /// version of native protocol in the frame header.
+    pub fn encode_with_protocol(mut self,
+                                compressor: Compression,
+                                threshold: usize,
+                                protocol: ProtocolVersion)
+                                -> error::Result<Vec<u8>> {
         let mut v = vec![];
 
-        let version_bytes = self.version.as_byte();
+        if self.flags.contains(&Flag::CustomPayload) {
+            if protocol < ProtocolVersion::V4 {
+                let msg = format!("Custom payload of a request is supported since protocol \
+                                   version V4, {:?} is used",
+                                  protocol);
+                return Err(error::Error::General(msg));
+            }
+            self.body = self.body_with_custom_payload();
+        }
+
+        let should_compress = compressor != Compression::None && self.opcode != Opcode::Startup &&
+                              self.body.len() >= threshold;
+        let encoded_body = if should_compress {
+            if !self.flags.contains(&Flag::Compression) {
+                self.flags.push(Flag::Compression);
+            }
+            try!(compressor.encode(self.body))
+        } else {
+            self.flags.retain(|flag| flag != &Flag::Compression);
+            self.body
+        };
+
+        // direction bit of the version is kept, protocol version bits are replaced
+        let version_bytes = (self.version.as_byte() & 0x80) | protocol.as_byte();
         let flag_bytes = Flag::many_to_cbytes(&self.flags);
         let opcode_bytes = self.opcode.as_byte();
-        let encoded_body = try!(compressor.encode(self.body));
         let body_len = encoded_body.len();
 
         v.push(version_bytes);
@@ -80,6 +167,19 @@ impl Frame {
 
         Ok(v)
     }
+
+    // Returns the body preceded by custom payload (an empty one if it's not provided)
+    // as the protocol requires when `CustomPayload` flag is set.
+    fn body_with_custom_payload(&self) -> Vec<u8> {
+        if !self.flags.contains(&Flag::CustomPayload) {
+            return self.body.clone();
+        }
+
+        let payload = self.custom_payload.clone().unwrap_or_default();
+        let mut body = CBytesMap::new(payload).into_cbytes();
+        body.extend_from_slice(self.body.as_slice());
+        body
+    }
 }
 
 impl<'a> IntoBytes for Frame {
@@ -89,14 +189,15 @@ impl<'a> IntoBytes for Frame {
         let version_bytes = self.version.as_byte();
         let flag_bytes = Flag::many_to_cbytes(&self.flags);
         let opcode_bytes = self.opcode.as_byte();
-        let body_len = self.body.len();
+        let body = self.body_with_custom_payload();
+        let body_len = body.len();
 
         v.push(version_bytes);
         v.push(flag_bytes);
         v.extend_from_slice(to_n_bytes(self.stream, STREAM_LEN).as_slice());
         v.push(opcode_bytes);
         v.extend_from_slice(to_n_bytes(body_len as u64, LENGTH_LEN).as_slice());
-        v.extend_from_slice(self.body.as_slice());
+        v.extend_from_slice(body.as_slice());
 
         v
     }
@@ -128,17 +229,96 @@ impl From<Vec<u8>> for Version {
                    VERSION_LEN,
                    v);
         }
-        match v[0] {
-            0x04 => Version::Request,
-            0x84 => Version::Response,
+        Version::from(v[0])
+    }
+}
+
+impl From<u8> for Version {
+    fn from(b: u8) -> Version {
+        match b {
+            0x03 | 0x04 | 0x05 => Version::Request,
+            0x83 | 0x84 | 0x85 => Version::Response,
             _ => {
-                error!("Unexpected Cassandra version {:?}", v);
-                panic!("Unexpected Cassandra version {:?}", v);
+                error!("Unexpected Cassandra version {:?}", b);
+                panic!("Unexpected Cassandra version {:?}", b);
             }
         }
     }
 }
 
+/// Version of native protocol which is used by a connection. 4-th version is used
+/// by default, 3-rd one is used if a server does not support 4-th. 5-th version
+/// is never negotiated: only its error bodies and query options are supported,
+/// while prepared statements and framing of 5-th version are not.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum ProtocolVersion {
+    V3,
+    V4,
+    V5,
+}
+
+impl ProtocolVersion {
+    /// Returns protocol versions supported by the driver starting from the highest one.
+    pub fn all() -> &'static [ProtocolVersion] {
+        const ALL: &'static [ProtocolVersion] = &[ProtocolVersion::V4, ProtocolVersion::V3];
+        ALL
+    }
+
+    /// Returns protocol version which corresponds to provided number.
+    pub fn from_number(number: u8) -> Option<ProtocolVersion> {
+        match number {
+            3 => Some(ProtocolVersion::V3),
+            4 => Some(ProtocolVersion::V4),
+            5 => Some(ProtocolVersion::V5),
+            _ => None,
+        }
+    }
+
+    /// Parses protocol versions supported by a server from a message of protocol error
+    /// it returns to unsupported version, e.g. `Invalid or unsupported protocol version (4);
+    /// supported versions are (3/v3)`, or from `PROTOCOL_VERSIONS` option, e.g. `3/v3`.
+    pub fn parse_supported(message: &str) -> Vec<u8> {
+        message.split(|c: char| c == '(' || c == ')' || c == ',' || c.is_whitespace())
+            .filter(|item| item.contains('/'))
+            .filter_map(|item| {
+                item.split('/').next().and_then(|number| number.parse::<u8>().ok())
+            })
+            .collect()
+    }
+
+    /// Returns the highest protocol version lower than this one which is supported by both
+    /// the driver and a server. Versions supported by the server are parsed from
+    /// the `message` of a protocol error. If the message does not contain supported
+    /// versions the next lower version is returned.
+    pub fn downgrade(&self, message: &str) -> Option<ProtocolVersion> {
+        let server_versions = ProtocolVersion::parse_supported(message);
+        ProtocolVersion::all()
+            .iter()
+            .filter(|version| *version < self)
+            .filter(|version| {
+                server_versions.is_empty() || server_versions.contains(&version.as_byte())
+            })
+            .cloned()
+            .next()
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> ProtocolVersion {
+        ProtocolVersion::V4
+    }
+}
+
+impl AsByte for ProtocolVersion {
+    fn as_byte(&self) -> u8 {
+        match self {
+            &ProtocolVersion::V3 => 0x03,
+            &ProtocolVersion::V4 => 0x04,
+            &ProtocolVersion::V5 => 0x05,
+        }
+    }
+}
+
 /// Frame's flag
 // Is not implemented functionality. Only Igonore works for now
 #[derive(Debug, PartialEq)]
@@ -200,6 +380,59 @@ impl Flag {
     }
 }
 
+/// Set of frame flags represented as a single flags byte.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FrameFlags(u8);
+
+impl FrameFlags {
+    /// Frame body is compressed.
+    pub const COMPRESSION: FrameFlags = FrameFlags(0x01);
+    /// Request tracing / response contains tracing id.
+    pub const TRACING: FrameFlags = FrameFlags(0x02);
+    /// Frame contains custom payload.
+    pub const CUSTOM_PAYLOAD: FrameFlags = FrameFlags(0x04);
+    /// Response contains warnings.
+    pub const WARNING: FrameFlags = FrameFlags(0x08);
+
+    /// Returns empty set of flags.
+    pub fn empty() -> FrameFlags {
+        FrameFlags(0)
+    }
+
+    /// Creates flags from flags byte. Unknown bits are ignored by specification.
+    pub fn from_bits(bits: u8) -> FrameFlags {
+        FrameFlags(bits & 0x0F)
+    }
+
+    /// Returns flags byte.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Checks if all of `other` flags are set.
+    pub fn contains(&self, other: FrameFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets `other` flags.
+    pub fn insert(&mut self, other: FrameFlags) {
+        self.0 |= other.0;
+    }
+
+    /// Clears `other` flags.
+    pub fn remove(&mut self, other: FrameFlags) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for FrameFlags {
+    type Output = FrameFlags;
+
+    fn bitor(self, other: FrameFlags) -> FrameFlags {
+        FrameFlags(self.0 | other.0)
+    }
+}
+
 impl AsByte for Flag {
     fn as_byte(&self) -> u8 {
         match self {
@@ -296,6 +529,7 @@ impl From<u8> for Opcode {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use types::from_bytes;
     use AsByte;
 
     #[test]
@@ -306,6 +540,29 @@ mod tests {
         assert_eq!(response_version.as_byte(), 0x84);
     }
 
+    #[test]
+    fn test_protocol_version_downgrade() {
+        let message = "Invalid or unsupported protocol version (4); supported versions are \
+                       (1/v1, 2/v2, 3/v3, 5/v5-beta)";
+        assert_eq!(ProtocolVersion::parse_supported(message), vec![1, 2, 3, 5]);
+        assert_eq!(ProtocolVersion::V4.downgrade(message), Some(ProtocolVersion::V3));
+        assert_eq!(ProtocolVersion::V4.downgrade("Invalid or unsupported protocol version"),
+                   Some(ProtocolVersion::V3));
+        assert_eq!(ProtocolVersion::V4.downgrade("supported versions are (1/v1, 2/v2)"),
+                   None);
+        assert_eq!(ProtocolVersion::V3.downgrade(message), None);
+        assert_eq!(ProtocolVersion::V5.downgrade("supported versions are (3/v3, 4/v4)"),
+                   Some(ProtocolVersion::V4));
+    }
+
+    #[test]
+    fn test_frame_encode_with_protocol() {
+        let encoded = Frame::new_req_options()
+            .encode_with_protocol(Compression::None, 0, ProtocolVersion::V3)
+            .unwrap();
+        assert_eq!(encoded[0], 0x03);
+    }
+
     #[test]
     fn test_frame_version_from() {
         let request: Vec<u8> = vec![0x04];
@@ -368,6 +625,94 @@ mod tests {
         assert_eq!(Flag::get_collection(1), one);
     }
 
+    #[test]
+    fn test_frame_encode_with_threshold() {
+        let body: Vec<u8> = (0..100).map(|i| (i % 10) as u8).collect();
+        let new_frame = |body: Vec<u8>| {
+            Frame {
+                version: Version::Request,
+                flags: vec![],
+                opcode: Opcode::Query,
+                stream: 0,
+                body: body,
+                tracing_id: None,
+                warnings: vec![],
+                custom_payload: None,
+            }
+        };
+
+        let encoded = new_frame(body.clone()).encode_with_threshold(Compression::Lz4, 64).unwrap();
+        assert!(Flag::has_compression(encoded[1]));
+        assert_eq!(Compression::Lz4.decode(encoded[9..].to_vec()).unwrap(), body);
+
+        let small = vec![1, 2, 3];
+        let encoded = new_frame(small.clone()).encode_with_threshold(Compression::Lz4, 64).unwrap();
+        assert!(!Flag::has_compression(encoded[1]));
+        assert_eq!(&encoded[9..], small.as_slice());
+    }
+
+    #[test]
+    fn test_frame_flags() {
+        let all = [FrameFlags::COMPRESSION,
+                   FrameFlags::TRACING,
+                   FrameFlags::CUSTOM_PAYLOAD,
+                   FrameFlags::WARNING];
+        for flag in all.iter() {
+            let parsed = FrameFlags::from_bits(flag.bits());
+            assert_eq!(&parsed, flag);
+            assert_eq!(Flag::many_to_cbytes(&Flag::get_collection(parsed.bits())),
+                       flag.bits());
+        }
+
+        let mut flags = FrameFlags::TRACING | FrameFlags::WARNING;
+        assert!(flags.contains(FrameFlags::TRACING));
+        assert!(!flags.contains(FrameFlags::COMPRESSION));
+        flags.remove(FrameFlags::TRACING);
+        flags.insert(FrameFlags::COMPRESSION);
+        assert_eq!(flags.bits(), 0x01 | 0x08);
+        assert_eq!(FrameFlags::from_bits(0xFF).bits(), 0x0F);
+    }
+
+    #[test]
+    fn test_frame_with_flag() {
+        let frame = Frame {
+                version: Version::Request,
+                flags: vec![],
+                opcode: Opcode::Query,
+                stream: 0,
+                body: vec![],
+                tracing_id: None,
+                warnings: vec![],
+                custom_payload: None,
+            }
+            .with_flag(FrameFlags::TRACING)
+            .with_flag(FrameFlags::WARNING);
+
+        assert_eq!(frame.flags, vec![Flag::Tracing, Flag::Warning]);
+        assert_eq!(frame.frame_flags(), FrameFlags::TRACING | FrameFlags::WARNING);
+        assert_eq!(frame.into_cbytes()[1], 0x02 | 0x08);
+    }
+
+    #[test]
+    fn test_frame_custom_payload() {
+        let mut payload = HashMap::new();
+        payload.insert("key".to_string(), vec![1, 2, 3]);
+        let payload_bytes = CBytesMap::new(payload.clone()).into_cbytes();
+        let new_frame = || Frame::new_req_options().with_custom_payload(payload.clone());
+
+        let mut expected = payload_bytes.clone();
+        expected.extend_from_slice(new_frame().body.as_slice());
+        assert_eq!(&new_frame().into_cbytes()[9..], expected.as_slice());
+        let encoded = new_frame()
+            .encode_with_protocol(Compression::None, 0, ProtocolVersion::V4)
+            .unwrap();
+        assert_eq!(encoded, new_frame().into_cbytes());
+        assert_eq!(from_bytes(&encoded[5..9]) as usize, expected.len());
+
+        let v3 = new_frame().encode_with_protocol(Compression::None, 0, ProtocolVersion::V3);
+        assert!(v3.is_err());
+    }
+
     #[test]
     fn test_opcode_as_byte() {
         assert_eq!(Opcode::Error.as_byte(), 0x00);
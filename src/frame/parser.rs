@@ -1,93 +1,421 @@
-use std::io::{Read, Cursor};
+use std::collections::HashMap;
+use std::io::{self, Read, Cursor};
+use uuid::Uuid;
 
-use FromCursor;
+use TryFromCursor;
 use compression::Compression;
-use frame::frame_response::ResponseBody;
+use frame::frame_error::CDRSError;
 use super::*;
-use types::{from_bytes, UUID_LEN, CStringList};
+use types::{from_bytes, UUID_LEN, CBytesMap, CStringList};
 use types::data_serialization_types::decode_timeuuid;
 use error;
 
-pub fn parse_frame(mut cursor: &mut Read, compressor: &Compression) -> error::Result<Frame> {
-    // TODO: try to use slices instead
-    let mut version_bytes = [0; VERSION_LEN];
-    let mut flag_bytes = [0; FLAG_LEN];
-    let mut opcode_bytes = [0; OPCODE_LEN];
-    let mut stream_bytes = [0; STREAM_LEN];
-    let mut length_bytes = [0; LENGTH_LEN];
-
-    // NOTE: order of reads matters
-    try!(cursor.read(&mut version_bytes));
-    try!(cursor.read(&mut flag_bytes));
-    try!(cursor.read(&mut stream_bytes));
-    try!(cursor.read(&mut opcode_bytes));
-    try!(cursor.read(&mut length_bytes));
-
-    let version = Version::from(version_bytes.to_vec());
-    let flags = Flag::get_collection(flag_bytes[0]);
-    let stream = from_bytes(stream_bytes.to_vec().as_slice());
-    let opcode = Opcode::from(opcode_bytes[0]);
-    let length = from_bytes(length_bytes.to_vec().as_slice()) as usize;
-
-    let mut body_bytes = Vec::with_capacity(length);
-    unsafe {
-        body_bytes.set_len(length);
-    }
-    try!(cursor.read_exact(&mut body_bytes));
-
-    let full_body = if flags.iter().any(|flag| flag == &Flag::Compression) {
-        try!(compressor.decode(body_bytes))
-    } else {
-        try!(Compression::None.decode(body_bytes))
-    };
+pub fn parse_frame(cursor: &mut Read, compressor: &Compression) -> error::Result<Frame> {
+    parse_raw_frame(cursor, compressor).and_then(convert_frame_into_result)
+}
 
-    // Use cursor to get tracing id, warnings and actual body
-    let mut body_cursor = Cursor::new(full_body.as_slice());
+/// Parses a frame the same way `parse_frame` does but it doesn't convert frames
+/// of type `Error` into `error::Error::Server`.
+pub fn parse_raw_frame(cursor: &mut Read, compressor: &Compression) -> error::Result<Frame> {
+    FrameCodec::new().read_raw_frame(cursor, compressor)
+}
 
-    let tracing_id = if flags.iter().any(|flag| flag == &Flag::Tracing) {
-        let mut tracing_bytes = Vec::with_capacity(UUID_LEN);
-        unsafe {
-            tracing_bytes.set_len(UUID_LEN);
+/// Default maximal length of a frame body which matches the default value of Cassandra's
+/// `native_transport_max_frame_size_in_mb` (256 MB).
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+// Capacity of a codec's buffer which is kept after reading a larger frame (64 KB).
+const MAX_RETAINED_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reader of frames which keeps a buffer of frame bodies between reads. The buffer
+/// grows up to the size of the largest frame which has been read, so reading a frame
+/// allocates nothing but a body of the resulting frame. A codec is kept per connection.
+/// After a frame larger than 64 KB the buffer is shrunk back, so a single large
+/// response doesn't keep its memory for the lifetime of a connection.
+///
+/// Frames which bodies are longer than `max_frame_size` are rejected before
+/// anything is allocated for them. It applies to decompressed bodies as well.
+/// Error bodies are decoded in accordance with a protocol version of the codec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameCodec {
+    buffer: Vec<u8>,
+    max_frame_size: usize,
+    protocol_version: ProtocolVersion,
+}
+
+impl Default for FrameCodec {
+    fn default() -> FrameCodec {
+        FrameCodec::new()
+    }
+}
+
+impl FrameCodec {
+    pub fn new() -> FrameCodec {
+        FrameCodec::with_max_frame_size(DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Creates a codec which rejects frames with bodies longer than `max_frame_size` bytes.
+    pub fn with_max_frame_size(max_frame_size: usize) -> FrameCodec {
+        FrameCodec {
+            buffer: vec![],
+            max_frame_size: max_frame_size,
+            protocol_version: ProtocolVersion::default(),
         }
-        // TODO: try to use slice instead
+    }
+
+    /// Returns maximal length of a frame body in bytes.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Sets maximal length of a frame body in bytes.
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /// Returns version of native protocol which error bodies are decoded with.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Sets version of native protocol which is negotiated by a connection.
+    pub fn set_protocol_version(&mut self, protocol_version: ProtocolVersion) {
+        self.protocol_version = protocol_version;
+    }
+
+    /// Reads a frame the same way `parse_frame` does.
+    pub fn read_frame(&mut self,
+                      cursor: &mut Read,
+                      compressor: &Compression)
+                      -> error::Result<Frame> {
+        let protocol = self.protocol_version;
+        self.read_raw_frame(cursor, compressor)
+            .and_then(|frame| convert_frame_into_result_with_protocol(frame, protocol))
+    }
+
+    /// Reads a frame the same way `parse_raw_frame` does.
+    pub fn read_raw_frame(&mut self,
+                          cursor: &mut Read,
+                          compressor: &Compression)
+                          -> error::Result<Frame> {
+        // Header is read exactly, so a closed connection is reported
+        // as an `UnexpectedEof` error rather than a malformed frame.
+        let mut header = [0; HEADER_LEN];
+        try!(cursor.read_exact(&mut header));
+
+        let version = Version::from(header[0]);
+        let flags = Flag::get_collection(header[VERSION_LEN]);
+        let stream_start = VERSION_LEN + FLAG_LEN;
+        let stream = from_bytes(&header[stream_start..stream_start + STREAM_LEN]);
+        let opcode = Opcode::from(header[stream_start + STREAM_LEN]);
+        let length = from_bytes(&header[HEADER_LEN - LENGTH_LEN..]) as usize;
+        // the rest of the frame is not read, so the connection should not be used anymore
+        try!(self.check_body_length(length));
+
+        if self.buffer.len() < length {
+            self.buffer.resize(length, 0);
+        }
+        let result = cursor.read_exact(&mut self.buffer[..length])
+            .map_err(error::Error::from)
+            .and_then(|_| self.decode_body(&flags, &self.buffer[..length], compressor));
+        self.shrink_buffer();
+        let (prefix, body) = try!(result);
+
+        Ok(Frame {
+            version: version,
+            flags: flags,
+            opcode: opcode,
+            stream: stream,
+            body: body,
+            tracing_id: prefix.tracing_id,
+            warnings: prefix.warnings,
+            custom_payload: prefix.custom_payload,
+        })
+    }
+
+    /// Decodes a frame from the beginning of `buffer` if the whole frame has been
+    /// received. It returns the frame along with a number of bytes it occupies or `None`
+    /// if more bytes are needed, so the same codec can be used by non-blocking connections.
+    /// Frames of type `Error` are not converted into `error::Error::Server`.
+    pub fn decode_raw_frame(&mut self,
+                            buffer: &[u8],
+                            compressor: &Compression)
+                            -> error::Result<Option<(Frame, usize)>> {
+        if buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let length = from_bytes(&buffer[HEADER_LEN - LENGTH_LEN..HEADER_LEN]) as usize;
+        try!(self.check_body_length(length));
+        if buffer.len() < HEADER_LEN + length {
+            return Ok(None);
+        }
+
+        let mut cursor = Cursor::new(&buffer[..HEADER_LEN + length]);
+        let frame = try!(self.read_raw_frame(&mut cursor, compressor));
+        Ok(Some((frame, HEADER_LEN + length)))
+    }
+
+    // Decompresses a full body if needed and splits it into a prefix and an actual body.
+    // A length of a decompressed body is checked before it is decompressed.
+    fn decode_body(&self,
+                   flags: &[Flag],
+                   full_body: &[u8],
+                   compressor: &Compression)
+                   -> error::Result<(BodyPrefix, Vec<u8>)> {
+        let compressed = flags.iter().any(|flag| flag == &Flag::Compression);
+        if compressed {
+            try!(self.check_body_length(try!(compressor.decoded_len(full_body))));
+            let mut full_body = try!(compressor.decode_slice(full_body));
+            let prefix = try!(parse_body_prefix(flags, full_body.as_slice()));
+            full_body.drain(..prefix.offset);
+            Ok((prefix, full_body))
+        } else {
+            let prefix = try!(parse_body_prefix(flags, full_body));
+            let body = full_body[prefix.offset..].to_vec();
+            Ok((prefix, body))
+        }
+    }
+
+    // Releases memory of the buffer which has grown for a large frame.
+    fn shrink_buffer(&mut self) {
+        if self.buffer.len() > MAX_RETAINED_BUFFER_SIZE {
+            self.buffer.truncate(MAX_RETAINED_BUFFER_SIZE);
+            self.buffer.shrink_to_fit();
+        }
+    }
+
+    fn check_body_length(&self, length: usize) -> error::Result<()> {
+        if length > self.max_frame_size {
+            let message = format!("Frame body of {} bytes exceeds maximal frame size of {} bytes",
+                                  length,
+                                  self.max_frame_size);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        }
+
+        Ok(())
+    }
+}
+
+// Tracing id, warnings and custom payload which precede an actual body of a response.
+struct BodyPrefix {
+    tracing_id: Option<Uuid>,
+    warnings: Vec<String>,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
+    // offset of the actual body
+    offset: usize,
+}
+
+// Reads tracing id, warnings and custom payload which precede an actual body
+// in this order.
+fn parse_body_prefix(flags: &[Flag], full_body: &[u8]) -> error::Result<BodyPrefix> {
+    let mut body_cursor = Cursor::new(full_body);
+
+    let tracing_id = if flags.iter().any(|flag| flag == &Flag::Tracing) {
+        let mut tracing_bytes = [0; UUID_LEN];
         try!(body_cursor.read_exact(&mut tracing_bytes));
 
-        decode_timeuuid(tracing_bytes.as_slice()).ok()
+        decode_timeuuid(&tracing_bytes).ok()
     } else {
         None
     };
 
     let warnings = if flags.iter().any(|flag| flag == &Flag::Warning) {
-        CStringList::from_cursor(&mut body_cursor).into_plain()
+        try!(CStringList::try_from_cursor(&mut body_cursor)).into_plain()
     } else {
         vec![]
     };
 
-    let mut body = vec![];
-
-    try!(body_cursor.read_to_end(&mut body));
+    let custom_payload = if flags.iter().any(|flag| flag == &Flag::CustomPayload) {
+        Some(try!(CBytesMap::try_from_cursor(&mut body_cursor)).into_plain())
+    } else {
+        None
+    };
 
-    let frame = Frame {
-        version: version,
-        flags: flags,
-        opcode: opcode,
-        stream: stream,
-        body: body,
+    Ok(BodyPrefix {
         tracing_id: tracing_id,
         warnings: warnings,
-    };
+        custom_payload: custom_payload,
+        offset: body_cursor.position() as usize,
+    })
+}
 
-    return conver_frame_into_result(frame);
+/// Converts a frame of type `Error` into `error::Error::Server`.
+pub fn convert_frame_into_result(frame: Frame) -> error::Result<Frame> {
+    convert_frame_into_result_with_protocol(frame, ProtocolVersion::default())
 }
 
-fn conver_frame_into_result(frame: Frame) -> error::Result<Frame> {
+/// Converts a frame the same way `convert_frame_into_result` does but decodes
+/// an error body in accordance with provided version of native protocol.
+pub fn convert_frame_into_result_with_protocol(frame: Frame,
+                                               protocol: ProtocolVersion)
+                                               -> error::Result<Frame> {
     match frame.opcode {
         Opcode::Error => {
-            match frame.get_body() {
-                ResponseBody::Error(err) => Err(error::Error::Server(err)),
-                _ => unreachable!(),
-            }
+            // a truncated error body results in an error rather than a panic
+            let mut cursor: Cursor<&[u8]> = Cursor::new(frame.body.as_slice());
+            Err(match CDRSError::try_from_cursor_with_protocol(&mut cursor, protocol) {
+                Ok(err) => error::Error::Server(err),
+                Err(err) => err,
+            })
         }
         _ => Ok(frame),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use compression::Compression;
+    use uuid::Uuid;
+    use types::to_int;
+    use super::*;
+
+    #[test]
+    fn parse_frame_with_warnings() {
+        let mut bytes = vec![0x84, 0x08, 0, 0, 0x08, 0, 0, 0, 16];
+        // warnings [string list]
+        bytes.extend_from_slice(&[0, 2, 0, 3, b'f', b'o', b'o', 0, 3, b'b', b'a', b'r']);
+        // void result
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+
+        let frame = parse_frame(&mut Cursor::new(bytes), &Compression::None).unwrap();
+        assert_eq!(frame.warnings(),
+                   Some(vec!["foo".to_string(), "bar".to_string()]));
+        assert_eq!(frame.body, vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn parse_frame_with_tracing_id() {
+        let mut bytes = vec![0x84, 0x02, 0, 0, 0x08, 0, 0, 0, 20];
+        // tracing id 13814000-1dd2-11b2-8000-000000000000
+        bytes.extend_from_slice(&[0x13, 0x81, 0x40, 0x00, 0x1D, 0xD2, 0x11, 0xB2, 0x80, 0, 0, 0,
+                                  0, 0, 0, 0]);
+        // void result
+        bytes.extend_from_slice(&[0, 0, 0, 1]);
+
+        let frame = parse_frame(&mut Cursor::new(bytes), &Compression::None).unwrap();
+        assert_eq!(frame.tracing_id(),
+                   Uuid::parse_str("13814000-1dd2-11b2-8000-000000000000").ok());
+        assert_eq!(frame.body, vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn parse_frame_without_warnings() {
+        let bytes = vec![0x84, 0x00, 0, 0, 0x08, 0, 0, 0, 4, 0, 0, 0, 1];
+        let frame = parse_frame(&mut Cursor::new(bytes), &Compression::None).unwrap();
+        assert_eq!(frame.warnings(), None);
+    }
+
+    #[test]
+    fn frame_codec_reuses_buffer() {
+        let mut bytes = vec![0x84, 0x00, 0, 1, 0x08, 0, 0, 0, 8, 0, 0, 0, 3, 0, 2, b'k', b's'];
+        bytes.extend_from_slice(&[0x84, 0x00, 0, 2, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]);
+        // compressed frame with warnings
+        let mut body = vec![0, 1, 0, 3, b'f', b'o', b'o', 0, 0, 0, 1];
+        body = Compression::Lz4.encode(body).unwrap();
+        bytes.extend_from_slice(&[0x84, 0x09, 0, 3, 0x08, 0, 0, 0, body.len() as u8]);
+        bytes.extend_from_slice(body.as_slice());
+        let mut cursor = Cursor::new(bytes);
+        let mut codec = FrameCodec::new();
+
+        let frame = codec.read_frame(&mut cursor, &Compression::Lz4).unwrap();
+        assert_eq!((frame.stream, frame.body), (1, vec![0, 0, 0, 3, 0, 2, b'k', b's']));
+        let buffer = codec.buffer.as_ptr();
+
+        let frame = codec.read_frame(&mut cursor, &Compression::Lz4).unwrap();
+        assert_eq!((frame.stream, frame.body), (2, vec![0, 0, 0, 1]));
+        // the buffer is not reallocated for smaller frames
+        assert_eq!(codec.buffer.as_ptr(), buffer);
+
+        let frame = codec.read_frame(&mut cursor, &Compression::Lz4).unwrap();
+        assert_eq!(frame.warnings(), Some(vec!["foo".to_string()]));
+        assert_eq!((frame.stream, frame.body), (3, vec![0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn frame_codec_rejects_oversized_frame() {
+        // header of a frame with 1 GB body which is not followed by the body
+        let bytes = vec![0x84, 0x00, 0, 1, 0x08, 0x40, 0, 0, 0];
+        let mut codec = FrameCodec::new();
+        match codec.read_frame(&mut Cursor::new(bytes), &Compression::None) {
+            Err(error::Error::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(codec.buffer.capacity() < 1024);
+
+        let bytes = vec![0x84, 0x00, 0, 1, 0x08, 0, 0, 0, 5, 0, 0, 0, 1, 0];
+        let mut codec = FrameCodec::with_max_frame_size(4);
+        assert!(codec.read_frame(&mut Cursor::new(bytes.clone()), &Compression::None).is_err());
+        codec.set_max_frame_size(5);
+        assert!(codec.read_frame(&mut Cursor::new(bytes), &Compression::None).is_ok());
+    }
+
+    #[test]
+    fn frame_codec_rejects_oversized_decompressed_frame() {
+        for compression in &[Compression::Lz4, Compression::Snappy] {
+            let body = compression.encode(vec![0; 1024]).unwrap();
+            let mut bytes = vec![0x84, 0x01, 0, 1, 0x08];
+            bytes.extend_from_slice(&to_int(body.len() as i32));
+            bytes.extend_from_slice(body.as_slice());
+            let mut codec = FrameCodec::with_max_frame_size(512);
+            assert!(body.len() < 512);
+
+            match codec.read_frame(&mut Cursor::new(bytes.clone()), compression) {
+                Err(error::Error::Io(ref err)) => {
+                    assert_eq!(err.kind(), io::ErrorKind::InvalidData)
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+            codec.set_max_frame_size(1024);
+            assert!(codec.read_frame(&mut Cursor::new(bytes), compression).is_ok());
+        }
+    }
+
+    #[test]
+    fn frame_codec_shrinks_buffer_after_large_frame() {
+        let length = MAX_RETAINED_BUFFER_SIZE + 4;
+        let mut bytes = vec![0x84, 0x00, 0, 1, 0x08];
+        bytes.extend_from_slice(&to_int(length as i32));
+        bytes.extend_from_slice(&to_int(0x0001));
+        bytes.resize(HEADER_LEN + length, 0);
+        bytes.extend_from_slice(&[0x84, 0x00, 0, 2, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]);
+        let mut cursor = Cursor::new(bytes);
+        let mut codec = FrameCodec::new();
+
+        let frame = codec.read_frame(&mut cursor, &Compression::None).unwrap();
+        assert_eq!(frame.body.len(), length);
+        assert!(codec.buffer.capacity() <= MAX_RETAINED_BUFFER_SIZE);
+
+        let frame = codec.read_frame(&mut cursor, &Compression::None).unwrap();
+        assert_eq!((frame.stream, frame.body), (2, vec![0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn frame_codec_rejects_truncated_warnings() {
+        // warnings flag is set but the list declares 2 strings and contains 1
+        let bytes = vec![0x84, 0x08, 0, 0, 0x08, 0, 0, 0, 7, 0, 2, 0, 3, b'f', b'o', b'o'];
+        let mut codec = FrameCodec::new();
+        assert!(codec.read_frame(&mut Cursor::new(bytes), &Compression::None).is_err());
+    }
+
+    #[test]
+    fn decode_partially_received_frames() {
+        let void = vec![0x84, 0, 0, 0x05, 0x08, 0, 0, 0, 4, 0, 0, 0, 1];
+        let mut buffer = void.clone();
+        buffer.extend_from_slice(&void[..5]);
+
+        let mut codec = FrameCodec::new();
+        assert!(codec.decode_raw_frame(&buffer[..8], &Compression::None).unwrap().is_none());
+        assert!(codec.decode_raw_frame(&buffer[..12], &Compression::None).unwrap().is_none());
+        let (frame, len) = codec.decode_raw_frame(&buffer, &Compression::None).unwrap().unwrap();
+        assert_eq!(len, void.len());
+        assert_eq!(frame.stream, 5);
+        assert_eq!(frame.opcode, Opcode::Result);
+        assert_eq!(frame.body, vec![0, 0, 0, 1]);
+        assert!(codec.decode_raw_frame(&buffer[len..], &Compression::None).unwrap().is_none());
+
+        let mut small = FrameCodec::with_max_frame_size(3);
+        assert!(small.decode_raw_frame(&void[..HEADER_LEN], &Compression::None).is_err());
+    }
+}
@@ -8,21 +8,46 @@ extern crate lz4_compress;
 extern crate uuid;
 #[cfg(feature = "ssl")]
 extern crate openssl;
+#[cfg(feature = "rust-tls")]
+extern crate rustls;
+#[cfg(feature = "rust-tls")]
+extern crate webpki;
 extern crate r2d2;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "num-bigint")]
+extern crate num_bigint;
+#[cfg(feature = "tokio")]
+extern crate futures;
+#[cfg(feature = "tokio")]
+extern crate tokio_io;
+#[cfg(feature = "tokio")]
+extern crate bytes;
 
 use std::io::Cursor;
 
 pub mod frame;
 pub mod types;
 
+#[cfg(feature = "tokio")]
+pub mod async_session;
 pub mod authenticators;
 pub mod client;
+pub mod cluster;
 pub mod compression;
 pub mod connection_manager;
 pub mod consistency;
 pub mod error;
 pub mod events;
+pub mod load_balancing;
+pub mod metrics;
+pub mod multiplexer;
+pub mod murmur3;
 pub mod query;
+pub mod reconnection_policy;
+pub mod retry_policy;
+pub mod speculative_execution;
+pub mod timestamp_generator;
 pub mod transport;
 
 
@@ -57,3 +82,11 @@ pub trait FromCursor {
     /// It should return an implementor from an `io::Cursor` over an array of bytes.
     fn from_cursor(&mut Cursor<&[u8]>) -> Self;
 }
+
+/// `TryFromCursor` is a fallible version of `FromCursor`. It should return an error
+/// rather than panic if a cursor does not contain enough bytes, e.g. a frame is truncated.
+pub trait TryFromCursor: Sized {
+    /// It should return an implementor from an `io::Cursor` or an error if bytes
+    /// are malformed.
+    fn try_from_cursor(&mut Cursor<&[u8]>) -> error::Result<Self>;
+}
@@ -0,0 +1,399 @@
+//! The module contains load balancing policies which define a node of a cluster
+//! a request should be sent to.
+//!
+//! A policy produces a query plan, i.e. nodes in order they should be tried.
+//! `Session::connect` goes through the plan and moves to the next node if
+//! connection to a node fails.
+use std::net;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use error;
+use murmur3::murmur3_token;
+use types::{AsRust, IntoRustByName};
+use types::list::List;
+use types::rows::Row;
+
+/// Query of a token ring information about a node a session is connected to.
+pub const LOCAL_TOKENS_QUERY: &'static str = "SELECT rpc_address, data_center, tokens \
+                                              FROM system.local";
+
+/// Query of a token ring information about other nodes of a cluster.
+pub const PEERS_TOKENS_QUERY: &'static str = "SELECT rpc_address, data_center, tokens \
+                                              FROM system.peers";
+
+/// Node of Cassandra cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// Address clients should connect to.
+    pub address: net::IpAddr,
+    /// Data center the node belongs to.
+    pub data_center: String,
+}
+
+impl Node {
+    pub fn new(address: net::IpAddr, data_center: String) -> Node {
+        Node {
+            address: address,
+            data_center: data_center,
+        }
+    }
+}
+
+/// Token ring of a cluster which maps tokens to nodes owning them.
+#[derive(Debug, Clone)]
+pub struct TokenRing {
+    nodes: Vec<Node>,
+    // tokens sorted in ascending order along with indexes of their owners in `nodes`
+    tokens: Vec<(i64, usize)>,
+}
+
+impl TokenRing {
+    /// Creates new token ring from nodes and tokens owned by them.
+    pub fn new(owners: Vec<(Node, Vec<i64>)>) -> TokenRing {
+        let mut nodes = vec![];
+        let mut tokens = vec![];
+        for (node, node_tokens) in owners {
+            tokens.extend(node_tokens.into_iter().map(|token| (token, nodes.len())));
+            nodes.push(node);
+        }
+        tokens.sort();
+
+        TokenRing {
+            nodes: nodes,
+            tokens: tokens,
+        }
+    }
+
+    /// Creates new token ring from rows returned by `LOCAL_TOKENS_QUERY` and
+    /// `PEERS_TOKENS_QUERY`.
+    pub fn from_rows(rows: Vec<Row>) -> error::Result<TokenRing> {
+        let mut owners = vec![];
+        for row in rows {
+            owners.push(try!(TokenRing::parse_row(&row)));
+        }
+
+        Ok(TokenRing::new(owners))
+    }
+
+    /// Parses a node and tokens owned by it from a row returned by `LOCAL_TOKENS_QUERY`
+    /// or `PEERS_TOKENS_QUERY`.
+    pub fn parse_row(row: &Row) -> error::Result<(Node, Vec<i64>)> {
+        let address: net::IpAddr = try!(required_column(row, "rpc_address"));
+        let data_center: String = try!(required_column(row, "data_center"));
+        let tokens_list: List = try!(required_column(row, "tokens"));
+        let tokens: Vec<String> = try!(tokens_list.as_rust());
+
+        let mut parsed = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match token.parse::<i64>() {
+                Ok(token) => parsed.push(token),
+                Err(_) => {
+                    let msg = format!("Token {} of {} is not a Murmur3 one", token, address);
+                    return Err(error::Error::General(msg));
+                }
+            }
+        }
+
+        Ok((Node::new(address, data_center), parsed))
+    }
+
+    /// Returns all nodes of the ring.
+    pub fn nodes(&self) -> &[Node] {
+        self.nodes.as_slice()
+    }
+
+    /// Returns a node which owns provided token, i.e. the node with the smallest token
+    /// which is greater or equal to provided one. The ring wraps around, so the node
+    /// with the smallest token owns tokens greater than the biggest one.
+    pub fn replica(&self, token: i64) -> Option<&Node> {
+        if self.tokens.is_empty() {
+            return None;
+        }
+
+        let i = match self.tokens.binary_search_by(|&(t, _)| t.cmp(&token)) {
+            Ok(i) => i,
+            Err(i) => i % self.tokens.len(),
+        };
+
+        Some(&self.nodes[self.tokens[i].1])
+    }
+
+    /// Returns a node which owns a partition with provided routing (partition) key.
+    pub fn replica_for_key(&self, routing_key: &[u8]) -> Option<&Node> {
+        self.replica(murmur3_token(routing_key))
+    }
+}
+
+fn required_column<T>(row: &Row, name: &str) -> error::Result<T>
+    where Row: IntoRustByName<T>
+{
+    match row.get_by_name(name) {
+        Some(value) => value,
+        None => Err(error::Error::General(format!("Column {} is not found", name))),
+    }
+}
+
+/// Nodes in order they should be tried by a request.
+pub type QueryPlan<'a> = Box<Iterator<Item = &'a Node> + 'a>;
+
+/// Load balancing policy defines which nodes and in which order should be tried
+/// by a request. `routing_key` is a serialized partition key of a request if it's known.
+pub trait LoadBalancingPolicy: Send + Sync {
+    /// Returns a query plan of a request.
+    fn query_plan<'a>(&'a self, routing_key: Option<&[u8]>) -> QueryPlan<'a>;
+}
+
+// Returns all `nodes` starting from the one with index `start` (modulo length).
+fn rotated<'a>(nodes: &'a [Node], start: usize) -> QueryPlan<'a> {
+    let len = nodes.len();
+    Box::new(nodes.iter().cycle().skip(if len == 0 { 0 } else { start % len }).take(len))
+}
+
+/// Load balancing policy which tries all nodes starting from the next one
+/// with each query plan.
+#[derive(Debug)]
+pub struct RoundRobin {
+    nodes: Vec<Node>,
+    next: AtomicUsize,
+}
+
+impl RoundRobin {
+    pub fn new(nodes: Vec<Node>) -> RoundRobin {
+        RoundRobin {
+            nodes: nodes,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl LoadBalancingPolicy for RoundRobin {
+    fn query_plan<'a>(&'a self, _routing_key: Option<&[u8]>) -> QueryPlan<'a> {
+        rotated(self.nodes.as_slice(),
+                self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Load balancing policy which tries nodes of a local data center in round-robin
+/// manner. Nodes of remote data centers are tried (in round-robin manner as well)
+/// only after all local ones.
+#[derive(Debug)]
+pub struct DcAwareRoundRobin {
+    local: Vec<Node>,
+    remote: Vec<Node>,
+    next: AtomicUsize,
+}
+
+impl DcAwareRoundRobin {
+    pub fn new(nodes: Vec<Node>, local_dc: &str) -> DcAwareRoundRobin {
+        let (local, remote) = nodes.into_iter().partition(|node| node.data_center == local_dc);
+        DcAwareRoundRobin {
+            local: local,
+            remote: remote,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl LoadBalancingPolicy for DcAwareRoundRobin {
+    fn query_plan<'a>(&'a self, _routing_key: Option<&[u8]>) -> QueryPlan<'a> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        Box::new(rotated(self.local.as_slice(), start)
+            .chain(rotated(self.remote.as_slice(), start)))
+    }
+}
+
+/// Load balancing policy which sends requests directly to a replica owning a partition
+/// when a routing key is known. Otherwise nodes of the ring are selected in round-robin
+/// manner.
+#[derive(Debug)]
+pub struct TokenAwareLoadBalancing {
+    ring: TokenRing,
+    next: AtomicUsize,
+}
+
+impl TokenAwareLoadBalancing {
+    pub fn new(ring: TokenRing) -> TokenAwareLoadBalancing {
+        TokenAwareLoadBalancing {
+            ring: ring,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a token ring the policy is based on.
+    pub fn ring(&self) -> &TokenRing {
+        &self.ring
+    }
+
+    /// Selects a node a request with provided routing key should be sent to.
+    /// It returns `None` only if the ring is empty.
+    pub fn select(&self, routing_key: Option<&[u8]>) -> Option<&Node> {
+        if let Some(node) = routing_key.and_then(|key| self.ring.replica_for_key(key)) {
+            return Some(node);
+        }
+
+        let nodes = self.ring.nodes();
+        if nodes.is_empty() {
+            return None;
+        }
+
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(&nodes[i % nodes.len()])
+    }
+}
+
+impl LoadBalancingPolicy for TokenAwareLoadBalancing {
+    /// A replica owning a partition goes first if the routing key is provided,
+    /// other nodes follow in round-robin manner.
+    fn query_plan<'a>(&'a self, routing_key: Option<&[u8]>) -> QueryPlan<'a> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let nodes = rotated(self.ring.nodes(), start);
+        match routing_key.and_then(|key| self.ring.replica_for_key(key)) {
+            Some(replica) => {
+                Box::new(Some(replica).into_iter().chain(nodes.filter(move |n| *n != replica)))
+            }
+            None => nodes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use frame::frame_result::BodyResResultRows;
+    use types::{to_int, to_short, CBytes, CString};
+    use {FromCursor, IntoBytes};
+    use super::*;
+
+    fn node(last_octet: u8) -> Node {
+        Node::new(net::IpAddr::V4(net::Ipv4Addr::new(10, 0, 0, last_octet)),
+                  "dc1".to_string())
+    }
+
+    fn ring() -> TokenRing {
+        TokenRing::new(vec![(node(1), vec![-100, 100]), (node(2), vec![0, 200])])
+    }
+
+    // Builds rows as returned by `LOCAL_TOKENS_QUERY` or `PEERS_TOKENS_QUERY`.
+    fn tokens_rows(nodes: &[(u8, &[&str])]) -> Vec<Row> {
+        let mut body = to_int(0x0001);
+        body.extend_from_slice(to_int(3).as_slice());
+        for name in &["system", "peers"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns: [(&str, &[i16]); 3] = [("rpc_address", &[0x0010]),
+                                            ("data_center", &[0x000D]),
+                                            ("tokens", &[0x0022, 0x000D])];
+        for &(name, types) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            for col_type in types {
+                body.extend_from_slice(to_short(*col_type).as_slice());
+            }
+        }
+
+        body.extend_from_slice(to_int(nodes.len() as i32).as_slice());
+        for &(last_octet, tokens) in nodes {
+            let mut set = to_int(tokens.len() as i32);
+            for token in tokens {
+                set.extend_from_slice(CBytes::new(token.as_bytes().to_vec())
+                    .into_cbytes()
+                    .as_slice());
+            }
+            let values = vec![vec![10, 0, 0, last_octet], b"dc1".to_vec(), set];
+            for value in values {
+                body.extend_from_slice(CBytes::new(value).into_cbytes().as_slice());
+            }
+        }
+
+        let mut cursor = Cursor::new(body.as_slice());
+        Row::from_frame_body(BodyResResultRows::from_cursor(&mut cursor))
+    }
+
+    #[test]
+    fn replica() {
+        let ring = ring();
+        assert_eq!(ring.replica(-200), Some(&node(1)));
+        assert_eq!(ring.replica(-100), Some(&node(1)));
+        assert_eq!(ring.replica(-99), Some(&node(2)));
+        assert_eq!(ring.replica(50), Some(&node(1)));
+        assert_eq!(ring.replica(150), Some(&node(2)));
+        // wraps around
+        assert_eq!(ring.replica(201), Some(&node(1)));
+        assert_eq!(TokenRing::new(vec![]).replica(0), None);
+    }
+
+    #[test]
+    fn from_rows() {
+        let ring = TokenRing::from_rows(tokens_rows(&[(1, &["-100", "100"]),
+                                                      (2, &["0", "200"])]))
+            .unwrap();
+        assert_eq!(ring.nodes(), &[node(1), node(2)]);
+        assert_eq!(ring.replica(-99), Some(&node(2)));
+        assert_eq!(ring.replica(201), Some(&node(1)));
+
+        assert!(TokenRing::from_rows(tokens_rows(&[(1, &["abc"])])).is_err());
+    }
+
+    fn plan(policy: &LoadBalancingPolicy, routing_key: Option<&[u8]>) -> Vec<Node> {
+        policy.query_plan(routing_key).cloned().collect()
+    }
+
+    #[test]
+    fn round_robin() {
+        let policy = RoundRobin::new(vec![node(1), node(2), node(3)]);
+        assert_eq!(plan(&policy, None), vec![node(1), node(2), node(3)]);
+        assert_eq!(plan(&policy, None), vec![node(2), node(3), node(1)]);
+        assert_eq!(plan(&policy, None), vec![node(3), node(1), node(2)]);
+        assert_eq!(plan(&policy, None), vec![node(1), node(2), node(3)]);
+
+        assert_eq!(plan(&RoundRobin::new(vec![]), None), vec![]);
+    }
+
+    #[test]
+    fn dc_aware_round_robin() {
+        let mut remote = node(3);
+        remote.data_center = "dc2".to_string();
+        let mut other_remote = node(4);
+        other_remote.data_center = "dc2".to_string();
+        let policy = DcAwareRoundRobin::new(vec![node(1), remote.clone(), node(2),
+                                                 other_remote.clone()],
+                                            "dc1");
+
+        assert_eq!(plan(&policy, None),
+                   vec![node(1), node(2), remote.clone(), other_remote.clone()]);
+        assert_eq!(plan(&policy, None),
+                   vec![node(2), node(1), other_remote.clone(), remote.clone()]);
+
+        // remote nodes only if there are no local ones
+        let policy = DcAwareRoundRobin::new(vec![remote.clone()], "dc1");
+        assert_eq!(plan(&policy, None), vec![remote]);
+    }
+
+    #[test]
+    fn token_aware_query_plan() {
+        let ring = TokenRing::new(vec![(node(1), vec![-100, 100]),
+                                       (node(2), vec![0, 200]),
+                                       (node(3), vec![300])]);
+        let policy = TokenAwareLoadBalancing::new(ring);
+
+        // the replica of int key 1 goes first
+        assert_eq!(plan(&policy, Some(&[0, 0, 0, 1])), vec![node(1), node(2), node(3)]);
+        // other nodes are rotated
+        assert_eq!(plan(&policy, Some(&[0, 0, 0, 1])), vec![node(1), node(2), node(3)]);
+        assert_eq!(plan(&policy, None), vec![node(3), node(1), node(2)]);
+    }
+
+    #[test]
+    fn token_aware_select() {
+        let policy = TokenAwareLoadBalancing::new(ring());
+        // token of int key 1 is -4069959284402364209
+        assert_eq!(policy.select(Some(&[0, 0, 0, 1])), Some(&node(1)));
+        assert_eq!(policy.select(Some(&[0, 0, 0, 1])), Some(&node(1)));
+
+        // round-robin when a routing key is unknown
+        assert_eq!(policy.select(None), Some(&node(1)));
+        assert_eq!(policy.select(None), Some(&node(2)));
+        assert_eq!(policy.select(None), Some(&node(1)));
+
+        assert_eq!(TokenAwareLoadBalancing::new(TokenRing::new(vec![])).select(None), None);
+    }
+}
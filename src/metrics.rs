@@ -0,0 +1,48 @@
+//! The module contains hooks which allow to monitor a session, e.g. to count requests,
+//! written and read bytes, latencies and errors and to export them to a monitoring system.
+use std::io;
+use std::time::Duration;
+
+use frame::Opcode;
+use frame::frame_error::CDRSError;
+
+/// Metrics receive notifications about activity of a session. All methods do nothing
+/// by default, so implementations override only the ones they are interested in.
+pub trait Metrics: Send + Sync {
+    /// Is called when a session over a new connection is started.
+    fn on_connection_opened(&self) {}
+
+    /// Is called when a connection of a session is closed.
+    fn on_connection_closed(&self) {}
+
+    /// Is called before a request is sent. Each call is followed by `on_request_finished`,
+    /// so the number of in-flight requests is the difference between numbers of the calls.
+    fn on_request_started(&self) {}
+
+    /// Is called once a response to a request is received or the request fails.
+    fn on_request_finished(&self) {}
+
+    /// Is called when a request frame of `bytes` length (header included) is sent.
+    fn on_request_sent(&self, _opcode: &Opcode, _bytes: usize) {}
+
+    /// Is called when `bytes` of a response frame (header included) are read,
+    /// before `on_response`. Bytes of frames which fail to be read are reported as well.
+    fn on_bytes_read(&self, _bytes: usize) {}
+
+    /// Is called when a response frame is received. `latency` is time elapsed since
+    /// the request was sent. Error responses are reported with `Opcode::Error`.
+    fn on_response(&self, _latency: Duration, _opcode: &Opcode) {}
+
+    /// Is called when a server responds with an error, after `on_response`.
+    fn on_error(&self, _error: &CDRSError) {}
+
+    /// Is called when a request fails because of IO error. The connection is broken
+    /// after that.
+    fn on_io_error(&self, _error: &io::Error) {}
+}
+
+/// Metrics which ignore all notifications. It's used by a session by default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
@@ -0,0 +1,777 @@
+//! The module contains functionality which allows multiple concurrent requests
+//! to share a single connection. Requests and responses are matched by stream id
+//! of a frame.
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net;
+use std::sync::{Condvar, Mutex, MutexGuard, TryLockError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use compression::Compression;
+use error;
+use frame::{Frame, Opcode};
+use frame::events::EVENT_STREAM_ID;
+use frame::parser::{FrameCodec, convert_frame_into_result};
+use transport::CDRSTransport;
+
+/// Maximal number of in-flight requests per connection supported by
+/// 4-th version of Cassandra protocol.
+pub const MAX_STREAMS: usize = 32768;
+
+/// Allocator of stream ids. Each in-flight request holds its own stream id which
+/// should be released once a response is received. An id is never handed out twice
+/// while it's in flight, so a late response cannot be matched to another request.
+#[derive(Debug)]
+pub struct StreamIdAllocator {
+    streams: Mutex<Streams>,
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct Streams {
+    free: Vec<u64>,
+    in_flight: HashSet<u64>,
+}
+
+impl Streams {
+    fn pop(&mut self) -> Option<u64> {
+        let stream = self.free.pop();
+        if let Some(stream) = stream {
+            self.in_flight.insert(stream);
+        }
+        stream
+    }
+}
+
+impl StreamIdAllocator {
+    /// Creates new allocator of `capacity` stream ids starting from 0.
+    pub fn new(capacity: usize) -> StreamIdAllocator {
+        StreamIdAllocator {
+            streams: Mutex::new(Streams {
+                free: (0..capacity as u64).rev().collect(),
+                in_flight: HashSet::new(),
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Allocates a stream id. It returns `None` if all ids are in use.
+    pub fn try_allocate(&self) -> Option<u64> {
+        self.streams.lock().unwrap().pop()
+    }
+
+    /// Allocates a stream id. If all ids are in use it blocks a thread until
+    /// some id is released.
+    pub fn allocate(&self) -> u64 {
+        let mut streams = self.streams.lock().unwrap();
+        loop {
+            if let Some(stream) = streams.pop() {
+                return stream;
+            }
+            streams = self.released.wait(streams).unwrap();
+        }
+    }
+
+    /// Releases provided stream id so that it could be reused by another request.
+    /// Ids which are not in flight are ignored, so releasing an id twice doesn't make it
+    /// available for two requests. It returns `true` if the id was released.
+    pub fn release(&self, stream: u64) -> bool {
+        let mut streams = self.streams.lock().unwrap();
+        if !streams.in_flight.remove(&stream) {
+            return false;
+        }
+        streams.free.push(stream);
+        // both allocating threads and threads waiting for all ids to be released are woken
+        self.released.notify_all();
+        true
+    }
+
+    /// Returns `true` if provided stream id is allocated and not released yet.
+    pub fn is_in_flight(&self, stream: u64) -> bool {
+        self.streams.lock().unwrap().in_flight.contains(&stream)
+    }
+
+    /// Blocks a thread until all stream ids are released but no longer than `timeout`.
+    /// It returns ids which are still in flight in ascending order.
+    pub fn wait_released(&self, timeout: Duration) -> Vec<u64> {
+        let deadline = Instant::now() + timeout;
+        let mut streams = self.streams.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if streams.in_flight.is_empty() || now >= deadline {
+                break;
+            }
+            streams = self.released.wait_timeout(streams, deadline - now).unwrap().0;
+        }
+
+        let mut in_flight: Vec<u64> = streams.in_flight.iter().cloned().collect();
+        in_flight.sort();
+        in_flight
+    }
+}
+
+struct Connection<X> {
+    transport: X,
+    // encoded frames which are not yet written to a transport
+    write_buffer: Vec<u8>,
+    buffered_frames: usize,
+    buffered_since: Option<Instant>,
+}
+
+impl<X: CDRSTransport> Connection<X> {
+    fn buffer(&mut self, bytes: &[u8]) {
+        self.write_buffer.extend_from_slice(bytes);
+        self.buffered_frames += 1;
+        if self.buffered_since.is_none() {
+            self.buffered_since = Some(Instant::now());
+        }
+    }
+
+    // Writes all buffered frames with a single write.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffered_frames == 0 {
+            return Ok(());
+        }
+
+        let result = self.transport.write_all(self.write_buffer.as_slice());
+        self.write_buffer.clear();
+        self.buffered_frames = 0;
+        self.buffered_since = None;
+        result
+    }
+}
+
+struct Reader<X> {
+    // dedicated reader of the transport, if it's `None` frames are read from
+    // the transport of the connection
+    transport: Option<X>,
+    codec: FrameCodec,
+}
+
+struct Responses {
+    // responses which were read from a transport but not yet received by requesters
+    frames: HashMap<u64, Frame>,
+    // streams which requesters gave up waiting for, their responses are discarded
+    abandoned: HashSet<u64>,
+    // `true` while one of requesters reads a frame
+    reading: bool,
+}
+
+/// Settings of coalescing of outgoing frames. Frames which are sent concurrently
+/// are accumulated in a buffer and written to a transport with a single write, so
+/// fewer system calls are made under high load. A frame is buffered only while another
+/// request is about to be sent, so a sole request is written immediately and
+/// a frame never waits longer than `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteCoalescing {
+    /// Maximal time a frame waits in the buffer for other frames.
+    pub window: Duration,
+    /// Maximal number of frames in the buffer, the buffer is flushed once it's full.
+    pub max_frames: usize,
+}
+
+impl Default for WriteCoalescing {
+    fn default() -> WriteCoalescing {
+        WriteCoalescing {
+            window: Duration::from_millis(1),
+            max_frames: 64,
+        }
+    }
+}
+
+/// Summary of closing a multiplexed connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseSummary {
+    /// Stream ids of requests which were still in flight when the connection was closed,
+    /// responses to them are lost.
+    pub abandoned: Vec<u64>,
+}
+
+/// Connection which is shared by multiple requests. A request frame is sent
+/// via `send` which returns its stream id and a response is received via `receive`
+/// by the stream id. Responses to other requests which are read meanwhile are kept
+/// until they are received.
+///
+/// Frames are read by one of receiving threads at a time while others wait for their
+/// responses without blocking on the transport. If the transport provides a dedicated
+/// reader (see `CDRSTransport::try_clone_reader`) requests are sent while a response
+/// is being read, otherwise sending waits until the read completes.
+pub struct MultiplexedConnection<X: CDRSTransport> {
+    streams: StreamIdAllocator,
+    capacity: usize,
+    compressor: Compression,
+    connection: Mutex<Connection<X>>,
+    reader: Mutex<Reader<X>>,
+    responses: Mutex<Responses>,
+    // notified once a reading thread reads a frame or fails
+    response_read: Condvar,
+    closed: AtomicBool,
+    // set if the connection was busy on close, the transport is shut down by a reader
+    shutdown_pending: AtomicBool,
+    write_coalescing: Option<WriteCoalescing>,
+    // number of requests which are waiting to be written
+    writers: AtomicUsize,
+}
+
+// Register requests cannot be sent via a multiplexed connection.
+fn check_opcode(frame: &Frame) -> error::Result<()> {
+    if frame.opcode == Opcode::Register {
+        return Err(error::Error::General("Events cannot be listened on a multiplexed \
+                                          connection"
+            .to_string()));
+    }
+
+    Ok(())
+}
+
+impl<X: CDRSTransport> MultiplexedConnection<X> {
+    /// Creates new multiplexed connection which allows up to `MAX_STREAMS`
+    /// in-flight requests.
+    pub fn new(transport: X, compressor: Compression) -> MultiplexedConnection<X> {
+        MultiplexedConnection::with_streams(transport, compressor, MAX_STREAMS)
+    }
+
+    /// Creates new multiplexed connection which allows up to `streams` in-flight requests.
+    /// When all of them are in use `send` blocks until one of responses is received.
+    pub fn with_streams(transport: X,
+                        compressor: Compression,
+                        streams: usize)
+                        -> MultiplexedConnection<X> {
+        let reader = transport.try_clone_reader().ok();
+        MultiplexedConnection {
+            streams: StreamIdAllocator::new(streams),
+            capacity: streams,
+            compressor: compressor,
+            connection: Mutex::new(Connection {
+                transport: transport,
+                write_buffer: vec![],
+                buffered_frames: 0,
+                buffered_since: None,
+            }),
+            reader: Mutex::new(Reader {
+                transport: reader,
+                codec: FrameCodec::new(),
+            }),
+            responses: Mutex::new(Responses {
+                frames: HashMap::new(),
+                abandoned: HashSet::new(),
+                reading: false,
+            }),
+            response_read: Condvar::new(),
+            closed: AtomicBool::new(false),
+            shutdown_pending: AtomicBool::new(false),
+            write_coalescing: None,
+            writers: AtomicUsize::new(0),
+        }
+    }
+
+    /// Enables coalescing of outgoing frames. Each frame is written separately
+    /// by default.
+    pub fn write_coalescing(mut self,
+                            write_coalescing: WriteCoalescing)
+                            -> MultiplexedConnection<X> {
+        self.write_coalescing = Some(write_coalescing);
+        self
+    }
+
+    /// Sends provided request frame and returns the stream id assigned to it.
+    /// Register requests are rejected since events should be listened on a dedicated
+    /// connection (see `Session::listen_for`). If all stream ids are in use it blocks
+    /// until one of responses is received.
+    pub fn send(&self, frame: Frame) -> error::Result<u64> {
+        try!(self.check_open());
+        try!(check_opcode(&frame));
+        let stream = self.streams.allocate();
+        self.send_with_stream(frame, stream)
+    }
+
+    /// Sends provided request frame the same way `send` does but it returns an error
+    /// rather than blocks if all stream ids are in use, so a caller can back off.
+    pub fn try_send(&self, frame: Frame) -> error::Result<u64> {
+        try!(self.check_open());
+        try!(check_opcode(&frame));
+        match self.streams.try_allocate() {
+            Some(stream) => self.send_with_stream(frame, stream),
+            None => {
+                Err(error::Error::General(format!("All {} stream ids are in use", self.capacity)))
+            }
+        }
+    }
+
+    fn send_with_stream(&self, mut frame: Frame, stream: u64) -> error::Result<u64> {
+        frame.stream = stream;
+
+        let result = frame.encode_with(self.compressor).and_then(|bytes| self.write(bytes));
+
+        match result {
+            Ok(_) => Ok(stream),
+            Err(err) => {
+                self.streams.release(stream);
+                Err(err)
+            }
+        }
+    }
+
+    fn write(&self, bytes: Vec<u8>) -> error::Result<()> {
+        let write_coalescing = match self.write_coalescing {
+            Some(write_coalescing) => write_coalescing,
+            None => {
+                let mut connection = self.connection.lock().unwrap();
+                return connection.transport.write_all(bytes.as_slice()).map_err(|err| err.into());
+            }
+        };
+
+        self.writers.fetch_add(1, Ordering::SeqCst);
+        let mut connection = self.connection.lock().unwrap();
+        // other writers flush the buffer after they append their frames to it
+        let writers_waiting = self.writers.fetch_sub(1, Ordering::SeqCst) > 1;
+        connection.buffer(bytes.as_slice());
+
+        let window_passed = connection.buffered_since
+            .map(|since| since.elapsed() >= write_coalescing.window)
+            .unwrap_or(false);
+        if !writers_waiting || window_passed ||
+           connection.buffered_frames >= write_coalescing.max_frames {
+            try!(connection.flush());
+        }
+
+        Ok(())
+    }
+
+    /// Receives a response to the request which was sent with provided stream id.
+    /// The stream id is released after that.
+    pub fn receive(&self, stream: u64) -> error::Result<Frame> {
+        // the request may still be in the buffer
+        try!(self.connection.lock().unwrap().flush());
+
+        loop {
+            {
+                let mut responses = self.responses.lock().unwrap();
+                loop {
+                    if let Some(frame) = responses.frames.remove(&stream) {
+                        self.streams.release(stream);
+                        return convert_frame_into_result(frame);
+                    }
+                    if !responses.reading {
+                        responses.reading = true;
+                        break;
+                    }
+                    responses = self.response_read.wait(responses).unwrap();
+                }
+            }
+
+            // responses are not locked while the frame is read, so other requesters
+            // can receive their responses and abandon requests meanwhile
+            let result = self.read_frame();
+            let mut responses = self.responses.lock().unwrap();
+            responses.reading = false;
+            self.response_read.notify_all();
+            let frame = try!(result);
+            if frame.stream == EVENT_STREAM_ID {
+                warn!("Event is received by a connection which is not registered for events");
+                continue;
+            }
+            if responses.abandoned.remove(&frame.stream) {
+                // a late response, the stream id can be reused from now on
+                self.streams.release(frame.stream);
+                continue;
+            }
+            responses.frames.insert(frame.stream, frame);
+        }
+    }
+
+    // Reads a frame with the dedicated reader if there is one. Otherwise the frame
+    // is read from the transport of the connection which is locked until the read
+    // completes.
+    fn read_frame(&self) -> error::Result<Frame> {
+        let mut guard = self.reader.lock().unwrap();
+        let reader = &mut *guard;
+        match reader.transport {
+            Some(ref mut transport) => {
+                try!(self.check_shutdown(transport));
+                let result = reader.codec.read_raw_frame(transport, &self.compressor);
+                // the connection may have been closed while the frame was being read
+                try!(self.check_shutdown(transport));
+                result
+            }
+            None => {
+                let mut connection = self.connection.lock().unwrap();
+                try!(self.check_shutdown(&mut connection.transport));
+                let result = reader.codec
+                    .read_raw_frame(&mut connection.transport, &self.compressor);
+                try!(self.check_shutdown(&mut connection.transport));
+                result
+            }
+        }
+    }
+
+    /// Gives up waiting for a response to the request which was sent with provided
+    /// stream id, e.g. on a timeout. The stream id is not reused until the response
+    /// is read from the connection, so the late response cannot be mistaken for
+    /// a response to another request.
+    pub fn abandon(&self, stream: u64) {
+        let mut responses = self.responses.lock().unwrap();
+        if responses.frames.remove(&stream).is_some() {
+            self.streams.release(stream);
+        } else if self.streams.is_in_flight(stream) {
+            responses.abandoned.insert(stream);
+        }
+    }
+
+    /// Sends provided request frame and waits for a response to it.
+    pub fn request(&self, frame: Frame) -> error::Result<Frame> {
+        let stream = try!(self.send(frame));
+        self.receive(stream)
+    }
+
+    /// Closes the connection gracefully. New requests are rejected from now on while
+    /// in-flight ones are given up to `timeout` to receive their responses. Then
+    /// the transport is shut down and requests which are still in flight (including
+    /// abandoned ones) are reported by the summary.
+    ///
+    /// The method returns once `timeout` passes even if the connection is busy, e.g.
+    /// a response is being read from a transport without a dedicated reader. In this
+    /// case the transport is shut down by the reader as soon as its read completes.
+    /// A dedicated reader is interrupted by the shutdown.
+    pub fn close(&self, timeout: Duration) -> error::Result<CloseSummary> {
+        let deadline = Instant::now() + timeout;
+        self.closed.store(true, Ordering::SeqCst);
+        let abandoned = self.streams.wait_released(timeout);
+        if !abandoned.is_empty() {
+            warn!("Connection is closed with {} requests in flight", abandoned.len());
+        }
+
+        let mut connection = match self.lock_until(deadline) {
+            Some(connection) => connection,
+            None => {
+                warn!("Connection is busy, it's shut down once a pending read completes");
+                self.shutdown_pending.store(true, Ordering::SeqCst);
+                // the reader may have released the connection before it saw the flag
+                match self.lock_until(Instant::now()) {
+                    Some(connection) => connection,
+                    None => return Ok(CloseSummary { abandoned: abandoned }),
+                }
+            }
+        };
+        self.shutdown_pending.store(false, Ordering::SeqCst);
+        try!(connection.flush());
+        try!(connection.transport.close(net::Shutdown::Both));
+
+        Ok(CloseSummary { abandoned: abandoned })
+    }
+
+    // Locks the connection but gives up once `deadline` passes.
+    fn lock_until(&self, deadline: Instant) -> Option<MutexGuard<Connection<X>>> {
+        loop {
+            match self.connection.try_lock() {
+                Ok(connection) => return Some(connection),
+                Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+                Err(TryLockError::WouldBlock) => {
+                    if Instant::now() >= deadline {
+                        return None;
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+
+    // Shuts down the transport if it was left to a reader by `close`.
+    fn check_shutdown(&self, transport: &mut X) -> error::Result<()> {
+        if !self.shutdown_pending.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        try!(transport.close(net::Shutdown::Both));
+        Err(error::Error::General("Connection is closed".to_string()))
+    }
+
+    /// Returns `true` if the connection is closed or being closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn check_open(&self) -> error::Result<()> {
+        if self.is_closed() {
+            return Err(error::Error::General("Connection is closed".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use transport::TransportMock;
+    use types::{to_int, CString};
+    use IntoBytes;
+    use super::*;
+
+    fn void_frame(stream: u8) -> Vec<u8> {
+        vec![0x84, 0, 0, stream, 0x08, 0, 0, 0, 4, 0, 0, 0, 1]
+    }
+
+    fn error_frame(stream: u8) -> Vec<u8> {
+        let mut body = to_int(0x0000);
+        body.extend_from_slice(CString::new("error".to_string()).into_cbytes().as_slice());
+
+        let mut frame = vec![0x84, 0, 0, stream, 0x00];
+        frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    #[test]
+    fn stream_id_allocator() {
+        let streams = StreamIdAllocator::new(2);
+        assert_eq!(streams.try_allocate(), Some(0));
+        assert_eq!(streams.try_allocate(), Some(1));
+        assert_eq!(streams.try_allocate(), None);
+
+        streams.release(1);
+        assert_eq!(streams.allocate(), 1);
+    }
+
+    #[test]
+    fn register_is_rejected() {
+        let connection = MultiplexedConnection::new(TransportMock::new(vec![]),
+                                                    Compression::None);
+        assert!(connection.send(Frame::new_req_register(vec![])).is_err());
+        assert!(connection.connection.lock().unwrap().transport.written.is_empty());
+    }
+
+    #[test]
+    fn interleaved_requests() {
+        // responses come in order different from the order of requests
+        let mut input = void_frame(2);
+        input.extend_from_slice(void_frame(0).as_slice());
+        input.extend_from_slice(error_frame(1).as_slice());
+        let connection = MultiplexedConnection::with_streams(TransportMock::new(input),
+                                                             Compression::None,
+                                                             3);
+
+        let first = connection.send(Frame::new_req_options()).unwrap();
+        let second = connection.send(Frame::new_req_options()).unwrap();
+        let third = connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!((first, second, third), (0, 1, 2));
+        assert_eq!(connection.streams.try_allocate(), None);
+
+        assert!(connection.receive(second).is_err());
+        assert_eq!(connection.receive(third).unwrap().stream, 2);
+        assert_eq!(connection.receive(first).unwrap().stream, 0);
+
+        // stream ids are reused once responses are received
+        assert_eq!(connection.streams.try_allocate(), Some(0));
+
+        let written = &connection.connection.lock().unwrap().transport.written;
+        let streams: Vec<u8> = written.iter().map(|frame| frame[3]).collect();
+        assert_eq!(streams, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn stream_id_is_released_once() {
+        let streams = StreamIdAllocator::new(2);
+        let stream = streams.allocate();
+        assert!(streams.is_in_flight(stream));
+        assert!(streams.release(stream));
+        assert!(!streams.release(stream));
+        // ids which were never allocated are ignored too
+        assert!(!streams.release(5));
+
+        assert_eq!(streams.try_allocate(), Some(stream));
+        assert_eq!(streams.try_allocate(), Some(1));
+        assert_eq!(streams.try_allocate(), None);
+    }
+
+    #[test]
+    fn exhausted_streams() {
+        let connection = MultiplexedConnection::with_streams(TransportMock::new(void_frame(1)),
+                                                             Compression::None,
+                                                             2);
+        let first = connection.try_send(Frame::new_req_options()).unwrap();
+        let second = connection.try_send(Frame::new_req_options()).unwrap();
+        assert!(connection.try_send(Frame::new_req_options()).is_err());
+        assert_eq!(connection.connection.lock().unwrap().transport.written.len(), 2);
+
+        assert_eq!(connection.receive(second).unwrap().stream, 1);
+        assert_eq!(connection.try_send(Frame::new_req_options()).unwrap(), second);
+        assert!(connection.streams.is_in_flight(first));
+    }
+
+    #[test]
+    fn abandoned_stream_is_reused_after_late_response() {
+        let mut input = void_frame(0);
+        input.extend_from_slice(void_frame(1).as_slice());
+        let connection = MultiplexedConnection::with_streams(TransportMock::new(input),
+                                                             Compression::None,
+                                                             2);
+        let first = connection.send(Frame::new_req_options()).unwrap();
+        let second = connection.send(Frame::new_req_options()).unwrap();
+
+        // e.g. the request timed out
+        connection.abandon(first);
+        assert_eq!(connection.streams.try_allocate(), None);
+
+        // the late response to the first request is discarded
+        assert_eq!(connection.receive(second).unwrap().stream, 1);
+        assert!(!connection.streams.is_in_flight(first));
+        assert!(connection.responses.lock().unwrap().frames.is_empty());
+        assert_eq!(connection.streams.try_allocate(), Some(1));
+        assert_eq!(connection.streams.try_allocate(), Some(0));
+    }
+
+    #[test]
+    fn close_waits_for_in_flight_requests() {
+        let connection = Arc::new(MultiplexedConnection::new(TransportMock::new(void_frame(0)),
+                                                             Compression::None));
+        let stream = connection.send(Frame::new_req_options()).unwrap();
+
+        // slow requester
+        let requester = connection.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            requester.receive(stream)
+        });
+
+        let started = Instant::now();
+        let summary = connection.close(Duration::from_secs(5)).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(summary.abandoned.is_empty());
+        assert_eq!(handle.join().unwrap().unwrap().stream, stream);
+
+        assert!(connection.is_closed());
+        assert!(connection.send(Frame::new_req_options()).is_err());
+        assert!(connection.try_send(Frame::new_req_options()).is_err());
+        assert_eq!(connection.connection.lock().unwrap().transport.written.len(), 1);
+    }
+
+    #[test]
+    fn close_reports_abandoned_requests() {
+        let connection = MultiplexedConnection::new(TransportMock::new(vec![]),
+                                                    Compression::None);
+        let first = connection.send(Frame::new_req_options()).unwrap();
+        let second = connection.send(Frame::new_req_options()).unwrap();
+        connection.abandon(second);
+
+        let summary = connection.close(Duration::from_millis(10)).unwrap();
+        assert_eq!(summary.abandoned, vec![first, second]);
+        assert!(connection.connection.lock().unwrap().transport.closed);
+    }
+
+    #[test]
+    fn close_does_not_wait_for_busy_connection() {
+        let connection = Arc::new(MultiplexedConnection::new(TransportMock::new(void_frame(0)),
+                                                             Compression::None));
+        let stream = connection.send(Frame::new_req_options()).unwrap();
+
+        // a reader which is blocked while it holds the connection
+        let (locked_sender, locked_receiver) = mpsc::channel();
+        let reader = connection.clone();
+        let handle = thread::spawn(move || {
+            let _connection = reader.connection.lock().unwrap();
+            locked_sender.send(()).unwrap();
+            thread::sleep(Duration::from_millis(300));
+        });
+        locked_receiver.recv().unwrap();
+
+        let started = Instant::now();
+        let summary = connection.close(Duration::from_millis(20)).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(summary.abandoned, vec![stream]);
+
+        handle.join().unwrap();
+        assert!(connection.receive(stream).is_err());
+        assert!(connection.connection.lock().unwrap().transport.closed);
+    }
+
+    #[test]
+    fn send_while_response_is_read() {
+        use std::io::{Read, Write};
+        use transport::TransportTcp;
+
+        // the server responds only once it receives both requests
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut requests = [0; 18];
+            socket.read_exact(&mut requests).unwrap();
+            socket.write_all(void_frame(1).as_slice()).unwrap();
+            socket.write_all(void_frame(0).as_slice()).unwrap();
+        });
+
+        let mut transport = TransportTcp::new(&addr.to_string()).unwrap();
+        transport.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let connection = Arc::new(MultiplexedConnection::new(transport, Compression::None));
+        let first = connection.send(Frame::new_req_options()).unwrap();
+        let (reading_sender, reading_receiver) = mpsc::channel();
+        let requester = connection.clone();
+        let handle = thread::spawn(move || {
+            reading_sender.send(()).unwrap();
+            requester.receive(first)
+        });
+        reading_receiver.recv().unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // the first requester is blocked on reading
+        let second = connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(connection.receive(second).unwrap().stream, second);
+        assert_eq!(handle.join().unwrap().unwrap().stream, first);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn write_coalescing() {
+        let write_coalescing = WriteCoalescing {
+            window: Duration::from_secs(60),
+            max_frames: 3,
+        };
+        let connection = MultiplexedConnection::new(TransportMock::new(void_frame(0)),
+                                                    Compression::None)
+            .write_coalescing(write_coalescing);
+        let written = |connection: &MultiplexedConnection<TransportMock>| {
+            connection.connection.lock().unwrap().transport.written.len()
+        };
+
+        // a sole request is written immediately
+        let first = connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(written(&connection), 1);
+        let frame_len = connection.connection.lock().unwrap().transport.written[0].len();
+
+        // another request is about to be written
+        connection.writers.fetch_add(1, Ordering::SeqCst);
+        connection.send(Frame::new_req_options()).unwrap();
+        connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(written(&connection), 1);
+        // the buffer is full
+        connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(written(&connection), 2);
+        assert_eq!(connection.connection.lock().unwrap().transport.written[1].len(),
+                   3 * frame_len);
+
+        // buffered requests are written before responses are read
+        connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(written(&connection), 2);
+        assert_eq!(connection.receive(first).unwrap().stream, 0);
+        assert_eq!(written(&connection), 3);
+    }
+
+    #[test]
+    fn write_coalescing_window() {
+        let write_coalescing = WriteCoalescing {
+            window: Duration::from_millis(0),
+            max_frames: 100,
+        };
+        let connection = MultiplexedConnection::new(TransportMock::new(vec![]),
+                                                    Compression::None)
+            .write_coalescing(write_coalescing);
+        connection.writers.fetch_add(1, Ordering::SeqCst);
+        connection.send(Frame::new_req_options()).unwrap();
+        connection.send(Frame::new_req_options()).unwrap();
+        assert_eq!(connection.connection.lock().unwrap().transport.written.len(), 2);
+    }
+}
@@ -0,0 +1,109 @@
+//! The module contains an implementation of Murmur3 hash function which is used by
+//! Cassandra's `Murmur3Partitioner` to calculate tokens of partition keys.
+use std::i64;
+use byteorder::{ByteOrder, LittleEndian};
+
+const C1: u64 = 0x87c37b91114253d5;
+const C2: u64 = 0x4cf5ad432745937f;
+
+/// Calculates a token of provided partition key in the same way `Murmur3Partitioner`
+/// does, i.e. it takes the first half of x64 128-bit Murmur3 hash with zero seed.
+pub fn murmur3_token(key: &[u8]) -> i64 {
+    let len = key.len();
+    let nblocks = len / 16;
+    let mut h1: u64 = 0;
+    let mut h2: u64 = 0;
+
+    for i in 0..nblocks {
+        let mut k1 = LittleEndian::read_u64(&key[i * 16..]);
+        let mut k2 = LittleEndian::read_u64(&key[i * 16 + 8..]);
+
+        k1 = k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 ^= k1;
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dce729);
+
+        k2 = k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 ^= k2;
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x38495ab5);
+    }
+
+    // Cassandra treats bytes of the tail as signed ones, so they are sign-extended
+    // before shifting. It differs from the reference implementation for bytes > 0x7F.
+    let tail = &key[nblocks * 16..];
+    let mut k1: u64 = 0;
+    let mut k2: u64 = 0;
+    for (i, byte) in tail.iter().enumerate() {
+        let value = *byte as i8 as i64 as u64;
+        if i < 8 {
+            k1 ^= value << (i * 8);
+        } else {
+            k2 ^= value << ((i - 8) * 8);
+        }
+    }
+    if tail.len() > 8 {
+        k2 = k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 ^= k2;
+    }
+    if !tail.is_empty() {
+        k1 = k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 ^= k1;
+    }
+
+    h1 ^= len as u64;
+    h2 ^= len as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+    h1 = h1.wrapping_add(h2);
+
+    // Cassandra never uses minimal token for keys
+    match h1 as i64 {
+        i64::MIN => i64::MAX,
+        token => token,
+    }
+}
+
+fn fmix(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51afd7ed558ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+    k ^= k >> 33;
+    k
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmur3_token_vectors() {
+        assert_eq!(murmur3_token(&[]), 0);
+        assert_eq!(murmur3_token(&[0]) as u64, 0x4610abe56eff5cb5);
+        assert_eq!(murmur3_token(&[0, 1]) as u64, 0x7cb3f5c58dab264c);
+        assert_eq!(murmur3_token(&[0, 1, 2]) as u64, 0xb872a12fef53e6be);
+        assert_eq!(murmur3_token(b"hello") as u64, 0xcbd8a7b341bd9b02);
+        assert_eq!(murmur3_token(b"hello, world") as u64, 0x342fac623a5ebc8e);
+        assert_eq!(murmur3_token(b"19 Jan 2038 at 3:14:07 AM") as u64,
+                   0xb89e5988b737affc);
+        assert_eq!(murmur3_token(b"The quick brown fox jumps over the lazy dog.") as u64,
+                   0xcd99481f9ee902c9);
+    }
+
+    #[test]
+    fn murmur3_token_of_int_key() {
+        // SELECT token(id) FROM ... WHERE id = 1, where id is of type int
+        assert_eq!(murmur3_token(&[0, 0, 0, 1]), -4069959284402364209);
+        assert_eq!(murmur3_token(&[0, 0, 0, 2]), -3248873570005575792);
+        assert_eq!(murmur3_token(&[0, 0, 0, 3]), 9010454139840013625);
+        assert_eq!(murmur3_token(&[0, 0, 0, 4]), -2729420104000364805);
+        assert_eq!(murmur3_token(&[0, 0, 0, 5]), -7509452495886106294);
+    }
+
+    #[test]
+    fn murmur3_token_of_bigint_key() {
+        // SELECT token(id) FROM ... WHERE id = 1, where id is of type bigint
+        assert_eq!(murmur3_token(&[0, 0, 0, 0, 0, 0, 0, 1]), 6292367497774912474);
+    }
+}
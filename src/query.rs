@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use types::*;
 use types::value::*;
 use error::{Result as CResult, Error as CError};
 use consistency::Consistency;
 use frame::frame_query::{ParamsReqQuery, QueryFlags};
 use frame::frame_batch::{BatchType, BatchQuery, BodyReqBatch, BatchQuerySubj};
+use frame::frame_result::BodyResResultPrepared;
 
 /// instead of writing functions which resemble
 /// ```
@@ -30,17 +32,25 @@ macro_rules! builder_opt_field {
 
 /// Structure that represents CQL query and parameters which will be applied during
 /// its execution
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Query {
     pub query: String,
     // query parameters
     pub consistency: Consistency,
-    pub values: Option<Vec<Value>>,
+    pub values: Option<QueryValues>,
     pub with_names: Option<bool>,
     pub page_size: Option<i32>,
     pub paging_state: Option<CBytes>,
     pub serial_consistency: Option<Consistency>,
     pub timestamp: Option<i64>,
+    pub with_tracing: Option<bool>,
+    /// Client-side option which isn't sent to a server, see `QueryBuilder::idempotent`.
+    pub idempotent: bool,
+    pub custom_payload: Option<HashMap<String, Vec<u8>>>,
+    /// Keyspace of the query (protocol v5), see `QueryBuilder::with_keyspace`.
+    pub keyspace: Option<String>,
+    /// Current time of the query (protocol v5), see `QueryBuilder::with_now_in_seconds`.
+    pub now_in_seconds: Option<i32>,
 }
 
 /// QueryBuilder is a helper sturcture that helps to construct `Query`. `Query` itself
@@ -51,12 +61,17 @@ pub struct Query {
 pub struct QueryBuilder {
     query: String,
     consistency: Consistency,
-    values: Option<Vec<Value>>,
+    values: Option<QueryValues>,
     with_names: Option<bool>,
     page_size: Option<i32>,
     paging_state: Option<CBytes>,
     serial_consistency: Option<Consistency>,
     timestamp: Option<i64>,
+    with_tracing: Option<bool>,
+    idempotent: bool,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
+    keyspace: Option<String>,
+    now_in_seconds: Option<i32>,
 }
 
 impl QueryBuilder {
@@ -73,8 +88,13 @@ impl QueryBuilder {
         return self;
     }
 
-    /// Sets new query values
-    builder_opt_field!(values, Vec<Value>);
+    /// Sets new query values. Values are bound either by position (`Vec<Value>`)
+    /// or by names (`HashMap<String, Value>`).
+    pub fn values<V: Into<QueryValues>>(mut self, values: V) -> Self {
+        self.values = Some(values.into());
+
+        return self;
+    }
 
     /// Sets new query with_names
     builder_opt_field!(with_names, bool);
@@ -85,12 +105,73 @@ impl QueryBuilder {
     /// Sets new query pagin state
     builder_opt_field!(paging_state, CBytes);
 
+    /// Sets new query paging state from raw bytes returned by a server as a part of
+    /// rows result, i.e. resumes fetching from the page that follows.
+    pub fn with_paging_state(mut self, paging_state: Vec<u8>) -> Self {
+        self.paging_state = Some(CBytes::new(paging_state));
+
+        return self;
+    }
+
     /// Sets new query serial_consistency
     builder_opt_field!(serial_consistency, Consistency);
 
+    /// Sets new query serial consistency which is used by conditional statements.
+    /// It returns an error if provided consistency is neither `Serial` nor `LocalSerial`.
+    pub fn with_serial_consistency(mut self, serial_consistency: Consistency) -> CResult<Self> {
+        self.serial_consistency = Some(try!(check_serial_consistency(serial_consistency)));
+
+        return Ok(self);
+    }
+
     /// Sets new quey timestamp
     builder_opt_field!(timestamp, i64);
 
+    /// Sets client-side timestamp (in microseconds) of the query. It overrides a timestamp
+    /// which would be provided by timestamp generator of a session.
+    pub fn with_timestamp(self, timestamp: i64) -> Self {
+        self.timestamp(timestamp)
+    }
+
+    /// Enables tracing of the query
+    builder_opt_field!(with_tracing, bool);
+
+    /// Marks the query as idempotent, i.e. applying it several times has the same effect
+    /// as applying it once. Only idempotent queries are retried on read and write timeouts
+    /// since the outcome of a timed out query is unknown. Queries are not idempotent
+    /// by default.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+
+        return self;
+    }
+
+    /// Attaches custom payload to the query which is passed to a custom query handler
+    /// of a server (protocol v4). `CustomPayload` flag of the request is set.
+    pub fn with_custom_payload(mut self, custom_payload: HashMap<String, Vec<u8>>) -> Self {
+        self.custom_payload = Some(custom_payload);
+
+        return self;
+    }
+
+    /// Sets keyspace the query is executed in, so that it doesn't need neither `USE`
+    /// statement nor fully qualified names of tables. It's supported since 5-th version
+    /// of the protocol, a session returns an error if the query is used with earlier ones.
+    pub fn with_keyspace(mut self, keyspace: &str) -> Self {
+        self.keyspace = Some(keyspace.to_string());
+
+        return self;
+    }
+
+    /// Sets current time (in seconds since epoch) which a server uses to compute TTLs
+    /// and tombstones of the query instead of its own clock, e.g. to make tests
+    /// reproducible. It's supported since 5-th version of the protocol.
+    pub fn with_now_in_seconds(mut self, now_in_seconds: i32) -> Self {
+        self.now_in_seconds = Some(now_in_seconds);
+
+        return self;
+    }
+
     pub fn apply_query_params(mut self, params: QueryParams) -> Self {
         self.consistency = params.consistency;
         self.values = params.values;
@@ -98,6 +179,8 @@ impl QueryBuilder {
         self.paging_state = params.paging_state;
         self.serial_consistency = params.serial_consistency;
         self.timestamp = params.timestamp;
+        self.idempotent = params.idempotent;
+        self.custom_payload = params.custom_payload;
 
         return self;
     }
@@ -113,22 +196,45 @@ impl QueryBuilder {
             paging_state: self.paging_state,
             serial_consistency: self.serial_consistency,
             timestamp: self.timestamp,
+            with_tracing: self.with_tracing,
+            idempotent: self.idempotent,
+            custom_payload: self.custom_payload,
+            keyspace: self.keyspace,
+            now_in_seconds: self.now_in_seconds,
         };
     }
 }
 
+fn check_serial_consistency(consistency: Consistency) -> CResult<Consistency> {
+    if !consistency.is_serial() {
+        return Err(CError::General(format!("Serial consistency should be either SERIAL or \
+                                            LOCAL_SERIAL but {} provided",
+                                           consistency)));
+    }
+
+    Ok(consistency)
+}
+
 pub type QueryParams = ParamsReqQuery;
 
-/// Query parameters builder
+/// Prepared statement, i.e. id and metadata returned by a server as a result of PREPARE query.
+pub type PreparedStatement = BodyResResultPrepared;
+
+/// Query parameters builder. Each option which is set toggles a corresponding flag
+/// of Query (or Execute) request, so the flags always match fields which are serialized
+/// after them (values, page size, paging state, serial consistency and timestamp in this
+/// order, as the protocol requires).
 #[derive(Debug)]
 pub struct QueryParamsBuilder {
     consistency: Consistency,
-    values: Option<Vec<Value>>,
+    values: Option<QueryValues>,
     with_names: bool,
     page_size: Option<i32>,
     paging_state: Option<CBytes>,
     serial_consistency: Option<Consistency>,
     timestamp: Option<i64>,
+    idempotent: bool,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
 }
 
 impl QueryParamsBuilder {
@@ -141,11 +247,13 @@ impl QueryParamsBuilder {
             paging_state: None,
             serial_consistency: None,
             timestamp: None,
+            idempotent: false,
+            custom_payload: None,
         };
     }
 
-    pub fn values(mut self, v: Vec<Value>) -> Self {
-        self.values = Some(v);
+    pub fn values<V: Into<QueryValues>>(mut self, v: V) -> Self {
+        self.values = Some(v.into());
 
         return self;
     }
@@ -168,18 +276,53 @@ impl QueryParamsBuilder {
         return self;
     }
 
+    pub fn with_paging_state(mut self, paging_state: Vec<u8>) -> Self {
+        self.paging_state = Some(CBytes::new(paging_state));
+
+        return self;
+    }
+
     pub fn serial_consistency(mut self, serial_consistency: Consistency) -> Self {
         self.serial_consistency = Some(serial_consistency);
 
         return self;
     }
 
+    /// Sets serial consistency which is used by conditional statements. It returns
+    /// an error if provided consistency is neither `Serial` nor `LocalSerial`.
+    pub fn with_serial_consistency(mut self, serial_consistency: Consistency) -> CResult<Self> {
+        self.serial_consistency = Some(try!(check_serial_consistency(serial_consistency)));
+
+        return Ok(self);
+    }
+
     pub fn timestamp(mut self, timestamp: i64) -> Self {
         self.timestamp = Some(timestamp);
 
         return self;
     }
 
+    /// Sets client-side timestamp (in microseconds) of the query. It overrides a timestamp
+    /// which would be provided by timestamp generator of a session.
+    pub fn with_timestamp(self, timestamp: i64) -> Self {
+        self.timestamp(timestamp)
+    }
+
+    /// Marks the query as idempotent the same way `QueryBuilder::idempotent` does.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+
+        return self;
+    }
+
+    /// Attaches custom payload to the request the same way
+    /// `QueryBuilder::with_custom_payload` does.
+    pub fn with_custom_payload(mut self, custom_payload: HashMap<String, Vec<u8>>) -> Self {
+        self.custom_payload = Some(custom_payload);
+
+        return self;
+    }
+
     pub fn finalize(self) -> QueryParams {
         // query flags
         let mut flags: Vec<QueryFlags> = vec![];
@@ -188,7 +331,8 @@ impl QueryParamsBuilder {
             flags.push(QueryFlags::Value);
         }
 
-        if self.with_names {
+        let values_with_names = self.values.as_ref().map(|v| v.with_names()).unwrap_or(false);
+        if self.with_names || values_with_names {
             flags.push(QueryFlags::WithNamesForValues);
         }
 
@@ -196,6 +340,10 @@ impl QueryParamsBuilder {
             flags.push(QueryFlags::PageSize);
         }
 
+        if self.paging_state.is_some() {
+            flags.push(QueryFlags::WithPagingState);
+        }
+
         if self.serial_consistency.is_some() {
             flags.push(QueryFlags::WithSerialConsistency);
         }
@@ -212,6 +360,10 @@ impl QueryParamsBuilder {
             paging_state: self.paging_state,
             serial_consistency: self.serial_consistency,
             timestamp: self.timestamp,
+            keyspace: None,
+            now_in_seconds: None,
+            idempotent: self.idempotent,
+            custom_payload: self.custom_payload,
         }
 
     }
@@ -226,6 +378,9 @@ pub struct BatchQueryBuilder {
     consistency: Consistency,
     serial_consistency: Option<Consistency>,
     timestamp: Option<i64>,
+    keyspace: Option<String>,
+    idempotent: bool,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
 }
 
 impl BatchQueryBuilder {
@@ -236,6 +391,9 @@ impl BatchQueryBuilder {
             consistency: Consistency::One,
             serial_consistency: None,
             timestamp: None,
+            keyspace: None,
+            idempotent: false,
+            custom_payload: None,
         }
     }
 
@@ -284,6 +442,26 @@ impl BatchQueryBuilder {
         self
     }
 
+    /// Marks the batch as idempotent the same way `QueryBuilder::idempotent` does.
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    /// Sets keyspace the batch is executed in the same way `QueryBuilder::with_keyspace`
+    /// does.
+    pub fn with_keyspace(mut self, keyspace: &str) -> Self {
+        self.keyspace = Some(keyspace.to_string());
+        self
+    }
+
+    /// Attaches custom payload to the batch the same way `QueryBuilder::with_custom_payload`
+    /// does.
+    pub fn with_custom_payload(mut self, custom_payload: HashMap<String, Vec<u8>>) -> Self {
+        self.custom_payload = Some(custom_payload);
+        self
+    }
+
     pub fn finalize(self) -> CResult<BodyReqBatch> {
         let mut flags = vec![];
 
@@ -314,6 +492,10 @@ impl BatchQueryBuilder {
             flags.push(QueryFlags::WithNamesForValues);
         }
 
+        if self.keyspace.is_some() {
+            flags.push(QueryFlags::WithKeyspace);
+        }
+
         Ok(BodyReqBatch {
             batch_type: self.batch_type,
             queries: self.queries,
@@ -321,6 +503,9 @@ impl BatchQueryBuilder {
             consistency: self.consistency,
             serial_consistency: self.serial_consistency,
             timestamp: self.timestamp,
+            keyspace: self.keyspace.map(CString::new),
+            idempotent: self.idempotent,
+            custom_payload: self.custom_payload,
         })
     }
 }
@@ -346,6 +531,7 @@ mod query_builder {
             .paging_state(CBytes::new(vec![1, 2, 3, 4, 5]))
             .serial_consistency(Consistency::One)
             .timestamp(1245678)
+            .with_tracing(true)
             .finalize();
     }
 }
@@ -372,8 +558,40 @@ mod query_params_builder {
     }
 }
 
+#[cfg(test)]
+mod serial_consistency {
+    use IntoBytes;
+    use super::*;
+
+    #[test]
+    fn with_serial_consistency() {
+        let params = QueryParamsBuilder::new(Consistency::Quorum)
+            .with_serial_consistency(Consistency::LocalSerial)
+            .unwrap()
+            .finalize();
+        assert_eq!(params.into_cbytes(), vec![0, 4, 0x10, 0, 9]);
+
+        let query = QueryBuilder::new("UPDATE t SET a = 1 WHERE id = 1 IF a = 0")
+            .with_serial_consistency(Consistency::Serial)
+            .unwrap()
+            .finalize();
+        assert_eq!(query.serial_consistency, Some(Consistency::Serial));
+    }
+
+    #[test]
+    fn with_non_serial_consistency() {
+        assert!(QueryParamsBuilder::new(Consistency::One)
+            .with_serial_consistency(Consistency::Quorum)
+            .is_err());
+        assert!(QueryBuilder::new("USE keyspace")
+            .with_serial_consistency(Consistency::One)
+            .is_err());
+    }
+}
+
 #[cfg(test)]
 mod batch_query_builder {
+    use IntoBytes;
     use super::*;
 
     #[test]
@@ -394,6 +612,24 @@ mod batch_query_builder {
         assert!(q.is_ok())
     }
 
+    #[test]
+    fn unlogged_prepared_into_cbytes() {
+        let batch = BatchQueryBuilder::new()
+            .batch_type(BatchType::Unlogged)
+            .add_query_prepared(CBytesShort::new(vec![1, 2]), vec![(None, Value::from(1 as i32))])
+            .add_query_prepared(CBytesShort::new(vec![3, 4]), vec![(None, Value::from(2 as i32))])
+            .consistency(Consistency::Quorum)
+            .serial_consistency(Some(Consistency::Serial))
+            .finalize()
+            .unwrap();
+
+        let expected = vec![1, 0, 2,
+                            1, 0, 2, 1, 2, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1,
+                            1, 0, 2, 3, 4, 0, 1, 0, 0, 0, 4, 0, 0, 0, 2,
+                            0, 4, 0x10, 0, 8];
+        assert_eq!(batch.into_cbytes(), expected);
+    }
+
     #[test]
     fn clear_queries() {
         let q = BatchQueryBuilder::new()
@@ -403,3 +639,127 @@ mod batch_query_builder {
         assert!(q.is_ok())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use frame::{Frame, ProtocolVersion};
+    use frame::frame_query::BodyReqQuery;
+    use types::CStringLong;
+    use {IntoBytes, TryFromCursor};
+    use super::*;
+
+    fn flags(params: QueryParams) -> u8 {
+        params.into_cbytes()[2]
+    }
+
+    #[test]
+    fn query_params_flags() {
+        let builder = || QueryParamsBuilder::new(Consistency::One);
+
+        assert_eq!(flags(builder().finalize()), 0x00);
+        assert_eq!(flags(builder().values(vec![Value::from(1)]).finalize()), 0x01);
+        assert_eq!(flags(builder().page_size(10).finalize()), 0x04);
+        assert_eq!(flags(builder().with_paging_state(vec![1]).finalize()), 0x08);
+        assert_eq!(flags(builder().serial_consistency(Consistency::Serial).finalize()),
+                   0x10);
+        assert_eq!(flags(builder().timestamp(1).finalize()), 0x20);
+        assert_eq!(flags(builder().with_names(true).finalize()), 0x40);
+
+        let mut named = HashMap::new();
+        named.insert("id".to_string(), Value::from(1));
+        assert_eq!(flags(builder().values(named).finalize()), 0x41);
+        assert_eq!(flags(builder().page_size(10).with_paging_state(vec![1]).finalize()),
+                   0x0C);
+        assert_eq!(flags(builder()
+                       .values(vec![Value::from(1)])
+                       .page_size(10)
+                       .with_paging_state(vec![1])
+                       .serial_consistency(Consistency::LocalSerial)
+                       .timestamp(1)
+                       .with_names(true)
+                       .finalize()),
+                   0x7D);
+    }
+
+    #[test]
+    fn query_params_body_order() {
+        let params = QueryParamsBuilder::new(Consistency::Quorum)
+            .timestamp(7)
+            .serial_consistency(Consistency::Serial)
+            .with_paging_state(vec![0xAB])
+            .page_size(5)
+            .values(vec![Value::from(1 as i8)])
+            .finalize();
+
+        let mut expected = vec![0, 4, 0x3D];
+        // values
+        expected.extend_from_slice(&[0, 1, 0, 0, 0, 1, 1]);
+        // page size
+        expected.extend_from_slice(&[0, 0, 0, 5]);
+        // paging state
+        expected.extend_from_slice(&[0, 0, 0, 1, 0xAB]);
+        // serial consistency
+        expected.extend_from_slice(&[0, 8]);
+        // timestamp
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(params.into_cbytes(), expected);
+    }
+
+    #[test]
+    fn query_frame_flags() {
+        let frame = Frame::new_req_query("SELECT * FROM t".to_string(),
+                                         Consistency::One,
+                                         Some(QueryValues::from(vec![Value::from(1)])),
+                                         None,
+                                         Some(10),
+                                         None,
+                                         None,
+                                         Some(1),
+                                         vec![]);
+        // [long string] query and [consistency] precede the flags
+        assert_eq!(frame.body[4 + 15 + 2], 0x25);
+    }
+
+    #[test]
+    fn long_query_string() {
+        let query = format!("SELECT * FROM t WHERE id IN ({})", vec!["1"; 40000].join(","));
+        let frame = Frame::new_req_query(query.clone(),
+                                         Consistency::One,
+                                         None,
+                                         None,
+                                         None,
+                                         None,
+                                         None,
+                                         None,
+                                         vec![]);
+        let mut cursor = Cursor::new(frame.body.as_slice());
+        assert_eq!(CStringLong::try_from_cursor(&mut cursor).unwrap().as_str(), query);
+        // [consistency] and flags follow the query
+        assert_eq!(&frame.body[cursor.position() as usize..], &[0, 1, 0]);
+    }
+
+    #[test]
+    fn query_with_now_in_seconds() {
+        let query = QueryBuilder::new("SELECT * FROM t").with_now_in_seconds(0x01020304).finalize();
+        let body = BodyReqQuery::from_query(query);
+        assert!(body.query_params.check_protocol(ProtocolVersion::V5).is_ok());
+        assert!(body.query_params.check_protocol(ProtocolVersion::V4).is_err());
+
+        // [long string] query, [consistency], [int] flags and [int] now in seconds
+        let bytes = body.into_cbytes_with_protocol(ProtocolVersion::V5);
+        assert_eq!(&bytes[4 + 15..], &[0, 1, 0, 0, 0x01, 0, 1, 2, 3, 4]);
+
+        let query = QueryBuilder::new("SELECT * FROM t")
+            .with_timestamp(1)
+            .with_keyspace("ks")
+            .with_now_in_seconds(5)
+            .finalize();
+        let bytes = BodyReqQuery::from_query(query).into_cbytes_with_protocol(ProtocolVersion::V5);
+        let mut expected = vec![0, 1, 0, 0, 0x01, 0xA0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2];
+        expected.extend_from_slice(b"ks");
+        expected.extend_from_slice(&[0, 0, 0, 5]);
+        assert_eq!(&bytes[4 + 15..], expected.as_slice());
+    }
+}
@@ -0,0 +1,286 @@
+//! The module contains reconnection policies which define how often a driver
+//! should try to reconnect to a node which went down.
+use std::collections::HashSet;
+use std::net;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error;
+use load_balancing::Node;
+
+/// Delays between successive reconnection attempts. Reconnection is given up
+/// once the schedule is exhausted and the node is considered up again, so it's tried
+/// by requests and a new schedule is started if it's still down.
+pub type ReconnectionSchedule = Box<Iterator<Item = Duration> + Send>;
+
+/// Reconnection policy produces a schedule of reconnection attempts to a node
+/// which went down. A new schedule is created each time a node goes down.
+pub trait ReconnectionPolicy: Send + Sync {
+    /// Returns delays of reconnection attempts.
+    fn new_schedule(&self) -> ReconnectionSchedule;
+}
+
+/// Reconnection policy which waits `base` before the first attempt and doubles
+/// the delay with each next attempt until it reaches `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialReconnectionPolicy {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl ExponentialReconnectionPolicy {
+    pub fn new(base: Duration, max: Duration) -> ExponentialReconnectionPolicy {
+        ExponentialReconnectionPolicy {
+            base: base,
+            max: max,
+        }
+    }
+}
+
+impl ReconnectionPolicy for ExponentialReconnectionPolicy {
+    fn new_schedule(&self) -> ReconnectionSchedule {
+        let base = self.base;
+        let max = self.max;
+        Box::new((0..).map(move |attempt: u32| {
+            if attempt >= 32 {
+                return max;
+            }
+            match base.checked_mul(1 << attempt) {
+                Some(delay) if delay < max => delay,
+                _ => max,
+            }
+        }))
+    }
+}
+
+/// Reconnection policy which waits the same `delay` before each attempt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantReconnectionPolicy {
+    pub delay: Duration,
+}
+
+impl ConstantReconnectionPolicy {
+    pub fn new(delay: Duration) -> ConstantReconnectionPolicy {
+        ConstantReconnectionPolicy { delay: delay }
+    }
+}
+
+impl ReconnectionPolicy for ConstantReconnectionPolicy {
+    fn new_schedule(&self) -> ReconnectionSchedule {
+        let delay = self.delay;
+        Box::new((0..).map(move |_: u32| delay))
+    }
+}
+
+/// Keeps track of nodes which are down and reconnects to them in background
+/// in accordance to a reconnection policy.
+pub struct NodeStates<P: ReconnectionPolicy> {
+    policy: P,
+    down: Mutex<HashSet<net::IpAddr>>,
+    stopped: Mutex<bool>,
+    // wakes reconnection threads up when they are stopped
+    stop_signal: Condvar,
+}
+
+impl<P: ReconnectionPolicy> NodeStates<P> {
+    /// Creates new node states where all nodes are considered up.
+    pub fn new(policy: P) -> NodeStates<P> {
+        NodeStates {
+            policy: policy,
+            down: Mutex::new(HashSet::new()),
+            stopped: Mutex::new(false),
+            stop_signal: Condvar::new(),
+        }
+    }
+
+    /// Stops all reconnection threads, they return an error without waiting for
+    /// their next attempts. No reconnection is started from now on.
+    pub fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.stop_signal.notify_all();
+    }
+
+    /// Returns `true` if reconnection is stopped.
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    // Blocks a thread for `delay` or until reconnection is stopped. Returns `false`
+    // if it's stopped.
+    fn wait(&self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        let mut stopped = self.stopped.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            if *stopped || now >= deadline {
+                return !*stopped;
+            }
+            stopped = self.stop_signal.wait_timeout(stopped, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Returns `false` if the node has been marked down and not reconnected yet.
+    pub fn is_up(&self, node: &Node) -> bool {
+        !self.down.lock().unwrap().contains(&node.address)
+    }
+
+    /// Marks the node down. Returns `false` if it has already been marked down.
+    pub fn mark_down(&self, node: &Node) -> bool {
+        self.down.lock().unwrap().insert(node.address)
+    }
+
+    /// Marks the node up.
+    pub fn mark_up(&self, node: &Node) {
+        self.down.lock().unwrap().remove(&node.address);
+    }
+}
+
+impl<P: ReconnectionPolicy + 'static> NodeStates<P> {
+    /// Marks the node down and spawns a thread which tries to `connect` to it
+    /// with delays of a new schedule. Once the connection succeeds the node is marked up
+    /// and the thread returns the connection. If the schedule is exhausted the error
+    /// of the last attempt is returned and the node is marked up as well, so it can be
+    /// reconnected with a new schedule once it fails again. `None` is returned
+    /// if the node is already being reconnected or reconnection is stopped (see `stop`).
+    pub fn reconnect<S, F>(states: &Arc<NodeStates<P>>,
+                           node: Node,
+                           mut connect: F)
+                           -> Option<thread::JoinHandle<error::Result<S>>>
+        where S: Send + 'static,
+              F: FnMut(&Node) -> error::Result<S> + Send + 'static
+    {
+        if states.is_stopped() || !states.mark_down(&node) {
+            return None;
+        }
+
+        let states = states.clone();
+        let schedule = states.policy.new_schedule();
+        Some(thread::spawn(move || {
+            let mut last_err = error::Error::General("Reconnection schedule is empty".to_string());
+            for delay in schedule {
+                if !states.wait(delay) {
+                    return Err(error::Error::General("Reconnection is stopped".to_string()));
+                }
+                match connect(&node) {
+                    Ok(connection) => {
+                        states.mark_up(&node);
+                        return Ok(connection);
+                    }
+                    Err(err) => {
+                        warn!("Failed to reconnect to {}: {:?}", node.address, err);
+                        last_err = err;
+                    }
+                }
+            }
+
+            states.mark_up(&node);
+            Err(last_err)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use super::*;
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn exponential_schedule() {
+        let policy = ExponentialReconnectionPolicy::new(millis(100), millis(1000));
+        let delays: Vec<Duration> = policy.new_schedule().take(6).collect();
+        assert_eq!(delays,
+                   vec![millis(100), millis(200), millis(400), millis(800), millis(1000),
+                        millis(1000)]);
+        assert_eq!(policy.new_schedule().nth(100), Some(millis(1000)));
+    }
+
+    #[test]
+    fn constant_schedule() {
+        let policy = ConstantReconnectionPolicy::new(millis(50));
+        let delays: Vec<Duration> = policy.new_schedule().take(3).collect();
+        assert_eq!(delays, vec![millis(50), millis(50), millis(50)]);
+    }
+
+    #[test]
+    fn reconnect_with_backoff() {
+        let node = Node::new("10.0.0.1".parse().unwrap(), "dc1".to_string());
+        let states = Arc::new(NodeStates::new(ExponentialReconnectionPolicy::new(millis(20),
+                                                                                 millis(200))));
+        let start = Instant::now();
+        let attempts = Arc::new(Mutex::new(vec![]));
+        let recorded = attempts.clone();
+        let handle = NodeStates::reconnect(&states, node.clone(), move |_| {
+                let mut attempts = recorded.lock().unwrap();
+                attempts.push(start.elapsed());
+                if attempts.len() < 3 {
+                    Err(error::Error::General("connection refused".to_string()))
+                } else {
+                    Ok(attempts.len())
+                }
+            })
+            .unwrap();
+        assert!(!states.is_up(&node));
+        assert!(NodeStates::reconnect(&states, node.clone(), |_| Ok(())).is_none());
+
+        assert_eq!(handle.join().unwrap().unwrap(), 3);
+        assert!(states.is_up(&node));
+        // attempts are made after 20, 20 + 40 and 20 + 40 + 80 ms
+        let attempts = attempts.lock().unwrap();
+        assert!(attempts[0] >= millis(20));
+        assert!(attempts[1] - attempts[0] >= millis(40));
+        assert!(attempts[2] - attempts[1] >= millis(80));
+    }
+
+    #[test]
+    fn mark_down_and_up() {
+        let node = Node::new("10.0.0.2".parse().unwrap(), "dc1".to_string());
+        let states = NodeStates::new(ConstantReconnectionPolicy::new(millis(1)));
+        assert!(states.mark_down(&node));
+        assert!(!states.mark_down(&node));
+        assert!(!states.is_up(&node));
+        states.mark_up(&node);
+        assert!(states.is_up(&node));
+    }
+
+    // Policy which makes only two reconnection attempts.
+    struct TwoAttempts;
+
+    impl ReconnectionPolicy for TwoAttempts {
+        fn new_schedule(&self) -> ReconnectionSchedule {
+            Box::new(vec![millis(1), millis(1)].into_iter())
+        }
+    }
+
+    #[test]
+    fn reconnect_gives_up() {
+        let node = Node::new("10.0.0.3".parse().unwrap(), "dc1".to_string());
+        let states = Arc::new(NodeStates::new(TwoAttempts));
+        let handle = NodeStates::reconnect(&states, node.clone(), |_| -> error::Result<()> {
+                Err(error::Error::General("connection refused".to_string()))
+            })
+            .unwrap();
+
+        assert!(handle.join().unwrap().is_err());
+        // the node is tried again with a new schedule
+        assert!(states.is_up(&node));
+        assert!(NodeStates::reconnect(&states, node, |_| Ok(())).is_some());
+    }
+
+    #[test]
+    fn stop_reconnection() {
+        let node = Node::new("10.0.0.4".parse().unwrap(), "dc1".to_string());
+        let states = Arc::new(NodeStates::new(ConstantReconnectionPolicy::new(millis(10000))));
+        let handle = NodeStates::reconnect(&states, node.clone(), |_| Ok(())).unwrap();
+
+        let start = Instant::now();
+        states.stop();
+        assert!(handle.join().unwrap().is_err());
+        assert!(start.elapsed() < millis(5000));
+        assert!(NodeStates::reconnect(&states, node, |_| Ok(())).is_none());
+    }
+}
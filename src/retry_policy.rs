@@ -0,0 +1,173 @@
+//! The module contains retry policies which define how a session should react
+//! on timeout and unavailable errors returned by a server.
+use frame::frame_error::{AdditionalErrorInfo, CDRSError, ReadTimeoutError, UnavailableError,
+                         WriteTimeoutError, WriteType};
+
+/// Decision that a retry policy makes about a failed request.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RetryDecision {
+    /// Retry the request on the same host.
+    Retry,
+    /// Retry the request on the next host. Since a session is bound to a single
+    /// connection and knows no other hosts, it retries the request on the same one.
+    RetryNextHost,
+    /// Return the error to a caller.
+    Rethrow,
+    /// Ignore the error, an empty (void) result is returned to a caller.
+    Ignore,
+}
+
+/// Retry policy defines what should be done when a server responds with `ReadTimeout`,
+/// `WriteTimeout` or `Unavailable` error. `retries` is a number of retries
+/// that have already been made for the request.
+pub trait RetryPolicy: Send + Sync {
+    /// Is called when a server responds with `ReadTimeout` error.
+    fn on_read_timeout(&self, err: &ReadTimeoutError, retries: u32) -> RetryDecision;
+
+    /// Is called when a server responds with `WriteTimeout` error.
+    fn on_write_timeout(&self, err: &WriteTimeoutError, retries: u32) -> RetryDecision;
+
+    /// Is called when a server responds with `Unavailable` error.
+    fn on_unavailable(&self, err: &UnavailableError, retries: u32) -> RetryDecision;
+
+    /// Dispatches server error to a method which corresponds to its kind.
+    /// Other errors are always rethrown.
+    fn on_error(&self, err: &CDRSError, retries: u32) -> RetryDecision {
+        match err.additional_info {
+            AdditionalErrorInfo::ReadTimeout(ref err) => self.on_read_timeout(err, retries),
+            AdditionalErrorInfo::WriteTimeout(ref err) => self.on_write_timeout(err, retries),
+            AdditionalErrorInfo::Unavailable(ref err) => self.on_unavailable(err, retries),
+            _ => RetryDecision::Rethrow,
+        }
+    }
+
+    /// Dispatches server error of a request the same way `on_error` does but rethrows
+    /// read and write timeouts of requests which are not `idempotent`: such request might
+    /// have been applied, so retrying it could apply it twice (e.g. a counter update).
+    fn on_request_error(&self, err: &CDRSError, retries: u32, idempotent: bool) -> RetryDecision {
+        match err.additional_info {
+            AdditionalErrorInfo::ReadTimeout(_) |
+            AdditionalErrorInfo::WriteTimeout(_) if !idempotent => RetryDecision::Rethrow,
+            _ => self.on_error(err, retries),
+        }
+    }
+}
+
+/// Default retry policy which behaves the same way as the one of DataStax drivers:
+///
+/// * read timeout is retried once if enough replicas responded but data was not retrieved;
+/// * write timeout is retried once if it happened during writing to the batch log;
+/// * unavailable error is retried once on the next host.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultRetryPolicy;
+
+impl RetryPolicy for DefaultRetryPolicy {
+    fn on_read_timeout(&self, err: &ReadTimeoutError, retries: u32) -> RetryDecision {
+        if retries == 0 && err.received >= err.blockfor && !err.replica_has_responded() {
+            return RetryDecision::Retry;
+        }
+
+        RetryDecision::Rethrow
+    }
+
+    fn on_write_timeout(&self, err: &WriteTimeoutError, retries: u32) -> RetryDecision {
+        if retries == 0 && err.write_type == WriteType::BatchLog {
+            return RetryDecision::Retry;
+        }
+
+        RetryDecision::Rethrow
+    }
+
+    fn on_unavailable(&self, _: &UnavailableError, retries: u32) -> RetryDecision {
+        if retries == 0 {
+            return RetryDecision::RetryNextHost;
+        }
+
+        RetryDecision::Rethrow
+    }
+}
+
+/// Retry policy which never retries and returns all errors to a caller.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FallthroughRetryPolicy;
+
+impl RetryPolicy for FallthroughRetryPolicy {
+    fn on_read_timeout(&self, _: &ReadTimeoutError, _: u32) -> RetryDecision {
+        RetryDecision::Rethrow
+    }
+
+    fn on_write_timeout(&self, _: &WriteTimeoutError, _: u32) -> RetryDecision {
+        RetryDecision::Rethrow
+    }
+
+    fn on_unavailable(&self, _: &UnavailableError, _: u32) -> RetryDecision {
+        RetryDecision::Rethrow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use FromCursor;
+    use super::*;
+
+    fn read_timeout(received: u8, blockfor: u8, data_present: u8) -> ReadTimeoutError {
+        let bytes = [0, 4, 0, 0, 0, received, 0, 0, 0, blockfor, data_present];
+        ReadTimeoutError::from_cursor(&mut Cursor::new(&bytes[..]))
+    }
+
+    fn write_timeout(write_type: &str) -> WriteTimeoutError {
+        let mut bytes = vec![0, 4, 0, 0, 0, 1, 0, 0, 0, 2, 0, write_type.len() as u8];
+        bytes.extend_from_slice(write_type.as_bytes());
+        WriteTimeoutError::from_cursor(&mut Cursor::new(bytes.as_slice()))
+    }
+
+    #[test]
+    fn default_on_read_timeout() {
+        let policy = DefaultRetryPolicy;
+        assert_eq!(policy.on_read_timeout(&read_timeout(2, 2, 0), 0),
+                   RetryDecision::Retry);
+        assert_eq!(policy.on_read_timeout(&read_timeout(2, 2, 0), 1),
+                   RetryDecision::Rethrow);
+        assert_eq!(policy.on_read_timeout(&read_timeout(1, 2, 0), 0),
+                   RetryDecision::Rethrow);
+        assert_eq!(policy.on_read_timeout(&read_timeout(2, 2, 1), 0),
+                   RetryDecision::Rethrow);
+    }
+
+    #[test]
+    fn default_on_write_timeout() {
+        let policy = DefaultRetryPolicy;
+        assert_eq!(policy.on_write_timeout(&write_timeout("BATCH_LOG"), 0),
+                   RetryDecision::Retry);
+        assert_eq!(policy.on_write_timeout(&write_timeout("BATCH_LOG"), 1),
+                   RetryDecision::Rethrow);
+        assert_eq!(policy.on_write_timeout(&write_timeout("SIMPLE"), 0),
+                   RetryDecision::Rethrow);
+    }
+
+    #[test]
+    fn timeouts_of_non_idempotent_requests() {
+        let mut bytes = vec![0, 0, 0x11, 0, 0, 0];
+        bytes.extend_from_slice(&[0, 4, 0, 0, 0, 1, 0, 0, 0, 2, 0, 9]);
+        bytes.extend_from_slice(b"BATCH_LOG");
+        let err = CDRSError::from_cursor(&mut Cursor::new(bytes.as_slice()));
+        let policy = DefaultRetryPolicy;
+        assert_eq!(policy.on_request_error(&err, 0, true), RetryDecision::Retry);
+        assert_eq!(policy.on_request_error(&err, 0, false), RetryDecision::Rethrow);
+
+        let bytes = [0, 0, 0x10, 0, 0, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0, 1];
+        let err = CDRSError::from_cursor(&mut Cursor::new(&bytes[..]));
+        // unavailable request was not applied, so it's always safe to retry it
+        assert_eq!(policy.on_request_error(&err, 0, false), RetryDecision::RetryNextHost);
+    }
+
+    #[test]
+    fn default_on_unavailable() {
+        let policy = DefaultRetryPolicy;
+        let bytes = [0, 4, 0, 0, 0, 2, 0, 0, 0, 1];
+        let err = UnavailableError::from_cursor(&mut Cursor::new(&bytes[..]));
+        assert_eq!(policy.on_unavailable(&err, 0), RetryDecision::RetryNextHost);
+        assert_eq!(policy.on_unavailable(&err, 1), RetryDecision::Rethrow);
+    }
+}
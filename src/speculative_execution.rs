@@ -0,0 +1,265 @@
+//! The module contains speculative execution policies. Speculative execution sends
+//! the same request to the next node of a query plan if the previous one doesn't respond
+//! within some time and uses the response which comes first. It cuts tail latency
+//! at cost of extra load, so only idempotent requests are executed speculatively.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use error;
+use load_balancing::Node;
+
+/// Delays of speculative executions. Each delay is counted since the previous
+/// execution has been started.
+pub type SpeculativeExecutionPlan = Box<Iterator<Item = Duration> + Send>;
+
+/// Speculative execution policy defines when speculative executions of a request
+/// should be started.
+pub trait SpeculativeExecutionPolicy: Send + Sync {
+    /// Returns delays of speculative executions of a request. The initial execution
+    /// isn't included.
+    fn new_plan(&self) -> SpeculativeExecutionPlan;
+}
+
+/// Policy which starts a speculative execution each `delay` until there are
+/// `max_executions` executions in total (the initial one included).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantSpeculativeExecutionPolicy {
+    pub delay: Duration,
+    pub max_executions: usize,
+}
+
+impl ConstantSpeculativeExecutionPolicy {
+    pub fn new(delay: Duration, max_executions: usize) -> ConstantSpeculativeExecutionPolicy {
+        ConstantSpeculativeExecutionPolicy {
+            delay: delay,
+            max_executions: max_executions,
+        }
+    }
+}
+
+impl SpeculativeExecutionPolicy for ConstantSpeculativeExecutionPolicy {
+    fn new_plan(&self) -> SpeculativeExecutionPlan {
+        let delay = self.delay;
+        let speculative = self.max_executions.saturating_sub(1);
+        Box::new((0..speculative).map(move |_| delay))
+    }
+}
+
+/// Policy which never starts speculative executions.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoSpeculativeExecutionPolicy;
+
+impl SpeculativeExecutionPolicy for NoSpeculativeExecutionPolicy {
+    fn new_plan(&self) -> SpeculativeExecutionPlan {
+        Box::new(None.into_iter())
+    }
+}
+
+/// Cancellation of speculative executions of a request. Once a response is returned
+/// by `execute_speculatively` the executions which are still running are cancelled, they
+/// should give up as soon as possible, e.g. not send a request if it's not sent yet.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    /// Returns `true` if the execution is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns an error if the execution is cancelled.
+    pub fn check(&self) -> error::Result<()> {
+        if self.is_cancelled() {
+            return Err(error::Error::General("Speculative execution is cancelled".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Executes a request via `execute` on nodes of a query plan. The first node is tried
+/// right away, next ones are tried in accordance to speculative execution plan
+/// of `policy` while there is no successful response yet. A failed execution starts
+/// the next one immediately. The first successful response is returned, responses
+/// of other executions are discarded and executions which haven't been started yet
+/// are never started. Executions which are still running are cancelled via
+/// `Cancellation` passed to `execute`. The error of the last execution is returned
+/// if all of them fail.
+///
+/// Non `idempotent` requests are executed only once on the first node.
+pub fn execute_speculatively<P, T, F>(policy: &P,
+                                      idempotent: bool,
+                                      nodes: Vec<Node>,
+                                      execute: F)
+                                      -> error::Result<T>
+    where P: SpeculativeExecutionPolicy + ?Sized,
+          T: Send + 'static,
+          F: Fn(&Node, &Cancellation) -> error::Result<T> + Send + Sync + 'static
+{
+    let cancellation = Cancellation::default();
+    let result = execute_plan(policy, idempotent, nodes, execute, &cancellation);
+    cancellation.cancel();
+    result
+}
+
+fn execute_plan<P, T, F>(policy: &P,
+                         idempotent: bool,
+                         nodes: Vec<Node>,
+                         execute: F,
+                         cancellation: &Cancellation)
+                         -> error::Result<T>
+    where P: SpeculativeExecutionPolicy + ?Sized,
+          T: Send + 'static,
+          F: Fn(&Node, &Cancellation) -> error::Result<T> + Send + Sync + 'static
+{
+    let mut nodes = nodes.into_iter();
+    let mut plan: SpeculativeExecutionPlan = if idempotent {
+        policy.new_plan()
+    } else {
+        Box::new(None.into_iter())
+    };
+    let execute = Arc::new(execute);
+    let (sender, receiver) = mpsc::channel();
+    let start = |node: Node| {
+        let execute = execute.clone();
+        let sender = sender.clone();
+        let cancellation = cancellation.clone();
+        thread::spawn(move || {
+            // the receiver is gone if another execution has already succeeded
+            let _ = sender.send(execute(&node, &cancellation));
+        });
+    };
+
+    let mut running = 0;
+    let mut last_err = error::Error::General("Query plan is empty".to_string());
+    if let Some(node) = nodes.next() {
+        start(node);
+        running += 1;
+    }
+    let mut next_delay = plan.next();
+
+    while running > 0 {
+        let result = match next_delay {
+            Some(delay) => receiver.recv_timeout(delay),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        let start_next = match result {
+            Ok(Ok(response)) => return Ok(response),
+            Ok(Err(err)) => {
+                running -= 1;
+                last_err = err;
+                next_delay.is_some()
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if start_next {
+            match nodes.next() {
+                Some(node) => {
+                    start(node);
+                    running += 1;
+                    next_delay = plan.next();
+                }
+                None => next_delay = None,
+            }
+        }
+    }
+
+    Err(last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net;
+    use std::sync::Mutex;
+    use std::time::Instant;
+    use super::*;
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn nodes() -> Vec<Node> {
+        vec![Node::new("10.0.0.1".parse().unwrap(), "dc1".to_string()),
+             Node::new("10.0.0.2".parse().unwrap(), "dc1".to_string()),
+             Node::new("10.0.0.3".parse().unwrap(), "dc1".to_string())]
+    }
+
+    // Responds with the last octet of node address; the first node responds slowly.
+    fn slow_first_node(node: &Node, _cancellation: &Cancellation) -> error::Result<u8> {
+        let octet = match node.address {
+            net::IpAddr::V4(address) => address.octets()[3],
+            net::IpAddr::V6(_) => unreachable!(),
+        };
+        if octet == 1 {
+            thread::sleep(millis(500));
+        }
+        Ok(octet)
+    }
+
+    #[test]
+    fn constant_plan() {
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(10), 3);
+        assert_eq!(policy.new_plan().collect::<Vec<_>>(), vec![millis(10), millis(10)]);
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(10), 0);
+        assert_eq!(policy.new_plan().count(), 0);
+        assert_eq!(NoSpeculativeExecutionPolicy.new_plan().count(), 0);
+    }
+
+    #[test]
+    fn speculative_execution_wins() {
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(20), 2);
+        let started = Instant::now();
+        let response = execute_speculatively(&policy, true, nodes(), slow_first_node);
+        assert_eq!(response.unwrap(), 2);
+        assert!(started.elapsed() < millis(500));
+    }
+
+    #[test]
+    fn non_idempotent_is_not_speculated() {
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(20), 2);
+        let response = execute_speculatively(&policy, false, nodes(), slow_first_node);
+        assert_eq!(response.unwrap(), 1);
+    }
+
+    #[test]
+    fn failed_executions() {
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(1000), 2);
+        let tried = Arc::new(Mutex::new(vec![]));
+        let recorded = tried.clone();
+        let response = execute_speculatively(&policy, true, nodes(), move |node, _| {
+            recorded.lock().unwrap().push(node.address);
+            Err(error::Error::General("unavailable".to_string())) as error::Result<()>
+        });
+        assert!(response.is_err());
+        // a failure starts the next execution right away, the third one isn't planned
+        assert_eq!(tried.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn losing_execution_is_cancelled() {
+        let policy = ConstantSpeculativeExecutionPolicy::new(millis(20), 2);
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let response = execute_speculatively(&policy, true, nodes(), move |node, cancellation| {
+            let response = slow_first_node(node, cancellation);
+            if node.address == nodes()[0].address {
+                sender.lock().unwrap().send(cancellation.check()).unwrap();
+            }
+            response
+        });
+        assert_eq!(response.unwrap(), 2);
+        assert!(receiver.recv().unwrap().is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! The module contains generators of client-side timestamps which are sent along with
+//! queries when the timestamp is not provided explicitly.
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Timestamp generator provides default timestamps (in microseconds since unix epoch)
+/// of queries which are sent with `WithDefaultTimestamp` flag.
+pub trait TimestampGenerator: Send + Sync {
+    /// Returns next timestamp in microseconds.
+    fn next_timestamp(&self) -> i64;
+}
+
+/// Timestamp generator which is based on system clock and guarantees that generated
+/// timestamps are strictly increasing even if the clock goes backwards or few
+/// timestamps are requested within the same microsecond.
+#[derive(Debug, Default)]
+pub struct MonotonicTimestampGenerator {
+    last: Mutex<i64>,
+}
+
+impl MonotonicTimestampGenerator {
+    /// Creates new `MonotonicTimestampGenerator`.
+    pub fn new() -> MonotonicTimestampGenerator {
+        MonotonicTimestampGenerator { last: Mutex::new(0) }
+    }
+}
+
+impl TimestampGenerator for MonotonicTimestampGenerator {
+    fn next_timestamp(&self) -> i64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64 * 1_000_000 + d.subsec_nanos() as i64 / 1_000)
+            .unwrap_or(0);
+        let mut last = self.last.lock().unwrap();
+        *last = if now > *last { now } else { *last + 1 };
+
+        *last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monotonic_timestamps_increase() {
+        let generator = MonotonicTimestampGenerator::new();
+        let mut last = generator.next_timestamp();
+        assert!(last > 0);
+        for _ in 0..1000 {
+            let next = generator.next_timestamp();
+            assert!(next > last);
+            last = next;
+        }
+    }
+}
@@ -1,17 +1,149 @@
+use std::cmp;
 use std::io;
 use std::io::{Read, Write};
 use std::net;
-use std::net::TcpStream;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use byteorder::{BigEndian, ByteOrder};
+use frame::{HEADER_LEN, LENGTH_LEN};
 #[cfg(feature = "ssl")]
 use openssl::ssl::{SslStream, SslConnector};
+#[cfg(feature = "rust-tls")]
+use std::sync::Arc;
+#[cfg(feature = "rust-tls")]
+use rustls::{Certificate, ClientConfig, ClientSession, PrivateKey, RootCertStore, Session,
+             StreamOwned};
+#[cfg(feature = "rust-tls")]
+use webpki::DNSNameRef;
 
 pub trait CDRSTransport: Sized + Read + Write + Send + Sync {
     fn try_clone(&self) -> io::Result<Self>;
     fn close(&mut self, close: net::Shutdown) -> io::Result<()>;
+
+    /// Sets a timeout of reads from the transport, `None` means that reads block
+    /// indefinitely. Transports which don't support timeouts return an error.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "Read timeouts are not supported"))
+    }
+
+    /// Returns a timeout of reads from the transport.
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Returns another handle of the same connection, so that frames could be read
+    /// from it while the transport is written to by another thread. Unlike `try_clone`
+    /// it doesn't open a new connection. Transports which cannot be shared between
+    /// a reader and a writer return an error.
+    fn try_clone_reader(&self) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Other, "Dedicated readers are not supported"))
+    }
+}
+
+// Tracks boundaries of frames which are being read, so that a read timeout is applied
+// to a whole frame rather than to a single read from a socket.
+#[derive(Debug, Default)]
+struct FrameTimer {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    header: Vec<u8>,
+    body_left: usize,
+}
+
+impl FrameTimer {
+    // Returns time left to read current frame. The frame is started if it's not yet.
+    fn time_left(&mut self) -> io::Result<Option<Duration>> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        let deadline = *self.deadline.get_or_insert_with(|| Instant::now() + timeout);
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(timed_out_err());
+        }
+
+        Ok(Some(deadline - now))
+    }
+
+    // Accounts bytes which were read. Once the whole frame is read the timer is reset.
+    fn consume(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.header.len() < HEADER_LEN {
+                let n = cmp::min(HEADER_LEN - self.header.len(), bytes.len());
+                self.header.extend_from_slice(&bytes[..n]);
+                bytes = &bytes[n..];
+                if self.header.len() < HEADER_LEN {
+                    continue;
+                }
+                self.body_left = BigEndian::read_u32(&self.header[HEADER_LEN - LENGTH_LEN..]) as
+                                 usize;
+            } else {
+                let n = cmp::min(self.body_left, bytes.len());
+                self.body_left -= n;
+                bytes = &bytes[n..];
+            }
+
+            if self.body_left == 0 {
+                self.header.clear();
+                self.deadline = None;
+            }
+        }
+    }
+}
+
+fn timed_out_err() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Timeout elapsed")
+}
+
+// Socket timeouts are reported as `WouldBlock` on some platforms.
+fn map_timeout_err(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => timed_out_err(),
+        _ => err,
+    }
+}
+
+/// Resolver of contact points, i.e. `host:port` strings, into socket addresses.
+pub trait Resolver {
+    /// Returns all addresses `addr` resolves to in order they should be tried.
+    fn resolve(&self, addr: &str) -> io::Result<Vec<net::SocketAddr>>;
+}
+
+/// Resolver which is based on system name resolution, so a hostname is resolved
+/// to all its A and AAAA records.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DnsResolver;
+
+impl Resolver for DnsResolver {
+    fn resolve(&self, addr: &str) -> io::Result<Vec<net::SocketAddr>> {
+        addr.to_socket_addrs().map(|addrs| addrs.collect())
+    }
+}
+
+/// Resolves `addr` with `resolver` and connects to resolved addresses in order
+/// until a connection succeeds, so a contact point is reachable while at least one
+/// of its addresses is. The error of the last address is returned if all of them fail.
+pub fn connect_tcp<R: Resolver + ?Sized>(addr: &str, resolver: &R) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("{} is not resolved to any address", addr));
+    for socket_addr in try!(resolver.resolve(addr)) {
+        match TcpStream::connect(socket_addr) {
+            Ok(tcp) => return Ok(tcp),
+            Err(err) => {
+                warn!("Failed to connect to {} ({}): {}", socket_addr, addr, err);
+                last_err = err;
+            }
+        }
+    }
+
+    Err(last_err)
 }
 
 pub struct TransportTcp {
     tcp: TcpStream,
+    read_timer: FrameTimer,
+    write_timeout: Option<Duration>,
 }
 
 impl TransportTcp {
@@ -25,19 +157,60 @@ impl TransportTcp {
     /// let tcp_transport = TransportTcp::new(addr).unwrap();
     /// ```
     pub fn new(addr: &str) -> io::Result<TransportTcp> {
-        TcpStream::connect(addr).map(|socket| TransportTcp { tcp: socket })
+        TransportTcp::with_resolver(addr, &DnsResolver)
+    }
+
+    /// Constructs a new `TransportTcp` connected to the first available address
+    /// which `addr` is resolved to by `resolver` (see `connect_tcp`).
+    pub fn with_resolver<R: Resolver + ?Sized>(addr: &str,
+                                               resolver: &R)
+                                               -> io::Result<TransportTcp> {
+        connect_tcp(addr, resolver).map(TransportTcp::from_stream)
+    }
+
+    fn from_stream(tcp: TcpStream) -> TransportTcp {
+        TransportTcp {
+            tcp: tcp,
+            read_timer: FrameTimer::default(),
+            write_timeout: None,
+        }
+    }
+
+    /// Sets a timeout of reading a single frame. If the frame is not read completely
+    /// within the timeout an error of kind `io::ErrorKind::TimedOut` is returned.
+    /// `None` (default) means that reads block indefinitely.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        try!(self.tcp.set_read_timeout(timeout));
+        self.read_timer.timeout = timeout;
+        self.read_timer.deadline = None;
+        Ok(())
+    }
+
+    /// Sets a timeout of writes to the socket. If a write is not completed within
+    /// the timeout an error of kind `io::ErrorKind::TimedOut` is returned.
+    /// `None` (default) means that writes block indefinitely.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        try!(self.tcp.set_write_timeout(timeout));
+        self.write_timeout = timeout;
+        Ok(())
     }
 }
 
 impl Read for TransportTcp {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.tcp.read(buf)
+        if let Some(time_left) = try!(self.read_timer.time_left()) {
+            try!(self.tcp.set_read_timeout(Some(time_left)));
+        }
+
+        let n = try!(self.tcp.read(buf).map_err(map_timeout_err));
+        self.read_timer.consume(&buf[..n]);
+        Ok(n)
     }
 }
 
 impl Write for TransportTcp {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.tcp.write(buf)
+        self.tcp.write(buf).map_err(map_timeout_err)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -49,16 +222,100 @@ impl CDRSTransport for TransportTcp {
     /// In opposite to `TcpStream`'s `try_clone` this method
     /// creates absolutely new connection - it gets an address
     /// of a peer from `Transport` and creates a new encrypted
-    /// transport with new TCP stream under hood.
+    /// transport with new TCP stream under hood. Timeouts are
+    /// copied to the new transport.
     fn try_clone(&self) -> io::Result<TransportTcp> {
         let addr = try!(self.tcp.peer_addr());
-        TcpStream::connect(addr).map(|socket| TransportTcp { tcp: socket })
-
+        let mut transport = try!(TcpStream::connect(addr).map(TransportTcp::from_stream));
+        try!(transport.set_read_timeout(self.read_timer.timeout));
+        try!(transport.set_write_timeout(self.write_timeout));
+        Ok(transport)
     }
 
     fn close(&mut self, close: net::Shutdown) -> io::Result<()> {
         self.tcp.shutdown(close)
     }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        TransportTcp::set_read_timeout(self, timeout)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.read_timer.timeout)
+    }
+
+    fn try_clone_reader(&self) -> io::Result<TransportTcp> {
+        let mut reader = TransportTcp::from_stream(try!(self.tcp.try_clone()));
+        // socket timeouts are shared by both handles
+        reader.read_timer.timeout = self.read_timer.timeout;
+        reader.write_timeout = self.write_timeout;
+        Ok(reader)
+    }
+}
+
+/// Transport which is used in tests instead of a real connection. It returns
+/// prepared `input` bytes on reads and stores everything written into it.
+#[cfg(test)]
+pub struct TransportMock {
+    input: io::Cursor<Vec<u8>>,
+    /// Written chunks of bytes.
+    pub written: Vec<Vec<u8>>,
+    /// `true` once the transport is closed.
+    pub closed: bool,
+    /// Read timeouts which were set, reads never time out though.
+    pub read_timeouts: Vec<Option<Duration>>,
+}
+
+#[cfg(test)]
+impl TransportMock {
+    pub fn new(input: Vec<u8>) -> TransportMock {
+        TransportMock {
+            input: io::Cursor::new(input),
+            written: vec![],
+            closed: false,
+            read_timeouts: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+impl Read for TransportMock {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+#[cfg(test)]
+impl Write for TransportMock {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CDRSTransport for TransportMock {
+    fn try_clone(&self) -> io::Result<TransportMock> {
+        Err(io::Error::new(io::ErrorKind::Other, "mock transport cannot be cloned"))
+    }
+
+    fn close(&mut self, _close: net::Shutdown) -> io::Result<()> {
+        self.closed = true;
+        Ok(())
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.read_timeouts.push(timeout);
+        Ok(())
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.read_timeouts.last().cloned().unwrap_or(None))
+    }
 }
 
 /// **********************************
@@ -73,7 +330,7 @@ pub struct TransportTls {
 impl TransportTls {
     pub fn new(addr: &str, connector: &SslConnector) -> io::Result<TransportTls> {
         let a: Vec<&str> = addr.split(':').collect();
-        let res = net::TcpStream::connect(addr).map(|socket| {
+        let res = connect_tcp(addr, &DnsResolver).map(|socket| {
             connector.connect(a[0], socket)
                 .map(|sslsocket| {
                     TransportTls {
@@ -138,4 +395,231 @@ impl CDRSTransport for TransportTls {
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
             .and_then(|_| Ok(()))
     }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.ssl.get_ref().set_read_timeout(timeout)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.ssl.get_ref().read_timeout()
+    }
+}
+
+/// **********************************
+/** rustls TLS**/
+/// ***********************************
+/// TLS transport which is based on rustls.
+#[cfg(feature = "rust-tls")]
+pub struct TransportRustls {
+    stream: StreamOwned<ClientSession, TcpStream>,
+    config: Arc<ClientConfig>,
+    dns_name: String,
+}
+
+#[cfg(feature = "rust-tls")]
+impl TransportRustls {
+    /// Constructs a new `TransportRustls`. `dns_name` is a name which a certificate
+    /// of the server is verified against. TLS handshake is performed with the first
+    /// read or write.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate cdrs;
+    /// extern crate rustls;
+    /// # fn main() {
+    /// use cdrs::transport::{TransportRustls, new_rustls_config};
+    /// let config = new_rustls_config(rustls::RootCertStore::empty(), None).unwrap();
+    /// let tls_transport = TransportRustls::new("127.0.0.1:9042", "localhost", config).unwrap();
+    /// # }
+    /// ```
+    pub fn new(addr: &str,
+               dns_name: &str,
+               config: Arc<ClientConfig>)
+               -> io::Result<TransportRustls> {
+        let tcp = try!(connect_tcp(addr, &DnsResolver));
+        let session = {
+            let name = try!(DNSNameRef::try_from_ascii_str(dns_name).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput,
+                               format!("Invalid DNS name {}", dns_name))
+            }));
+            ClientSession::new(&config, name)
+        };
+
+        Ok(TransportRustls {
+            stream: StreamOwned::new(session, tcp),
+            config: config,
+            dns_name: dns_name.to_string(),
+        })
+    }
+}
+
+/// Creates rustls client configuration which trusts server certificates signed by
+/// `ca_roots`. If `client_cert` (a certificate chain and a private key) is provided
+/// it's used to authenticate the client, i.e. for mutual TLS.
+#[cfg(feature = "rust-tls")]
+pub fn new_rustls_config(ca_roots: RootCertStore,
+                         client_cert: Option<(Vec<Certificate>, PrivateKey)>)
+                         -> io::Result<Arc<ClientConfig>> {
+    let mut config = ClientConfig::new();
+    config.root_store = ca_roots;
+    if let Some((certs, key)) = client_cert {
+        try!(config.set_single_client_cert(certs, key)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+    }
+
+    Ok(Arc::new(config))
+}
+
+#[cfg(feature = "rust-tls")]
+impl Read for TransportRustls {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+#[cfg(feature = "rust-tls")]
+impl Write for TransportRustls {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(feature = "rust-tls")]
+impl CDRSTransport for TransportRustls {
+    /// Creates absolutely new TLS connection to the same peer using the same
+    /// configuration.
+    fn try_clone(&self) -> io::Result<TransportRustls> {
+        let addr = try!(self.stream.sock.peer_addr());
+        TransportRustls::new(addr.to_string().as_str(),
+                             self.dns_name.as_str(),
+                             self.config.clone())
+    }
+
+    fn close(&mut self, close: net::Shutdown) -> io::Result<()> {
+        self.stream.sess.send_close_notify();
+        try!(self.stream.flush());
+        self.stream.sock.shutdown(close)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.sock.set_read_timeout(timeout)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.stream.sock.read_timeout()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use compression::Compression;
+    use error;
+    use frame::parser::parse_frame;
+    use super::*;
+
+    // Starts a server which sends a header of a frame with 10 bytes body and then sends
+    // `body_bytes` of the body one by one with provided `delay`.
+    fn slow_server(body_bytes: usize, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            socket.write_all(&[0x84, 0, 0, 0, 0x02, 0, 0, 0, 10]).unwrap();
+            for _ in 0..body_bytes {
+                thread::sleep(delay);
+                socket.write_all(&[0]).unwrap();
+            }
+            thread::sleep(Duration::from_secs(3));
+        });
+        addr
+    }
+
+    fn assert_timed_out(result: error::Result<::frame::Frame>) {
+        match result {
+            Err(error::Error::Io(ref err)) if err.kind() == io::ErrorKind::TimedOut => (),
+            other => panic!("timeout is expected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn read_timeout() {
+        let addr = slow_server(0, Duration::from_millis(0));
+        let mut transport = TransportTcp::new(addr.as_str()).unwrap();
+        transport.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+
+        let started = Instant::now();
+        assert_timed_out(parse_frame(&mut transport, &Compression::None));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn read_timeout_is_applied_per_frame() {
+        // each byte comes within the timeout but the whole frame does not
+        let addr = slow_server(10, Duration::from_millis(100));
+        let mut transport = TransportTcp::new(addr.as_str()).unwrap();
+        transport.set_read_timeout(Some(Duration::from_millis(400))).unwrap();
+
+        let started = Instant::now();
+        assert_timed_out(parse_frame(&mut transport, &Compression::None));
+        assert!(started.elapsed() < Duration::from_millis(900));
+    }
+
+    #[test]
+    fn frame_timer_resets_after_frame() {
+        let mut timer = FrameTimer::default();
+        timer.timeout = Some(Duration::from_secs(1));
+        timer.time_left().unwrap();
+        // a frame with 2 bytes body and the beginning of the next frame
+        timer.consume(&[0x84, 0, 0, 0, 0x08, 0, 0, 0, 2, 1]);
+        assert!(timer.deadline.is_some());
+        timer.consume(&[2, 0x84]);
+        assert!(timer.deadline.is_none());
+        assert_eq!(timer.header, vec![0x84]);
+    }
+
+    // Resolver stub which resolves any contact point to provided addresses.
+    struct StubResolver(Vec<net::SocketAddr>);
+
+    impl Resolver for StubResolver {
+        fn resolve(&self, _addr: &str) -> io::Result<Vec<net::SocketAddr>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    // Returns an address nobody listens to, so a connection to it is refused.
+    fn refusing_addr() -> net::SocketAddr {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    #[test]
+    fn connect_tries_all_resolved_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let available = listener.local_addr().unwrap();
+        let resolver = StubResolver(vec![refusing_addr(), available]);
+
+        let transport = TransportTcp::with_resolver("cassandra.local:9042", &resolver).unwrap();
+        assert_eq!(transport.tcp.peer_addr().unwrap(), available);
+    }
+
+    #[test]
+    fn connect_fails_if_all_addresses_fail() {
+        let resolver = StubResolver(vec![refusing_addr(), refusing_addr()]);
+        let err = connect_tcp("cassandra.local:9042", &resolver).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        let err = connect_tcp("cassandra.local:9042", &StubResolver(vec![])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
 }
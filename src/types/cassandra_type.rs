@@ -0,0 +1,331 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::net::IpAddr;
+use uuid::Uuid;
+use types::data_serialization_types::{Decimal, Duration};
+
+/// Decoded Cassandra value. It allows to hold values of different CQL types
+/// (e.g. elements of a collection) in a generic way.
+///
+/// Values implement `Eq` and `Hash`, so decoded values (e.g. frozen collections of
+/// a primary key) can be used as keys of a `HashMap`. `Double` and `Float` values are
+/// compared and hashed by their bit patterns rather than by IEEE 754 rules, i.e. NaN
+/// is equal to NaN with the same bits while `0.0` is not equal to `-0.0`.
+///
+/// `Display` renders values in a form close to CQL literals which is handy for logging,
+/// e.g. `{'a': [1, 2]}` for `map<text, list<int>>`.
+#[derive(Debug, Clone)]
+pub enum CassandraType {
+    /// Value of a custom type. The payload is not decoded, it should be interpreted
+    /// according to the Java class name of the type, e.g.
+    /// `org.apache.cassandra.db.marshal.DateRangeType`.
+    Custom { class_name: String, bytes: Vec<u8> },
+    Ascii(String),
+    Bigint(i64),
+    Blob(Vec<u8>),
+    Boolean(bool),
+    Counter(i64),
+    Decimal(Decimal),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Timestamp(i64),
+    Uuid(Uuid),
+    Varchar(String),
+    Varint(i64),
+    Timeuuid(Uuid),
+    Inet(IpAddr),
+    Date(i32),
+    Time(i64),
+    Smallint(i16),
+    Tinyint(i8),
+    Duration(Duration),
+    List(Vec<CassandraType>),
+    Set(Vec<CassandraType>),
+    /// Map entries in the order they were received.
+    Map(Vec<(CassandraType, CassandraType)>),
+    /// UDT fields as pairs `(name, value)` in the order of UDT definition.
+    Udt(Vec<(String, CassandraType)>),
+    /// Tuple elements in the order of tuple definition.
+    Tuple(Vec<CassandraType>),
+    /// NULL value, i.e. a value with negative length.
+    Null,
+    /// Placeholder of a value of `column` which failed to be decoded. It's produced
+    /// instead of an error by rows which are decoded leniently (see
+    /// `RowIterator::decode_lenient`).
+    DecodeError { column: String, error: String },
+}
+
+impl PartialEq for CassandraType {
+    fn eq(&self, other: &CassandraType) -> bool {
+        match (self, other) {
+            (&CassandraType::Custom { class_name: ref a, bytes: ref a_bytes },
+             &CassandraType::Custom { class_name: ref b, bytes: ref b_bytes }) => {
+                a == b && a_bytes == b_bytes
+            }
+            (&CassandraType::Ascii(ref a), &CassandraType::Ascii(ref b)) => a == b,
+            (&CassandraType::Bigint(a), &CassandraType::Bigint(b)) => a == b,
+            (&CassandraType::Blob(ref a), &CassandraType::Blob(ref b)) => a == b,
+            (&CassandraType::Boolean(a), &CassandraType::Boolean(b)) => a == b,
+            (&CassandraType::Counter(a), &CassandraType::Counter(b)) => a == b,
+            (&CassandraType::Decimal(a), &CassandraType::Decimal(b)) => a == b,
+            (&CassandraType::Double(a), &CassandraType::Double(b)) => a.to_bits() == b.to_bits(),
+            (&CassandraType::Float(a), &CassandraType::Float(b)) => a.to_bits() == b.to_bits(),
+            (&CassandraType::Int(a), &CassandraType::Int(b)) => a == b,
+            (&CassandraType::Timestamp(a), &CassandraType::Timestamp(b)) => a == b,
+            (&CassandraType::Uuid(ref a), &CassandraType::Uuid(ref b)) => a == b,
+            (&CassandraType::Varchar(ref a), &CassandraType::Varchar(ref b)) => a == b,
+            (&CassandraType::Varint(a), &CassandraType::Varint(b)) => a == b,
+            (&CassandraType::Timeuuid(ref a), &CassandraType::Timeuuid(ref b)) => a == b,
+            (&CassandraType::Inet(ref a), &CassandraType::Inet(ref b)) => a == b,
+            (&CassandraType::Date(a), &CassandraType::Date(b)) => a == b,
+            (&CassandraType::Time(a), &CassandraType::Time(b)) => a == b,
+            (&CassandraType::Smallint(a), &CassandraType::Smallint(b)) => a == b,
+            (&CassandraType::Tinyint(a), &CassandraType::Tinyint(b)) => a == b,
+            (&CassandraType::Duration(a), &CassandraType::Duration(b)) => a == b,
+            (&CassandraType::List(ref a), &CassandraType::List(ref b)) => a == b,
+            (&CassandraType::Set(ref a), &CassandraType::Set(ref b)) => a == b,
+            (&CassandraType::Map(ref a), &CassandraType::Map(ref b)) => a == b,
+            (&CassandraType::Udt(ref a), &CassandraType::Udt(ref b)) => a == b,
+            (&CassandraType::Tuple(ref a), &CassandraType::Tuple(ref b)) => a == b,
+            (&CassandraType::Null, &CassandraType::Null) => true,
+            (&CassandraType::DecodeError { column: ref a, error: ref a_error },
+             &CassandraType::DecodeError { column: ref b, error: ref b_error }) => {
+                a == b && a_error == b_error
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for CassandraType {}
+
+impl Hash for CassandraType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            &CassandraType::Custom { ref class_name, ref bytes } => {
+                class_name.hash(state);
+                bytes.hash(state);
+            }
+            &CassandraType::Ascii(ref v) |
+            &CassandraType::Varchar(ref v) => v.hash(state),
+            &CassandraType::Bigint(v) |
+            &CassandraType::Counter(v) |
+            &CassandraType::Timestamp(v) |
+            &CassandraType::Varint(v) |
+            &CassandraType::Time(v) => v.hash(state),
+            &CassandraType::Blob(ref v) => v.hash(state),
+            &CassandraType::Boolean(v) => v.hash(state),
+            &CassandraType::Decimal(v) => v.hash(state),
+            &CassandraType::Float(v) => v.to_bits().hash(state),
+            &CassandraType::Double(v) => v.to_bits().hash(state),
+            &CassandraType::Int(v) |
+            &CassandraType::Date(v) => v.hash(state),
+            &CassandraType::Uuid(ref v) |
+            &CassandraType::Timeuuid(ref v) => v.hash(state),
+            &CassandraType::Inet(ref v) => v.hash(state),
+            &CassandraType::Smallint(v) => v.hash(state),
+            &CassandraType::Tinyint(v) => v.hash(state),
+            &CassandraType::Duration(v) => v.hash(state),
+            &CassandraType::List(ref v) |
+            &CassandraType::Set(ref v) |
+            &CassandraType::Tuple(ref v) => v.hash(state),
+            &CassandraType::Map(ref v) => v.hash(state),
+            &CassandraType::Udt(ref v) => v.hash(state),
+            &CassandraType::Null => (),
+            &CassandraType::DecodeError { ref column, ref error } => {
+                column.hash(state);
+                error.hash(state);
+            }
+        }
+    }
+}
+
+impl fmt::Display for CassandraType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &CassandraType::Ascii(ref v) |
+            &CassandraType::Varchar(ref v) => write_quoted(f, v),
+            &CassandraType::Bigint(v) |
+            &CassandraType::Counter(v) |
+            &CassandraType::Timestamp(v) |
+            &CassandraType::Varint(v) |
+            &CassandraType::Time(v) => write!(f, "{}", v),
+            &CassandraType::Custom { ref bytes, .. } |
+            &CassandraType::Blob(ref bytes) => {
+                try!(write!(f, "0x"));
+                for byte in bytes {
+                    try!(write!(f, "{:02x}", byte));
+                }
+                Ok(())
+            }
+            &CassandraType::Boolean(v) => write!(f, "{}", v),
+            &CassandraType::Decimal(v) => write!(f, "{}", v),
+            &CassandraType::Float(v) => write!(f, "{}", v),
+            &CassandraType::Double(v) => write!(f, "{}", v),
+            &CassandraType::Int(v) |
+            &CassandraType::Date(v) => write!(f, "{}", v),
+            &CassandraType::Uuid(ref v) |
+            &CassandraType::Timeuuid(ref v) => write!(f, "{}", v),
+            &CassandraType::Inet(ref v) => write_quoted(f, &v.to_string()),
+            &CassandraType::Smallint(v) => write!(f, "{}", v),
+            &CassandraType::Tinyint(v) => write!(f, "{}", v),
+            &CassandraType::Duration(v) => write!(f, "{}", v),
+            &CassandraType::List(ref v) => write_elements(f, "[", v, "]"),
+            &CassandraType::Set(ref v) => write_elements(f, "{", v, "}"),
+            &CassandraType::Tuple(ref v) => write_elements(f, "(", v, ")"),
+            &CassandraType::Map(ref v) => {
+                try!(write!(f, "{{"));
+                for (i, &(ref key, ref value)) in v.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}: {}", key, value));
+                }
+                write!(f, "}}")
+            }
+            &CassandraType::Udt(ref v) => {
+                try!(write!(f, "{{"));
+                for (i, &(ref name, ref value)) in v.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}: {}", name, value));
+                }
+                write!(f, "}}")
+            }
+            &CassandraType::Null => write!(f, "null"),
+            &CassandraType::DecodeError { ref column, ref error } => {
+                write!(f, "<{} decode error: {}>", column, error)
+            }
+        }
+    }
+}
+
+// Writes a string literal in single quotes. Quotes inside of it are doubled as CQL does.
+fn write_quoted(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "'{}'", value.replace("'", "''"))
+}
+
+fn write_elements(f: &mut fmt::Formatter,
+                  open: &str,
+                  elements: &[CassandraType],
+                  close: &str)
+                  -> fmt::Result {
+    try!(write!(f, "{}", open));
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            try!(write!(f, ", "));
+        }
+        try!(write!(f, "{}", element));
+    }
+    write!(f, "{}", close)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    #[test]
+    fn tuple_as_hash_map_key() {
+        let key = CassandraType::Tuple(vec![CassandraType::Int(1),
+                                            CassandraType::Varchar("a".to_string()),
+                                            CassandraType::Null]);
+        let mut map = HashMap::new();
+        map.insert(key.clone(), 1);
+        map.insert(CassandraType::Tuple(vec![CassandraType::Int(2)]), 2);
+
+        assert_eq!(map.get(&key), Some(&1));
+        assert_eq!(map.get(&CassandraType::Tuple(vec![CassandraType::Int(2)])), Some(&2));
+        // the same value of another type is another key
+        assert_eq!(map.get(&CassandraType::List(vec![CassandraType::Int(2)])), None);
+    }
+
+    #[test]
+    fn floats_are_compared_by_bits() {
+        let nan = CassandraType::Tuple(vec![CassandraType::Double(::std::f64::NAN)]);
+        let mut map = HashMap::new();
+        map.insert(nan.clone(), 1);
+        assert_eq!(map.get(&nan), Some(&1));
+
+        assert_eq!(CassandraType::Float(1.5), CassandraType::Float(1.5));
+        assert!(CassandraType::Float(0.0) != CassandraType::Float(-0.0));
+    }
+
+    #[test]
+    fn display_scalars() {
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let custom = CassandraType::Custom {
+            class_name: "org.apache.cassandra.db.marshal.DateRangeType".to_string(),
+            bytes: vec![0x01, 0xAB],
+        };
+        let cases = vec![(custom, "0x01ab"),
+                         (CassandraType::Ascii("a".to_string()), "'a'"),
+                         (CassandraType::Varchar("it's".to_string()), "'it''s'"),
+                         (CassandraType::Bigint(-5), "-5"),
+                         (CassandraType::Blob(vec![0x0A, 0xFF]), "0x0aff"),
+                         (CassandraType::Blob(vec![]), "0x"),
+                         (CassandraType::Boolean(true), "true"),
+                         (CassandraType::Counter(7), "7"),
+                         (CassandraType::Decimal(Decimal::new(150, 2)), "1.50"),
+                         (CassandraType::Double(2.25), "2.25"),
+                         (CassandraType::Float(-0.5), "-0.5"),
+                         (CassandraType::Int(42), "42"),
+                         (CassandraType::Timestamp(1000), "1000"),
+                         (CassandraType::Uuid(uuid), "550e8400-e29b-41d4-a716-446655440000"),
+                         (CassandraType::Timeuuid(uuid), "550e8400-e29b-41d4-a716-446655440000"),
+                         (CassandraType::Varint(9), "9"),
+                         (CassandraType::Inet("127.0.0.1".parse().unwrap()), "'127.0.0.1'"),
+                         (CassandraType::Inet("::1".parse().unwrap()), "'::1'"),
+                         (CassandraType::Date(17000), "17000"),
+                         (CassandraType::Time(3), "3"),
+                         (CassandraType::Smallint(-2), "-2"),
+                         (CassandraType::Tinyint(1), "1"),
+                         (CassandraType::Duration(Duration {
+                              months: 1,
+                              days: 2,
+                              nanos: 3,
+                          }),
+                          "1mo2d3ns"),
+                         (CassandraType::Duration(Duration {
+                              months: -1,
+                              days: 0,
+                              nanos: i64::MIN,
+                          }),
+                          "-1mo0d9223372036854775808ns"),
+                         (CassandraType::Null, "null")];
+        for (value, expected) in cases {
+            assert_eq!(value.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn display_collections() {
+        let ints = |v: Vec<i32>| {
+            CassandraType::List(v.into_iter().map(CassandraType::Int).collect())
+        };
+        assert_eq!(ints(vec![]).to_string(), "[]");
+        assert_eq!(CassandraType::Set(vec![CassandraType::Int(1), CassandraType::Null])
+                       .to_string(),
+                   "{1, null}");
+        assert_eq!(CassandraType::Tuple(vec![CassandraType::Int(1),
+                                             CassandraType::Varchar("a".to_string())])
+                       .to_string(),
+                   "(1, 'a')");
+        assert_eq!(CassandraType::Udt(vec![("name".to_string(),
+                                            CassandraType::Varchar("John".to_string())),
+                                           ("age".to_string(), CassandraType::Null)])
+                       .to_string(),
+                   "{name: 'John', age: null}");
+
+        // map<text, list<int>>
+        let map = CassandraType::Map(vec![(CassandraType::Varchar("a".to_string()),
+                                           ints(vec![1, 2])),
+                                          (CassandraType::Varchar("b".to_string()),
+                                           ints(vec![]))]);
+        assert_eq!(map.to_string(), "{'a': [1, 2], 'b': []}");
+    }
+}
@@ -1,32 +1,92 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::ops::Mul;
+use std::fmt;
 use std::io;
+use std::io::Read;
 use std::net;
+use std::str::{self, Utf8Error};
 use std::string::FromUtf8Error;
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid;
+use byteorder::{BigEndian, ReadBytesExt};
+use error;
+#[cfg(feature = "chrono")]
+use chrono::{self, NaiveDate, NaiveDateTime, NaiveTime};
+#[cfg(feature = "num-bigint")]
+use num_bigint::BigInt;
 use super::*;
+use super::cassandra_type::CassandraType;
+use frame::frame_result::{ColType, ColTypeOption, ColTypeOptionValue};
 use FromCursor;
 
 
 // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L813
 
-// Decodes Cassandra `ascii` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Decodes Cassandra `custom` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 sequences are replaced with U+FFFD, `decode_custom_strict` should be used
+// to detect them.
 pub fn decode_custom(bytes: &[u8]) -> Result<String, FromUtf8Error> {
     Ok(String::from_utf8_lossy(bytes).into_owned())
 }
 
-// Decodes Cassandra `ascii` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
-pub fn decode_ascii(bytes: &[u8]) -> Result<String, FromUtf8Error> {
-    Ok(String::from_utf8_lossy(bytes).into_owned())
+// Decodes Cassandra `custom` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 results in an error.
+pub fn decode_custom_strict(bytes: &[u8]) -> Result<String, FromUtf8Error> {
+    String::from_utf8(bytes.to_vec())
+}
+
+// Decodes Cassandra `ascii` data (bytes) into Rust's `Result<String, io::Error>`.
+// Bytes above 0x7F are not allowed by the protocol, so they result in an error.
+pub fn decode_ascii(bytes: &[u8]) -> Result<String, io::Error> {
+    decode_ascii_borrowed(bytes).map(|ascii| ascii.to_string())
 }
 
 // Decodes Cassandra `varchar` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 sequences are replaced with U+FFFD, `decode_varchar_strict` should be used
+// to detect them.
 pub fn decode_varchar(bytes: &[u8]) -> Result<String, FromUtf8Error> {
     Ok(String::from_utf8_lossy(bytes).into_owned())
 }
 
-// Decodes Cassandra `bigint` data (bytes) into Rust's `Result<i32, io::Error>`
+// Decodes Cassandra `varchar` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 results in an error.
+pub fn decode_varchar_strict(bytes: &[u8]) -> Result<String, FromUtf8Error> {
+    String::from_utf8(bytes.to_vec())
+}
+
+// Decodes Cassandra `ascii` data (bytes) into Rust's `&str` without copying.
+// Bytes above 0x7F result in an error.
+pub fn decode_ascii_borrowed(bytes: &[u8]) -> Result<&str, io::Error> {
+    match bytes.iter().position(|byte| !byte.is_ascii()) {
+        Some(i) => {
+            Err(io::Error::new(io::ErrorKind::InvalidData,
+                               format!("ascii should contain only bytes up to 0x7F, got {:#X} \
+                                        at {}",
+                                       bytes[i],
+                                       i)))
+        }
+        // ASCII is valid UTF-8
+        None => str::from_utf8(bytes).map_err(invalid_data),
+    }
+}
+
+// Decodes Cassandra `varchar` data (bytes) into Rust's `&str` without copying.
+pub fn decode_varchar_borrowed(bytes: &[u8]) -> Result<&str, Utf8Error> {
+    str::from_utf8(bytes)
+}
+
+// Decodes Cassandra `bigint` data (bytes) into Rust's `Result<i64, io::Error>`
 pub fn decode_bigint(bytes: &[u8]) -> Result<i64, io::Error> {
-    try_from_bytes(bytes).map(|i| i as i64)
+    try_i64_from_bytes(bytes)
+}
+
+// Decodes Cassandra `counter` data (bytes) into Rust's `Result<i64, io::Error>`.
+// Counters are 8-byte signed integers, bytes of any other length result in an error.
+pub fn decode_counter(bytes: &[u8]) -> Result<i64, io::Error> {
+    try_i64_from_bytes(bytes)
 }
 
 // Decodes Cassandra `blob` data (bytes) into Rust's `Result<Vec<u8>, io::Error>`
@@ -35,11 +95,14 @@ pub fn decode_blob(bytes: Vec<u8>) -> Result<Vec<u8>, io::Error> {
     Ok(bytes)
 }
 
-// Decodes Cassandra `boolean` data (bytes) into Rust's `Result<i32, io::Error>`
+// Decodes Cassandra `boolean` data (bytes) into Rust's `Result<bool, io::Error>`
 pub fn decode_boolean(bytes: &[u8]) -> Result<bool, io::Error> {
     let false_byte: u8 = 0;
     if bytes.is_empty() {
         Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no bytes were found"))
+    } else if bytes.len() != 1 {
+        Err(io::Error::new(io::ErrorKind::InvalidData,
+                           format!("boolean should contain exactly 1 byte, got {}", bytes.len())))
     } else {
         Ok(bytes[0] != false_byte)
     }
@@ -47,7 +110,8 @@ pub fn decode_boolean(bytes: &[u8]) -> Result<bool, io::Error> {
 
 // Decodes Cassandra `int` data (bytes) into Rust's `Result<i32, io::Error>`
 pub fn decode_int(bytes: &[u8]) -> Result<i32, io::Error> {
-    try_from_bytes(bytes).map(|i| i as i32)
+    try!(check_bytes_len(bytes, INT_LEN, "int"));
+    try_i32_from_bytes(bytes)
 }
 
 // Decodes Cassandra `date` data (bytes) into Rust's `Result<i32, io::Error>` in following way
@@ -55,36 +119,138 @@ pub fn decode_int(bytes: &[u8]) -> Result<i32, io::Error> {
 // 2^31: 1970-1-1
 // 2^32: 5881580-07-11
 pub fn decode_date(bytes: &[u8]) -> Result<i32, io::Error> {
-    try_from_bytes(bytes).map(|i| i as i32)
+    try_i32_from_bytes(bytes)
+}
+
+// Decodes Cassandra `date` data (bytes) into `chrono::NaiveDate`. Days are stored
+// unsigned with the epoch (1970-1-1) at 2^31. Dates which `chrono` cannot represent
+// result in an error.
+#[cfg(feature = "chrono")]
+pub fn decode_date_naive(bytes: &[u8]) -> Result<NaiveDate, io::Error> {
+    let days = try!(decode_date(bytes)) as u32 as i64 - (1i64 << 31);
+
+    NaiveDate::from_ymd(1970, 1, 1)
+        .checked_add_signed(chrono::Duration::days(days))
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData,
+                              format!("date {} days from epoch is out of range", days)))
+}
+
+/// Cassandra `decimal` value, i.e. `unscaled * 10^(-scale)`. Digits are kept exactly,
+/// so values which differ only by a scale (e.g. `1.5` and `1.50`) are not equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Decimal {
+    pub unscaled: i64,
+    pub scale: i32,
 }
 
-// TODO: make sure this method meets the specification.
-// Decodes Cassandra `decimal` data (bytes) into Rust's `Result<f32, io::Error>`
-pub fn decode_decimal(bytes: &[u8]) -> Result<f32, io::Error> {
-    let ref separator = b'E';
-    let lr: Vec<Vec<u8>> = bytes.split(|ch| ch == separator).map(|p| p.to_vec()).collect();
-    let unscaled = try_i_from_bytes(lr[0].as_slice());
-    if unscaled.is_err() {
-        return Err(unscaled.unwrap_err());
+impl Decimal {
+    pub fn new(unscaled: i64, scale: i32) -> Decimal {
+        Decimal {
+            unscaled: unscaled,
+            scale: scale,
+        }
     }
-    let scaled = try_i_from_bytes(lr[1].as_slice());
-    if scaled.is_err() {
-        return Err(scaled.unwrap_err());
+
+    /// Converts the decimal into `f64`. Precision is lost if the decimal has more
+    /// significant digits than `f64` can hold.
+    pub fn to_f64(&self) -> f64 {
+        let dec: f64 = 10.0;
+        (self.unscaled as f64).mul(dec.powi(-self.scale))
     }
+}
+
+impl fmt::Display for Decimal {
+    /// Renders exact digits of the decimal, e.g. `-0.05` for unscaled `-5` with scale `2`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.scale <= 0 {
+            try!(write!(f, "{}", self.unscaled));
+            if self.unscaled == 0 {
+                return Ok(());
+            }
+            for _ in self.scale..0 {
+                try!(write!(f, "0"));
+            }
+            return Ok(());
+        }
+
+        let repr = self.unscaled.to_string();
+        let (sign, digits) = if repr.starts_with('-') {
+            ("-", &repr[1..])
+        } else {
+            ("", &repr[..])
+        };
+        let scale = self.scale as usize;
+        if digits.len() > scale {
+            let point = digits.len() - scale;
+            return write!(f, "{}{}.{}", sign, &digits[..point], &digits[point..]);
+        }
+
+        try!(write!(f, "{}0.", sign));
+        for _ in digits.len()..scale {
+            try!(write!(f, "0"));
+        }
+        write!(f, "{}", digits)
+    }
+}
+
+// Decodes Cassandra `decimal` data (bytes) into Rust's `Result<Decimal, io::Error>`.
+// The value is encoded as a 4-byte `scale` followed by an unscaled `varint`. It returns
+// an error if the unscaled value does not fit `i64`.
+pub fn decode_decimal(bytes: &[u8]) -> Result<Decimal, io::Error> {
+    if bytes.len() <= INT_LEN {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                  format!("decimal should contain at least {} bytes, got {}",
+                                          INT_LEN + 1,
+                                          bytes.len())));
+    }
+    if bytes.len() > INT_LEN + 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("unscaled value of decimal of {} bytes does not \
+                                           fit i64",
+                                          bytes.len() - INT_LEN)));
+    }
+
+    let scale = try!(try_i32_from_bytes(&bytes[..INT_LEN]));
+    let unscaled = try!(decode_varint(&bytes[INT_LEN..]));
+
+    Ok(Decimal::new(unscaled, scale))
+}
+
+// Encodes decimal `unscaled * 10^(-scale)` into Cassandra `decimal` data (bytes):
+// 4-byte `scale` followed by `unscaled` as a `varint`.
+pub fn encode_decimal(unscaled: i64, scale: i32) -> Vec<u8> {
+    let mut bytes = to_int(scale);
+    bytes.extend_from_slice(encode_varint(unscaled).as_slice());
+    bytes
+}
+
+// Encodes `i64` into Cassandra `varint` data (bytes), i.e. minimal signed big-endian
+// two's-complement representation.
+pub fn encode_varint(int: i64) -> Vec<u8> {
+    let mut bytes = to_bigint(int);
+    while bytes.len() > 1 &&
+          ((bytes[0] == 0x00 && bytes[1] & 0x80 == 0) ||
+           (bytes[0] == 0xFF && bytes[1] & 0x80 != 0)) {
+        bytes.remove(0);
+    }
+    bytes
+}
 
-    let unscaled_unwrapped: f32 = unscaled.unwrap() as f32;
-    let scaled_unwrapped: i32 = scaled.unwrap() as i32;
-    let dec: f32 = 10.0;
-    Ok(unscaled_unwrapped.mul(dec.powi(scaled_unwrapped)))
+// Encodes `num_bigint::BigInt` into Cassandra `varint` data (bytes).
+#[cfg(feature = "num-bigint")]
+pub fn encode_varint_big(int: &BigInt) -> Vec<u8> {
+    int.to_signed_bytes_be()
 }
 
-// Decodes Cassandra `double` data (bytes) into Rust's `Result<f32, io::Error>`
+// Decodes Cassandra `double` data (bytes) into Rust's `Result<f64, io::Error>`
 pub fn decode_double(bytes: &[u8]) -> Result<f64, io::Error> {
+    try!(check_bytes_len(bytes, 8, "double"));
     try_f64_from_bytes(bytes)
 }
 
 // Decodes Cassandra `float` data (bytes) into Rust's `Result<f32, io::Error>`
 pub fn decode_float(bytes: &[u8]) -> Result<f32, io::Error> {
+    try!(check_bytes_len(bytes, 4, "float"));
     try_f32_from_bytes(bytes)
 }
 
@@ -105,16 +271,44 @@ pub fn decode_inet(bytes: &[u8]) -> Result<net::IpAddr, io::Error> {
             let h = from_u16_bytes(&bytes[14..16]);
             Ok(net::IpAddr::V6(net::Ipv6Addr::new(a, b, c, d, e, f, g, h)))
         }
-        _ => unreachable!(),
+        l => {
+            Err(io::Error::new(io::ErrorKind::InvalidData,
+                               format!("inet should contain 4 or 16 bytes, got {}", l)))
+        }
+    }
+}
+
+// Encodes Rust's `net::IpAddr` into Cassandra `inet` data (bytes): 4 bytes for v4 address
+// and 16 bytes for v6 one.
+pub fn encode_inet(addr: &net::IpAddr) -> Vec<u8> {
+    match *addr {
+        net::IpAddr::V4(ref ip) => ip.octets().to_vec(),
+        net::IpAddr::V6(ref ip) => ip.octets().to_vec(),
     }
 }
 
 // Decodes Cassandra `timestamp` data (bytes) into Rust's `Result<i64, io::Error>`
-// `i32` represets a millisecond-precision
+// `i64` represets a millisecond-precision
 //  offset from the unix epoch (00:00:00, January 1st, 1970).  Negative values
 //  represent a negative offset from the epoch.
 pub fn decode_timestamp(bytes: &[u8]) -> Result<i64, io::Error> {
-    try_from_bytes(bytes).map(|i| i as i64)
+    try_i64_from_bytes(bytes)
+}
+
+// Decodes Cassandra `timestamp` data (bytes) into `chrono::NaiveDateTime` (UTC).
+#[cfg(feature = "chrono")]
+pub fn decode_timestamp_naive(bytes: &[u8]) -> Result<NaiveDateTime, io::Error> {
+    let millis = try!(decode_timestamp(bytes));
+    let mut secs = millis / 1000;
+    let mut rem_millis = millis % 1000;
+    if rem_millis < 0 {
+        secs -= 1;
+        rem_millis += 1000;
+    }
+
+    NaiveDateTime::from_timestamp_opt(secs, rem_millis as u32 * 1_000_000)
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData,
+                              format!("timestamp {} is out of range", millis)))
 }
 
 // Decodes Cassandra `list` data (bytes) into Rust's `Result<Vec<CBytes>, io::Error>`
@@ -133,6 +327,238 @@ pub fn decode_set(bytes: &[u8]) -> Result<Vec<CBytes>, io::Error> {
     Ok(list)
 }
 
+// Decodes Cassandra `list` data (bytes) into Rust's `Result<Vec<CassandraType>, io::Error>`
+// decoding each element according to provided `element_type`.
+pub fn decode_list_typed(bytes: &[u8],
+                         element_type: &ColTypeOption)
+                         -> Result<Vec<CassandraType>, io::Error> {
+    let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
+    let l = try!(cursor.read_i32::<BigEndian>());
+    (0..l).map(|_| decode_typed_value(&mut cursor, element_type)).collect()
+}
+
+// Decodes Cassandra `set` data (bytes) into Rust's `Result<Vec<CassandraType>, io::Error>`
+// decoding each element according to provided `element_type`.
+pub fn decode_set_typed(bytes: &[u8],
+                        element_type: &ColTypeOption)
+                        -> Result<Vec<CassandraType>, io::Error> {
+    decode_list_typed(bytes, element_type)
+}
+
+// Reads `[bytes]` value from the cursor and decodes it according to `col_type`.
+// Values with negative length are NULL ones. The length comes from the wire, so it's
+// checked against the rest of the cursor and the value is borrowed rather than copied.
+fn decode_typed_value(cursor: &mut io::Cursor<&[u8]>,
+                      col_type: &ColTypeOption)
+                      -> Result<CassandraType, io::Error> {
+    let len = try!(cursor.read_i32::<BigEndian>());
+    if len < 0 {
+        return Ok(CassandraType::Null);
+    }
+
+    let bytes: &[u8] = *cursor.get_ref();
+    let start = (cursor.position() as usize).min(bytes.len());
+    let len = len as usize;
+    if len > bytes.len() - start {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                  format!("value of {} bytes is longer than {} remaining bytes",
+                                          len,
+                                          bytes.len() - start)));
+    }
+    cursor.set_position((start + len) as u64);
+    decode_by_type(&bytes[start..start + len], col_type)
+}
+
+// Decodes nullable Cassandra data (bytes) of provided type. `None` bytes stand for NULL
+// value which is decoded into `None`, unlike empty bytes which are a present value.
+pub fn decode_nullable(bytes: Option<&[u8]>,
+                       col_type: &ColTypeOption)
+                       -> Result<Option<CassandraType>, io::Error> {
+    match bytes {
+        Some(bytes) => decode_by_type(bytes, col_type).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn invalid_data<E: fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+// Decodes Cassandra data (bytes) of provided type into `CassandraType`. Collections
+// and UDTs are decoded recursively according to types of their items.
+pub fn decode_by_type(bytes: &[u8], col_type: &ColTypeOption) -> Result<CassandraType, io::Error> {
+    let value = match col_type.id {
+        ColType::Custom => {
+            match col_type.value {
+                Some(ColTypeOptionValue::CString(ref class_name)) => {
+                    CassandraType::Custom {
+                        class_name: class_name.as_plain(),
+                        bytes: bytes.to_vec(),
+                    }
+                }
+                _ => return Err(invalid_data("custom type should contain its class name")),
+            }
+        }
+        ColType::Ascii => CassandraType::Ascii(try!(decode_ascii(bytes))),
+        ColType::Bigint => CassandraType::Bigint(try!(decode_bigint(bytes))),
+        ColType::Blob => CassandraType::Blob(try!(decode_blob(bytes.to_vec()))),
+        ColType::Boolean => CassandraType::Boolean(try!(decode_boolean(bytes))),
+        ColType::Counter => CassandraType::Counter(try!(decode_counter(bytes))),
+        ColType::Decimal => CassandraType::Decimal(try!(decode_decimal(bytes))),
+        ColType::Double => CassandraType::Double(try!(decode_double(bytes))),
+        ColType::Float => CassandraType::Float(try!(decode_float(bytes))),
+        ColType::Int => CassandraType::Int(try!(decode_int(bytes))),
+        ColType::Timestamp => CassandraType::Timestamp(try!(decode_timestamp(bytes))),
+        ColType::Uuid => CassandraType::Uuid(try!(decode_uuid(bytes))),
+        ColType::Varchar => {
+            CassandraType::Varchar(try!(decode_varchar(bytes).map_err(invalid_data)))
+        }
+        ColType::Varint => CassandraType::Varint(try!(decode_varint(bytes))),
+        ColType::Timeuuid => {
+            CassandraType::Timeuuid(try!(decode_timeuuid(bytes).map_err(invalid_data)))
+        }
+        ColType::Inet => CassandraType::Inet(try!(decode_inet(bytes))),
+        ColType::Date => CassandraType::Date(try!(decode_date(bytes))),
+        ColType::Time => CassandraType::Time(try!(decode_time(bytes))),
+        ColType::Smallint => CassandraType::Smallint(try!(decode_smallint(bytes))),
+        ColType::Tinyint => CassandraType::Tinyint(try!(decode_tinyint(bytes))),
+        ColType::Duration => CassandraType::Duration(try!(decode_duration(bytes))),
+        ColType::List => {
+            match col_type.value {
+                Some(ColTypeOptionValue::CList(ref element_type)) => {
+                    CassandraType::List(try!(decode_list_typed(bytes, element_type)))
+                }
+                _ => return Err(invalid_data("list type should contain its element type")),
+            }
+        }
+        ColType::Set => {
+            match col_type.value {
+                Some(ColTypeOptionValue::CSet(ref element_type)) => {
+                    CassandraType::Set(try!(decode_set_typed(bytes, element_type)))
+                }
+                _ => return Err(invalid_data("set type should contain its element type")),
+            }
+        }
+        ColType::Map => {
+            match col_type.value {
+                Some(ColTypeOptionValue::CMap((ref key_type, ref value_type))) => {
+                    CassandraType::Map(try!(decode_map_typed(bytes, key_type, value_type)))
+                }
+                _ => return Err(invalid_data("map type should contain its key and value types")),
+            }
+        }
+        ColType::Udt => {
+            match col_type.value {
+                Some(ColTypeOptionValue::UdtType(ref udt)) => {
+                    let field_defs: Vec<(String, ColTypeOption)> = udt.descriptions
+                        .iter()
+                        .map(|&(ref name, ref field_type)| (name.as_plain(), field_type.clone()))
+                        .collect();
+                    CassandraType::Udt(try!(decode_udt_named(bytes, field_defs.as_slice())))
+                }
+                _ => return Err(invalid_data("udt type should contain its fields description")),
+            }
+        }
+        ColType::Tuple => {
+            match col_type.value {
+                Some(ColTypeOptionValue::TupleType(ref element_types)) => {
+                    CassandraType::Tuple(try!(decode_tuple(bytes, element_types.as_slice())))
+                }
+                _ => return Err(invalid_data("tuple type should contain types of its elements")),
+            }
+        }
+        ColType::Null => CassandraType::Null,
+        ref unsupported => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("{:?} is not supported yet", unsupported)))
+        }
+    };
+
+    Ok(value)
+}
+
+// Encodes `CassandraType` into Cassandra data (bytes), the inverse of `decode_by_type`.
+// `None` stands for NULL value. Collections, UDTs and tuples are encoded recursively,
+// their NULL items are encoded with negative length. Placeholders of decode errors
+// cannot be encoded since original values are lost, an error is returned instead
+// of NULL which would silently create a tombstone.
+pub fn encode_cassandra_type(value: &CassandraType) -> error::Result<Option<Vec<u8>>> {
+    let bytes = match *value {
+        CassandraType::Custom { ref bytes, .. } => bytes.clone(),
+        CassandraType::Ascii(ref v) |
+        CassandraType::Varchar(ref v) => v.as_bytes().to_vec(),
+        CassandraType::Bigint(v) => encode_bigint(v),
+        CassandraType::Blob(ref v) => v.clone(),
+        CassandraType::Boolean(v) => encode_boolean(v),
+        CassandraType::Counter(v) => encode_bigint(v),
+        CassandraType::Decimal(v) => encode_decimal(v.unscaled, v.scale),
+        CassandraType::Double(v) => encode_double(v),
+        CassandraType::Float(v) => encode_float(v),
+        CassandraType::Int(v) => encode_int(v),
+        CassandraType::Timestamp(v) => encode_timestamp(v),
+        CassandraType::Uuid(ref v) |
+        CassandraType::Timeuuid(ref v) => encode_uuid(v),
+        CassandraType::Varint(v) => encode_varint(v),
+        CassandraType::Inet(ref v) => encode_inet(v),
+        CassandraType::Date(v) => encode_date(v),
+        CassandraType::Time(v) => encode_time(v),
+        CassandraType::Smallint(v) => encode_smallint(v),
+        CassandraType::Tinyint(v) => encode_tinyint(v),
+        CassandraType::Duration(v) => encode_duration(v),
+        CassandraType::List(ref items) |
+        CassandraType::Set(ref items) => {
+            let mut bytes = to_int(items.len() as i32);
+            for item in items {
+                try!(encode_item(&mut bytes, item));
+            }
+            bytes
+        }
+        CassandraType::Map(ref entries) => {
+            let mut bytes = to_int(entries.len() as i32);
+            for &(ref key, ref value) in entries {
+                try!(encode_item(&mut bytes, key));
+                try!(encode_item(&mut bytes, value));
+            }
+            bytes
+        }
+        CassandraType::Udt(ref fields) => {
+            let mut bytes = vec![];
+            for &(_, ref value) in fields {
+                try!(encode_item(&mut bytes, value));
+            }
+            bytes
+        }
+        CassandraType::Tuple(ref items) => {
+            let mut bytes = vec![];
+            for item in items {
+                try!(encode_item(&mut bytes, item));
+            }
+            bytes
+        }
+        CassandraType::Null => return Ok(None),
+        CassandraType::DecodeError { ref column, ref error } => {
+            return Err(error::Error::General(format!("Value of column {} cannot be encoded \
+                                                      since it failed to be decoded: {}",
+                                                     column,
+                                                     error)));
+        }
+    };
+
+    Ok(Some(bytes))
+}
+
+// Appends an item of a collection, UDT or tuple as [bytes].
+fn encode_item(bytes: &mut Vec<u8>, item: &CassandraType) -> error::Result<()> {
+    match try!(encode_cassandra_type(item)) {
+        Some(item) => {
+            bytes.extend_from_slice(to_int(item.len() as i32).as_slice());
+            bytes.extend_from_slice(item.as_slice());
+        }
+        None => bytes.extend_from_slice(to_int(-1).as_slice()),
+    }
+    Ok(())
+}
+
 // Decodes Cassandra `map` data (bytes) into Rust's `Result<Vec<(CBytes, CBytes)>, io::Error>`
 pub fn decode_map(bytes: &[u8]) -> Result<Vec<(CBytes, CBytes)>, io::Error> {
     let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
@@ -143,36 +569,304 @@ pub fn decode_map(bytes: &[u8]) -> Result<Vec<(CBytes, CBytes)>, io::Error> {
     Ok(list)
 }
 
+// Decodes Cassandra `map` data (bytes) into Rust's
+// `Result<Vec<(CassandraType, CassandraType)>, io::Error>` decoding keys and values
+// according to provided `key_type` and `value_type`. The order of entries is preserved.
+pub fn decode_map_typed(bytes: &[u8],
+                        key_type: &ColTypeOption,
+                        value_type: &ColTypeOption)
+                        -> Result<Vec<(CassandraType, CassandraType)>, io::Error> {
+    let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
+    let l = try!(cursor.read_i32::<BigEndian>());
+    (0..l)
+        .map(|_| {
+            let key = try!(decode_typed_value(&mut cursor, key_type));
+            let value = try!(decode_typed_value(&mut cursor, value_type));
+            Ok((key, value))
+        })
+        .collect()
+}
+
 // Decodes Cassandra `smallint` data (bytes) into Rust's `Result<i16, io::Error>`
 pub fn decode_smallint(bytes: &[u8]) -> Result<i16, io::Error> {
-    try_from_bytes(bytes).map(|i| i as i16)
+    try!(check_bytes_len(bytes, SHORT_LEN, "smallint"));
+    try_i16_from_bytes(bytes)
 }
 
 // Decodes Cassandra `tinyint` data (bytes) into Rust's `Result<i8, io::Error>`
 pub fn decode_tinyint(bytes: &[u8]) -> Result<i8, io::Error> {
-    Ok(bytes[0] as i8)
+    if bytes.is_empty() {
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no bytes were found"))
+    } else {
+        Ok(bytes[0] as i8)
+    }
 }
 
 // Decodes Cassandra `text` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 sequences are replaced with U+FFFD, `decode_text_strict` should be used
+// to detect them.
 pub fn decode_text(bytes: &[u8]) -> Result<String, FromUtf8Error> {
     Ok(String::from_utf8_lossy(bytes).into_owned())
 }
 
-// Decodes Cassandra `time` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Decodes Cassandra `text` data (bytes) into Rust's `Result<String, FromUtf8Error>`.
+// Invalid UTF-8 results in an error.
+pub fn decode_text_strict(bytes: &[u8]) -> Result<String, FromUtf8Error> {
+    String::from_utf8(bytes.to_vec())
+}
+
+// Decodes Cassandra `text` data (bytes) into Rust's `&str` without copying.
+// Unlike `decode_text` invalid UTF-8 results in an error.
+pub fn decode_text_borrowed(bytes: &[u8]) -> Result<&str, Utf8Error> {
+    str::from_utf8(bytes)
+}
+
+// Number of nanoseconds in a day. Cassandra `time` is within `[0, NANOS_PER_DAY)`.
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+// Decodes Cassandra `time` data (bytes) into Rust's `Result<i64, io::Error>`.
+// `i64` represents nanoseconds since midnight, values out of `[0, 86399999999999]`
+// result in an error.
 pub fn decode_time(bytes: &[u8]) -> Result<i64, io::Error> {
-    try_i_from_bytes(bytes)
+    try!(check_bytes_len(bytes, 8, "time"));
+    let nanos = try!(try_i64_from_bytes(bytes));
+    if nanos < 0 || nanos >= NANOS_PER_DAY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("time {} is out of nanoseconds-of-day range", nanos)));
+    }
+
+    Ok(nanos)
+}
+
+// Decodes Cassandra `time` data (bytes) into `chrono::NaiveTime`.
+#[cfg(feature = "chrono")]
+pub fn decode_time_naive(bytes: &[u8]) -> Result<NaiveTime, io::Error> {
+    let nanos = try!(decode_time(bytes));
+
+    NaiveTime::from_num_seconds_from_midnight_opt((nanos / 1_000_000_000) as u32,
+                                                  (nanos % 1_000_000_000) as u32)
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData,
+                              format!("time {} is out of range", nanos)))
+}
+
+// Decodes Cassandra `uuid` data (bytes) into Rust's `Result<uuid::Uuid, io::Error>`.
+// The data should be exactly 16 bytes long, UUIDs of any version are accepted.
+pub fn decode_uuid(bytes: &[u8]) -> Result<uuid::Uuid, io::Error> {
+    if bytes.len() != 16 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("uuid should be 16 bytes long, got {}", bytes.len())));
+    }
+
+    uuid::Uuid::from_bytes(bytes).map_err(invalid_data)
+}
+
+// Decodes Cassandra `timeuuid` data (bytes) into Rust's `Result<uuid::Uuid, error::Error>`.
+// Unlike `decode_uuid` it checks that decoded UUID is a time-based (version 1) one.
+pub fn decode_timeuuid(bytes: &[u8]) -> Result<uuid::Uuid, error::Error> {
+    let uuid = try!(decode_uuid(bytes));
+    let version = uuid.get_version_num();
+    if version != 1 {
+        return Err(error::Error::General(format!("timeuuid should be a version 1 UUID, got \
+                                                  version {}",
+                                                 version)));
+    }
+
+    Ok(uuid)
+}
+
+// Extracts 60-bit timestamp of version 1 UUID. The result is a number of 100-nanosecond
+// intervals since 00:00:00.00, 15 October 1582 (the date of Gregorian reform).
+pub fn timeuuid_timestamp(uuid: &uuid::Uuid) -> u64 {
+    let b = uuid.as_bytes();
+    let time_low = (b[0] as u64) << 24 | (b[1] as u64) << 16 | (b[2] as u64) << 8 | b[3] as u64;
+    let time_mid = (b[4] as u64) << 8 | b[5] as u64;
+    let time_hi = ((b[6] & 0x0F) as u64) << 8 | b[7] as u64;
+
+    time_hi << 48 | time_mid << 32 | time_low
+}
+
+// Number of 100-nanosecond intervals between the Gregorian reform and the Unix epoch.
+const GREGORIAN_TO_UNIX_EPOCH: u64 = 0x01B21DD213814000;
+
+// Clock sequence and node id used by time UUIDs generated by the current thread
+// along with the timestamp of the last of them.
+struct TimeuuidState {
+    timestamp: u64,
+    clock_sequence: u16,
+    node: [u8; 6],
 }
 
-// Decodes Cassandra `timeuuid` data (bytes) into Rust's `Result<uuid::Uuid, uuid::ParseError>`
-pub fn decode_timeuuid(bytes: &[u8]) -> Result<uuid::Uuid, uuid::ParseError> {
-    uuid::Uuid::from_bytes(bytes)
+thread_local! {
+    static TIMEUUID_STATE: RefCell<TimeuuidState> = RefCell::new(TimeuuidState::new());
 }
 
-// Decodes Cassandra `varint` data (bytes) into Rust's `Result<i64, io::Error>`
+impl TimeuuidState {
+    fn new() -> TimeuuidState {
+        let random = random_u64();
+        let mut node = [0; 6];
+        for (i, byte) in node.iter_mut().enumerate() {
+            *byte = (random >> (i * 8)) as u8;
+        }
+        // multicast bit marks a random node id which is not a MAC address
+        node[0] |= 0x01;
+
+        TimeuuidState {
+            timestamp: 0,
+            clock_sequence: (random >> 48) as u16 & 0x3FFF,
+            node: node,
+        }
+    }
+}
+
+// Returns a random number. Hashers of `RandomState` are seeded randomly, so no random
+// number generator is needed.
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+// Generates a time-based (version 1) UUID of the current time, e.g. a value
+// of a `timeuuid` primary key. UUIDs generated by a thread are unique and monotonic:
+// clock sequence is incremented when the timestamp is not greater than the timestamp
+// of the previous UUID. Node id is random.
+pub fn generate_timeuuid() -> uuid::Uuid {
+    generate_timeuuid_at(SystemTime::now())
+}
+
+// Generates a time-based (version 1) UUID of provided time the same way
+// `generate_timeuuid` does, e.g. for backfilling.
+pub fn generate_timeuuid_at(time: SystemTime) -> uuid::Uuid {
+    let intervals = |duration: ::std::time::Duration| {
+        duration.as_secs() * 10_000_000 + (duration.subsec_nanos() / 100) as u64
+    };
+    let timestamp = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => GREGORIAN_TO_UNIX_EPOCH + intervals(since_epoch),
+        Err(err) => GREGORIAN_TO_UNIX_EPOCH.saturating_sub(intervals(err.duration())),
+    };
+
+    TIMEUUID_STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if timestamp <= state.timestamp {
+            state.clock_sequence = (state.clock_sequence + 1) & 0x3FFF;
+        }
+        state.timestamp = timestamp;
+
+        let mut bytes = [0; 16];
+        bytes[0..4].copy_from_slice(&to_u(timestamp as u32));
+        bytes[4..6].copy_from_slice(&to_u_short((timestamp >> 32) as u16));
+        bytes[6..8].copy_from_slice(&to_u_short((timestamp >> 48) as u16 & 0x0FFF | 0x1000));
+        bytes[8..10].copy_from_slice(&to_u_short(state.clock_sequence | 0x8000));
+        bytes[10..16].copy_from_slice(&state.node);
+        uuid::Uuid::from_bytes(&bytes).unwrap()
+    })
+}
+
+// Decodes Cassandra `varint` data (bytes) into Rust's `Result<i64, io::Error>`.
+// Empty bytes and values which don't fit `i64` (longer than 8 bytes) result in an error.
 pub fn decode_varint(bytes: &[u8]) -> Result<i64, io::Error> {
+    if bytes.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no bytes were found"));
+    }
+    if bytes.len() > 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("varint of {} bytes does not fit i64", bytes.len())));
+    }
+
     try_i_from_bytes(bytes)
 }
 
+/// Cassandra `duration` value. Months, days and nanoseconds are kept separately
+/// because the number of days in a month and nanoseconds in a day may vary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Duration {
+    pub months: i32,
+    pub days: i32,
+    pub nanos: i64,
+}
+
+impl fmt::Display for Duration {
+    /// Renders the duration in CQL form, e.g. `1mo2d3ns` or `-1mo2d3ns`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let negative = self.months < 0 || self.days < 0 || self.nanos < 0;
+        write!(f,
+               "{}{}mo{}d{}ns",
+               if negative { "-" } else { "" },
+               (self.months as i64).abs(),
+               (self.days as i64).abs(),
+               self.nanos.wrapping_abs() as u64)
+    }
+}
+
+// Reads zig-zag encoded variable length signed integer. A number of leading 1-bits
+// of the first byte is a number of bytes that follow it.
+fn decode_vint(cursor: &mut io::Cursor<&[u8]>) -> Result<i64, io::Error> {
+    let first = try!(cursor.read_u8());
+    let extra = (!first).leading_zeros();
+    let mut value = if extra >= 8 {
+        0
+    } else {
+        (first & (0xFF >> extra)) as u64
+    };
+
+    for _ in 0..extra {
+        value = value << 8 | try!(cursor.read_u8()) as u64;
+    }
+
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+// Writes zig-zag encoded variable length signed integer, the inverse of `decode_vint`.
+fn encode_vint(bytes: &mut Vec<u8>, value: i64) {
+    let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    let bits = 64 - zigzag.leading_zeros();
+    let extra = if bits <= 7 {
+        0
+    } else {
+        cmp::min(8, (bits - 1) / 7)
+    };
+
+    let start = bytes.len();
+    for i in (0..extra + 1).rev() {
+        bytes.push(if i >= 8 { 0 } else { (zigzag >> (8 * i)) as u8 });
+    }
+    bytes[start] |= !(0xFFu8.checked_shr(extra).unwrap_or(0));
+}
+
+// Encodes Rust's `Duration` into Cassandra `duration` data (bytes), the inverse
+// of `decode_duration`.
+pub fn encode_duration(duration: Duration) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(3);
+    encode_vint(&mut bytes, duration.months as i64);
+    encode_vint(&mut bytes, duration.days as i64);
+    encode_vint(&mut bytes, duration.nanos);
+    bytes
+}
+
+// Decodes Cassandra `duration` data (bytes) into Rust's `Result<Duration, io::Error>`.
+// The value is encoded as three vints: months, days and nanoseconds.
+pub fn decode_duration(bytes: &[u8]) -> Result<Duration, io::Error> {
+    let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
+    let months = try!(decode_vint(&mut cursor));
+    let days = try!(decode_vint(&mut cursor));
+    let nanos = try!(decode_vint(&mut cursor));
+
+    if cursor.position() != bytes.len() as u64 {
+        return Err(invalid_data(format!("duration has {} trailing bytes",
+                                        bytes.len() as u64 - cursor.position())));
+    }
+    if months < i32::MIN as i64 || months > i32::MAX as i64 {
+        return Err(invalid_data(format!("duration months {} do not fit i32", months)));
+    }
+    if days < i32::MIN as i64 || days > i32::MAX as i64 {
+        return Err(invalid_data(format!("duration days {} do not fit i32", days)));
+    }
+
+    Ok(Duration {
+        months: months as i32,
+        days: days as i32,
+        nanos: nanos,
+    })
+}
+
 // Decodes Cassandra `Udt` data (bytes) into Rust's `Result<Vec<CBytes>, io::Error>`
 // each `CBytes` is encoded type of field of user defined type
 pub fn decode_udt(bytes: &[u8], l: usize) -> Result<Vec<CBytes>, io::Error> {
@@ -180,3 +874,815 @@ pub fn decode_udt(bytes: &[u8], l: usize) -> Result<Vec<CBytes>, io::Error> {
     let list = (0..l).map(|_| CBytes::from_cursor(&mut cursor)).collect();
     Ok(list)
 }
+
+// Decodes Cassandra `Udt` data (bytes) into Rust's
+// `Result<Vec<(String, CassandraType)>, io::Error>` where each item is a pair of field name
+// and decoded field value in the order of `field_defs`. Trailing fields that are absent
+// in `bytes` (e.g. ones added to UDT after the value was written) are decoded as NULL.
+pub fn decode_udt_named(bytes: &[u8],
+                        field_defs: &[(String, ColTypeOption)])
+                        -> Result<Vec<(String, CassandraType)>, io::Error> {
+    let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
+    field_defs.iter()
+        .map(|&(ref name, ref field_type)| {
+            let value = if cursor.position() as usize >= bytes.len() {
+                CassandraType::Null
+            } else {
+                try!(decode_typed_value(&mut cursor, field_type))
+            };
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+// Decodes Cassandra `tuple` data (bytes) into Rust's `Result<Vec<CassandraType>, io::Error>`
+// decoding each element by its type. NULL elements, including trailing ones which
+// are omitted, are decoded into `CassandraType::Null`.
+pub fn decode_tuple(bytes: &[u8],
+                    element_types: &[ColTypeOption])
+                    -> Result<Vec<CassandraType>, io::Error> {
+    let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(bytes);
+    element_types.iter()
+        .map(|element_type| {
+            if cursor.position() as usize >= bytes.len() {
+                return Ok(CassandraType::Null);
+            }
+            decode_typed_value(&mut cursor, element_type)
+        })
+        .collect()
+}
+
+// Encodes Rust's `i32` into Cassandra `int` data (bytes), the inverse of `decode_int`.
+pub fn encode_int(int: i32) -> Vec<u8> {
+    to_int(int)
+}
+
+// Encodes Rust's `i64` into Cassandra `bigint` data (bytes), the inverse of `decode_bigint`.
+pub fn encode_bigint(int: i64) -> Vec<u8> {
+    to_bigint(int)
+}
+
+// Encodes Rust's `i16` into Cassandra `smallint` data (bytes), the inverse of
+// `decode_smallint`.
+pub fn encode_smallint(int: i16) -> Vec<u8> {
+    to_short(int)
+}
+
+// Encodes Rust's `i8` into Cassandra `tinyint` data (bytes), the inverse of `decode_tinyint`.
+pub fn encode_tinyint(int: i8) -> Vec<u8> {
+    vec![int as u8]
+}
+
+// Encodes Rust's `f32` into Cassandra `float` data (bytes), the inverse of `decode_float`.
+pub fn encode_float(float: f32) -> Vec<u8> {
+    to_float(float)
+}
+
+// Encodes Rust's `f64` into Cassandra `double` data (bytes), the inverse of `decode_double`.
+pub fn encode_double(double: f64) -> Vec<u8> {
+    to_float_big(double)
+}
+
+// Encodes Rust's `bool` into Cassandra `boolean` data (bytes), the inverse of
+// `decode_boolean`.
+pub fn encode_boolean(boolean: bool) -> Vec<u8> {
+    vec![if boolean { 1 } else { 0 }]
+}
+
+// Encodes milliseconds since the unix epoch into Cassandra `timestamp` data (bytes),
+// the inverse of `decode_timestamp`.
+pub fn encode_timestamp(millis: i64) -> Vec<u8> {
+    to_bigint(millis)
+}
+
+// Encodes days into Cassandra `date` data (bytes) in the same way `decode_date` decodes
+// them, i.e. the epoch (1970-1-1) is at 2^31.
+pub fn encode_date(days: i32) -> Vec<u8> {
+    to_int(days)
+}
+
+// Encodes nanoseconds since midnight into Cassandra `time` data (bytes), the inverse
+// of `decode_time`. Cassandra rejects values out of `[0, 86399999999999]`.
+pub fn encode_time(nanos: i64) -> Vec<u8> {
+    to_bigint(nanos)
+}
+
+// Encodes Rust's `uuid::Uuid` into Cassandra `uuid` or `timeuuid` data (bytes), the inverse
+// of `decode_uuid`.
+pub fn encode_uuid(uuid: &uuid::Uuid) -> Vec<u8> {
+    uuid.as_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32, f64, i16, i32, i64, i8};
+    use super::*;
+
+    #[test]
+    fn decode_boolean_test() {
+        assert_eq!(decode_boolean(&[0x00]).unwrap(), false);
+        assert_eq!(decode_boolean(&[0x01]).unwrap(), true);
+        assert!(decode_boolean(&[]).is_err());
+        assert!(decode_boolean(&[0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn decode_int_test() {
+        assert_eq!(decode_int(&[0, 0, 0, 5]).unwrap(), 5);
+        assert_eq!(decode_int(&[0xFF, 0xFF, 0xFF, 0xFB]).unwrap(), -5);
+        assert!(decode_int(&[0, 0, 5]).is_err());
+        assert!(decode_int(&[0, 0, 0, 0, 5]).is_err());
+    }
+
+    #[test]
+    fn decode_smallint_test() {
+        assert_eq!(decode_smallint(&[0, 5]).unwrap(), 5);
+        assert_eq!(decode_smallint(&[0xFF, 0xFB]).unwrap(), -5);
+        assert!(decode_smallint(&[5]).is_err());
+        assert!(decode_smallint(&[0, 0, 0, 5]).is_err());
+        assert_eq!(decode_smallint(&[0, 0, 0, 5]).unwrap_err().to_string(),
+                   "expected 2 bytes for smallint, got 4");
+    }
+
+    #[test]
+    fn decode_float_test() {
+        assert_eq!(decode_float(&[0x3F, 0xC0, 0, 0]).unwrap(), 1.5);
+        assert!(decode_float(&[0x7F, 0xC0, 0, 0]).unwrap().is_nan());
+        assert_eq!(decode_float(&[0x7F, 0x80, 0, 0]).unwrap(), ::std::f32::INFINITY);
+        assert_eq!(decode_float(&[0x3F, 0xC0]).unwrap_err().to_string(),
+                   "expected 4 bytes for float, got 2");
+    }
+
+    #[test]
+    fn decode_double_test() {
+        assert_eq!(decode_double(&[0x3F, 0xF8, 0, 0, 0, 0, 0, 0]).unwrap(), 1.5);
+        assert!(decode_double(&[0x7F, 0xF8, 0, 0, 0, 0, 0, 0]).unwrap().is_nan());
+        assert_eq!(decode_double(&[0xFF, 0xF0, 0, 0, 0, 0, 0, 0]).unwrap(),
+                   ::std::f64::NEG_INFINITY);
+        assert!(decode_double(&[0x3F, 0xF8, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn decode_bigint_test() {
+        assert_eq!(decode_bigint(&[0x00, 0x00, 0x00, 0x02, 0x18, 0x71, 0x1A, 0x00]).unwrap(),
+                   9_000_000_000);
+        assert_eq!(decode_bigint(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap(),
+                   -1);
+    }
+
+    #[test]
+    fn decode_counter_test() {
+        assert_eq!(decode_counter(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]).unwrap(),
+                   -2);
+        assert!(decode_counter(&[0xFF, 0xFF, 0xFF, 0xFE]).is_err());
+    }
+
+    #[test]
+    fn decode_timestamp_test() {
+        // 2039-09-18T23:06:40Z, beyond the 32-bit seconds range
+        assert_eq!(decode_timestamp(&[0, 0, 2, 0, 58, 55, 240, 0]).unwrap(),
+                   2_200_000_000_000);
+        assert_eq!(decode_timestamp(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap(),
+                   -1);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn decode_timestamp_naive_test() {
+        let date_time = decode_timestamp_naive(&[0, 0, 2, 0, 58, 55, 240, 0]).unwrap();
+        assert_eq!(date_time, NaiveDate::from_ymd(2039, 9, 18).and_hms(23, 6, 40));
+
+        let minus_one = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let before_epoch = decode_timestamp_naive(&minus_one).unwrap();
+        assert_eq!(before_epoch,
+                   NaiveDate::from_ymd(1969, 12, 31).and_hms_milli(23, 59, 59, 999));
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn decode_date_naive_test() {
+        // 0: -5877641-06-23 is beyond the range supported by chrono
+        assert!(decode_date_naive(&[0, 0, 0, 0]).is_err());
+        // 2^31: 1970-1-1
+        assert_eq!(decode_date_naive(&[0x80, 0, 0, 0]).unwrap(),
+                   NaiveDate::from_ymd(1970, 1, 1));
+        assert_eq!(decode_date_naive(&[0x7F, 0xFF, 0xFF, 0xFF]).unwrap(),
+                   NaiveDate::from_ymd(1969, 12, 31));
+        // 2^32: 5881580-07-11 is beyond the range supported by chrono
+        assert!(decode_date_naive(&[0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn decode_time_test() {
+        assert_eq!(decode_time(&[0, 0, 0, 0, 0, 0, 0, 0]).unwrap(), 0);
+        let before_midnight = [0, 0, 0x4E, 0x94, 0x91, 0x4E, 0xFF, 0xFF];
+        assert_eq!(decode_time(&before_midnight).unwrap(), 86_399_999_999_999);
+        let midnight = [0, 0, 0x4E, 0x94, 0x91, 0x4F, 0, 0];
+        assert!(decode_time(&midnight).is_err());
+        assert!(decode_time(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+        // time is always encoded with 8 bytes
+        assert!(decode_time(&[]).is_err());
+        assert!(decode_time(&[0; 7]).is_err());
+        assert!(decode_time(&[0; 9]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn decode_time_naive_test() {
+        assert_eq!(decode_time_naive(&[0, 0, 0, 0, 0, 0, 0, 0]).unwrap(),
+                   NaiveTime::from_hms(0, 0, 0));
+        let before_midnight = [0, 0, 0x4E, 0x94, 0x91, 0x4E, 0xFF, 0xFF];
+        assert_eq!(decode_time_naive(&before_midnight).unwrap(),
+                   NaiveTime::from_hms_nano(23, 59, 59, 999_999_999));
+        assert!(decode_time_naive(&[0, 0, 0x4E, 0x94, 0x91, 0x4F, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn decode_timeuuid_test() {
+        // 13814000-1dd2-11b2-8000-000000000000 is a time UUID of the Unix epoch
+        let epoch = [0x13, 0x81, 0x40, 0x00, 0x1D, 0xD2, 0x11, 0xB2, 0x80, 0, 0, 0, 0, 0, 0, 0];
+        let uuid = decode_timeuuid(&epoch).unwrap();
+        assert_eq!(timeuuid_timestamp(&uuid), 0x01B21DD213814000);
+
+        let v4 = [0x13, 0x81, 0x40, 0x00, 0x1D, 0xD2, 0x41, 0xB2, 0x80, 0, 0, 0, 0, 0, 0, 0];
+        assert!(decode_uuid(&v4).is_ok());
+        assert!(decode_timeuuid(&v4).is_err());
+    }
+
+    #[test]
+    fn generate_timeuuid_test() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let uuid = generate_timeuuid();
+        assert_eq!(uuid.get_version_num(), 1);
+        assert!(decode_timeuuid(uuid.as_bytes()).is_ok());
+
+        let epoch = generate_timeuuid_at(UNIX_EPOCH);
+        assert_eq!(timeuuid_timestamp(&epoch), 0x01B21DD213814000);
+        let time = UNIX_EPOCH + Duration::new(1_500_000_000, 123_456_700);
+        let uuid = generate_timeuuid_at(time);
+        assert_eq!(uuid.get_version_num(), 1);
+        assert_eq!(timeuuid_timestamp(&uuid),
+                   0x01B21DD213814000 + 15_000_000_001_234_567);
+        // RFC 4122 variant
+        assert_eq!(uuid.as_bytes()[8] & 0xC0, 0x80);
+
+        // UUIDs of the same tick differ by clock sequence
+        let clock_sequence = |uuid: &uuid::Uuid| {
+            (uuid.as_bytes()[8] as u16 & 0x3F) << 8 | uuid.as_bytes()[9] as u16
+        };
+        let same_tick = generate_timeuuid_at(time);
+        assert_eq!(timeuuid_timestamp(&same_tick), timeuuid_timestamp(&uuid));
+        assert_eq!(clock_sequence(&same_tick), (clock_sequence(&uuid) + 1) & 0x3FFF);
+
+        let now = SystemTime::now();
+        let mut previous = generate_timeuuid_at(now);
+        for _ in 0..1000 {
+            let next = generate_timeuuid();
+            if timeuuid_timestamp(&next) == timeuuid_timestamp(&previous) {
+                assert_eq!(clock_sequence(&next), (clock_sequence(&previous) + 1) & 0x3FFF);
+            } else {
+                assert!(timeuuid_timestamp(&next) > timeuuid_timestamp(&previous));
+            }
+            previous = next;
+        }
+    }
+
+    #[test]
+    fn uuid_round_trip() {
+        let v4 = uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        assert_eq!(v4.get_version_num(), 4);
+        assert_eq!(decode_uuid(encode_uuid(&v4).as_slice()).unwrap(), v4);
+
+        let nil = uuid::Uuid::nil();
+        assert_eq!(encode_uuid(&nil), vec![0; 16]);
+        assert_eq!(decode_uuid(encode_uuid(&nil).as_slice()).unwrap(), nil);
+
+        assert!(decode_uuid(&[0; 15]).is_err());
+        assert!(decode_uuid(&[0; 17]).is_err());
+    }
+
+    #[test]
+    fn decode_duration_test() {
+        // 1mo2d3ns
+        assert_eq!(decode_duration(&[0x02, 0x04, 0x06]).unwrap(),
+                   Duration {
+                       months: 1,
+                       days: 2,
+                       nanos: 3,
+                   });
+        // -1mo2d3ns
+        assert_eq!(decode_duration(&[0x01, 0x03, 0x05]).unwrap(),
+                   Duration {
+                       months: -1,
+                       days: -2,
+                       nanos: -3,
+                   });
+        // 1h
+        assert_eq!(decode_duration(&[0x00, 0x00, 0xFC, 0x06, 0x8C, 0x61, 0x71, 0x40, 0x00])
+                       .unwrap(),
+                   Duration {
+                       months: 0,
+                       days: 0,
+                       nanos: 3_600_000_000_000,
+                   });
+        assert!(decode_duration(&[0x02, 0x04]).is_err());
+        assert_eq!(encode_duration(Duration {
+                       months: 0,
+                       days: 0,
+                       nanos: 3_600_000_000_000,
+                   }),
+                   vec![0x00, 0x00, 0xFC, 0x06, 0x8C, 0x61, 0x71, 0x40, 0x00]);
+        for nanos in &[0, 63, -64, 64, -65, 1 << 55, i64::MIN, i64::MAX] {
+            let duration = Duration {
+                months: i32::MIN,
+                days: i32::MAX,
+                nanos: *nanos,
+            };
+            assert_eq!(decode_duration(encode_duration(duration).as_slice()).unwrap(),
+                       duration);
+        }
+        // trailing byte
+        assert!(decode_duration(&[0x02, 0x04, 0x06, 0x00]).is_err());
+        // months = 2^31 and days = -2^31 - 1
+        assert!(decode_duration(&[0xF1, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).is_err());
+        assert!(decode_duration(&[0x00, 0xF1, 0x00, 0x00, 0x00, 0x01, 0x00]).is_err());
+        // months = 2^31 - 1
+        assert_eq!(decode_duration(&[0xF0, 0xFF, 0xFF, 0xFF, 0xFE, 0x00, 0x00]).unwrap().months,
+                   i32::MAX);
+    }
+
+    #[test]
+    fn decode_by_type_row() {
+        let simple = |id| {
+            ColTypeOption {
+                id: id,
+                value: None,
+            }
+        };
+        let list_type = ColTypeOption {
+            id: ColType::List,
+            value: Some(ColTypeOptionValue::CList(Box::new(simple(ColType::Int)))),
+        };
+        let map_type = ColTypeOption {
+            id: ColType::Map,
+            value: Some(ColTypeOptionValue::CMap((Box::new(simple(ColType::Ascii)),
+                                                  Box::new(simple(ColType::Bigint))))),
+        };
+        let row: Vec<(ColTypeOption, Vec<u8>)> =
+            vec![(simple(ColType::Ascii), vec![b'i', b'd']),
+                 (simple(ColType::Int), vec![0, 0, 0, 7]),
+                 (simple(ColType::Bigint), vec![0, 0, 0, 0, 0, 0, 0x01, 0x00]),
+                 (simple(ColType::Blob), vec![1, 2]),
+                 (simple(ColType::Boolean), vec![1]),
+                 (simple(ColType::Varint), vec![0x01, 0x00]),
+                 (simple(ColType::Time), vec![0, 0, 0, 0, 0, 0, 0x03, 0xE8]),
+                 (simple(ColType::Duration), vec![0x02, 0x04, 0x06]),
+                 (list_type, vec![0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 3]),
+                 (map_type,
+                  vec![0, 0, 0, 1, 0, 0, 0, 1, b'k', 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 9])];
+
+        let decoded: Vec<CassandraType> = row.iter()
+            .map(|&(ref col_type, ref bytes)| decode_by_type(bytes.as_slice(), col_type).unwrap())
+            .collect();
+
+        // malformed varints and times are errors rather than panics
+        for bytes in &[vec![], vec![0; 9]] {
+            assert!(decode_by_type(bytes.as_slice(), &simple(ColType::Varint)).is_err());
+            assert!(decode_by_type(bytes.as_slice(), &simple(ColType::Time)).is_err());
+        }
+
+        assert_eq!(decoded,
+                   vec![CassandraType::Ascii("id".to_string()),
+                        CassandraType::Int(7),
+                        CassandraType::Bigint(256),
+                        CassandraType::Blob(vec![1, 2]),
+                        CassandraType::Boolean(true),
+                        CassandraType::Varint(256),
+                        CassandraType::Time(1000),
+                        CassandraType::Duration(Duration {
+                            months: 1,
+                            days: 2,
+                            nanos: 3,
+                        }),
+                        CassandraType::List(vec![CassandraType::Int(3)]),
+                        CassandraType::Map(vec![(CassandraType::Ascii("k".to_string()),
+                                                 CassandraType::Bigint(9))])]);
+    }
+
+    #[test]
+    fn decode_nullable_test() {
+        let text_type = ColTypeOption {
+            id: ColType::Varchar,
+            value: None,
+        };
+        assert_eq!(decode_nullable(None, &text_type).unwrap(), None);
+        assert_eq!(decode_nullable(Some(&[]), &text_type).unwrap(),
+                   Some(CassandraType::Varchar("".to_string())));
+
+        let blob_type = ColTypeOption {
+            id: ColType::Blob,
+            value: None,
+        };
+        let mut cursor: io::Cursor<&[u8]> = io::Cursor::new(&[255, 255, 255, 255, 0, 0, 0, 0]);
+        let null = CBytes::from_cursor(&mut cursor);
+        let empty = CBytes::from_cursor(&mut cursor);
+        assert_eq!(decode_nullable(null.as_opt_slice(), &blob_type).unwrap(), None);
+        assert_eq!(decode_nullable(empty.as_opt_slice(), &blob_type).unwrap(),
+                   Some(CassandraType::Blob(vec![])));
+    }
+
+    #[test]
+    fn decode_nullable_empty_scalars() {
+        // empty but present values are either decoded or rejected, they never panic
+        let empty_values = vec![ColType::Ascii, ColType::Blob, ColType::Varchar];
+        let rejected = vec![ColType::Bigint,
+                            ColType::Boolean,
+                            ColType::Counter,
+                            ColType::Decimal,
+                            ColType::Double,
+                            ColType::Float,
+                            ColType::Int,
+                            ColType::Timestamp,
+                            ColType::Uuid,
+                            ColType::Varint,
+                            ColType::Timeuuid,
+                            ColType::Inet,
+                            ColType::Date,
+                            ColType::Time,
+                            ColType::Smallint,
+                            ColType::Tinyint,
+                            ColType::Duration];
+
+        for id in empty_values {
+            let col_type = ColTypeOption {
+                id: id,
+                value: None,
+            };
+            assert!(decode_nullable(Some(&[]), &col_type).unwrap().is_some(),
+                    "{:?}",
+                    col_type.id);
+        }
+        for id in rejected {
+            let col_type = ColTypeOption {
+                id: id,
+                value: None,
+            };
+            assert!(decode_nullable(Some(&[]), &col_type).is_err(), "{:?}", col_type.id);
+        }
+    }
+
+    #[test]
+    fn decode_list_typed_test() {
+        let text_type = ColTypeOption {
+            id: ColType::Varchar,
+            value: None,
+        };
+        let texts = [0, 0, 0, 3, 0, 0, 0, 1, b'a', 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(decode_list_typed(&texts, &text_type).unwrap(),
+                   vec![CassandraType::Varchar("a".to_string()),
+                        CassandraType::Varchar("".to_string()),
+                        CassandraType::Null]);
+
+        let int_type = ColTypeOption {
+            id: ColType::Int,
+            value: None,
+        };
+        let ints = [0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 4, 0xFF, 0xFF, 0xFF, 0xFE];
+        assert_eq!(decode_list_typed(&ints, &int_type).unwrap(),
+                   vec![CassandraType::Int(1), CassandraType::Int(-2)]);
+        assert!(decode_list_typed(&ints[..10], &int_type).is_err());
+
+        // the length of the element exceeds the rest of the list
+        let oversized = [0, 0, 0, 1, 0x7F, 0xFF, 0xFF, 0xFF, 0, 0, 0, 1];
+        match decode_list_typed(&oversized, &int_type) {
+            Err(ref err) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_list_typed_nested() {
+        let nested_type = ColTypeOption {
+            id: ColType::List,
+            value: Some(ColTypeOptionValue::CList(Box::new(ColTypeOption {
+                id: ColType::Int,
+                value: None,
+            }))),
+        };
+        let nested = [0, 0, 0, 1, 0, 0, 0, 12, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 7];
+        assert_eq!(decode_list_typed(&nested, &nested_type).unwrap(),
+                   vec![CassandraType::List(vec![CassandraType::Int(7)])]);
+    }
+
+    #[test]
+    fn decode_map_typed_test() {
+        let key_type = ColTypeOption {
+            id: ColType::Varchar,
+            value: None,
+        };
+        let value_type = ColTypeOption {
+            id: ColType::Int,
+            value: None,
+        };
+        let map = [0, 0, 0, 2, 0, 0, 0, 1, b'b', 0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 1, b'a', 0xFF,
+                   0xFF, 0xFF, 0xFF];
+        assert_eq!(decode_map_typed(&map, &key_type, &value_type).unwrap(),
+                   vec![(CassandraType::Varchar("b".to_string()), CassandraType::Int(2)),
+                        (CassandraType::Varchar("a".to_string()), CassandraType::Null)]);
+
+        assert_eq!(decode_map_typed(&[0, 0, 0, 0], &key_type, &value_type).unwrap(),
+                   vec![]);
+    }
+
+    #[test]
+    fn decode_udt_named_test() {
+        let field_defs = vec![("name".to_string(),
+                               ColTypeOption {
+                                   id: ColType::Varchar,
+                                   value: None,
+                               }),
+                              ("age".to_string(),
+                               ColTypeOption {
+                                   id: ColType::Int,
+                                   value: None,
+                               })];
+        let udt = [0, 0, 0, 2, b'j', b'o', 0, 0, 0, 4, 0, 0, 0, 30];
+        assert_eq!(decode_udt_named(&udt, field_defs.as_slice()).unwrap(),
+                   vec![("name".to_string(), CassandraType::Varchar("jo".to_string())),
+                        ("age".to_string(), CassandraType::Int(30))]);
+
+        // `age` field was added after the value had been written
+        assert_eq!(decode_udt_named(&udt[..6], field_defs.as_slice()).unwrap(),
+                   vec![("name".to_string(), CassandraType::Varchar("jo".to_string())),
+                        ("age".to_string(), CassandraType::Null)]);
+    }
+
+    #[test]
+    fn decode_tuple_test() {
+        let element_types: Vec<ColTypeOption> = vec![ColType::Int, ColType::Varchar, ColType::Uuid]
+            .into_iter()
+            .map(|id| {
+                ColTypeOption {
+                    id: id,
+                    value: None,
+                }
+            })
+            .collect();
+        let mut tuple = vec![0, 0, 0, 4, 0, 0, 0, 7, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 16];
+        tuple.extend_from_slice(&[0x13, 0x81, 0x40, 0x00, 0x1D, 0xD2, 0x11, 0xB2, 0x80, 0, 0, 0,
+                                  0, 0, 0, 0]);
+        let uuid = uuid::Uuid::parse_str("13814000-1dd2-11b2-8000-000000000000").unwrap();
+        assert_eq!(decode_tuple(tuple.as_slice(), element_types.as_slice()).unwrap(),
+                   vec![CassandraType::Int(7), CassandraType::Null, CassandraType::Uuid(uuid)]);
+
+        // trailing NULL elements are omitted
+        assert_eq!(decode_tuple(&tuple[..8], element_types.as_slice()).unwrap(),
+                   vec![CassandraType::Int(7), CassandraType::Null, CassandraType::Null]);
+
+        let tuple_type = ColTypeOption {
+            id: ColType::Tuple,
+            value: Some(ColTypeOptionValue::TupleType(element_types)),
+        };
+        assert_eq!(decode_by_type(&tuple[..8], &tuple_type).unwrap(),
+                   CassandraType::Tuple(vec![CassandraType::Int(7),
+                                             CassandraType::Null,
+                                             CassandraType::Null]));
+    }
+
+    #[test]
+    fn decode_custom_by_type() {
+        let class_name = "org.apache.cassandra.db.marshal.DateRangeType";
+        // column type metadata: custom type id followed by its class name
+        let mut metadata = vec![0, 0];
+        metadata.extend_from_slice(CString::new(class_name.to_string()).into_cbytes().as_slice());
+        let custom_type = ColTypeOption::from_cursor(&mut io::Cursor::new(metadata.as_slice()));
+
+        let bytes = [0x00, 0xFF, 0xFE];
+        assert_eq!(decode_by_type(&bytes, &custom_type).unwrap(),
+                   CassandraType::Custom {
+                       class_name: class_name.to_string(),
+                       bytes: bytes.to_vec(),
+                   });
+
+        let no_class_name = ColTypeOption {
+            id: ColType::Custom,
+            value: None,
+        };
+        assert!(decode_by_type(&bytes, &no_class_name).is_err());
+    }
+
+    #[test]
+    fn decode_tinyint_test() {
+        assert_eq!(decode_tinyint(&[0x7F]).unwrap(), 127);
+        assert_eq!(decode_tinyint(&[0xFF]).unwrap(), -1);
+        assert!(decode_tinyint(&[]).is_err());
+    }
+
+    #[test]
+    fn decode_text_strict_test() {
+        let invalid = &[b'a', 0xFF, b'b'];
+        assert_eq!(decode_text(invalid).unwrap(), "a\u{FFFD}b");
+        assert_eq!(decode_varchar(invalid).unwrap(), "a\u{FFFD}b");
+        assert_eq!(decode_custom(invalid).unwrap(), "a\u{FFFD}b");
+        assert!(decode_text_strict(invalid).is_err());
+        assert!(decode_varchar_strict(invalid).is_err());
+        assert!(decode_custom_strict(invalid).is_err());
+        assert_eq!(decode_text_strict("текст".as_bytes()).unwrap(), "текст");
+    }
+
+    #[test]
+    fn decode_ascii_test() {
+        assert_eq!(decode_ascii(b"ascii").unwrap(), "ascii");
+        // valid UTF-8 but not ASCII
+        assert_eq!(decode_ascii("é".as_bytes()).unwrap_err().kind(),
+                   io::ErrorKind::InvalidData);
+        assert!(decode_ascii(&[b'a', 0x80]).is_err());
+    }
+
+    #[test]
+    fn decode_text_borrowed_test() {
+        let bytes = b"text".to_vec();
+        let text = decode_text_borrowed(bytes.as_slice()).unwrap();
+        assert_eq!(text, "text");
+        assert_eq!(text.as_ptr(), bytes.as_ptr());
+        assert_eq!(decode_varchar_borrowed(b"varchar").unwrap(), "varchar");
+        assert_eq!(decode_ascii_borrowed(b"ascii").unwrap(), "ascii");
+        assert!(decode_text_borrowed(&[0xFF, 0xFE]).is_err());
+    }
+
+    #[test]
+    fn decode_inet_test() {
+        assert_eq!(decode_inet(&[127, 0, 0, 1]).unwrap(),
+                   net::IpAddr::V4(net::Ipv4Addr::new(127, 0, 0, 1)));
+        assert_eq!(decode_inet(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap(),
+                   net::IpAddr::V6(net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)));
+        assert!(decode_inet(&[1, 2, 3, 4, 5, 6, 7]).is_err());
+    }
+
+    #[test]
+    fn encode_inet_test() {
+        let addrs = ["127.0.0.1", "0.0.0.0", "255.255.255.255", "::1", "::", "fe80::1:2",
+                     "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"];
+        for addr in addrs.iter() {
+            let addr: net::IpAddr = addr.parse().unwrap();
+            let bytes = encode_inet(&addr);
+            assert_eq!(bytes.len(), if addr.is_ipv4() { 4 } else { 16 });
+            assert_eq!(decode_inet(bytes.as_slice()).unwrap(), addr);
+        }
+        assert_eq!(encode_inet(&"10.0.0.1".parse().unwrap()), vec![10, 0, 0, 1]);
+    }
+
+    #[test]
+    fn decode_decimal_test() {
+        // 12.34 => scale 2, unscaled 1234
+        assert_eq!(decode_decimal(&[0, 0, 0, 2, 0x04, 0xD2]).unwrap(),
+                   Decimal::new(1234, 2));
+        // -1.5 => scale 1, unscaled -15
+        assert_eq!(decode_decimal(&[0, 0, 0, 1, 0xF1]).unwrap(), Decimal::new(-15, 1));
+        // 200 => scale 0, unscaled 200 (leading zero keeps it positive)
+        assert_eq!(decode_decimal(&[0, 0, 0, 0, 0x00, 0xC8]).unwrap(), Decimal::new(200, 0));
+        // 1200 => scale -2, unscaled 12
+        assert_eq!(decode_decimal(&[0xFF, 0xFF, 0xFF, 0xFE, 0x0C]).unwrap(),
+                   Decimal::new(12, -2));
+    }
+
+    #[test]
+    fn decode_decimal_exact() {
+        // 0.1 and 9007199254740993 (2^53 + 1) have no exact f32/f64 representation
+        let decimal = decode_decimal(&[0, 0, 0, 1, 0x01]).unwrap();
+        assert_eq!(decimal.to_string(), "0.1");
+        let decimal = decode_decimal(encode_decimal(9_007_199_254_740_993, 0).as_slice())
+            .unwrap();
+        assert_eq!(decimal.to_string(), "9007199254740993");
+        assert_eq!(decimal.to_f64(), 9_007_199_254_740_992.0);
+    }
+
+    #[test]
+    fn decode_decimal_too_long() {
+        // i64::MAX + 1 as unscaled value
+        let bytes = [0, 0, 0, 0, 0x00, 0x80, 0, 0, 0, 0, 0, 0, 0];
+        assert!(decode_decimal(&bytes).is_err());
+    }
+
+    #[test]
+    fn decimal_display() {
+        let cases = [(Decimal::new(1234, 2), "12.34"),
+                     (Decimal::new(-15, 1), "-1.5"),
+                     (Decimal::new(-5, 2), "-0.05"),
+                     (Decimal::new(150, 2), "1.50"),
+                     (Decimal::new(12, -2), "1200"),
+                     (Decimal::new(0, 3), "0.000"),
+                     (Decimal::new(0, -3), "0"),
+                     (Decimal::new(i64::min_value(), 1), "-922337203685477580.8")];
+        for &(ref decimal, expected) in cases.iter() {
+            assert_eq!(decimal.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn encode_decimal_test() {
+        assert_eq!(encode_decimal(1234, 2), vec![0, 0, 0, 2, 0x04, 0xD2]);
+        assert_eq!(encode_decimal(-15, 1), vec![0, 0, 0, 1, 0xF1]);
+        assert_eq!(encode_decimal(200, 0), vec![0, 0, 0, 0, 0x00, 0xC8]);
+        assert_eq!(encode_decimal(0, 3), vec![0, 0, 0, 3, 0x00]);
+    }
+
+    #[test]
+    fn encode_varint_test() {
+        assert_eq!(encode_varint(0), vec![0x00]);
+        assert_eq!(encode_varint(127), vec![0x7F]);
+        assert_eq!(encode_varint(128), vec![0x00, 0x80]);
+        assert_eq!(encode_varint(255), vec![0x00, 0xFF]);
+        assert_eq!(encode_varint(-1), vec![0xFF]);
+        assert_eq!(encode_varint(-128), vec![0x80]);
+        assert_eq!(encode_varint(-129), vec![0xFF, 0x7F]);
+        assert_eq!(encode_varint(i64::max_value() - 1),
+                   vec![0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn encode_varint_round_trip() {
+        let values = [0i64, 255, -128, 65535, -65536, i64::max_value() - 1, i64::min_value()];
+        for &value in values.iter() {
+            assert_eq!(decode_varint(encode_varint(value).as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn encode_varint_big_test() {
+        assert_eq!(encode_varint_big(&BigInt::from(255)), vec![0x00, 0xFF]);
+        assert_eq!(encode_varint_big(&BigInt::from(-128)), vec![0x80]);
+
+        // i64::MAX + 1
+        let big = BigInt::from(i64::max_value()) + BigInt::from(1);
+        let bytes = encode_varint_big(&big);
+        assert_eq!(bytes, vec![0x00, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(BigInt::from_signed_bytes_be(bytes.as_slice()), big);
+    }
+
+    #[test]
+    fn encode_decimal_round_trip() {
+        let values = [0i64, 1, -1, 127, -128, 128, 12345, -12345, 9_007_199_254_740_991];
+        for &unscaled in values.iter() {
+            for &scale in [-3i32, 0, 2, 5].iter() {
+                let decoded = decode_decimal(encode_decimal(unscaled, scale).as_slice()).unwrap();
+                assert_eq!(decoded, Decimal::new(unscaled, scale));
+            }
+        }
+    }
+
+    #[test]
+    fn decode_decimal_too_short() {
+        assert!(decode_decimal(&[]).is_err());
+        assert!(decode_decimal(&[0, 0, 0, 2]).is_err());
+    }
+
+    #[test]
+    fn encode_scalars_round_trip() {
+        for int in &[0, 1, -1, i32::MIN, i32::MAX] {
+            assert_eq!(encode_int(*int).len(), 4);
+            assert_eq!(decode_int(encode_int(*int).as_slice()).unwrap(), *int);
+            assert_eq!(decode_date(encode_date(*int).as_slice()).unwrap(), *int);
+        }
+        for int in &[0, 1, -1, i64::MIN, i64::MAX] {
+            assert_eq!(encode_bigint(*int).len(), 8);
+            assert_eq!(decode_bigint(encode_bigint(*int).as_slice()).unwrap(), *int);
+            assert_eq!(decode_timestamp(encode_timestamp(*int).as_slice()).unwrap(), *int);
+        }
+        for int in &[0, 1, -1, i16::MIN, i16::MAX] {
+            assert_eq!(encode_smallint(*int).len(), 2);
+            assert_eq!(decode_smallint(encode_smallint(*int).as_slice()).unwrap(), *int);
+        }
+        for int in &[0, 1, -1, i8::MIN, i8::MAX] {
+            assert_eq!(encode_tinyint(*int).len(), 1);
+            assert_eq!(decode_tinyint(encode_tinyint(*int).as_slice()).unwrap(), *int);
+        }
+        for nanos in &[0, 1, NANOS_PER_DAY - 1] {
+            assert_eq!(encode_time(*nanos).len(), 8);
+            assert_eq!(decode_time(encode_time(*nanos).as_slice()).unwrap(), *nanos);
+        }
+        for boolean in &[true, false] {
+            assert_eq!(decode_boolean(encode_boolean(*boolean).as_slice()).unwrap(), *boolean);
+        }
+        assert_eq!(encode_int(1), vec![0, 0, 0, 1]);
+        assert_eq!(encode_smallint(-2), vec![0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn encode_floats_round_trip() {
+        for float in &[0.0, -0.0, 1.5, f32::MIN, f32::MAX, f32::INFINITY] {
+            let bytes = encode_float(*float);
+            assert_eq!(bytes.len(), 4);
+            assert_eq!(decode_float(bytes.as_slice()).unwrap().to_bits(), float.to_bits());
+        }
+        for double in &[0.0, -0.0, 1.5, f64::MIN, f64::MAX, f64::NEG_INFINITY] {
+            let bytes = encode_double(*double);
+            assert_eq!(bytes.len(), 8);
+            assert_eq!(decode_double(bytes.as_slice()).unwrap().to_bits(), double.to_bits());
+        }
+        assert!(decode_double(encode_double(f64::NAN).as_slice()).unwrap().is_nan());
+        assert_eq!(encode_float(-0.0), vec![0x80, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,274 @@
+//! Conversions of decoded Cassandra values into Rust types.
+//!
+//! Collections are converted into standard containers, e.g. `list<int>` into `Vec<i32>`
+//! and `map<text, int>` into `HashMap<String, i32>`, if their elements can be converted.
+//! Columns of a row can be read directly as such containers:
+//!
+//! ```ignore
+//! let ids: Vec<i32> = try!(row.get("ids"));
+//! let scores: HashMap<String, i32> = try!(row.get("scores"));
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use error::{Error, Result, type_mismatch_err};
+use types::cassandra_type::CassandraType;
+use types::data_serialization_types::Decimal;
+
+/// Should be implemented by Rust types which can be built from a decoded Cassandra value.
+pub trait FromCassandraType: Sized {
+    fn from_cassandra_type(value: CassandraType) -> Result<Self>;
+}
+
+impl FromCassandraType for CassandraType {
+    fn from_cassandra_type(value: CassandraType) -> Result<CassandraType> {
+        Ok(value)
+    }
+}
+
+impl<T: FromCassandraType> FromCassandraType for Option<T> {
+    /// NULL value is converted into `None`.
+    fn from_cassandra_type(value: CassandraType) -> Result<Option<T>> {
+        match value {
+            CassandraType::Null => Ok(None),
+            value => T::from_cassandra_type(value).map(Some),
+        }
+    }
+}
+
+impl FromCassandraType for bool {
+    fn from_cassandra_type(value: CassandraType) -> Result<bool> {
+        match value {
+            CassandraType::Boolean(v) => Ok(v),
+            _ => Err(type_mismatch_err("Boolean", value)),
+        }
+    }
+}
+
+impl FromCassandraType for i8 {
+    fn from_cassandra_type(value: CassandraType) -> Result<i8> {
+        match value {
+            CassandraType::Tinyint(v) => Ok(v),
+            _ => Err(type_mismatch_err("Tinyint", value)),
+        }
+    }
+}
+
+impl FromCassandraType for i16 {
+    fn from_cassandra_type(value: CassandraType) -> Result<i16> {
+        match value {
+            CassandraType::Smallint(v) => Ok(v),
+            CassandraType::Tinyint(v) => Ok(v as i16),
+            _ => Err(type_mismatch_err("Smallint,Tinyint", value)),
+        }
+    }
+}
+
+impl FromCassandraType for i32 {
+    fn from_cassandra_type(value: CassandraType) -> Result<i32> {
+        match value {
+            CassandraType::Int(v) |
+            CassandraType::Date(v) => Ok(v),
+            _ => Err(type_mismatch_err("Int,Date", value)),
+        }
+    }
+}
+
+impl FromCassandraType for i64 {
+    fn from_cassandra_type(value: CassandraType) -> Result<i64> {
+        match value {
+            CassandraType::Int(v) => Ok(v as i64),
+            CassandraType::Bigint(v) |
+            CassandraType::Counter(v) |
+            CassandraType::Timestamp(v) |
+            CassandraType::Time(v) |
+            CassandraType::Varint(v) => Ok(v),
+            _ => Err(type_mismatch_err("Int,Bigint,Counter,Timestamp,Time,Varint", value)),
+        }
+    }
+}
+
+impl FromCassandraType for f32 {
+    /// `Decimal` is converted lossily, use `Decimal` to read its exact value.
+    fn from_cassandra_type(value: CassandraType) -> Result<f32> {
+        match value {
+            CassandraType::Float(v) => Ok(v),
+            CassandraType::Decimal(v) => Ok(v.to_f64() as f32),
+            _ => Err(type_mismatch_err("Float,Decimal", value)),
+        }
+    }
+}
+
+impl FromCassandraType for f64 {
+    fn from_cassandra_type(value: CassandraType) -> Result<f64> {
+        match value {
+            CassandraType::Double(v) => Ok(v),
+            CassandraType::Float(v) => Ok(v as f64),
+            _ => Err(type_mismatch_err("Double,Float", value)),
+        }
+    }
+}
+
+impl FromCassandraType for Decimal {
+    fn from_cassandra_type(value: CassandraType) -> Result<Decimal> {
+        match value {
+            CassandraType::Decimal(v) => Ok(v),
+            _ => Err(type_mismatch_err("Decimal", value)),
+        }
+    }
+}
+
+impl FromCassandraType for String {
+    fn from_cassandra_type(value: CassandraType) -> Result<String> {
+        match value {
+            CassandraType::Ascii(v) |
+            CassandraType::Varchar(v) => Ok(v),
+            _ => Err(type_mismatch_err("Ascii,Varchar", value)),
+        }
+    }
+}
+
+impl FromCassandraType for Uuid {
+    fn from_cassandra_type(value: CassandraType) -> Result<Uuid> {
+        match value {
+            CassandraType::Uuid(v) |
+            CassandraType::Timeuuid(v) => Ok(v),
+            _ => Err(type_mismatch_err("Uuid,Timeuuid", value)),
+        }
+    }
+}
+
+impl FromCassandraType for IpAddr {
+    fn from_cassandra_type(value: CassandraType) -> Result<IpAddr> {
+        match value {
+            CassandraType::Inet(v) => Ok(v),
+            _ => Err(type_mismatch_err("Inet", value)),
+        }
+    }
+}
+
+// Wraps an error of a collection element conversion, `position` names the element.
+fn element_err(position: String, err: Error) -> Error {
+    Error::TypeMismatch(format!("{} cannot be converted: {}", position, err))
+}
+
+// Converts elements of a list or a set. Cassandra returns empty collections as NULL,
+// so NULL is converted into no elements.
+fn elements<T: FromCassandraType>(value: CassandraType) -> Result<Vec<T>> {
+    let items = match value {
+        CassandraType::List(items) |
+        CassandraType::Set(items) => items,
+        CassandraType::Null => vec![],
+        _ => return Err(type_mismatch_err("List,Set", value)),
+    };
+
+    let mut converted = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        match T::from_cassandra_type(item) {
+            Ok(item) => converted.push(item),
+            Err(err) => return Err(element_err(format!("Element {}", i), err)),
+        }
+    }
+
+    Ok(converted)
+}
+
+impl<T: FromCassandraType> FromCassandraType for Vec<T> {
+    fn from_cassandra_type(value: CassandraType) -> Result<Vec<T>> {
+        elements(value)
+    }
+}
+
+impl<T: FromCassandraType + Eq + Hash> FromCassandraType for HashSet<T> {
+    fn from_cassandra_type(value: CassandraType) -> Result<HashSet<T>> {
+        elements(value).map(|items| items.into_iter().collect())
+    }
+}
+
+impl<K, V> FromCassandraType for HashMap<K, V>
+    where K: FromCassandraType + Eq + Hash,
+          V: FromCassandraType
+{
+    /// NULL is converted into an empty map as Cassandra returns empty maps as NULL.
+    fn from_cassandra_type(value: CassandraType) -> Result<HashMap<K, V>> {
+        let entries = match value {
+            CassandraType::Map(entries) => entries,
+            CassandraType::Null => vec![],
+            _ => return Err(type_mismatch_err("Map", value)),
+        };
+
+        let mut converted = HashMap::with_capacity(entries.len());
+        for (key, value) in entries {
+            let key_literal = key.to_string();
+            let key = match K::from_cassandra_type(key) {
+                Ok(key) => key,
+                Err(err) => return Err(element_err(format!("Key {}", key_literal), err)),
+            };
+            match V::from_cassandra_type(value) {
+                Ok(value) => converted.insert(key, value),
+                Err(err) => {
+                    return Err(element_err(format!("Value of key {}", key_literal), err))
+                }
+            };
+        }
+
+        Ok(converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<CassandraType> {
+        values.iter().map(|v| CassandraType::Int(*v)).collect()
+    }
+
+    #[test]
+    fn list_into_vec() {
+        let list = CassandraType::List(ints(&[1, 2, 3]));
+        assert_eq!(Vec::<i32>::from_cassandra_type(list.clone()).unwrap(), vec![1, 2, 3]);
+        assert_eq!(Vec::<i64>::from_cassandra_type(list.clone()).unwrap(), vec![1, 2, 3]);
+        let set: HashSet<i32> = HashSet::from_cassandra_type(list).unwrap();
+        assert_eq!(set, [1, 2, 3].iter().cloned().collect());
+        assert!(Vec::<i32>::from_cassandra_type(CassandraType::Null).unwrap().is_empty());
+
+        let mixed = CassandraType::List(vec![CassandraType::Int(1),
+                                             CassandraType::Varchar("a".to_string())]);
+        match Vec::<i32>::from_cassandra_type(mixed) {
+            Err(Error::TypeMismatch(msg)) => assert!(msg.starts_with("Element 1 ")),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(Vec::<i32>::from_cassandra_type(CassandraType::Int(1)).is_err());
+    }
+
+    #[test]
+    fn map_into_hash_map() {
+        let map = CassandraType::Map(vec![(CassandraType::Varchar("a".to_string()),
+                                           CassandraType::Int(1)),
+                                          (CassandraType::Varchar("b".to_string()),
+                                           CassandraType::Int(2))]);
+        let converted: HashMap<String, i32> = HashMap::from_cassandra_type(map).unwrap();
+        assert_eq!(converted.len(), 2);
+        assert_eq!(converted["a"], 1);
+        assert_eq!(converted["b"], 2);
+
+        let with_null = CassandraType::Map(vec![(CassandraType::Varchar("a".to_string()),
+                                                 CassandraType::Null)]);
+        let converted: HashMap<String, Option<i32>> =
+            HashMap::from_cassandra_type(with_null.clone()).unwrap();
+        assert_eq!(converted["a"], None);
+        match HashMap::<String, i32>::from_cassandra_type(with_null) {
+            Err(Error::TypeMismatch(msg)) => assert!(msg.starts_with("Value of key 'a' ")),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let int_keys = CassandraType::Map(vec![(CassandraType::Int(1), CassandraType::Int(1))]);
+        match HashMap::<String, i32>::from_cassandra_type(int_keys) {
+            Err(Error::TypeMismatch(msg)) => assert!(msg.starts_with("Key 1 ")),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
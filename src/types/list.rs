@@ -5,7 +5,7 @@ use types::{CBytes, AsRust};
 use types::data_serialization_types::*;
 use types::map::Map;
 use types::udt::UDT;
-use error::Result;
+use error::{Error, Result};
 
 // TODO: consider using pointers to ColTypeOption and Vec<CBytes> instead of owning them.
 #[derive(Debug)]
@@ -32,6 +32,16 @@ impl List {
             .map(f)
             .collect()
     }
+
+    // the same as `map` but stops at the first item which fails to be converted
+    fn try_map<T, F>(&self, f: F) -> Result<Vec<T>>
+        where F: FnMut(&CBytes) -> Result<T>
+    {
+        self.data
+            .iter()
+            .map(f)
+            .collect()
+    }
 }
 
 impl AsRust<Vec<Vec<u8>>> for List {
@@ -64,7 +74,9 @@ impl AsRust<Vec<String>> for List {
                     ColType::Custom => {
                         Ok(self.map(|bytes| decode_custom(bytes.as_slice()).unwrap()))
                     }
-                    ColType::Ascii => Ok(self.map(|bytes| decode_ascii(bytes.as_slice()).unwrap())),
+                    ColType::Ascii => {
+                        self.try_map(|bytes| decode_ascii(bytes.as_slice()).map_err(Error::from))
+                    }
                     ColType::Varchar => {
                         Ok(self.map(|bytes| decode_varchar(bytes.as_slice()).unwrap()))
                     }
@@ -76,7 +88,9 @@ impl AsRust<Vec<String>> for List {
                     ColType::Custom => {
                         Ok(self.map(|bytes| decode_custom(bytes.as_slice()).unwrap()))
                     }
-                    ColType::Ascii => Ok(self.map(|bytes| decode_ascii(bytes.as_slice()).unwrap())),
+                    ColType::Ascii => {
+                        self.try_map(|bytes| decode_ascii(bytes.as_slice()).map_err(Error::from))
+                    }
                     ColType::Varchar => {
                         Ok(self.map(|bytes| decode_varchar(bytes.as_slice()).unwrap()))
                     }
@@ -257,7 +271,9 @@ impl AsRust<Vec<f32>> for List {
             Some(ColTypeOptionValue::CList(ref type_option)) => {
                 match type_option.id {
                     ColType::Decimal => {
-                        Ok(self.map(|bytes| decode_decimal(bytes.as_slice()).unwrap()))
+                        Ok(self.map(|bytes| {
+                            decode_decimal(bytes.as_slice()).unwrap().to_f64() as f32
+                        }))
                     }
                     ColType::Float => Ok(self.map(|bytes| decode_float(bytes.as_slice()).unwrap())),
                     _ => unreachable!(),
@@ -266,7 +282,9 @@ impl AsRust<Vec<f32>> for List {
             Some(ColTypeOptionValue::CSet(ref type_option)) => {
                 match type_option.id {
                     ColType::Decimal => {
-                        Ok(self.map(|bytes| decode_decimal(bytes.as_slice()).unwrap()))
+                        Ok(self.map(|bytes| {
+                            decode_decimal(bytes.as_slice()).unwrap().to_f64() as f32
+                        }))
                     }
                     ColType::Float => Ok(self.map(|bytes| decode_float(bytes.as_slice()).unwrap())),
                     _ => unreachable!(),
@@ -305,7 +323,7 @@ impl AsRust<Vec<Uuid>> for List {
             Some(ColTypeOptionValue::CList(ref type_option)) => {
                 match type_option.id {
                     ColType::Uuid => {
-                        Ok(self.map(|bytes| decode_timeuuid(bytes.as_slice()).unwrap()))
+                        Ok(self.map(|bytes| decode_uuid(bytes.as_slice()).unwrap()))
                     }
                     ColType::Timeuuid => {
                         Ok(self.map(|bytes| decode_timeuuid(bytes.as_slice()).unwrap()))
@@ -316,7 +334,7 @@ impl AsRust<Vec<Uuid>> for List {
             Some(ColTypeOptionValue::CSet(ref type_option)) => {
                 match type_option.id {
                     ColType::Uuid => {
-                        Ok(self.map(|bytes| decode_timeuuid(bytes.as_slice()).unwrap()))
+                        Ok(self.map(|bytes| decode_uuid(bytes.as_slice()).unwrap()))
                     }
                     ColType::Timeuuid => {
                         Ok(self.map(|bytes| decode_timeuuid(bytes.as_slice()).unwrap()))
@@ -387,10 +405,10 @@ impl AsRust<Vec<Map>> for List {
                 match type_option.id {
                     // T is Map
                     ColType::Map => {
-                        Ok(self.map(|bytes| {
-                            Map::new(decode_map(bytes.as_slice()).unwrap(),
+                        self.try_map(|bytes| {
+                            Map::new(try!(decode_map(bytes.as_slice())),
                                      type_option.as_ref().clone())
-                        }))
+                        })
                     }
                     _ => unreachable!(),
                 }
@@ -400,10 +418,10 @@ impl AsRust<Vec<Map>> for List {
                 match type_option.id {
                     // T is Map
                     ColType::Map => {
-                        Ok(self.map(|bytes| {
-                            Map::new(decode_map(bytes.as_slice()).unwrap(),
+                        self.try_map(|bytes| {
+                            Map::new(try!(decode_map(bytes.as_slice())),
                                      type_option.as_ref().clone())
-                        }))
+                        })
                     }
                     _ => unreachable!(),
                 }
@@ -7,7 +7,7 @@ use frame::frame_result::{ColTypeOption, ColTypeOptionValue, ColType};
 use types::data_serialization_types::*;
 use types::list::List;
 use types::udt::UDT;
-use error::Result;
+use error::{Error, Result, type_mismatch_err};
 
 #[derive(Debug)]
 pub struct Map {
@@ -16,41 +16,36 @@ pub struct Map {
 }
 
 impl Map {
-    /// Creates new `Map` basing on provided data and key and value types.
-    // TODO: Need to return Result<Map>. If key has not string-like type
-    // return Err otherwise return Ok.
-    pub fn new(data: Vec<(CBytes, CBytes)>, meta: ColTypeOption) -> Map {
-        let accumulator: HashMap<String, CBytes> = HashMap::new();
-
+    /// Creates new `Map` basing on provided data and key and value types. An error is
+    /// returned if keys are not string-like or cannot be decoded, e.g. `ascii` keys
+    /// which contain non-ASCII bytes.
+    pub fn new(data: Vec<(CBytes, CBytes)>, meta: ColTypeOption) -> Result<Map> {
         // check that key could be converted into String
-        let serializer = if let Some(ColTypeOptionValue::CMap((ref key_type, _))) = meta.value {
-            match key_type.id {
-                ColType::Custom => decode_custom,
-                ColType::Ascii => decode_ascii,
-                ColType::Varchar => decode_varchar,
-                // unreachable ??
-                // do we need this arm? is it reachable due to the protocol?
-                _ => unimplemented!(),
+        let key_type = match meta.value {
+            Some(ColTypeOptionValue::CMap((ref key_type, _))) => {
+                match key_type.id {
+                    ColType::Custom | ColType::Ascii | ColType::Varchar => key_type.id.clone(),
+                    ref id => return Err(type_mismatch_err("Custom,Ascii,Varchar", id)),
+                }
             }
-        } else {
-            // do we need this arm? is it reachable due to the protocol?
-            unreachable!();
+            _ => return Err(type_mismatch_err("Map", &meta.id)),
         };
 
-        let map: HashMap<String, CBytes> = data.iter()
-            .fold(accumulator, |mut acc, kv| {
-                let (key_b, value_b) = kv.clone();
-                let key: String = serializer(key_b.as_slice()).unwrap();
-
-                acc.insert(key, value_b);
+        let mut map: HashMap<String, CBytes> = HashMap::with_capacity(data.len());
+        for (key_b, value_b) in data {
+            let key: String = match key_type {
+                ColType::Ascii => try!(decode_ascii(key_b.as_slice())),
+                ColType::Custom => try!(decode_custom(key_b.as_slice())),
+                _ => try!(decode_varchar(key_b.as_slice())),
+            };
 
-                return acc;
-            });
+            map.insert(key, value_b);
+        }
 
-        return Map {
+        Ok(Map {
             metadata: meta,
             data: map,
-        };
+        })
     }
 }
 
@@ -96,12 +91,11 @@ impl AsRust<HashMap<String, String>> for Map {
                             }))
                     }
                     ColType::Ascii => {
-                        Ok(self.data
-                            .iter()
-                            .fold(map, |mut acc, (k, vb)| {
-                                acc.insert(k.clone(), decode_ascii(vb.as_slice()).unwrap());
-                                return acc;
-                            }))
+                        let mut map = map;
+                        for (k, vb) in self.data.iter() {
+                            map.insert(k.clone(), try!(decode_ascii(vb.as_slice())));
+                        }
+                        Ok(map)
                     }
                     ColType::Varchar => {
                         Ok(self.data
@@ -310,7 +304,8 @@ impl AsRust<HashMap<String, f32>> for Map {
                         Ok(self.data
                             .iter()
                             .fold(map, |mut acc, (k, vb)| {
-                                acc.insert(k.clone(), decode_decimal(vb.as_slice()).unwrap());
+                                let decimal = decode_decimal(vb.as_slice()).unwrap();
+                                acc.insert(k.clone(), decimal.to_f64() as f32);
                                 return acc;
                             }))
                     }
@@ -366,7 +361,7 @@ impl AsRust<HashMap<String, Uuid>> for Map {
                         Ok(self.data
                             .iter()
                             .fold(map, |mut acc, (k, vb)| {
-                                acc.insert(k.clone(), decode_timeuuid(vb.as_slice()).unwrap());
+                                acc.insert(k.clone(), decode_uuid(vb.as_slice()).unwrap());
                                 return acc;
                             }))
                     }
@@ -431,14 +426,13 @@ impl AsRust<HashMap<String, Map>> for Map {
             Some(ColTypeOptionValue::CMap((_, ref value_type_option))) => {
                 match value_type_option.id {
                     ColType::Map => {
-                        Ok(self.data
-                            .iter()
-                            .fold(map, |mut acc, (k, vb)| {
-                                let list = Map::new(decode_map(vb.as_slice()).unwrap(),
-                                                    value_type_option.as_ref().clone());
-                                acc.insert(k.clone(), list);
-                                return acc;
-                            }))
+                        let mut map = map;
+                        for (k, vb) in self.data.iter() {
+                            let value = try!(Map::new(try!(decode_map(vb.as_slice())),
+                                                      value_type_option.as_ref().clone()));
+                            map.insert(k.clone(), value);
+                        }
+                        Ok(map)
                     }
                     _ => unreachable!(),
                 }
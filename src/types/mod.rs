@@ -5,18 +5,22 @@ pub const SHORT_LEN: usize = 2;
 pub const INT_LEN: usize = 4;
 pub const UUID_LEN: usize = 16;
 
+use std::collections::HashMap;
 use std::io;
 use std::io::{Cursor, Read};
 use std::net::SocketAddr;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt, ByteOrder};
-use {FromBytes, IntoBytes, FromCursor};
-use error::Result as CDRSResult;
+use {FromBytes, IntoBytes, FromCursor, TryFromCursor};
+use error::{self, Result as CDRSResult};
 use types::data_serialization_types::decode_inet;
 
+pub mod cassandra_type;
 pub mod data_serialization_types;
+pub mod from_cassandra_type;
 pub mod list;
 pub mod map;
 pub mod rows;
+pub mod rows_ref;
 pub mod udt;
 pub mod value;
 
@@ -78,14 +82,36 @@ pub fn try_i_from_bytes(bytes: &[u8]) -> Result<i64, io::Error> {
     return c.read_int::<BigEndian>(l);
 }
 
-/// Tries to decode bytes array into `i32`.
+// Checks that bytes array of `type_name` type contains exactly `len` bytes.
+fn check_bytes_len(bytes: &[u8], len: usize, type_name: &str) -> Result<(), io::Error> {
+    if bytes.len() != len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("expected {} bytes for {}, got {}",
+                                          len,
+                                          type_name,
+                                          bytes.len())));
+    }
+
+    Ok(())
+}
+
+/// Tries to decode bytes array of exactly 8 bytes into `i64`.
+pub fn try_i64_from_bytes(bytes: &[u8]) -> Result<i64, io::Error> {
+    try!(check_bytes_len(bytes, 8, "i64"));
+    let mut c = Cursor::new(bytes);
+    return c.read_i64::<BigEndian>();
+}
+
+/// Tries to decode bytes array of exactly 4 bytes into `i32`.
 pub fn try_i32_from_bytes(bytes: &[u8]) -> Result<i32, io::Error> {
+    try!(check_bytes_len(bytes, INT_LEN, "i32"));
     let mut c = Cursor::new(bytes);
     return c.read_i32::<BigEndian>();
 }
 
-/// Tries to decode bytes array into `i16`.
+/// Tries to decode bytes array of exactly 2 bytes into `i16`.
 pub fn try_i16_from_bytes(bytes: &[u8]) -> Result<i16, io::Error> {
+    try!(check_bytes_len(bytes, SHORT_LEN, "i16"));
     let mut c = Cursor::new(bytes);
     return c.read_i16::<BigEndian>();
 }
@@ -238,6 +264,16 @@ impl FromCursor for CString {
     }
 }
 
+impl TryFromCursor for CString {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CString> {
+        let len = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let body_bytes = try!(try_cursor_next_value(&mut cursor, len as u64));
+        let string = try!(String::from_utf8(body_bytes));
+
+        Ok(CString { string: string })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CStringLong {
     string: String,
@@ -284,6 +320,20 @@ impl FromCursor for CStringLong {
     }
 }
 
+impl TryFromCursor for CStringLong {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CStringLong> {
+        let len = try!(CInt::try_from_cursor(&mut cursor));
+        if len < 0 {
+            let msg = format!("Invalid length {} of [long string]", len);
+            return Err(error::Error::General(msg));
+        }
+        let body_bytes = try!(try_cursor_next_value(&mut cursor, len as u64));
+        let string = try!(String::from_utf8(body_bytes));
+
+        Ok(CStringLong { string: string })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CStringList {
     pub list: Vec<CString>,
@@ -330,28 +380,103 @@ impl FromCursor for CStringList {
     }
 }
 
+impl TryFromCursor for CStringList {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CStringList> {
+        let len = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let list = try!((0..len).map(|_| CString::try_from_cursor(&mut cursor)).collect());
+        Ok(CStringList { list: list })
+    }
+}
+
+/// Cassandra [string multimap], i.e. a map of [string] keys to [string list] values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CStringMultimap {
+    pub map: HashMap<String, Vec<String>>,
+}
+
+impl CStringMultimap {
+    pub fn new(map: HashMap<String, Vec<String>>) -> CStringMultimap {
+        CStringMultimap { map: map }
+    }
+
+    pub fn into_plain(self) -> HashMap<String, Vec<String>> {
+        self.map
+    }
+}
+
+impl IntoBytes for CStringMultimap {
+    fn into_cbytes(&self) -> Vec<u8> {
+        let mut bytes = to_short(self.map.len() as i16);
+        for (key, values) in self.map.iter() {
+            bytes.extend_from_slice(CString::new(key.clone()).into_cbytes().as_slice());
+            bytes.extend_from_slice(to_short(values.len() as i16).as_slice());
+            for value in values {
+                bytes.extend_from_slice(CString::new(value.clone()).into_cbytes().as_slice());
+            }
+        }
+
+        bytes
+    }
+}
+
+impl FromCursor for CStringMultimap {
+    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CStringMultimap {
+        CStringMultimap::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for CStringMultimap {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CStringMultimap> {
+        let len = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let mut map = HashMap::with_capacity(len as usize);
+        for _ in 0..len {
+            let key = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+            let values = try!(CStringList::try_from_cursor(&mut cursor)).into_plain();
+            map.insert(key, values);
+        }
+
+        Ok(CStringMultimap { map: map })
+    }
+}
+
 //
 
 #[derive(Debug, Clone)]
 /// The structure that represents Cassandra byte type
 pub struct CBytes {
-    bytes: Vec<u8>,
+    /// `None` stands for NULL (i.e. negative length) value.
+    bytes: Option<Vec<u8>>,
 }
 
 impl CBytes {
     pub fn new(bytes: Vec<u8>) -> CBytes {
-        return CBytes { bytes: bytes };
+        return CBytes { bytes: Some(bytes) };
     }
-    /// Converts `CBytes` into a plain array of bytes
+    /// Creates NULL `CBytes`.
+    pub fn new_null() -> CBytes {
+        return CBytes { bytes: None };
+    }
+    /// Returns `true` if it's NULL value rather than empty one.
+    pub fn is_null(&self) -> bool {
+        return self.bytes.is_none();
+    }
+    /// Converts `CBytes` into a plain array of bytes. NULL is converted into empty array.
     pub fn into_plain(self) -> Vec<u8> {
-        return self.bytes;
+        return self.bytes.unwrap_or(vec![]);
     }
     // TODO: try to replace usage of `as_plain` by `as_slice`
     pub fn as_plain(&self) -> Vec<u8> {
-        return self.bytes.clone();
+        return self.as_slice().to_vec();
     }
     pub fn as_slice(&self) -> &[u8] {
-        self.bytes.as_slice()
+        match self.bytes {
+            Some(ref bytes) => bytes.as_slice(),
+            None => &[],
+        }
+    }
+    /// Returns `None` for NULL value and `Some` slice of bytes otherwise.
+    pub fn as_opt_slice(&self) -> Option<&[u8]> {
+        return self.bytes.as_ref().map(|bytes| bytes.as_slice());
     }
 }
 
@@ -362,9 +487,21 @@ impl FromCursor for CBytes {
         let len = CInt::from_cursor(&mut cursor);
         // null or not set value
         if len < 0 {
-            return CBytes { bytes: vec![] };
+            return CBytes::new_null();
         }
-        return CBytes { bytes: cursor_next_value(&mut cursor, len as u64) };
+        return CBytes::new(cursor_next_value(&mut cursor, len as u64));
+    }
+}
+
+impl TryFromCursor for CBytes {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CBytes> {
+        let len = try!(CInt::try_from_cursor(&mut cursor));
+        // null or not set value
+        if len < 0 {
+            return Ok(CBytes::new_null());
+        }
+        let bytes = try!(try_cursor_next_value(&mut cursor, len as u64));
+        Ok(CBytes::new(bytes))
     }
 }
 
@@ -372,13 +509,66 @@ impl FromCursor for CBytes {
 impl IntoBytes for CBytes {
     fn into_cbytes(&self) -> Vec<u8> {
         let mut v: Vec<u8> = vec![];
-        let l = self.bytes.len() as i32;
-        v.extend_from_slice(to_int(l).as_slice());
-        v.extend_from_slice(self.bytes.as_slice());
+        match self.bytes {
+            Some(ref bytes) => {
+                v.extend_from_slice(to_int(bytes.len() as i32).as_slice());
+                v.extend_from_slice(bytes.as_slice());
+            }
+            None => v.extend_from_slice(to_int(-1).as_slice()),
+        }
         return v;
     }
 }
 
+/// Cassandra [bytes map], i.e. a map of [string] keys to [bytes] values. NULL values
+/// are read as empty ones.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CBytesMap {
+    pub map: HashMap<String, Vec<u8>>,
+}
+
+impl CBytesMap {
+    pub fn new(map: HashMap<String, Vec<u8>>) -> CBytesMap {
+        CBytesMap { map: map }
+    }
+
+    pub fn into_plain(self) -> HashMap<String, Vec<u8>> {
+        self.map
+    }
+}
+
+impl IntoBytes for CBytesMap {
+    fn into_cbytes(&self) -> Vec<u8> {
+        let mut bytes = to_short(self.map.len() as i16);
+        for (key, value) in self.map.iter() {
+            bytes.extend_from_slice(CString::new(key.clone()).into_cbytes().as_slice());
+            bytes.extend_from_slice(CBytes::new(value.clone()).into_cbytes().as_slice());
+        }
+
+        bytes
+    }
+}
+
+impl FromCursor for CBytesMap {
+    fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CBytesMap {
+        CBytesMap::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for CBytesMap {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CBytesMap> {
+        let len = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let mut map = HashMap::with_capacity(len as usize);
+        for _ in 0..len {
+            let key = try!(CString::try_from_cursor(&mut cursor)).into_plain();
+            let value = try!(CBytes::try_from_cursor(&mut cursor)).into_plain();
+            map.insert(key, value);
+        }
+
+        Ok(CBytesMap { map: map })
+    }
+}
+
 /// Cassandra short bytes
 #[derive(Debug, Clone)]
 pub struct CBytesShort {
@@ -404,6 +594,14 @@ impl FromCursor for CBytesShort {
     }
 }
 
+impl TryFromCursor for CBytesShort {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CBytesShort> {
+        let len = try!(CIntShort::try_from_cursor(&mut cursor)) as u16;
+        let bytes = try!(try_cursor_next_value(&mut cursor, len as u64));
+        Ok(CBytesShort { bytes: bytes })
+    }
+}
+
 // Use extended Rust Vec<u8> as Cassandra [bytes]
 impl IntoBytes for CBytesShort {
     fn into_cbytes(&self) -> Vec<u8> {
@@ -426,6 +624,13 @@ impl FromCursor for CInt {
     }
 }
 
+impl TryFromCursor for CInt {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CInt> {
+        let bytes = try!(try_cursor_next_value(&mut cursor, INT_LEN as u64));
+        try_i32_from_bytes(bytes.as_slice()).map_err(error::Error::from)
+    }
+}
+
 /// Cassandra int short type.
 pub type CIntShort = i16;
 
@@ -436,6 +641,13 @@ impl FromCursor for CIntShort {
     }
 }
 
+impl TryFromCursor for CIntShort {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CIntShort> {
+        let bytes = try!(try_cursor_next_value(&mut cursor, SHORT_LEN as u64));
+        try_i16_from_bytes(bytes.as_slice()).map_err(error::Error::from)
+    }
+}
+
 // Use extended Rust Vec<u8> as Cassandra [bytes]
 impl FromBytes for Vec<u8> {
     fn from_bytes(bytes: &[u8]) -> Vec<u8> {
@@ -453,14 +665,28 @@ pub struct CInet {
     pub addr: SocketAddr,
 }
 
+impl CInet {
+    /// Converts `CInet` into a socket address it contains.
+    pub fn into_plain(self) -> SocketAddr {
+        self.addr
+    }
+}
+
 impl FromCursor for CInet {
     fn from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CInet {
-        let n = CIntShort::from_cursor(&mut cursor);
-        let ip = decode_inet(cursor_next_value(&mut cursor, n as u64).as_slice()).unwrap();
-        let port = CInt::from_cursor(&mut cursor);
+        CInet::try_from_cursor(&mut cursor).unwrap()
+    }
+}
+
+impl TryFromCursor for CInet {
+    fn try_from_cursor(mut cursor: &mut Cursor<&[u8]>) -> CDRSResult<CInet> {
+        // [inet] starts with a single byte which is a length of an address
+        let n = try!(try_cursor_next_value(&mut cursor, 1))[0];
+        let ip = try!(decode_inet(try!(try_cursor_next_value(&mut cursor, n as u64)).as_slice()));
+        let port = try!(CInt::try_from_cursor(&mut cursor));
         let socket_addr = SocketAddr::new(ip, port as u16);
 
-        CInet { addr: socket_addr }
+        Ok(CInet { addr: socket_addr })
     }
 }
 
@@ -479,6 +705,22 @@ pub fn cursor_next_value(mut cursor: &mut Cursor<&[u8]>, len: u64) -> Vec<u8> {
     return buff;
 }
 
+/// Reads next `len` bytes of a cursor. Unlike `cursor_next_value` it returns
+/// `UnexpectedEof` error if the cursor contains less than `len` bytes.
+pub fn try_cursor_next_value(cursor: &mut Cursor<&[u8]>, len: u64) -> io::Result<Vec<u8>> {
+    let position = cursor.position();
+    let available = (cursor.get_ref().len() as u64).saturating_sub(position);
+    if len > available {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                  format!("expected {} more bytes, got {}", len, available)));
+    }
+
+    let start = position as usize;
+    let bytes = cursor.get_ref()[start..start + len as usize].to_vec();
+    cursor.set_position(position + len);
+    Ok(bytes)
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -566,6 +808,30 @@ mod tests {
         assert_eq!(cstring.as_str(), "foo");
     }
 
+    #[test]
+    fn test_cstringlong_longer_than_short_len() {
+        // a length doesn't fit into [short] used by [string]
+        let foo = "f".repeat(70000);
+        let mut bytes = CStringLong::new(foo.clone()).into_cbytes();
+        assert_eq!(&bytes[..4], &[0, 1, 0x11, 0x70]);
+        bytes.extend_from_slice(&[0, 1]);
+
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        assert_eq!(CStringLong::from_cursor(&mut cursor).into_plain(), foo);
+        assert_eq!(cursor.position(), 4 + 70000);
+        let mut cursor: Cursor<&[u8]> = Cursor::new(bytes.as_slice());
+        assert_eq!(CStringLong::try_from_cursor(&mut cursor).unwrap().into_plain(), foo);
+        assert_eq!(CIntShort::from_cursor(&mut cursor), 1);
+    }
+
+    #[test]
+    fn test_cstringlong_try_from_cursor_errors() {
+        let truncated: &[u8] = &[0, 0, 0, 4, 102, 111, 111];
+        assert!(CStringLong::try_from_cursor(&mut Cursor::new(truncated)).is_err());
+        let negative: &[u8] = &[255, 255, 255, 255];
+        assert!(CStringLong::try_from_cursor(&mut Cursor::new(negative)).is_err());
+    }
+
     // CStringList
     #[test]
     fn test_cstringlist() {
@@ -579,6 +845,42 @@ mod tests {
         }
     }
 
+    // CStringMultimap
+    #[test]
+    fn test_cstringmultimap() {
+        let mut a = vec![0, 2, 0, 3, 102, 111, 111, 0, 2, 0, 1, 97, 0, 1, 98];
+        a.extend_from_slice(&[0, 3, 98, 97, 114, 0, 0]);
+        let mut cursor: Cursor<&[u8]> = Cursor::new(a.as_slice());
+        let multimap = CStringMultimap::from_cursor(&mut cursor);
+        assert_eq!(cursor.position(), a.len() as u64);
+        assert_eq!(multimap.map.len(), 2);
+        assert_eq!(multimap.map["foo"], vec!["a".to_string(), "b".to_string()]);
+        assert!(multimap.map["bar"].is_empty());
+
+        let encoded = multimap.into_cbytes();
+        let decoded = CStringMultimap::try_from_cursor(&mut Cursor::new(encoded.as_slice()));
+        assert_eq!(decoded.unwrap(), multimap);
+        assert!(CStringMultimap::try_from_cursor(&mut Cursor::new(&a[..a.len() - 1])).is_err());
+    }
+
+    // CBytesMap
+    #[test]
+    fn test_cbytesmap() {
+        let mut a = vec![0, 2, 0, 3, 102, 111, 111, 0, 0, 0, 2, 1, 2];
+        a.extend_from_slice(&[0, 3, 98, 97, 114, 255, 255, 255, 255]);
+        let mut cursor: Cursor<&[u8]> = Cursor::new(a.as_slice());
+        let bytes_map = CBytesMap::from_cursor(&mut cursor);
+        assert_eq!(cursor.position(), a.len() as u64);
+        assert_eq!(bytes_map.map.len(), 2);
+        assert_eq!(bytes_map.map["foo"], vec![1, 2]);
+        assert!(bytes_map.map["bar"].is_empty());
+
+        let encoded = bytes_map.into_cbytes();
+        let decoded = CBytesMap::try_from_cursor(&mut Cursor::new(encoded.as_slice()));
+        assert_eq!(decoded.unwrap(), bytes_map);
+        assert!(CBytesMap::try_from_cursor(&mut Cursor::new(&a[..a.len() - 1])).is_err());
+    }
+
     // CBytes
     #[test]
     fn test_cbytes_new() {
@@ -600,11 +902,24 @@ mod tests {
         assert_eq!(cbytes.into_plain(), &[1, 2, 3]);
     }
 
+    #[test]
+    fn test_cbytes_from_cursor_null() {
+        let a = &[255, 255, 255, 255, 0, 0, 0, 0];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(a);
+        let null = CBytes::from_cursor(&mut cursor);
+        assert!(null.is_null());
+        assert_eq!(null.as_opt_slice(), None);
+        let empty = CBytes::from_cursor(&mut cursor);
+        assert!(!empty.is_null());
+        assert_eq!(empty.as_opt_slice(), Some(&[][..]));
+    }
+
     #[test]
     fn test_cbytes_into_cbytes() {
         let bytes_vec = vec![1, 2, 3];
         let cbytes = CBytes::new(bytes_vec);
         assert_eq!(cbytes.into_cbytes(), &[0, 0, 0, 3, 1, 2, 3]);
+        assert_eq!(CBytes::new_null().into_cbytes(), &[255, 255, 255, 255]);
     }
 
     // CBytesShort
@@ -653,6 +968,61 @@ mod tests {
         assert_eq!(i, 5);
     }
 
+    // CInet
+    #[test]
+    fn test_cinet_from_cursor_v4() {
+        let a = &[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(a);
+        let inet = CInet::from_cursor(&mut cursor);
+        assert_eq!(inet.into_plain(), "127.0.0.1:9042".parse().unwrap());
+        assert_eq!(cursor.position(), 9);
+    }
+
+    #[test]
+    fn test_cinet_from_cursor_v6() {
+        let a = &[16, 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0x23, 0x52];
+        let mut cursor: Cursor<&[u8]> = Cursor::new(a);
+        let inet = CInet::from_cursor(&mut cursor);
+        assert_eq!(inet.into_plain(), "[fe80::1]:9042".parse().unwrap());
+        assert_eq!(cursor.position(), 21);
+    }
+
+    #[test]
+    fn test_cinet_try_from_cursor_errors() {
+        let a: &[u8] = &[4, 127, 0, 0, 1, 0, 0, 0x23, 0x52];
+        let inet = CInet::try_from_cursor(&mut Cursor::new(a)).unwrap();
+        assert_eq!(inet.into_plain(), "127.0.0.1:9042".parse().unwrap());
+
+        assert!(CInet::try_from_cursor(&mut Cursor::new(&[][..])).is_err());
+        assert!(CInet::try_from_cursor(&mut Cursor::new(&a[..4])).is_err());
+        assert!(CInet::try_from_cursor(&mut Cursor::new(&a[..7])).is_err());
+        // neither IPv4 nor IPv6 address
+        let b: &[u8] = &[5, 127, 0, 0, 1, 1, 0, 0, 0x23, 0x52];
+        assert!(CInet::try_from_cursor(&mut Cursor::new(b)).is_err());
+    }
+
+    // try_*_from_bytes
+    #[test]
+    fn test_try_i16_from_bytes() {
+        assert_eq!(try_i16_from_bytes(&[0xFF, 0xFE]).unwrap(), -2);
+        assert!(try_i16_from_bytes(&[0xFF]).is_err());
+        assert!(try_i16_from_bytes(&[0, 0xFF, 0xFE]).is_err());
+    }
+
+    #[test]
+    fn test_try_i32_from_bytes() {
+        assert_eq!(try_i32_from_bytes(&[0xFF, 0xFF, 0xFF, 0xFE]).unwrap(), -2);
+        assert!(try_i32_from_bytes(&[0xFF, 0xFE]).is_err());
+        assert!(try_i32_from_bytes(&[0, 0, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_try_i64_from_bytes() {
+        assert_eq!(try_i64_from_bytes(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]).unwrap(),
+                   -2);
+        assert!(try_i64_from_bytes(&[0xFF, 0xFF, 0xFF, 0xFE]).is_err());
+    }
+
     // cursor_next_value
     #[test]
     fn test_cursor_next_value() {
@@ -1,19 +1,30 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::io::Cursor;
 use std::net;
 use uuid::Uuid;
 
-use frame::frame_result::{RowsMetadata, ColType, ColSpec, BodyResResultRows, ColTypeOptionValue};
-use types::{CBytes, IntoRustByName};
+use {FromCursor, TryFromCursor};
+use frame::frame_result::{RowsMetadata, RowsMetadataFlag, ColType, ColSpec, BodyResResultRows,
+                          ColTypeOptionValue};
+use types::{CBytes, CInt, IntoRustByName};
+use types::cassandra_type::CassandraType;
 use types::data_serialization_types::*;
+use types::from_cassandra_type::FromCassandraType;
 use types::list::List;
 use types::map::Map;
 use types::udt::UDT;
-use error::{Result, column_is_empty_err};
-use std::io;
+use error::{Error, Result, column_is_empty_err, type_mismatch_err};
+
+// `[int]` kind of a result of type `Rows`
+const RESULT_KIND_ROWS: CInt = 0x0002;
 
 #[derive(Debug)]
 pub struct Row {
     metadata: RowsMetadata,
     row_content: Vec<CBytes>,
+    // cells which fail to be decoded by `values` are replaced with placeholders
+    lenient: bool,
 }
 
 impl Row {
@@ -24,11 +35,104 @@ impl Row {
                 Row {
                     metadata: body.metadata.clone(),
                     row_content: row.clone(),
+                    lenient: false,
                 }
             })
             .collect();
     }
 
+    /// Uses result metadata of a prepared statement
+    /// (`BodyResResultPrepared::result_metadata`) to decode the row if it's received
+    /// without metadata, i.e. with `No_metadata` flag. Otherwise the row is left as is.
+    pub fn with_result_metadata(mut self, result_metadata: &RowsMetadata) -> Result<Row> {
+        self.metadata = try!(merge_result_metadata(&self.metadata, result_metadata));
+        Ok(self)
+    }
+
+    /// Returns a value of a column with provided name converted into `T`. The value is
+    /// decoded in accordance to CQL type of the column, `Error::TypeMismatch` is returned
+    /// if the type cannot be converted into `T`. NULL values can be read as `Option<T>`.
+    ///
+    /// ```ignore
+    /// let age: i32 = try!(row.get("age"));
+    /// let nickname: Option<String> = try!(row.get("nickname"));
+    /// ```
+    pub fn get<T>(&self, name: &str) -> Result<T>
+        where Row: IntoRustByName<T>
+    {
+        try!(self.check_metadata());
+        match self.get_by_name(name) {
+            Some(value) => value,
+            None => Err(Error::General(format!("Column {} is not found", name))),
+        }
+    }
+
+    /// Returns a value of a column with provided index converted into `T` the same way
+    /// `get` does.
+    pub fn get_by_index<T>(&self, index: usize) -> Result<T>
+        where Row: IntoRustByName<T>
+    {
+        if index >= self.row_content.len() {
+            return Err(Error::General(format!("Column index {} is out of range", index)));
+        }
+        try!(self.check_metadata());
+
+        // a row of a single column is used, so columns with the same names don't clash
+        let mut metadata = self.metadata.clone();
+        let spec = metadata.col_specs.swap_remove(index);
+        let name = spec.name.as_str().to_string();
+        metadata.col_specs = vec![spec];
+        metadata.columns_count = 1;
+        let column = Row {
+            metadata: metadata,
+            row_content: vec![self.row_content[index].clone()],
+            lenient: self.lenient,
+        };
+
+        column.get(name.as_str())
+    }
+
+    /// Returns values of all columns decoded in accordance to their CQL types. NULL values
+    /// are decoded into `CassandraType::Null`. The first cell which fails to be decoded
+    /// fails the whole row unless the row is decoded leniently, then the cell is replaced
+    /// with `CassandraType::DecodeError` placeholder.
+    pub fn values(&self) -> Result<Vec<CassandraType>> {
+        try!(self.check_metadata());
+        let mut values = Vec::with_capacity(self.row_content.len());
+        for (spec, cbytes) in self.metadata.col_specs.iter().zip(self.row_content.iter()) {
+            if cbytes.is_null() {
+                values.push(CassandraType::Null);
+                continue;
+            }
+
+            match decode_by_type(cbytes.as_slice(), &spec.col_type) {
+                Ok(value) => values.push(value),
+                Err(err) => {
+                    if !self.lenient {
+                        let msg = format!("Column {} cannot be decoded: {}",
+                                          spec.name.as_str(),
+                                          err);
+                        return Err(Error::General(msg));
+                    }
+                    values.push(CassandraType::DecodeError {
+                        column: spec.name.as_str().to_string(),
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    // column specs are not sent with rows which have `No_metadata` flag
+    fn check_metadata(&self) -> Result<()> {
+        if self.metadata.col_specs.len() < self.row_content.len() {
+            return Err(Error::NoMetadata);
+        }
+        Ok(())
+    }
+
     fn get_col_by_name(&self, name: &str) -> Option<(&ColType, &CBytes)> {
         let i_opt = self.metadata.col_specs.iter().position(|spec| spec.name.as_str() == name);
         if !i_opt.is_some() {
@@ -52,13 +156,322 @@ impl Row {
     }
 }
 
+/// Should be implemented by types which can be built from a row, e.g. domain structs
+/// which fields correspond to columns. Implementations for structs can be generated with
+/// `impl_try_from_row!` macro.
+pub trait TryFromRow: Sized {
+    fn try_from_row(row: Row) -> Result<Self>;
+}
+
+impl Row {
+    /// Converts the row into `T`, e.g. `let user: User = try!(row.try_into());`.
+    pub fn try_into<T: TryFromRow>(self) -> Result<T> {
+        T::try_from_row(self)
+    }
+}
+
+/// Iterator over rows of a `Rows` result which decodes one row per `next()` call
+/// rather than all of them up front, e.g.
+///
+/// ```ignore
+/// for row in try!(RowIterator::new(frame.body)) {
+///     let id: i32 = try!(try!(row).get("id"));
+/// }
+/// ```
+///
+/// Once a row fails to be decoded the error is returned and iteration stops.
+#[derive(Debug)]
+pub struct RowIterator {
+    lenient: bool,
+    metadata: RowsMetadata,
+    rows_count: i32,
+    // number of already returned rows
+    current: i32,
+    body: Vec<u8>,
+    // position of the next row in the body
+    position: u64,
+}
+
+impl RowIterator {
+    /// Creates new iterator over a body of a result frame. Only result metadata and
+    /// the number of rows are decoded, an error is returned if the result is not `Rows`.
+    pub fn new(body: Vec<u8>) -> Result<RowIterator> {
+        let (metadata, rows_count, position) = {
+            let mut cursor = Cursor::new(body.as_slice());
+            let kind = try!(CInt::try_from_cursor(&mut cursor));
+            if kind != RESULT_KIND_ROWS {
+                return Err(Error::General(format!("Rows result is expected, got kind {}",
+                                                  kind)));
+            }
+            let metadata = try!(RowsMetadata::try_from_cursor(&mut cursor));
+            let rows_count = try!(CInt::try_from_cursor(&mut cursor));
+            (metadata, rows_count, cursor.position())
+        };
+
+        Ok(RowIterator {
+            lenient: false,
+            metadata: metadata,
+            rows_count: rows_count,
+            current: 0,
+            body: body,
+            position: position,
+        })
+    }
+
+    /// Makes rows returned by the iterator decode their values leniently, i.e.
+    /// `Row::values` replaces cells which fail to be decoded with
+    /// `CassandraType::DecodeError` placeholders rather than fails. It allows to skip
+    /// occasional corrupted cells while scanning a table. Rows are decoded strictly
+    /// by default.
+    pub fn decode_lenient(mut self) -> RowIterator {
+        self.lenient = true;
+        self
+    }
+
+    /// Uses result metadata of a prepared statement
+    /// (`BodyResResultPrepared::result_metadata`) to decode rows if they are received
+    /// without metadata, i.e. with `No_metadata` flag.
+    pub fn with_result_metadata(mut self, result_metadata: &RowsMetadata) -> Result<RowIterator> {
+        self.metadata = try!(merge_result_metadata(&self.metadata, result_metadata));
+        Ok(self)
+    }
+
+    /// Returns metadata of the result.
+    pub fn metadata(&self) -> &RowsMetadata {
+        &self.metadata
+    }
+
+    /// Returns total number of rows in the result.
+    pub fn rows_count(&self) -> i32 {
+        self.rows_count
+    }
+
+    fn next_row(&mut self) -> Result<Row> {
+        let mut cursor = Cursor::new(self.body.as_slice());
+        cursor.set_position(self.position);
+        let mut row_content = Vec::with_capacity(self.metadata.columns_count as usize);
+        for _ in 0..self.metadata.columns_count {
+            row_content.push(try!(CBytes::try_from_cursor(&mut cursor)));
+        }
+        self.position = cursor.position();
+
+        Ok(Row {
+            metadata: self.metadata.clone(),
+            row_content: row_content,
+            lenient: self.lenient,
+        })
+    }
+}
+
+impl Iterator for RowIterator {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Result<Row>> {
+        if self.current >= self.rows_count {
+            return None;
+        }
+
+        let row = self.next_row();
+        self.current = if row.is_ok() {
+            self.current + 1
+        } else {
+            self.rows_count
+        };
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.rows_count - self.current).max(0) as usize;
+        (left, Some(left))
+    }
+}
+
+// Replaces column specs of metadata which is received with `No_metadata` flag by ones
+// of result metadata of a prepared statement. Paging state is kept.
+fn merge_result_metadata(metadata: &RowsMetadata,
+                         result_metadata: &RowsMetadata)
+                         -> Result<RowsMetadata> {
+    if !RowsMetadataFlag::has_no_metadata(metadata.flags) {
+        return Ok(metadata.clone());
+    }
+    if result_metadata.col_specs.len() != metadata.columns_count as usize {
+        return Err(Error::General(format!("Result metadata describes {} columns, \
+                                           but rows have {}",
+                                          result_metadata.col_specs.len(),
+                                          metadata.columns_count)));
+    }
+
+    let mut flags = result_metadata.flags;
+    if RowsMetadataFlag::has_has_more_pages(metadata.flags) {
+        flags = RowsMetadataFlag::set_has_more_pages(flags);
+    }
+    Ok(RowsMetadata {
+        flags: flags,
+        columns_count: metadata.columns_count,
+        paging_state: metadata.paging_state.clone(),
+        global_table_space: result_metadata.global_table_space.clone(),
+        col_specs: result_metadata.col_specs.clone(),
+    })
+}
+
+/// Returns a value of a column which corresponds to a struct field with the same name.
+/// Unlike `Row::get` errors name the field. It's used by `impl_try_from_row!` macro.
+pub fn get_field<T>(row: &Row, field: &str) -> Result<T>
+    where Row: IntoRustByName<T>
+{
+    row.get(field).map_err(|err| match err {
+        Error::TypeMismatch(msg) => Error::TypeMismatch(format!("Field {}: {}", field, msg)),
+        Error::General(msg) => Error::General(format!("Field {}: {}", field, msg)),
+        err => err,
+    })
+}
+
+/// Implements `TryFromRow` for a struct with provided fields. Each field is read from
+/// a column with the same name and should have a type supported by `Row::get`,
+/// `Option` should be used for columns which may be NULL.
+///
+/// ```
+/// #[macro_use]
+/// extern crate cdrs;
+///
+/// struct User {
+///     id: i32,
+///     name: String,
+///     nickname: Option<String>,
+/// }
+///
+/// impl_try_from_row!(User { id: i32, name: String, nickname: Option<String> });
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! impl_try_from_row {
+    ($name:ident { $($field:ident: $field_type:ty),* $(,)* }) => {
+        impl $crate::types::rows::TryFromRow for $name {
+            fn try_from_row(row: $crate::types::rows::Row) -> $crate::error::Result<$name> {
+                Ok($name {
+                    $($field: try!($crate::types::rows::get_field::<$field_type>(
+                        &row, stringify!($field))),)*
+                })
+            }
+        }
+    };
+}
+
+/// Name of a column which indicates if a conditional statement was applied.
+pub const APPLIED_COLUMN: &'static str = "[applied]";
+
+/// Result of a conditional statement (lightweight transaction), e.g.
+/// `INSERT ... IF NOT EXISTS`.
+#[derive(Debug)]
+pub struct CasResult {
+    /// Indicates if the statement was applied.
+    pub applied: bool,
+    /// Current values of the row if the statement was not applied. It's `None`
+    /// if the statement was applied or if there is no such row.
+    pub existing: Option<Row>,
+}
+
+impl CasResult {
+    /// Converts a row returned as a result of conditional statement into `CasResult`.
+    /// `[applied]` column is removed from the row of existing values.
+    pub fn from_row(mut row: Row) -> Result<CasResult> {
+        let applied: bool = match row.get_by_name(APPLIED_COLUMN) {
+            Some(applied) => try!(applied),
+            None => {
+                return Err(Error::General(format!("Row does not contain {} column",
+                                                  APPLIED_COLUMN)))
+            }
+        };
+
+        if applied {
+            return Ok(CasResult {
+                applied: true,
+                existing: None,
+            });
+        }
+
+        let i = row.metadata.col_specs.iter().position(|spec| spec.name.as_str() == APPLIED_COLUMN);
+        if let Some(i) = i {
+            row.metadata.col_specs.remove(i);
+            row.metadata.columns_count -= 1;
+            row.row_content.remove(i);
+        }
+
+        let existing = if row.row_content.is_empty() {
+            None
+        } else {
+            Some(row)
+        };
+
+        Ok(CasResult {
+            applied: false,
+            existing: existing,
+        })
+    }
+}
+
+impl<T> IntoRustByName<Option<T>> for Row
+    where Row: IntoRustByName<T>
+{
+    /// NULL value of a column is converted into `None`.
+    fn get_by_name(&self, name: &str) -> Option<Result<Option<T>>> {
+        return self.get_col_by_name(name).map(|(_, cbytes)| {
+            if cbytes.is_null() {
+                return Ok(None);
+            }
+
+            let value: Result<T> = self.get_by_name(name).unwrap();
+            value.map(Some)
+        });
+    }
+}
+
+// Decodes a column with provided name into `T` via `CassandraType`.
+fn get_from_cassandra_type<T: FromCassandraType>(row: &Row, name: &str) -> Option<Result<T>> {
+    row.get_col_spec_by_name(name).map(|(spec, cbytes)| {
+        let value = if cbytes.is_null() {
+            CassandraType::Null
+        } else {
+            try!(decode_by_type(cbytes.as_slice(), &spec.col_type))
+        };
+
+        T::from_cassandra_type(value)
+    })
+}
+
+impl<T: FromCassandraType> IntoRustByName<Vec<T>> for Row {
+    /// A `list` or a `set` column is converted into a vector, NULL is converted into
+    /// an empty one.
+    fn get_by_name(&self, name: &str) -> Option<Result<Vec<T>>> {
+        get_from_cassandra_type(self, name)
+    }
+}
+
+impl<T: FromCassandraType + Eq + Hash> IntoRustByName<HashSet<T>> for Row {
+    /// A `list` or a `set` column is converted into a set, NULL is converted into
+    /// an empty one.
+    fn get_by_name(&self, name: &str) -> Option<Result<HashSet<T>>> {
+        get_from_cassandra_type(self, name)
+    }
+}
+
+impl<K, V> IntoRustByName<HashMap<K, V>> for Row
+    where K: FromCassandraType + Eq + Hash,
+          V: FromCassandraType
+{
+    /// A `map` column is converted into a hash map, NULL is converted into an empty one.
+    fn get_by_name(&self, name: &str) -> Option<Result<HashMap<K, V>>> {
+        get_from_cassandra_type(self, name)
+    }
+}
+
 impl IntoRustByName<Vec<u8>> for Row {
     fn get_by_name(&self, name: &str) -> Option<Result<Vec<u8>>> {
         return self.get_col_by_name(name).map(|(cassandra_type, cbytes)| {
             let bytes = cbytes.as_plain();
             let converted = match cassandra_type {
                 &ColType::Blob => decode_blob(bytes),
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("Blob", cassandra_type)),
             };
             return converted.map_err(|err| err.into());
         });
@@ -75,14 +488,14 @@ impl IntoRustByName<String> for Row {
             }
 
             let converted = match cassandra_type {
-                &ColType::Custom => decode_custom(bytes.as_slice()),
-                &ColType::Ascii => decode_ascii(bytes.as_slice()),
-                &ColType::Varchar => decode_varchar(bytes.as_slice()),
+                &ColType::Custom => decode_custom(bytes.as_slice()).map_err(Error::from),
+                &ColType::Ascii => decode_ascii(bytes.as_slice()).map_err(Error::from),
+                &ColType::Varchar => decode_varchar(bytes.as_slice()).map_err(Error::from),
                 // TODO: clarify when to use decode_text.
                 // it's not mentioned in
                 // https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec#L582
                 // &ColType::XXX => decode_text(bytes).ok(),
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("Custom,Ascii,Varchar", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -101,14 +514,7 @@ impl IntoRustByName<bool> for Row {
 
             let converted = match cassandra_type {
                 &ColType::Boolean => decode_boolean(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (bool ) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Boolean", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -126,19 +532,17 @@ impl IntoRustByName<i64> for Row {
             }
 
             let converted = match cassandra_type {
-                &ColType::Int => decode_bigint(bytes),
+                &ColType::Int => decode_int(bytes).map(|i| i as i64),
                 &ColType::Bigint => decode_bigint(bytes),
+                &ColType::Counter => decode_counter(bytes),
                 &ColType::Timestamp => decode_timestamp(bytes),
                 &ColType::Time => decode_time(bytes),
                 &ColType::Varint => decode_varint(bytes),
                 &ColType::Float => decode_varint(bytes),
                 _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Int,Bigint,Timestamp,Time,Varint,Float ) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
+                    return Err(type_mismatch_err("Int,Bigint,Counter,Timestamp,Time,Varint,\
+                                                  Float",
+                                                 cassandra_type))
                 }
             };
 
@@ -159,14 +563,7 @@ impl IntoRustByName<i32> for Row {
             let converted = match cassandra_type {
                 &ColType::Int => decode_int(bytes),
                 &ColType::Date => decode_date(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Int,date ) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Int,Date", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -185,14 +582,7 @@ impl IntoRustByName<i16> for Row {
 
             let converted = match cassandra_type {
                 &ColType::Smallint => decode_smallint(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Smallint) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Smallint", cassandra_type)),
             };
             return converted.map_err(|err| err.into());
         });
@@ -210,14 +600,7 @@ impl IntoRustByName<i8> for Row {
 
             let converted = match cassandra_type {
                 &ColType::Tinyint => decode_tinyint(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Tinyint) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Tinyint", cassandra_type)),
             };
             return converted.map_err(|err| err.into());
         });
@@ -235,14 +618,7 @@ impl IntoRustByName<f64> for Row {
 
             let converted = match cassandra_type {
                 &ColType::Double => decode_double(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Double) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Double", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -260,16 +636,47 @@ impl IntoRustByName<f32> for Row {
             }
 
             let converted = match cassandra_type {
-                &ColType::Decimal => decode_decimal(bytes),
+                &ColType::Decimal => decode_decimal(bytes).map(|d| d.to_f64() as f32),
                 &ColType::Float => decode_float(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Float,Decimal) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Float,Decimal", cassandra_type)),
+            };
+
+            return converted.map_err(|err| err.into());
+        });
+    }
+}
+
+impl IntoRustByName<Decimal> for Row {
+    fn get_by_name(&self, name: &str) -> Option<Result<Decimal>> {
+        return self.get_col_by_name(name).map(|(cassandra_type, cbytes)| {
+            let bytes = cbytes.as_slice();
+
+            if bytes.is_empty() {
+                return Err(column_is_empty_err());
+            }
+
+            let converted = match cassandra_type {
+                &ColType::Decimal => decode_decimal(bytes),
+                _ => return Err(type_mismatch_err("Decimal", cassandra_type)),
+            };
+
+            return converted.map_err(|err| err.into());
+        });
+    }
+}
+
+impl IntoRustByName<Duration> for Row {
+    fn get_by_name(&self, name: &str) -> Option<Result<Duration>> {
+        return self.get_col_by_name(name).map(|(cassandra_type, cbytes)| {
+            let bytes = cbytes.as_slice();
+
+            if bytes.is_empty() {
+                return Err(column_is_empty_err());
+            }
+
+            let converted = match cassandra_type {
+                &ColType::Duration => decode_duration(bytes),
+                _ => return Err(type_mismatch_err("Duration", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -288,14 +695,7 @@ impl IntoRustByName<net::IpAddr> for Row {
 
             let converted = match cassandra_type {
                 &ColType::Inet => decode_inet(bytes),
-                _ => {
-                    let io_err =
-                        io::Error::new(io::ErrorKind::NotFound,
-                                       format!("Unsupported type of converter. {:?} got, but
-                    (Inet) is only supported.",
-                                               cassandra_type));
-                    Err(io_err)
-                }
+                _ => return Err(type_mismatch_err("Inet", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -313,9 +713,9 @@ impl IntoRustByName<Uuid> for Row {
             }
 
             let converted = match cassandra_type {
-                &ColType::Uuid => decode_timeuuid(bytes),
+                &ColType::Uuid => decode_uuid(bytes).map_err(|err| err.into()),
                 &ColType::Timeuuid => decode_timeuuid(bytes),
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("Uuid,Timeuuid", cassandra_type)),
             };
 
             return converted.map_err(|err| err.into());
@@ -340,7 +740,7 @@ impl IntoRustByName<List> for Row {
                 ColType::Set => {
                     Ok(List::new(decode_set(bytes).unwrap(), cassandra_type.col_type.clone()))
                 }
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("List,Set", &cassandra_type.col_type.id)),
             };
         });
     }
@@ -356,11 +756,8 @@ impl IntoRustByName<Map> for Row {
             }
 
             return match cassandra_type.col_type.id {
-                // in fact, both decode_map and decode_set return Ok
-                ColType::Map => {
-                    Ok(Map::new(decode_map(bytes).unwrap(), cassandra_type.col_type.clone()))
-                }
-                _ => unreachable!(),
+                ColType::Map => Map::new(try!(decode_map(bytes)), cassandra_type.col_type.clone()),
+                _ => return Err(type_mismatch_err("Map", &cassandra_type.col_type.id)),
             };
         });
     }
@@ -377,15 +774,356 @@ impl IntoRustByName<UDT> for Row {
 
             let cudt = match cassandra_type.col_type.value {
                 Some(ColTypeOptionValue::UdtType(ref t)) => t,
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("Udt", &cassandra_type.col_type.id)),
             };
 
             return match cassandra_type.col_type.id {
                 ColType::Udt => {
                     Ok(UDT::new(decode_udt(bytes, cudt.descriptions.len()).unwrap(), cudt))
                 }
-                _ => unreachable!(),
+                _ => return Err(type_mismatch_err("Udt", &cassandra_type.col_type.id)),
             };
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use types::{to_int, to_short, AsRust, CBytes, CString};
+    use {FromCursor, IntoBytes};
+    use super::*;
+
+    // Row of `SELECT id, name, score, nickname FROM ks.users` where nickname is NULL.
+    fn user_row() -> Row {
+        let mut body = to_int(0x0001);
+        body.extend_from_slice(to_int(4).as_slice());
+        for name in &["ks", "users"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns = [("id", 0x0009), ("name", 0x000D), ("score", 0x0007), ("nickname", 0x000D)];
+        for &(name, col_type) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            body.extend_from_slice(to_short(col_type).as_slice());
+        }
+        body.extend_from_slice(to_int(1).as_slice());
+        body.extend_from_slice(CBytes::new(to_int(42)).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(b"john".to_vec()).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(vec![64, 9, 0, 0, 0, 0, 0, 0]).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new_null().into_cbytes().as_slice());
+
+        let mut cursor = Cursor::new(body.as_slice());
+        Row::from_frame_body(BodyResResultRows::from_cursor(&mut cursor)).remove(0)
+    }
+
+    #[test]
+    fn get() {
+        let row = user_row();
+        assert_eq!(row.get::<i32>("id").unwrap(), 42);
+        assert_eq!(row.get::<String>("name").unwrap(), "john".to_string());
+        assert_eq!(row.get::<f64>("score").unwrap(), 3.125);
+        assert_eq!(row.get::<Option<String>>("nickname").unwrap(), None);
+        assert_eq!(row.get::<Option<i32>>("id").unwrap(), Some(42));
+        assert!(row.get::<i32>("age").is_err());
+    }
+
+    #[test]
+    fn get_by_index() {
+        let row = user_row();
+        assert_eq!(row.get_by_index::<i32>(0).unwrap(), 42);
+        assert_eq!(row.get_by_index::<String>(1).unwrap(), "john".to_string());
+        assert_eq!(row.get_by_index::<f64>(2).unwrap(), 3.125);
+        assert_eq!(row.get_by_index::<Option<String>>(3).unwrap(), None);
+        assert!(row.get_by_index::<i32>(4).is_err());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i32,
+        name: String,
+        nickname: Option<String>,
+    }
+
+    impl_try_from_row!(User { id: i32, name: String, nickname: Option<String> });
+
+    struct Player {
+        #[allow(dead_code)]
+        name: String,
+        #[allow(dead_code)]
+        score: i32,
+    }
+
+    impl_try_from_row!(Player { name: String, score: i32 });
+
+    struct Account {
+        #[allow(dead_code)]
+        id: i32,
+        #[allow(dead_code)]
+        email: String,
+    }
+
+    impl_try_from_row!(Account { id: i32, email: String });
+
+    #[test]
+    fn try_into_struct() {
+        let user: User = user_row().try_into().unwrap();
+        assert_eq!(user,
+                   User {
+                       id: 42,
+                       name: "john".to_string(),
+                       nickname: None,
+                   });
+    }
+
+    #[test]
+    fn try_into_struct_errors() {
+        match user_row().try_into::<Player>() {
+            Err(Error::TypeMismatch(msg)) => assert!(msg.starts_with("Field score:")),
+            Err(err) => panic!("type mismatch is expected, got {:?}", err),
+            Ok(_) => panic!("type mismatch is expected"),
+        }
+        match user_row().try_into::<Account>() {
+            Err(Error::General(msg)) => assert_eq!(msg, "Field email: Column email is not found"),
+            Err(err) => panic!("missing column error is expected, got {:?}", err),
+            Ok(_) => panic!("missing column error is expected"),
+        }
+    }
+
+    #[test]
+    fn get_with_type_mismatch() {
+        let row = user_row();
+        match row.get::<String>("id") {
+            Err(Error::TypeMismatch(_)) => (),
+            other => panic!("type mismatch is expected, got {:?}", other),
+        }
+        match row.get_by_index::<bool>(2) {
+            Err(Error::TypeMismatch(_)) => (),
+            other => panic!("type mismatch is expected, got {:?}", other),
+        }
+    }
+
+    // Body of a Rows result of `SELECT id, name FROM ks.users` with provided rows.
+    fn users_body(rows_count: i32, cells: Vec<CBytes>) -> Vec<u8> {
+        let mut body = to_int(0x0002);
+        body.extend_from_slice(to_int(0x0001).as_slice());
+        body.extend_from_slice(to_int(2).as_slice());
+        for name in &["ks", "users", "id"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(to_short(0x0009).as_slice());
+        body.extend_from_slice(CString::new("name".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(to_short(0x000D).as_slice());
+        body.extend_from_slice(to_int(rows_count).as_slice());
+        for cell in cells {
+            body.extend_from_slice(cell.into_cbytes().as_slice());
+        }
+        body
+    }
+
+    #[test]
+    fn row_iterator() {
+        let cells = vec![CBytes::new(to_int(1)),
+                         CBytes::new(b"john".to_vec()),
+                         CBytes::new(to_int(2)),
+                         CBytes::new_null(),
+                         CBytes::new(to_int(3)),
+                         CBytes::new(b"ann".to_vec())];
+        let mut rows = RowIterator::new(users_body(3, cells)).unwrap();
+        assert_eq!(rows.rows_count(), 3);
+        assert_eq!(rows.metadata().col_specs.len(), 2);
+        assert_eq!(rows.size_hint(), (3, Some(3)));
+
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.get::<i32>("id").unwrap(), 1);
+        assert_eq!(row.get::<String>("name").unwrap(), "john");
+        assert_eq!(rows.size_hint(), (2, Some(2)));
+
+        let values: Vec<(i32, Option<String>)> = rows.map(|row| {
+                let row = row.unwrap();
+                (row.get("id").unwrap(), row.get("name").unwrap())
+            })
+            .collect();
+        assert_eq!(values, vec![(2, None), (3, Some("ann".to_string()))]);
+    }
+
+    #[test]
+    fn row_iterator_errors() {
+        // the second row is truncated
+        let mut body = users_body(3, vec![CBytes::new(to_int(1)), CBytes::new(b"a".to_vec())]);
+        body.extend_from_slice(&[0, 0, 0, 4, 0, 0]);
+        let mut rows = RowIterator::new(body).unwrap();
+        assert!(rows.next().unwrap().is_ok());
+        match rows.next() {
+            Some(Err(Error::Io(_))) => (),
+            other => panic!("io error is expected, got {:?}", other),
+        }
+        assert!(rows.next().is_none());
+
+        let void = to_int(0x0001);
+        assert!(RowIterator::new(void).is_err());
+    }
+
+    #[test]
+    fn row_iterator_truncated_metadata() {
+        let body = users_body(0, vec![]);
+        // the body is cut off within column specs, before the number of rows
+        for len in 5..body.len() - 4 {
+            assert!(RowIterator::new(body[..len].to_vec()).is_err());
+        }
+
+        // unknown type of the last column
+        let mut body = users_body(0, vec![]);
+        let type_start = body.len() - 6;
+        body[type_start + 1] = 0xFF;
+        assert!(RowIterator::new(body).is_err());
+    }
+
+    #[test]
+    fn rows_without_metadata() {
+        // flags: No_metadata, columns count: 2
+        let mut body = to_int(0x0002);
+        body.extend_from_slice(to_int(0x0004).as_slice());
+        body.extend_from_slice(to_int(2).as_slice());
+        body.extend_from_slice(to_int(1).as_slice());
+        body.extend_from_slice(CBytes::new(to_int(1)).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(b"john".to_vec()).into_cbytes().as_slice());
+
+        let row = RowIterator::new(body.clone()).unwrap().next().unwrap().unwrap();
+        for result in vec![row.get::<i32>("id").map(|_| ()),
+                           row.get_by_index::<i32>(0).map(|_| ()),
+                           row.values().map(|_| ())] {
+            match result {
+                Err(Error::NoMetadata) => (),
+                other => panic!("no metadata error is expected, got {:?}", other),
+            }
+        }
+
+        // result metadata of the prepared statement `SELECT id, name FROM ks.users`
+        let users = users_body(0, vec![]);
+        let result_metadata = RowIterator::new(users).unwrap().metadata().clone();
+        let row = row.with_result_metadata(&result_metadata).unwrap();
+        assert_eq!(row.get::<i32>("id").unwrap(), 1);
+        assert_eq!(row.get_by_index::<String>(1).unwrap(), "john");
+
+        let mut rows = RowIterator::new(body.clone())
+            .unwrap()
+            .with_result_metadata(&result_metadata)
+            .unwrap();
+        assert_eq!(rows.next().unwrap().unwrap().values().unwrap(),
+                   vec![CassandraType::Int(1), CassandraType::Varchar("john".to_string())]);
+
+        let mut single_column = result_metadata.clone();
+        single_column.col_specs.pop();
+        assert!(RowIterator::new(body).unwrap().with_result_metadata(&single_column).is_err());
+    }
+
+    #[test]
+    fn get_collections() {
+        let mut body = to_int(0x0001);
+        body.extend_from_slice(to_int(3).as_slice());
+        for name in &["ks", "scores"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns: [(&str, &[i16]); 3] = [("ids", &[0x0020, 0x0009]),
+                                            ("scores", &[0x0021, 0x000D, 0x0009]),
+                                            ("tags", &[0x0022, 0x000D])];
+        for &(name, types) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            for col_type in types {
+                body.extend_from_slice(to_short(*col_type).as_slice());
+            }
+        }
+        body.extend_from_slice(to_int(1).as_slice());
+        let mut ids = to_int(2);
+        for id in &[1, 2] {
+            ids.extend_from_slice(CBytes::new(to_int(*id)).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(CBytes::new(ids).into_cbytes().as_slice());
+        let mut scores = to_int(1);
+        scores.extend_from_slice(CBytes::new(b"a".to_vec()).into_cbytes().as_slice());
+        scores.extend_from_slice(CBytes::new(to_int(10)).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(scores).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new_null().into_cbytes().as_slice());
+        let mut cursor = Cursor::new(body.as_slice());
+        let row = Row::from_frame_body(BodyResResultRows::from_cursor(&mut cursor)).remove(0);
+
+        let ids: Vec<i32> = row.get("ids").unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        let scores: HashMap<String, i32> = row.get("scores").unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores["a"], 10);
+        let tags: HashSet<String> = row.get("tags").unwrap();
+        assert!(tags.is_empty());
+        let names: Result<Vec<String>> = row.get("ids");
+        assert!(names.is_err());
+    }
+
+    #[test]
+    fn get_map_with_invalid_ascii() {
+        // row of columns keys map<ascii, int> and values map<text, ascii>
+        let mut body = to_int(0x0001);
+        body.extend_from_slice(to_int(2).as_slice());
+        for name in &["ks", "maps"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        let columns: [(&str, &[i16]); 2] = [("keys", &[0x0021, 0x0001, 0x0009]),
+                                            ("values", &[0x0021, 0x000D, 0x0001])];
+        for &(name, types) in columns.iter() {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+            for col_type in types {
+                body.extend_from_slice(to_short(*col_type).as_slice());
+            }
+        }
+        body.extend_from_slice(to_int(1).as_slice());
+        let mut keys = to_int(1);
+        keys.extend_from_slice(CBytes::new(vec![b'a', 0xC3, 0xA9]).into_cbytes().as_slice());
+        keys.extend_from_slice(CBytes::new(to_int(1)).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(keys).into_cbytes().as_slice());
+        let mut values = to_int(1);
+        values.extend_from_slice(CBytes::new(b"a".to_vec()).into_cbytes().as_slice());
+        values.extend_from_slice(CBytes::new(vec![0xFF]).into_cbytes().as_slice());
+        body.extend_from_slice(CBytes::new(values).into_cbytes().as_slice());
+        let mut cursor = Cursor::new(body.as_slice());
+        let row = Row::from_frame_body(BodyResResultRows::from_cursor(&mut cursor)).remove(0);
+
+        assert!(row.get::<Map>("keys").is_err());
+        assert!(row.get::<HashMap<String, i32>>("keys").is_err());
+        let values: Map = row.get("values").unwrap();
+        let values: Result<HashMap<String, String>> = values.as_rust();
+        assert!(values.is_err());
+    }
+
+    #[test]
+    fn decode_lenient() {
+        // id of the second row has invalid length
+        let cells = vec![CBytes::new(to_int(1)),
+                         CBytes::new(b"john".to_vec()),
+                         CBytes::new(vec![0, 0, 2]),
+                         CBytes::new(b"ann".to_vec()),
+                         CBytes::new(to_int(3)),
+                         CBytes::new_null()];
+
+        let body = users_body(3, cells.clone());
+        let strict: Vec<Result<Vec<CassandraType>>> = RowIterator::new(body)
+            .unwrap()
+            .map(|row| row.unwrap().values())
+            .collect();
+        assert!(strict[0].is_ok());
+        assert!(strict[1].is_err());
+        assert!(strict[2].is_ok());
+
+        let values: Vec<Vec<CassandraType>> = RowIterator::new(users_body(3, cells))
+            .unwrap()
+            .decode_lenient()
+            .map(|row| row.unwrap().values().unwrap())
+            .collect();
+        assert_eq!(values[0],
+                   vec![CassandraType::Int(1), CassandraType::Varchar("john".to_string())]);
+        match values[1][0] {
+            CassandraType::DecodeError { ref column, .. } => assert_eq!(column, "id"),
+            ref value => panic!("unexpected value {:?}", value),
+        }
+        assert_eq!(values[1][1], CassandraType::Varchar("ann".to_string()));
+        assert_eq!(values[2], vec![CassandraType::Int(3), CassandraType::Null]);
+    }
+}
@@ -0,0 +1,320 @@
+//! The module contains rows which borrow values of cells from a body of a response frame
+//! rather than copy each of them as `Row` does. They are useful for large result sets
+//! which are processed in place.
+use std::io::Cursor;
+use std::str;
+
+use frame::{Frame, Opcode};
+use frame::frame_result::{ColType, RowsMetadata};
+use types::{CInt, INT_LEN, try_i32_from_bytes};
+use types::data_serialization_types::decode_text_borrowed;
+use error::{Error, Result, type_mismatch_err};
+use TryFromCursor;
+
+// Result kind of Rows result.
+const ROWS_KIND: i32 = 0x0002;
+
+/// Rows of a Rows result which borrow values of cells from a frame.
+///
+/// ```ignore
+/// let frame = try!(session.query(query, false, false));
+/// let rows = try!(RowsRef::from_frame(&frame));
+/// for row in rows.iter() {
+///     let name: Option<&str> = try!(row.get_str("name"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RowsRef<'a> {
+    metadata: RowsMetadata,
+    rows: Vec<Vec<Option<&'a [u8]>>>,
+}
+
+impl<'a> RowsRef<'a> {
+    /// Creates rows which borrow from provided Result frame.
+    pub fn from_frame(frame: &'a Frame) -> Result<RowsRef<'a>> {
+        if frame.opcode != Opcode::Result {
+            return Err(Error::General(format!("Rows are expected, got {:?}", frame.opcode)));
+        }
+
+        RowsRef::from_body(frame.body.as_slice())
+    }
+
+    /// Creates rows which borrow from provided (decompressed) body of a Result frame.
+    pub fn from_body(body: &'a [u8]) -> Result<RowsRef<'a>> {
+        let mut cursor = Cursor::new(body);
+        let kind = try!(read_int(&mut cursor));
+        if kind != ROWS_KIND {
+            return Err(Error::General(format!("Rows are expected, got result of kind {}", kind)));
+        }
+
+        let metadata = try!(RowsMetadata::try_from_cursor(&mut cursor));
+        let rows_count = try!(read_int(&mut cursor));
+        // counts come from the wire, each cell takes at least [int] length of the body
+        let max_cells = (body.len() - cursor.position() as usize) / INT_LEN;
+        let mut rows = Vec::with_capacity((rows_count.max(0) as usize).min(max_cells));
+        for _ in 0..rows_count {
+            let columns_count = (metadata.columns_count.max(0) as usize).min(max_cells);
+            let mut cells = Vec::with_capacity(columns_count);
+            for _ in 0..metadata.columns_count {
+                cells.push(try!(read_cell(&mut cursor)));
+            }
+            rows.push(cells);
+        }
+
+        Ok(RowsRef {
+            metadata: metadata,
+            rows: rows,
+        })
+    }
+
+    /// Returns metadata of the rows.
+    pub fn metadata(&self) -> &RowsMetadata {
+        &self.metadata
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns a row with provided index.
+    pub fn get<'b>(&'b self, index: usize) -> Option<RowRef<'b, 'a>> {
+        self.rows.get(index).map(|cells| {
+            RowRef {
+                metadata: &self.metadata,
+                cells: cells.as_slice(),
+            }
+        })
+    }
+
+    /// Returns an iterator over the rows.
+    pub fn iter<'b>(&'b self) -> RowsRefIter<'b, 'a> {
+        RowsRefIter {
+            rows: self,
+            next: 0,
+        }
+    }
+}
+
+/// Iterator over rows of `RowsRef`.
+pub struct RowsRefIter<'b, 'a: 'b> {
+    rows: &'b RowsRef<'a>,
+    next: usize,
+}
+
+impl<'b, 'a: 'b> Iterator for RowsRefIter<'b, 'a> {
+    type Item = RowRef<'b, 'a>;
+
+    fn next(&mut self) -> Option<RowRef<'b, 'a>> {
+        let row = self.rows.get(self.next);
+        self.next += 1;
+        row
+    }
+}
+
+/// A single row of `RowsRef`. Values are returned as slices of the frame body.
+/// `None` stands for NULL value.
+#[derive(Debug, Clone, Copy)]
+pub struct RowRef<'b, 'a: 'b> {
+    metadata: &'b RowsMetadata,
+    cells: &'b [Option<&'a [u8]>],
+}
+
+impl<'b, 'a: 'b> RowRef<'b, 'a> {
+    /// Returns raw bytes of a column with provided name.
+    pub fn get_bytes(&self, name: &str) -> Result<Option<&'a [u8]>> {
+        self.get_bytes_by_index(try!(self.index_of(name)))
+    }
+
+    /// Returns raw bytes of a column with provided index.
+    pub fn get_bytes_by_index(&self, index: usize) -> Result<Option<&'a [u8]>> {
+        match self.cells.get(index) {
+            Some(cell) => Ok(*cell),
+            None => Err(Error::General(format!("Column index {} is out of range", index))),
+        }
+    }
+
+    /// Returns a value of a text (`ascii`, `varchar` or `custom`) column with provided name
+    /// without copying it.
+    pub fn get_str(&self, name: &str) -> Result<Option<&'a str>> {
+        self.get_str_by_index(try!(self.index_of(name)))
+    }
+
+    /// Returns a value of a text column with provided index without copying it.
+    pub fn get_str_by_index(&self, index: usize) -> Result<Option<&'a str>> {
+        let bytes = try!(self.get_bytes_by_index(index));
+        // col_specs are empty if rows are received without metadata
+        match self.metadata.col_specs.get(index).map(|spec| &spec.col_type.id) {
+            Some(&ColType::Ascii) | Some(&ColType::Varchar) | Some(&ColType::Custom) => (),
+            Some(col_type) => return Err(type_mismatch_err("&str", col_type)),
+            None => {
+                return Err(Error::General(format!("Type of column {} is unknown", index)));
+            }
+        }
+
+        match bytes {
+            Some(bytes) => {
+                decode_text_borrowed(bytes)
+                    .map(Some)
+                    .map_err(|err| Error::General(format!("Column {}: {}", index, err)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes a value of a column with provided name by `decode` function, e.g.
+    /// `row.get_with("id", decode_int)`. Decoders of `data_serialization_types`
+    /// read values directly from the frame body.
+    pub fn get_with<T, E, F>(&self, name: &str, decode: F) -> Result<Option<T>>
+        where F: FnOnce(&'a [u8]) -> ::std::result::Result<T, E>,
+              E: Into<Error>
+    {
+        match try!(self.get_bytes(name)) {
+            Some(bytes) => decode(bytes).map(Some).map_err(|err| err.into()),
+            None => Ok(None),
+        }
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize> {
+        self.metadata
+            .col_specs
+            .iter()
+            .position(|spec| spec.name.as_str() == name)
+            .ok_or_else(|| Error::General(format!("Column {} is not found", name)))
+    }
+}
+
+fn read_int(cursor: &mut Cursor<&[u8]>) -> Result<i32> {
+    let position = cursor.position() as usize;
+    let bytes = try!(cursor_slice(cursor, position, INT_LEN));
+    cursor.set_position((position + INT_LEN) as u64);
+    try_i32_from_bytes(bytes).map_err(Error::Io)
+}
+
+// Reads [bytes] which is a value of a cell. The value is a slice of the cursor's buffer.
+fn read_cell<'a>(cursor: &mut Cursor<&'a [u8]>) -> Result<Option<&'a [u8]>> {
+    let len: CInt = try!(read_int(cursor));
+    if len < 0 {
+        return Ok(None);
+    }
+
+    let position = cursor.position() as usize;
+    let bytes = try!(cursor_slice(cursor, position, len as usize));
+    cursor.set_position((position + len as usize) as u64);
+    Ok(Some(bytes))
+}
+
+fn cursor_slice<'a>(cursor: &Cursor<&'a [u8]>, position: usize, len: usize) -> Result<&'a [u8]> {
+    let buffer: &'a [u8] = *cursor.get_ref();
+    if position + len > buffer.len() {
+        return Err(Error::General("Unexpected end of rows body".to_string()));
+    }
+
+    Ok(&buffer[position..position + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Version;
+    use types::{to_int, to_short, CBytes, CString};
+    use types::data_serialization_types::decode_int;
+    use IntoBytes;
+    use super::*;
+
+    // Builds a Rows result frame with columns id int and name varchar.
+    fn rows_frame(rows: &[(i32, Option<&str>)]) -> Frame {
+        let mut body = to_int(ROWS_KIND);
+        body.extend_from_slice(to_int(0x0001).as_slice());
+        body.extend_from_slice(to_int(2).as_slice());
+        for name in &["ks", "users", "id"] {
+            body.extend_from_slice(CString::new(name.to_string()).into_cbytes().as_slice());
+        }
+        body.extend_from_slice(to_short(0x0009).as_slice());
+        body.extend_from_slice(CString::new("name".to_string()).into_cbytes().as_slice());
+        body.extend_from_slice(to_short(0x000D).as_slice());
+        body.extend_from_slice(to_int(rows.len() as i32).as_slice());
+        for &(id, name) in rows {
+            body.extend_from_slice(CBytes::new(to_int(id)).into_cbytes().as_slice());
+            let name = match name {
+                Some(name) => CBytes::new(name.as_bytes().to_vec()),
+                None => CBytes::new_null(),
+            };
+            body.extend_from_slice(name.into_cbytes().as_slice());
+        }
+
+        Frame {
+            version: Version::Response,
+            flags: vec![],
+            opcode: Opcode::Result,
+            stream: 0,
+            body: body,
+            tracing_id: None,
+            warnings: vec![],
+            custom_payload: None,
+        }
+    }
+
+    #[test]
+    fn rows_ref() {
+        let frame = rows_frame(&[(1, Some("John")), (2, None)]);
+        let rows = RowsRef::from_frame(&frame).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let names: Vec<Option<&str>> =
+            rows.iter().map(|row| row.get_str("name").unwrap()).collect();
+        assert_eq!(names, vec![Some("John"), None]);
+
+        let row = rows.get(0).unwrap();
+        assert_eq!(row.get_with("id", decode_int).unwrap(), Some(1));
+        assert_eq!(row.get_bytes_by_index(0).unwrap(), Some(&[0, 0, 0, 1][..]));
+        // the value is borrowed from the frame body
+        let name = row.get_str("name").unwrap().unwrap();
+        let body = frame.body.as_slice();
+        assert!(name.as_ptr() >= body.as_ptr() && name.as_ptr() < body[body.len()..].as_ptr());
+    }
+
+    #[test]
+    fn rows_ref_errors() {
+        let frame = rows_frame(&[(1, Some("John"))]);
+        let rows = RowsRef::from_frame(&frame).unwrap();
+        let row = rows.get(0).unwrap();
+        assert!(row.get_str("id").is_err());
+        assert!(row.get_bytes("age").is_err());
+        assert!(row.get_bytes_by_index(2).is_err());
+
+        let mut truncated = rows_frame(&[(1, Some("John"))]);
+        let len = truncated.body.len();
+        truncated.body.truncate(len - 2);
+        assert!(RowsRef::from_frame(&truncated).is_err());
+
+        // rows count claims more rows than the body holds
+        let mut huge = rows_frame(&[]);
+        let len = huge.body.len();
+        huge.body.truncate(len - INT_LEN);
+        huge.body.extend_from_slice(to_int(i32::max_value()).as_slice());
+        assert!(RowsRef::from_frame(&huge).is_err());
+
+        let mut void = rows_frame(&[]);
+        void.body = to_int(0x0001);
+        assert!(RowsRef::from_frame(&void).is_err());
+    }
+
+    #[test]
+    fn rows_ref_without_metadata() {
+        // flags: No_metadata, columns count: 1, rows count: 1
+        let mut body = to_int(ROWS_KIND);
+        body.extend_from_slice(to_int(0x0004).as_slice());
+        body.extend_from_slice(to_int(1).as_slice());
+        body.extend_from_slice(to_int(1).as_slice());
+        body.extend_from_slice(CBytes::new(b"John".to_vec()).into_cbytes().as_slice());
+
+        let rows = RowsRef::from_body(body.as_slice()).unwrap();
+        let row = rows.get(0).unwrap();
+        assert_eq!(row.get_bytes_by_index(0).unwrap(), Some(&b"John"[..]));
+        assert!(row.get_str_by_index(0).is_err());
+        assert!(row.get_str_by_index(1).is_err());
+    }
+}
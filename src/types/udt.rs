@@ -7,7 +7,7 @@ use types::{CBytes, IntoRustByName};
 use types::data_serialization_types::*;
 use types::list::List;
 use types::map::Map;
-use error::{Result, column_is_empty_err};
+use error::{Error, Result, column_is_empty_err};
 
 #[derive(Debug)]
 pub struct UDT {
@@ -56,9 +56,9 @@ impl IntoRustByName<String> for UDT {
             }
 
             let converted = match col_type.id {
-                ColType::Custom => decode_custom(bytes.as_slice()),
-                ColType::Ascii => decode_ascii(bytes.as_slice()),
-                ColType::Varchar => decode_varchar(bytes.as_slice()),
+                ColType::Custom => decode_custom(bytes.as_slice()).map_err(Error::from),
+                ColType::Ascii => decode_ascii(bytes.as_slice()).map_err(Error::from),
+                ColType::Varchar => decode_varchar(bytes.as_slice()).map_err(Error::from),
                 _ => unreachable!(),
             };
             return converted.map_err(|err| err.into());
@@ -188,7 +188,7 @@ impl IntoRustByName<f32> for UDT {
             }
 
             let converted = match col_type.id {
-                ColType::Decimal => decode_decimal(bytes.as_slice()),
+                ColType::Decimal => decode_decimal(bytes.as_slice()).map(|d| d.to_f64() as f32),
                 ColType::Float => decode_float(bytes.as_slice()),
                 _ => unreachable!(),
             };
@@ -197,6 +197,24 @@ impl IntoRustByName<f32> for UDT {
     }
 }
 
+impl IntoRustByName<Decimal> for UDT {
+    fn get_by_name(&self, name: &str) -> Option<Result<Decimal>> {
+        return self.data.get(name).map(|v| {
+            let &(ref col_type, ref bytes) = v;
+
+            if bytes.as_plain().is_empty() {
+                return Err(column_is_empty_err());
+            }
+
+            let converted = match col_type.id {
+                ColType::Decimal => decode_decimal(bytes.as_slice()),
+                _ => unreachable!(),
+            };
+            return converted.map_err(|err| err.into());
+        });
+    }
+}
+
 impl IntoRustByName<net::IpAddr> for UDT {
     fn get_by_name(&self, name: &str) -> Option<Result<net::IpAddr>> {
         return self.data.get(name).map(|v| {
@@ -225,7 +243,7 @@ impl IntoRustByName<Uuid> for UDT {
             }
 
             let converted = match col_type.id {
-                ColType::Uuid => decode_timeuuid(bytes.as_slice()),
+                ColType::Uuid => decode_uuid(bytes.as_slice()).map_err(|err| err.into()),
                 ColType::Timeuuid => decode_timeuuid(bytes.as_slice()),
                 _ => unreachable!(),
             };
@@ -267,9 +285,9 @@ impl IntoRustByName<Map> for UDT {
                 return Err(column_is_empty_err());
             }
 
-            let list_bytes = decode_map(bytes.as_slice()).unwrap();
+            let list_bytes = try!(decode_map(bytes.as_slice()));
             return match col_type.id {
-                ColType::Map => Ok(Map::new(list_bytes, col_type.clone().clone())),
+                ColType::Map => Map::new(list_bytes, col_type.clone()),
                 _ => unreachable!(),
             };
         });
@@ -1,8 +1,15 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use uuid::Uuid;
 use IntoBytes;
 use super::*;
+use super::cassandra_type::CassandraType;
+use super::from_cassandra_type::FromCassandraType;
+use super::data_serialization_types::*;
 use std::convert::Into;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use error;
 
 use std::fmt::Debug;
 
@@ -24,11 +31,46 @@ impl IntoBytes for ValueType {
     }
 }
 
-/// Cassandra value which could be an array of bytes, null and non-set values.
+/// Cassandra value which is bound to a query. Typed variants correspond to CQL types and
+/// are serialized in accordance to them, `Bytes` holds a value which is already serialized,
+/// e.g. by `Into<Bytes>` of a custom type. `Null` value removes a column (i.e. creates
+/// a tombstone) while `NotSet` value leaves it untouched.
+///
+/// Values implement `Eq` and `Hash`, so they can be used as keys of a `HashMap` bound
+/// as a `map`. Values are equal if they have the same variant and serialized bytes,
+/// i.e. `Double` and `Float` values are compared by their bit patterns.
 #[derive(Debug, Clone)]
-pub struct Value {
-    pub body: Vec<u8>,
-    pub value_type: ValueType,
+pub enum Value {
+    Ascii(String),
+    Bigint(i64),
+    Blob(Vec<u8>),
+    Boolean(bool),
+    Counter(i64),
+    Decimal(Decimal),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Timestamp(i64),
+    Uuid(Uuid),
+    Varchar(String),
+    Varint(i64),
+    Timeuuid(Uuid),
+    Inet(IpAddr),
+    Date(i32),
+    Time(i64),
+    Smallint(i16),
+    Tinyint(i8),
+    Duration(Duration),
+    List(Vec<Value>),
+    Set(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// Fields of a UDT in the order of the type definition.
+    Udt(Vec<(String, Value)>),
+    Tuple(Vec<Value>),
+    /// Already serialized value, it's sent as is.
+    Bytes(Vec<u8>),
+    Null,
+    NotSet,
 }
 
 impl Value {
@@ -36,43 +78,351 @@ impl Value {
     pub fn new_normal<B>(v: B) -> Value
         where B: Into<Bytes>
     {
-        let bytes = v.into().0;
-        let l = bytes.len() as i32;
-        return Value {
-            body: bytes.to_vec(),
-            value_type: ValueType::Normal(l),
-        };
+        Value::Bytes(v.into().0)
     }
 
     /// The factory method which creates null Cassandra value.
     pub fn new_null() -> Value {
-        return Value {
-            body: vec![],
-            value_type: ValueType::Null,
-        };
+        Value::Null
     }
 
-    /// The factory method which creates non-set Cassandra value.
+    /// The factory method which creates non-set Cassandra value. In opposite to null value
+    /// it leaves a column untouched, i.e. binding it doesn't create a tombstone.
+    /// Supported since 4-th version of Cassandra protocol.
     pub fn new_not_set() -> Value {
-        return Value {
-            body: vec![],
-            value_type: ValueType::NotSet,
-        };
+        Value::NotSet
+    }
+
+    /// Returns the type of the value, i.e. normal value with its length, null or not set.
+    pub fn value_type(&self) -> ValueType {
+        match *self {
+            Value::Null => ValueType::Null,
+            Value::NotSet => ValueType::NotSet,
+            _ => ValueType::Normal(self.body().len() as i32),
+        }
+    }
+
+    /// Returns serialized value without its length. It's empty for null and not-set values.
+    pub fn body(&self) -> Vec<u8> {
+        match *self {
+            Value::Ascii(ref v) |
+            Value::Varchar(ref v) => v.as_bytes().to_vec(),
+            Value::Bigint(v) |
+            Value::Counter(v) => encode_bigint(v),
+            Value::Blob(ref v) |
+            Value::Bytes(ref v) => v.clone(),
+            Value::Boolean(v) => encode_boolean(v),
+            Value::Decimal(v) => encode_decimal(v.unscaled, v.scale),
+            Value::Double(v) => encode_double(v),
+            Value::Float(v) => encode_float(v),
+            Value::Int(v) => encode_int(v),
+            Value::Timestamp(v) => encode_timestamp(v),
+            Value::Uuid(ref v) |
+            Value::Timeuuid(ref v) => encode_uuid(v),
+            Value::Varint(v) => encode_varint(v),
+            Value::Inet(ref v) => encode_inet(v),
+            Value::Date(v) => encode_date(v),
+            Value::Time(v) => encode_time(v),
+            Value::Smallint(v) => encode_smallint(v),
+            Value::Tinyint(v) => encode_tinyint(v),
+            Value::Duration(v) => encode_duration(v),
+            Value::List(ref items) |
+            Value::Set(ref items) => {
+                let mut bytes = to_int(items.len() as i32);
+                for item in items {
+                    bytes.extend_from_slice(item.into_cbytes().as_slice());
+                }
+                bytes
+            }
+            Value::Map(ref entries) => {
+                let mut bytes = to_int(entries.len() as i32);
+                for &(ref key, ref value) in entries {
+                    bytes.extend_from_slice(key.into_cbytes().as_slice());
+                    bytes.extend_from_slice(value.into_cbytes().as_slice());
+                }
+                bytes
+            }
+            Value::Udt(ref fields) => {
+                let mut bytes = vec![];
+                for &(_, ref value) in fields {
+                    bytes.extend_from_slice(value.into_cbytes().as_slice());
+                }
+                bytes
+            }
+            Value::Tuple(ref items) => {
+                let mut bytes = vec![];
+                for item in items {
+                    bytes.extend_from_slice(item.into_cbytes().as_slice());
+                }
+                bytes
+            }
+            Value::Null | Value::NotSet => vec![],
+        }
     }
 }
 
 impl IntoBytes for Value {
     fn into_cbytes(&self) -> Vec<u8> {
-        let mut v: Vec<u8> = vec![];
-        v.extend_from_slice(self.value_type.into_cbytes().as_slice());
-        v.extend_from_slice(self.body.as_slice());
+        let body = self.body();
+        let mut v = self.value_type().into_cbytes();
+        v.extend_from_slice(body.as_slice());
+        v
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        mem::discriminant(self) == mem::discriminant(other) && self.body() == other.body()
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        self.body().hash(state);
+    }
+}
+
+/// Values which are bound to a query either by position or by names of bind markers.
+#[derive(Debug, Clone)]
+pub enum QueryValues {
+    /// Values bound by position.
+    Simple(Vec<Value>),
+    /// Values bound by names.
+    Named(HashMap<String, Value>),
+}
+
+impl QueryValues {
+    /// Returns `true` if values are bound by names.
+    pub fn with_names(&self) -> bool {
+        match *self {
+            QueryValues::Simple(_) => false,
+            QueryValues::Named(_) => true,
+        }
+    }
+
+    /// Returns a number of bound values.
+    pub fn len(&self) -> usize {
+        match *self {
+            QueryValues::Simple(ref values) => values.len(),
+            QueryValues::Named(ref values) => values.len(),
+        }
+    }
+
+    /// Returns `true` if there are no bound values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl IntoBytes for QueryValues {
+    fn into_cbytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = to_short(self.len() as i16);
+        match *self {
+            QueryValues::Simple(ref values) => {
+                for value in values {
+                    v.extend_from_slice(value.into_cbytes().as_slice());
+                }
+            }
+            QueryValues::Named(ref values) => {
+                for (name, value) in values {
+                    v.extend_from_slice(CString::new(name.clone()).into_cbytes().as_slice());
+                    v.extend_from_slice(value.into_cbytes().as_slice());
+                }
+            }
+        }
         return v;
     }
 }
 
-impl<T: Into<Bytes>> From<T> for Value {
-    fn from(b: T) -> Value {
-        Value::new_normal(b.into())
+impl From<Vec<Value>> for QueryValues {
+    fn from(values: Vec<Value>) -> QueryValues {
+        QueryValues::Simple(values)
+    }
+}
+
+impl From<HashMap<String, Value>> for QueryValues {
+    fn from(values: HashMap<String, Value>) -> QueryValues {
+        QueryValues::Named(values)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::Varchar(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(value: &'a str) -> Value {
+        Value::Varchar(value.to_string())
+    }
+}
+
+impl From<i8> for Value {
+    fn from(value: i8) -> Value {
+        Value::Tinyint(value)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(value: i16) -> Value {
+        Value::Smallint(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Value {
+        Value::Int(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        Value::Bigint(value)
+    }
+}
+
+impl From<Counter> for Value {
+    fn from(value: Counter) -> Value {
+        Value::Counter(value.0)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Boolean(value)
+    }
+}
+
+impl From<Uuid> for Value {
+    fn from(value: Uuid) -> Value {
+        Value::Uuid(value)
+    }
+}
+
+impl From<IpAddr> for Value {
+    fn from(value: IpAddr) -> Value {
+        Value::Inet(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Value {
+        Value::Float(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::Double(value)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(value: Decimal) -> Value {
+        Value::Decimal(value)
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(value: Duration) -> Value {
+        Value::Duration(value)
+    }
+}
+
+/// Bytes are bound as a `blob`.
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Value {
+        Value::Blob(value)
+    }
+}
+
+/// Already serialized value, e.g. `Value::from(Bytes::new(bytes))`.
+impl From<Bytes> for Value {
+    fn from(value: Bytes) -> Value {
+        Value::Bytes(value.0)
+    }
+}
+
+/// List (or set) of values which may contain nulls, e.g.
+/// `Value::from(vec![Value::from(1), Value::new_null()])` or `Value::from(vec![1, 2])`.
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Value {
+        Value::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Map of values, e.g. `HashMap<String, i32>` or `HashMap<Value, Value>`.
+impl<K, V> From<HashMap<K, V>> for Value
+    where K: Into<Value> + Eq + Hash,
+          V: Into<Value>
+{
+    fn from(values: HashMap<K, V>) -> Value {
+        Value::Map(values.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// Typed value, e.g. `Value::from_cassandra_type(CassandraType::Udt(fields))`.
+/// `CassandraType::Custom` is converted into already serialized value. `DecodeError`
+/// placeholders (also nested ones) cannot be converted, an error is returned for them.
+impl FromCassandraType for Value {
+    fn from_cassandra_type(value: CassandraType) -> error::Result<Value> {
+        let convert_all = |values: Vec<CassandraType>| -> error::Result<Vec<Value>> {
+            values.into_iter().map(Value::from_cassandra_type).collect()
+        };
+        let value = match value {
+            CassandraType::Custom { bytes, .. } => Value::Bytes(bytes),
+            CassandraType::Ascii(v) => Value::Ascii(v),
+            CassandraType::Bigint(v) => Value::Bigint(v),
+            CassandraType::Blob(v) => Value::Blob(v),
+            CassandraType::Boolean(v) => Value::Boolean(v),
+            CassandraType::Counter(v) => Value::Counter(v),
+            CassandraType::Decimal(v) => Value::Decimal(v),
+            CassandraType::Double(v) => Value::Double(v),
+            CassandraType::Float(v) => Value::Float(v),
+            CassandraType::Int(v) => Value::Int(v),
+            CassandraType::Timestamp(v) => Value::Timestamp(v),
+            CassandraType::Uuid(v) => Value::Uuid(v),
+            CassandraType::Varchar(v) => Value::Varchar(v),
+            CassandraType::Varint(v) => Value::Varint(v),
+            CassandraType::Timeuuid(v) => Value::Timeuuid(v),
+            CassandraType::Inet(v) => Value::Inet(v),
+            CassandraType::Date(v) => Value::Date(v),
+            CassandraType::Time(v) => Value::Time(v),
+            CassandraType::Smallint(v) => Value::Smallint(v),
+            CassandraType::Tinyint(v) => Value::Tinyint(v),
+            CassandraType::Duration(v) => Value::Duration(v),
+            CassandraType::List(v) => Value::List(try!(convert_all(v))),
+            CassandraType::Set(v) => Value::Set(try!(convert_all(v))),
+            CassandraType::Map(entries) => {
+                let mut map = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    map.push((try!(Value::from_cassandra_type(key)),
+                              try!(Value::from_cassandra_type(value))));
+                }
+                Value::Map(map)
+            }
+            CassandraType::Udt(fields) => {
+                let mut udt = Vec::with_capacity(fields.len());
+                for (name, value) in fields {
+                    udt.push((name, try!(Value::from_cassandra_type(value))));
+                }
+                Value::Udt(udt)
+            }
+            CassandraType::Tuple(v) => Value::Tuple(try!(convert_all(v))),
+            CassandraType::Null => Value::Null,
+            CassandraType::DecodeError { column, error } => {
+                return Err(error::Error::General(format!("Value of column {} cannot be \
+                                                          bound since it failed to be \
+                                                          decoded: {}",
+                                                         column,
+                                                         error)));
+            }
+        };
+        Ok(value)
     }
 }
 
@@ -121,6 +471,17 @@ impl Into<Bytes> for i64 {
     }
 }
 
+/// Increment of a counter column, e.g. a value of `UPDATE t SET c = c + ? WHERE ...`.
+/// Negative values decrement the counter. It's encoded as 8-byte signed integer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Counter(pub i64);
+
+impl Into<Bytes> for Counter {
+    fn into(self) -> Bytes {
+        Bytes(to_bigint(self.0))
+    }
+}
+
 impl Into<Bytes> for u8 {
     fn into(self) -> Bytes {
         Bytes(vec![self])
@@ -157,6 +518,12 @@ impl Into<Bytes> for Uuid {
     }
 }
 
+impl Into<Bytes> for IpAddr {
+    fn into(self) -> Bytes {
+        Bytes(encode_inet(&self))
+    }
+}
+
 impl Into<Bytes> for f32 {
     fn into(self) -> Bytes {
         Bytes(to_float(self))
@@ -169,6 +536,12 @@ impl Into<Bytes> for f64 {
     }
 }
 
+impl Into<Bytes> for Decimal {
+    fn into(self) -> Bytes {
+        Bytes(encode_decimal(self.unscaled, self.scale))
+    }
+}
+
 impl<T: Into<Bytes> + Clone + Debug> From<Vec<T>> for Bytes {
     fn from(vec: Vec<T>) -> Bytes {
         let mut bytes: Vec<u8> = vec![];
@@ -223,8 +596,8 @@ mod tests {
         let plain_value = "hello";
         let len = plain_value.len() as i32;
         let normal_value = Value::new_normal(plain_value);
-        assert_eq!(normal_value.body, b"hello");
-        match normal_value.value_type {
+        assert_eq!(normal_value.body(), b"hello");
+        match normal_value.value_type() {
             ValueType::Normal(l) => assert_eq!(l, len),
             _ => unreachable!(),
         }
@@ -245,11 +618,190 @@ mod tests {
         let _ = Value::new_normal(true);
     }
 
+    #[test]
+    fn test_counter_value_round_trip() {
+        use types::data_serialization_types::decode_counter;
+        let value = Value::from(Counter(-42));
+        assert_eq!(value.body(), vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xD6]);
+        assert_eq!(decode_counter(value.body().as_slice()).unwrap(), -42);
+    }
+
+    #[test]
+    fn test_inet_value_round_trip() {
+        use types::data_serialization_types::decode_inet;
+        let v4: IpAddr = "0.0.0.0".parse().unwrap();
+        let value = Value::from(v4);
+        assert_eq!(value.into_cbytes(), vec![0, 0, 0, 4, 0, 0, 0, 0]);
+        assert_eq!(decode_inet(value.body().as_slice()).unwrap(), v4);
+
+        let v6: IpAddr = "::1".parse().unwrap();
+        let value = Value::from(v6);
+        assert_eq!(value.body().len(), 16);
+        assert_eq!(decode_inet(value.body().as_slice()).unwrap(), v6);
+    }
+
+    #[test]
+    fn test_typed_values_into_cbytes() {
+        use std::net::Ipv4Addr;
+        let uuid = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let cases = vec![(CassandraType::Ascii("a".to_string()), vec![0, 0, 0, 1, 97]),
+                         (CassandraType::Varchar("ü".to_string()), vec![0, 0, 0, 2, 0xC3, 0xBC]),
+                         (CassandraType::Custom {
+                              class_name: "a.b.C".to_string(),
+                              bytes: vec![99],
+                          },
+                          vec![0, 0, 0, 1, 99]),
+                         (CassandraType::Blob(vec![1, 2]), vec![0, 0, 0, 2, 1, 2]),
+                         (CassandraType::Boolean(true), vec![0, 0, 0, 1, 1]),
+                         (CassandraType::Tinyint(-1), vec![0, 0, 0, 1, 0xFF]),
+                         (CassandraType::Smallint(2), vec![0, 0, 0, 2, 0, 2]),
+                         (CassandraType::Int(3), vec![0, 0, 0, 4, 0, 0, 0, 3]),
+                         (CassandraType::Date(4), vec![0, 0, 0, 4, 0, 0, 0, 4]),
+                         (CassandraType::Bigint(5), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 5]),
+                         (CassandraType::Counter(6), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 6]),
+                         (CassandraType::Timestamp(7), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 7]),
+                         (CassandraType::Time(8), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 8]),
+                         (CassandraType::Varint(-129), vec![0, 0, 0, 2, 0xFF, 0x7F]),
+                         (CassandraType::Float(1.0), vec![0, 0, 0, 4, 0x3F, 0x80, 0, 0]),
+                         (CassandraType::Double(-2.0),
+                          vec![0, 0, 0, 8, 0xC0, 0, 0, 0, 0, 0, 0, 0]),
+                         (CassandraType::Decimal(Decimal::new(1234, 2)),
+                          vec![0, 0, 0, 6, 0, 0, 0, 2, 0x04, 0xD2]),
+                         (CassandraType::Decimal(Decimal::new(12, -2)),
+                          vec![0, 0, 0, 5, 0xFF, 0xFF, 0xFF, 0xFE, 12]),
+                         (CassandraType::Inet(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4))),
+                          vec![0, 0, 0, 4, 1, 2, 3, 4]),
+                         (CassandraType::Duration(Duration {
+                              months: 1,
+                              days: -1,
+                              nanos: 0,
+                          }),
+                          vec![0, 0, 0, 3, 0x02, 0x01, 0x00]),
+                         (CassandraType::Null, vec![0xFF, 0xFF, 0xFF, 0xFF])];
+        for (value, expected) in cases {
+            assert_eq!(Value::from_cassandra_type(value).unwrap().into_cbytes(), expected);
+        }
+
+        let mut uuid_bytes = vec![0, 0, 0, 16];
+        uuid_bytes.extend_from_slice(&[0; 15]);
+        uuid_bytes.push(1);
+        for value in vec![CassandraType::Uuid(uuid), CassandraType::Timeuuid(uuid)] {
+            assert_eq!(Value::from_cassandra_type(value).unwrap().into_cbytes(), uuid_bytes);
+        }
+    }
+
+    #[test]
+    fn test_typed_collections_into_cbytes() {
+        let list = CassandraType::List(vec![CassandraType::Int(1), CassandraType::Null]);
+        assert_eq!(Value::from_cassandra_type(list).unwrap().into_cbytes(),
+                   vec![0, 0, 0, 16, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let set = CassandraType::Set(vec![CassandraType::Tinyint(1)]);
+        assert_eq!(Value::from_cassandra_type(set).unwrap().into_cbytes(),
+                   vec![0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 1, 1]);
+        let map = CassandraType::Map(vec![(CassandraType::Int(1), CassandraType::Boolean(true))]);
+        assert_eq!(Value::from_cassandra_type(map).unwrap().into_cbytes(),
+                   vec![0, 0, 0, 17, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 1]);
+        let udt = CassandraType::Udt(vec![("a".to_string(), CassandraType::Tinyint(2)),
+                                          ("b".to_string(), CassandraType::Null)]);
+        assert_eq!(Value::from_cassandra_type(udt).unwrap().into_cbytes(),
+                   vec![0, 0, 0, 9, 0, 0, 0, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let tuple = CassandraType::Tuple(vec![CassandraType::Tinyint(3)]);
+        assert_eq!(Value::from_cassandra_type(tuple).unwrap().into_cbytes(),
+                   vec![0, 0, 0, 5, 0, 0, 0, 1, 3]);
+
+        let values = Value::from(vec![Value::from(1 as i8), Value::new_null()]);
+        assert_eq!(values.into_cbytes(),
+                   vec![0, 0, 0, 13, 0, 0, 0, 2, 0, 0, 0, 1, 1, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_values_into_cbytes() {
+        let uuid = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+        let mut uuid_bytes = vec![0, 0, 0, 16];
+        uuid_bytes.extend_from_slice(&[0; 15]);
+        uuid_bytes.push(1);
+        let ip: IpAddr = "1.2.3.4".parse().unwrap();
+        let cases = vec![(Value::from("a"), vec![0, 0, 0, 1, 97]),
+                         (Value::from("b".to_string()), vec![0, 0, 0, 1, 98]),
+                         (Value::Ascii("c".to_string()), vec![0, 0, 0, 1, 99]),
+                         (Value::from(-1 as i8), vec![0, 0, 0, 1, 0xFF]),
+                         (Value::from(2 as i16), vec![0, 0, 0, 2, 0, 2]),
+                         (Value::from(3 as i32), vec![0, 0, 0, 4, 0, 0, 0, 3]),
+                         (Value::from(4 as i64), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 4]),
+                         (Value::from(Counter(5)), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 5]),
+                         (Value::Timestamp(6), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 6]),
+                         (Value::Time(7), vec![0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 7]),
+                         (Value::Date(8), vec![0, 0, 0, 4, 0, 0, 0, 8]),
+                         (Value::Varint(-129), vec![0, 0, 0, 2, 0xFF, 0x7F]),
+                         (Value::from(true), vec![0, 0, 0, 1, 1]),
+                         (Value::from(1.0 as f32), vec![0, 0, 0, 4, 0x3F, 0x80, 0, 0]),
+                         (Value::from(-2.0 as f64), vec![0, 0, 0, 8, 0xC0, 0, 0, 0, 0, 0, 0, 0]),
+                         (Value::from(Decimal::new(1234, 2)),
+                          vec![0, 0, 0, 6, 0, 0, 0, 2, 0x04, 0xD2]),
+                         (Value::from(uuid), uuid_bytes.clone()),
+                         (Value::Timeuuid(uuid), uuid_bytes),
+                         (Value::from(ip), vec![0, 0, 0, 4, 1, 2, 3, 4]),
+                         (Value::from(vec![1 as u8, 2]), vec![0, 0, 0, 2, 1, 2]),
+                         (Value::from(Bytes::new(vec![3])), vec![0, 0, 0, 1, 3]),
+                         (Value::from(vec![1 as i8, 2]),
+                          vec![0, 0, 0, 14, 0, 0, 0, 2, 0, 0, 0, 1, 1, 0, 0, 0, 1, 2]),
+                         (Value::Set(vec![Value::from(1 as i8)]),
+                          vec![0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 1, 1]),
+                         (Value::Udt(vec![("a".to_string(), Value::from(2 as i8)),
+                                          ("b".to_string(), Value::new_null())]),
+                          vec![0, 0, 0, 9, 0, 0, 0, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF]),
+                         (Value::Tuple(vec![Value::from(3 as i8)]),
+                          vec![0, 0, 0, 5, 0, 0, 0, 1, 3]),
+                         (Value::new_null(), vec![0xFF, 0xFF, 0xFF, 0xFF]),
+                         (Value::new_not_set(), vec![0xFF, 0xFF, 0xFF, 0xFE])];
+        for (value, expected) in cases {
+            assert_eq!(value.into_cbytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_map_values_into_cbytes() {
+        let mut map = HashMap::new();
+        map.insert(Value::from(1 as i32), Value::from(true));
+        assert_eq!(Value::from(map).into_cbytes(),
+                   vec![0, 0, 0, 17, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 1]);
+
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), 1 as i8);
+        assert_eq!(Value::from(map).into_cbytes(),
+                   vec![0, 0, 0, 14, 0, 0, 0, 1, 0, 0, 0, 1, 97, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_values_as_hash_map_keys() {
+        let mut map = HashMap::new();
+        map.insert(Value::from(1 as i32), "int");
+        map.insert(Value::Date(1), "date");
+        map.insert(Value::from(0.0 as f64), "zero");
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&Value::Int(1)], "int");
+        assert_eq!(map[&Value::Date(1)], "date");
+        assert!(map.get(&Value::from(-0.0 as f64)).is_none());
+    }
+
+    #[test]
+    fn test_decode_error_into_value() {
+        let decode_error = || {
+            CassandraType::DecodeError {
+                column: "a".to_string(),
+                error: "invalid length".to_string(),
+            }
+        };
+        assert!(Value::from_cassandra_type(decode_error()).is_err());
+        let list = CassandraType::List(vec![CassandraType::Int(1), decode_error()]);
+        assert!(Value::from_cassandra_type(list).is_err());
+    }
+
     #[test]
     fn test_new_null_value() {
         let null_value = Value::new_null();
-        assert_eq!(null_value.body, vec![]);
-        match null_value.value_type {
+        assert_eq!(null_value.body(), vec![]);
+        match null_value.value_type() {
             ValueType::Null => assert!(true),
             _ => unreachable!(),
         }
@@ -258,8 +810,8 @@ mod tests {
     #[test]
     fn test_new_not_set_value() {
         let not_set_value = Value::new_not_set();
-        assert_eq!(not_set_value.body, vec![]);
-        match not_set_value.value_type {
+        assert_eq!(not_set_value.body(), vec![]);
+        match not_set_value.value_type() {
             ValueType::NotSet => assert!(true),
             _ => unreachable!(),
         }
@@ -271,4 +823,27 @@ mod tests {
         assert_eq!(value.into_cbytes(), vec![0, 0, 0, 1, 1]);
     }
 
+    #[test]
+    fn test_not_set_value_into_cbytes() {
+        assert_eq!(Value::new_not_set().into_cbytes(), vec![0xFF, 0xFF, 0xFF, 0xFE]);
+        assert_eq!(Value::new_null().into_cbytes(), vec![0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let values = QueryValues::from(vec![Value::new_normal(1 as u8), Value::new_not_set()]);
+        assert_eq!(values.into_cbytes(),
+                   vec![0, 2, 0, 0, 0, 1, 1, 0xFF, 0xFF, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn test_query_values_into_cbytes() {
+        let simple = QueryValues::from(vec![Value::new_normal(1 as u8)]);
+        assert!(!simple.with_names());
+        assert_eq!(simple.into_cbytes(), vec![0, 1, 0, 0, 0, 1, 1]);
+
+        let mut values = HashMap::new();
+        values.insert("a".to_string(), Value::new_normal(1 as u8));
+        let named = QueryValues::from(values);
+        assert!(named.with_names());
+        assert_eq!(named.into_cbytes(), vec![0, 1, 0, 1, 97, 0, 0, 0, 1, 1]);
+    }
+
 }
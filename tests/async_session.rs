@@ -0,0 +1,98 @@
+#![cfg(feature = "tokio")]
+extern crate cdrs;
+extern crate futures;
+extern crate tokio_core;
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::thread;
+
+use futures::Future;
+use futures::future::join_all;
+use tokio_core::net::TcpStream;
+use tokio_core::reactor::Core;
+
+use cdrs::async_session::AsyncSession;
+use cdrs::compression::Compression;
+use cdrs::frame::Opcode;
+use cdrs::frame::parser::parse_frame;
+use cdrs::query::QueryBuilder;
+use cdrs::types::IntoRustByName;
+
+const QUERIES: i32 = 5;
+
+fn to_int(int: i32) -> Vec<u8> {
+    vec![(int >> 24) as u8, (int >> 16) as u8, (int >> 8) as u8, int as u8]
+}
+
+fn response_frame(stream: u64, opcode: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut frame = vec![0x84, 0, (stream >> 8) as u8, stream as u8, opcode];
+    frame.extend_from_slice(to_int(body.len() as i32).as_slice());
+    frame.extend_from_slice(body.as_slice());
+    frame
+}
+
+// Rows result with a single int column `id` of a single row.
+fn rows_body(id: i32) -> Vec<u8> {
+    let mut body = to_int(0x0002);
+    body.extend_from_slice(to_int(0x0001).as_slice());
+    body.extend_from_slice(to_int(1).as_slice());
+    for name in &["ks", "table", "id"] {
+        body.extend_from_slice(&[0, name.len() as u8]);
+        body.extend_from_slice(name.as_bytes());
+    }
+    body.extend_from_slice(&[0, 0x09]);
+    body.extend_from_slice(to_int(1).as_slice());
+    body.extend_from_slice(to_int(4).as_slice());
+    body.extend_from_slice(to_int(id).as_slice());
+    body
+}
+
+#[test]
+fn concurrent_async_queries() {
+    // mock server which answers `SELECT <id>` queries with `id` once it has received
+    // all of them, responses are sent in reverse order
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let startup = parse_frame(&mut socket, &Compression::None).unwrap();
+        assert_eq!(startup.opcode, Opcode::Startup);
+        socket.write_all(response_frame(startup.stream, 0x02, vec![]).as_slice()).unwrap();
+
+        let mut queries = vec![];
+        for _ in 0..QUERIES {
+            let frame = parse_frame(&mut socket, &Compression::None).unwrap();
+            assert_eq!(frame.opcode, Opcode::Query);
+            // [long string] query goes first
+            let query = String::from_utf8(frame.body[4..].to_vec()).unwrap();
+            let id: i32 = query["SELECT ".len()..].split(' ').next().unwrap().parse().unwrap();
+            queries.push((frame.stream, id));
+        }
+        for &(stream, id) in queries.iter().rev() {
+            socket.write_all(response_frame(stream, 0x08, rows_body(id)).as_slice()).unwrap();
+        }
+    });
+
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+    let stream = core.run(TcpStream::connect(&addr, &handle)).unwrap();
+    let (session, connection) = AsyncSession::new(stream, Compression::None);
+    handle.spawn(connection.map_err(|err| panic!("Connection failed: {:?}", err)));
+
+    core.run(session.startup()).unwrap();
+    let queries = (0..QUERIES).map(|id| {
+        let query = QueryBuilder::new(format!("SELECT {} FROM ks.table", id)).finalize();
+        session.query(query)
+    });
+    let frames = core.run(join_all(queries)).unwrap();
+
+    let ids: Vec<i32> = frames.into_iter()
+        .map(|frame| {
+            let rows = frame.get_body().into_rows().unwrap();
+            rows[0].get_by_name("id").unwrap().unwrap()
+        })
+        .collect();
+    assert_eq!(ids, (0..QUERIES).collect::<Vec<_>>());
+    server.join().unwrap();
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "rust-tls")]
+extern crate cdrs;
+extern crate rustls;
+
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+use rustls::internal::pemfile::{certs, pkcs8_private_keys};
+use rustls::{NoClientAuth, RootCertStore, ServerConfig, ServerSession, StreamOwned};
+
+use cdrs::IntoBytes;
+use cdrs::frame::Frame;
+use cdrs::transport::{TransportRustls, new_rustls_config};
+
+fn fixture(name: &str) -> BufReader<File> {
+    let path = format!("{}/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+    BufReader::new(File::open(path).unwrap())
+}
+
+#[test]
+fn rustls_handshake_and_frame_round_trip() {
+    let frame = Frame::new_req_options().into_cbytes();
+    let frame_len = frame.len();
+
+    // TLS echo server
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        let cert = certs(&mut fixture("server.pem")).unwrap();
+        let key = pkcs8_private_keys(&mut fixture("server.key")).unwrap().remove(0);
+        config.set_single_cert(cert, key).unwrap();
+
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = StreamOwned::new(ServerSession::new(&Arc::new(config)), tcp);
+        let mut buf = vec![0; frame_len];
+        stream.read_exact(&mut buf).unwrap();
+        stream.write_all(&buf).unwrap();
+        stream.flush().unwrap();
+    });
+
+    let mut roots = RootCertStore::empty();
+    roots.add_pem_file(&mut fixture("ca.pem")).unwrap();
+    let config = new_rustls_config(roots, None).unwrap();
+    let mut transport = TransportRustls::new(addr.to_string().as_str(), "localhost", config)
+        .unwrap();
+
+    transport.write_all(frame.as_slice()).unwrap();
+    transport.flush().unwrap();
+    let mut echoed = vec![0; frame_len];
+    transport.read_exact(&mut echoed).unwrap();
+    assert_eq!(echoed, frame);
+
+    server.join().unwrap();
+}